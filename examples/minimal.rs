@@ -248,6 +248,7 @@ async fn handle_event(index: usize, node: &mut Routing, event: Event) -> bool {
             key,
             elders,
             self_status_change,
+            ..
         } => {
             info!(
                 "Node #{} elders changed - prefix: {:b}, key: {:?}, elders: {:?}. Node elder status change: {:?}",
@@ -272,6 +273,23 @@ async fn handle_event(index: usize, node: &mut Routing, event: Event) -> bool {
                 index, previous_name, new_name,
             );
         }
+        Event::DkgProgress {
+            session_id,
+            contributions_received,
+            total,
+            ..
+        } => info!(
+            "Node #{} DKG {:?} progress: {}/{}",
+            index, session_id, contributions_received, total
+        ),
+        Event::DkgComplete {
+            session_id,
+            public_key,
+        } => info!(
+            "Node #{} DKG {:?} complete - public_key: {:?}",
+            index, session_id, public_key
+        ),
+        Event::DkgFailed { session_id } => info!("Node #{} DKG {:?} failed", index, session_id),
         Event::RestartRequired => {
             info!("Node #{} requires restart", index);
             return false;
@@ -281,6 +299,19 @@ async fn handle_event(index: usize, node: &mut Routing, event: Event) -> bool {
             index, src, content
         ),
         Event::ClientLost(addr) => info!("Node #{} received ClientLost({:?})", index, addr),
+        Event::OurAgeChanged { old, new } => {
+            info!("Node #{} age changed - old: {}, new: {}", index, old, new)
+        }
+        Event::MessageDropped { reason, src } => info!(
+            "Node #{} dropped message - reason: {:?}, src: {:?}",
+            index, reason, src
+        ),
+        Event::NameCollision { name } => {
+            info!("Node #{} refused join of duplicate name {}", index, name)
+        }
+        Event::ClockSkewDetected { name, skew } => {
+            info!("Node #{} clock skew with elder {}: {:?}", index, name, skew)
+        }
     }
 
     true
@@ -302,6 +302,7 @@ impl Network {
                     key,
                     elders,
                     self_status_change,
+                    ..
                 } => {
                     if let Some(Node::Joined {
                         name,
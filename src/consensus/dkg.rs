@@ -6,10 +6,10 @@
 // KIND, either express or implied. Please review the Licences for the specific language governing
 // permissions and limitations relating to use of the SAFE Network Software.
 
+use super::Quorum;
 use crate::{
     crypto::{self, Digest256, Keypair, PublicKey, Signature, Verifier},
     error::Result,
-    majority,
     messages::{Message, Variant},
     node::Node,
     peer::Peer,
@@ -22,13 +22,14 @@ use hex_fmt::HexFmt;
 use itertools::Itertools;
 use serde::{Deserialize, Serialize};
 use std::{
-    collections::{HashMap, VecDeque},
+    collections::{BTreeSet, HashMap, VecDeque},
     fmt::{self, Debug, Formatter},
     iter, mem,
     net::SocketAddr,
     time::Duration,
 };
 use tiny_keccak::{Hasher, Sha3};
+use xor_name::XorName;
 
 // Interval to progress DKG timed phase
 const DKG_PROGRESS_INTERVAL: Duration = Duration::from_secs(30);
@@ -40,6 +41,7 @@ const BACKLOG_CAPACITY: usize = 100;
 pub struct DkgKey(Digest256);
 
 impl DkgKey {
+    /// Creates the key of the DKG session with the given participants.
     pub fn new(elders_info: &EldersInfo) -> Self {
         // Calculate the hash without involving serialization to avoid having to return `Result`.
         let mut hasher = Sha3::v256();
@@ -87,18 +89,27 @@ pub(crate) struct DkgVoter {
     // we created the corresponding session. To avoid losing those messages, we store them in this
     // backlog and replay them once we create the session.
     backlog: Backlog,
+
+    // Quorum of participants the generated key requires to sign and that need to observe a failure
+    // for it to be agreed on.
+    quorum: Quorum,
 }
 
 impl Default for DkgVoter {
     fn default() -> Self {
+        Self::new(Quorum::default())
+    }
+}
+
+impl DkgVoter {
+    pub fn new(quorum: Quorum) -> Self {
         Self {
             sessions: HashMap::default(),
             backlog: Backlog::new(),
+            quorum,
         }
     }
-}
 
-impl DkgVoter {
     // Starts a new DKG session.
     pub fn start(
         &mut self,
@@ -139,7 +150,7 @@ impl DkgVoter {
             }];
         }
 
-        let threshold = majority(elders_info.elders.len()) - 1;
+        let threshold = self.quorum.count(elders_info.elders.len()) - 1;
         let participants = elders_info
             .elders
             .values()
@@ -156,19 +167,20 @@ impl DkgVoter {
                     elders_info,
                     key_index,
                     participant_index,
+                    quorum: self.quorum,
                     timer_token: 0,
                     failures: Default::default(),
+                    contributors: BTreeSet::new(),
                     complete: false,
                 };
 
                 let mut commands = vec![];
                 commands.extend(session.broadcast(&dkg_key, keypair, message));
-                commands.extend(
-                    self.backlog
-                        .take(&dkg_key)
-                        .into_iter()
-                        .flat_map(|message| session.process_message(&dkg_key, keypair, message)),
-                );
+                commands.extend(self.backlog.take(&dkg_key).into_iter().flat_map(
+                    |(sender, message)| {
+                        session.process_message(&dkg_key, keypair, &sender, message)
+                    },
+                ));
 
                 let _ = self.sessions.insert(dkg_key, session);
                 self.sessions
@@ -202,16 +214,26 @@ impl DkgVoter {
         &mut self,
         keypair: &Keypair,
         dkg_key: &DkgKey,
+        sender: &XorName,
         message: DkgMessage,
     ) -> Vec<DkgCommand> {
         if let Some(session) = self.sessions.get_mut(dkg_key) {
-            session.process_message(dkg_key, keypair, message)
+            session.process_message(dkg_key, keypair, sender, message)
         } else {
-            self.backlog.push(*dkg_key, message);
+            self.backlog.push(*dkg_key, *sender, message);
             vec![]
         }
     }
 
+    // Returns the participants of the given DKG session together with the number of those we've
+    // received a valid DKG message from so far (including ourselves), or `None` if we are not
+    // participating in the session.
+    pub fn progress(&self, dkg_key: &DkgKey) -> Option<(&EldersInfo, usize)> {
+        self.sessions
+            .get(dkg_key)
+            .map(|session| (&session.elders_info, session.contributors.len()))
+    }
+
     pub fn process_failure(
         &mut self,
         dkg_key: &DkgKey,
@@ -230,9 +252,12 @@ struct Session {
     key_index: u64,
     // Our participant index.
     participant_index: usize,
+    quorum: Quorum,
     key_gen: KeyGen,
     timer_token: u64,
     failures: DkgFailureProofSet,
+    // Participants we've received at least one valid DKG message from.
+    contributors: BTreeSet<XorName>,
     // Flag to track whether this session has completed (either with success or failure). We don't
     // remove complete sessions because the other participants might still need us to respond to
     // their messages.
@@ -244,13 +269,23 @@ impl Session {
         &mut self,
         dkg_key: &DkgKey,
         keypair: &Keypair,
+        sender: &XorName,
         message: DkgMessage,
     ) -> Vec<DkgCommand> {
         trace!("process DKG message {:?}", message);
-        let responses = self
+        let responses = match self
             .key_gen
             .handle_message(&mut rand::thread_rng(), message)
-            .unwrap_or_default();
+        {
+            Ok(responses) => {
+                if self.elders_info.elders.contains_key(sender) {
+                    let _ = self.contributors.insert(*sender);
+                }
+
+                responses
+            }
+            Err(_) => vec![],
+        };
 
         // Only a valid DkgMessage, which results in some responses, shall reset the ticker.
         let reset_timer = if responses.is_empty() {
@@ -296,7 +331,8 @@ impl Session {
             });
         }
 
-        commands.extend(self.process_message(dkg_key, keypair, message));
+        let our_name = crypto::name(&keypair.public);
+        commands.extend(self.process_message(dkg_key, keypair, &our_name, message));
         commands
     }
 
@@ -408,7 +444,7 @@ impl Session {
     }
 
     fn check_failure_agreement(&mut self) -> Option<DkgCommand> {
-        if self.failures.has_agreement(&self.elders_info) {
+        if self.failures.has_agreement(&self.elders_info, self.quorum) {
             self.complete = true;
 
             Some(DkgCommand::HandleFailureAgreement {
@@ -468,13 +504,13 @@ impl DkgFailureProofSet {
         }
     }
 
-    // Check whether we have proofs from a majority of the participants. The contained proofs are
+    // Check whether we have proofs from a quorum of the participants. The contained proofs are
     // assumed valid.
-    fn has_agreement(&self, elders_info: &EldersInfo) -> bool {
-        self.0.len() >= majority(elders_info.elders.len())
+    fn has_agreement(&self, elders_info: &EldersInfo, quorum: Quorum) -> bool {
+        self.0.len() >= quorum.count(elders_info.elders.len())
     }
 
-    pub fn verify(&self, elders_info: &EldersInfo) -> bool {
+    pub fn verify(&self, elders_info: &EldersInfo, quorum: Quorum) -> bool {
         let hash = failure_proof_hash(&DkgKey::new(elders_info));
         let votes = self
             .0
@@ -487,7 +523,7 @@ impl DkgFailureProofSet {
             .filter(|proof| proof.public_key.verify(&hash, &proof.signature).is_ok())
             .count();
 
-        votes >= majority(elders_info.elders.len())
+        votes >= quorum.count(elders_info.elders.len())
     }
 }
 
@@ -507,31 +543,31 @@ fn failure_proof_hash(dkg_key: &DkgKey) -> Digest256 {
     output
 }
 
-struct Backlog(VecDeque<(DkgKey, DkgMessage)>);
+struct Backlog(VecDeque<(DkgKey, XorName, DkgMessage)>);
 
 impl Backlog {
     fn new() -> Self {
         Self(VecDeque::with_capacity(BACKLOG_CAPACITY))
     }
 
-    fn push(&mut self, dkg_key: DkgKey, message: DkgMessage) {
+    fn push(&mut self, dkg_key: DkgKey, sender: XorName, message: DkgMessage) {
         if self.0.len() == self.0.capacity() {
             let _ = self.0.pop_front();
         }
 
-        self.0.push_back((dkg_key, message))
+        self.0.push_back((dkg_key, sender, message))
     }
 
-    fn take(&mut self, dkg_key: &DkgKey) -> Vec<DkgMessage> {
+    fn take(&mut self, dkg_key: &DkgKey) -> Vec<(XorName, DkgMessage)> {
         let mut output = Vec::new();
         let max = self.0.len();
 
         for _ in 0..max {
-            if let Some((message_dkg_key, message)) = self.0.pop_front() {
+            if let Some((message_dkg_key, sender, message)) = self.0.pop_front() {
                 if &message_dkg_key == dkg_key {
-                    output.push(message)
+                    output.push((sender, message))
                 } else {
-                    self.0.push_back((message_dkg_key, message))
+                    self.0.push_back((message_dkg_key, sender, message))
                 }
             }
         }
@@ -682,6 +718,33 @@ mod tests {
         assert_matches!(&commands[..], &[DkgCommand::HandleOutcome { .. }]);
     }
 
+    #[test]
+    fn progress() {
+        let node0 = Node::new(crypto::gen_keypair(), gen_addr());
+        let node1 = Node::new(crypto::gen_keypair(), gen_addr());
+        let elders_info = EldersInfo::new(vec![node0.peer(), node1.peer()], Prefix::default());
+        let dkg_key = DkgKey::new(&elders_info);
+
+        let mut voter0 = DkgVoter::default();
+        let mut voter1 = DkgVoter::default();
+
+        assert!(voter0.progress(&dkg_key).is_none());
+
+        let _ = voter0.start(&node0.keypair, dkg_key, elders_info.clone(), 0);
+        let commands = voter1.start(&node1.keypair, dkg_key, elders_info.clone(), 0);
+
+        // Only we contributed so far.
+        assert_matches!(voter0.progress(&dkg_key), Some((info, 1)) => assert_eq!(*info, elders_info));
+
+        for command in commands {
+            if let DkgCommand::SendMessage { message, .. } = command {
+                let _ = voter0.process_message(&node0.keypair, &dkg_key, &node1.name(), message);
+            }
+        }
+
+        assert_matches!(voter0.progress(&dkg_key), Some((_, 2)));
+    }
+
     proptest! {
         // Run a DKG session where every participant handles every message sent to them.
         // Expect the session to successfully complete without timed transitions.
@@ -730,12 +793,13 @@ mod tests {
             // NOTE: this panics if `messages` is empty, but that's OK because it would mean
             // failure anyway.
             let index = rng.gen_range(0, messages.len());
-            let (addr, message) = messages.swap_remove(index);
+            let (addr, sender, message) = messages.swap_remove(index);
 
             let actor = actors.get_mut(&addr).expect("unknown message recipient");
-            let commands = actor
-                .voter
-                .process_message(&actor.node.keypair, &dkg_key, message);
+            let commands =
+                actor
+                    .voter
+                    .process_message(&actor.node.keypair, &dkg_key, &sender, message);
 
             for command in commands {
                 messages.extend(actor.handle(command, &dkg_key))
@@ -762,7 +826,7 @@ mod tests {
             &mut self,
             command: DkgCommand,
             expected_dkg_key: &DkgKey,
-        ) -> Vec<(SocketAddr, DkgMessage)> {
+        ) -> Vec<(SocketAddr, XorName, DkgMessage)> {
             match command {
                 DkgCommand::SendMessage {
                     recipients,
@@ -773,7 +837,7 @@ mod tests {
                     assert_eq!(dkg_key, *expected_dkg_key);
                     recipients
                         .into_iter()
-                        .map(|addr| (addr, message.clone()))
+                        .map(|addr| (addr, self.node.name(), message.clone()))
                         .collect()
                 }
                 DkgCommand::HandleOutcome { outcome, .. } => {
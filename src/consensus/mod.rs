@@ -8,11 +8,12 @@
 
 mod dkg;
 mod proven;
+mod quorum;
 #[cfg(test)]
 pub mod test_utils;
 mod vote;
 
-pub use self::{dkg::DkgKey, proven::Proven};
+pub use self::{dkg::DkgKey, proven::Proven, quorum::Quorum};
 pub(crate) use self::{
     dkg::{DkgCommands, DkgFailureProof, DkgFailureProofSet, DkgVoter},
     vote::{Vote, VoteAccumulationError, VoteAccumulator},
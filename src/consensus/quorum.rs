@@ -0,0 +1,102 @@
+// Copyright 2020 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under The General Public License (GPL), version 3.
+// Unless required by applicable law or agreed to in writing, the SAFE Network Software distributed
+// under the GPL Licence is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied. Please review the Licences for the specific language governing
+// permissions and limitations relating to use of the SAFE Network Software.
+
+use std::cmp;
+
+/// Fraction of the elders of a section required to agree for the section to sign or reach
+/// consensus on anything (see `Config::quorum`). Every node of the network must use the same
+/// quorum. The default is a strict majority (more than 1/2).
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct Quorum {
+    numerator: usize,
+    denominator: usize,
+}
+
+impl Quorum {
+    /// Creates a quorum of more than `numerator / denominator` of the elders. Returns `None` if
+    /// the fraction is less than 1/2 or more than 1.
+    pub fn new(numerator: usize, denominator: usize) -> Option<Self> {
+        if denominator == 0 || numerator > denominator || 2 * numerator < denominator {
+            return None;
+        }
+
+        Some(Self {
+            numerator,
+            denominator,
+        })
+    }
+
+    /// Numerator of the fraction.
+    pub fn numerator(&self) -> usize {
+        self.numerator
+    }
+
+    /// Denominator of the fraction.
+    pub fn denominator(&self) -> usize {
+        self.denominator
+    }
+
+    /// Number of votes out of `num_possible_voters` required to reach this quorum: more than the
+    /// fraction of them, but never more than all of them.
+    pub fn count(&self, num_possible_voters: usize) -> usize {
+        cmp::min(
+            1 + num_possible_voters * self.numerator / self.denominator,
+            cmp::max(num_possible_voters, 1),
+        )
+    }
+}
+
+impl Default for Quorum {
+    fn default() -> Self {
+        Self {
+            numerator: 1,
+            denominator: 2,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::majority;
+    use proptest::prelude::*;
+
+    #[test]
+    fn invalid_fraction() {
+        assert!(Quorum::new(1, 3).is_none());
+        assert!(Quorum::new(5, 4).is_none());
+        assert!(Quorum::new(0, 0).is_none());
+        assert!(Quorum::new(1, 2).is_some());
+        assert!(Quorum::new(1, 1).is_some());
+    }
+
+    #[test]
+    fn three_quarters() {
+        let quorum = Quorum::new(3, 4).unwrap();
+
+        for (size, expected) in &[
+            (1, 1),
+            (2, 2),
+            (3, 3),
+            (4, 4),
+            (5, 4),
+            (7, 6),
+            (8, 7),
+            (12, 10),
+        ] {
+            assert_eq!(quorum.count(*size), *expected, "section size {}", size);
+        }
+    }
+
+    proptest! {
+        #[test]
+        fn proptest_default_is_majority(num_possible_voters in 1..1000usize) {
+            assert_eq!(Quorum::default().count(num_possible_voters), majority(num_possible_voters));
+        }
+    }
+}
@@ -66,6 +66,21 @@ pub(crate) enum Vote {
 
     // Voted to concensus whether new node shall be allowed to join
     JoinsAllowed(bool),
+
+    // Voted to let the given elder step down from the elder set.
+    StepDown(XorName),
+
+    // Voted to let the given member who stepped down be picked as an elder again.
+    CancelStepDown(XorName),
+
+    // Voted to not relocate the given member on the next `churn_events` churn events.
+    DeferRelocation {
+        name: XorName,
+        churn_events: u32,
+    },
+
+    // Voted to end the relocation deferral of the given member early.
+    CancelRelocationDeferral(XorName),
 }
 
 impl Vote {
@@ -104,6 +119,12 @@ impl<'a> Serialize for SignableView<'a> {
             Vote::TheirKnowledge { prefix, key_index } => (prefix, key_index).serialize(serializer),
             Vote::SendMessage { message, .. } => message.as_signable().serialize(serializer),
             Vote::JoinsAllowed(joins_allowed) => joins_allowed.serialize(serializer),
+            Vote::StepDown(name) => name.serialize(serializer),
+            Vote::CancelStepDown(name) => name.serialize(serializer),
+            Vote::DeferRelocation { name, churn_events } => {
+                (name, churn_events).serialize(serializer)
+            }
+            Vote::CancelRelocationDeferral(name) => name.serialize(serializer),
         }
     }
 }
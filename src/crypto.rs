@@ -12,6 +12,7 @@ pub use ed25519_dalek::{
     Keypair, PublicKey, SecretKey, Signature, Verifier, PUBLIC_KEY_LENGTH, SIGNATURE_LENGTH,
 };
 
+use bytes::Bytes;
 use ed25519_dalek::ExpandedSecretKey;
 use std::ops::RangeInclusive;
 use xor_name::XorName;
@@ -60,6 +61,23 @@ pub fn gen_keypair_within_range(range: &RangeInclusive<XorName>) -> Keypair {
     }
 }
 
+/// Encrypt `payload` so that it can be read only by the holder of the secret key corresponding to
+/// `public_key`.
+pub fn encrypt_payload(
+    public_key: &bls::PublicKey,
+    payload: &[u8],
+) -> Result<Bytes, bincode::Error> {
+    let ciphertext = public_key.encrypt(payload);
+    Ok(bincode::serialize(&ciphertext)?.into())
+}
+
+/// Decrypt payload previously encrypted with `encrypt_payload`. Returns `None` if the payload is
+/// malformed or wasn't encrypted to the public key corresponding to `secret_key`.
+pub fn decrypt_payload(secret_key: &bls::SecretKey, encrypted: &[u8]) -> Option<Bytes> {
+    let ciphertext: bls::Ciphertext = bincode::deserialize(encrypted).ok()?;
+    secret_key.decrypt(&ciphertext).map(Bytes::from)
+}
+
 #[cfg(test)]
 pub(crate) mod test_utils {
     use super::*;
@@ -78,3 +96,24 @@ pub(crate) mod test_utils {
         })
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn encrypt_and_decrypt_payload() {
+        let secret_key = bls::SecretKey::random();
+        let payload = b"hello world";
+
+        let encrypted = encrypt_payload(&secret_key.public_key(), payload).unwrap();
+        assert_ne!(&encrypted[..], &payload[..]);
+        assert_eq!(
+            decrypt_payload(&secret_key, &encrypted),
+            Some(Bytes::from_static(payload))
+        );
+
+        let other_secret_key = bls::SecretKey::random();
+        assert_eq!(decrypt_payload(&other_secret_key, &encrypted), None);
+    }
+}
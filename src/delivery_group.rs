@@ -9,9 +9,9 @@
 //! Utilities for sn_routing messages through the network.
 
 use crate::{
+    consensus::Quorum,
     error::{Error, Result},
     location::DstLocation,
-    majority,
     network::Network,
     peer::Peer,
     section::Section,
@@ -21,8 +21,63 @@ use itertools::Itertools;
 use std::{cmp, iter};
 use xor_name::XorName;
 
+/// Mapping from a destination name to the prefix of the section messages to it are delivered to,
+/// consulted instead of the prefix matching (see `Config::section_override`). For tests only.
+#[cfg(any(test, feature = "testing"))]
+pub struct SectionOverride(Box<dyn Fn(&XorName) -> xor_name::Prefix + Send + Sync>);
+
+#[cfg(any(test, feature = "testing"))]
+impl SectionOverride {
+    /// Creates the override from the given mapping.
+    pub fn new<F>(mapping: F) -> Self
+    where
+        F: Fn(&XorName) -> xor_name::Prefix + Send + Sync + 'static,
+    {
+        Self(Box::new(mapping))
+    }
+}
+
+#[cfg(any(test, feature = "testing"))]
+impl std::fmt::Debug for SectionOverride {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "SectionOverride(..)")
+    }
+}
+
+/// Like `delivery_targets`, but a message for a `DstLocation::Section` is sent to all the elders
+/// of the section whose prefix `section_override` maps the destination to. Returns `None`, so the
+/// regular targets apply, if the destination is not a section, we are not an elder (the message
+/// is relayed through our elders then) or we don't know the section it maps to.
+#[cfg(any(test, feature = "testing"))]
+pub(crate) fn overridden_delivery_targets(
+    section_override: &SectionOverride,
+    dst: &DstLocation,
+    our_name: &XorName,
+    section: &Section,
+    network: &Network,
+) -> Option<(Vec<Peer>, usize)> {
+    let target_name = match dst {
+        DstLocation::Section(target_name) if section.is_elder(our_name) => target_name,
+        _ => return None,
+    };
+
+    let prefix = (section_override.0)(target_name);
+    let elders_info = iter::once(section.elders_info())
+        .chain(network.all())
+        .find(|info| info.prefix == prefix)?;
+    let targets: Vec<_> = elders_info
+        .peers()
+        .filter(|peer| peer.name() != our_name)
+        .copied()
+        .collect();
+    let dg_size = targets.len();
+
+    Some((targets, dg_size))
+}
+
 /// Returns a set of nodes to which a message for the given `DstLocation` could be sent
 /// onwards, sorted by priority, along with the number of targets the message should be sent to.
+/// When relaying through other sections, that number is `quorum` of their elders.
 /// If the total number of targets returned is larger than this number, the spare targets can
 /// be used if the message can't be delivered to some of the initial ones.
 ///
@@ -40,6 +95,7 @@ pub(crate) fn delivery_targets(
     our_name: &XorName,
     section: &Section,
     network: &Network,
+    quorum: Quorum,
 ) -> Result<(Vec<Peer>, usize)> {
     if !section.is_elder(our_name) {
         // We are not Elder - return all the elders of our section, so the message can be properly
@@ -58,7 +114,7 @@ pub(crate) fn delivery_targets(
                 return Ok((vec![*node], 1));
             }
 
-            candidates(target_name, our_name, section, network)?
+            candidates(target_name, our_name, section, network, quorum)?
         }
         DstLocation::Section(target_name) => {
             // Find closest section to `target_name` out of the ones we know (including our own)
@@ -81,7 +137,7 @@ pub(crate) fn delivery_targets(
                 return Ok((section, dg_size));
             }
 
-            candidates(target_name, our_name, section, network)?
+            candidates(target_name, our_name, section, network, quorum)?
         }
         DstLocation::Direct => return Err(Error::CannotRoute),
     };
@@ -95,6 +151,7 @@ fn candidates(
     our_name: &XorName,
     section: &Section,
     network: &Network,
+    quorum: Quorum,
 ) -> Result<(Vec<Peer>, usize)> {
     // All sections we know (including our own), sorted by distance to `target_name`.
     let sections = iter::once(section.elders_info())
@@ -102,18 +159,18 @@ fn candidates(
         .sorted_by(|lhs, rhs| lhs.prefix.cmp_distance(&rhs.prefix, target_name))
         .map(|info| (&info.prefix, info.elders.len(), info.elders.values()));
 
-    let mut dg_size = majority(ELDER_SIZE);
+    let full_dg_size = quorum.count(ELDER_SIZE);
+    let mut dg_size = full_dg_size;
     let mut nodes_to_send = Vec::new();
     for (idx, (prefix, len, connected)) in sections.enumerate() {
         nodes_to_send.extend(connected.cloned());
         // If we don't have enough contacts send to as many as possible
-        // up to majority of Elders
+        // up to quorum of Elders
         dg_size = cmp::min(len, dg_size);
-        if len < majority(ELDER_SIZE) {
+        if len < full_dg_size {
             warn!(
                 "Delivery group only {:?} when it should be {:?}",
-                len,
-                majority(ELDER_SIZE)
+                len, full_dg_size
             )
         }
 
@@ -147,8 +204,8 @@ fn get_peer<'a>(name: &XorName, section: &'a Section, network: &'a Network) -> O
 }
 
 // Returns the set of peers that are responsible for collecting signatures to verify a message;
-// this may contain us or only other nodes.
-pub fn signature_targets<I>(dst: &DstLocation, our_elders: I) -> Vec<Peer>
+// this may contain us or only other nodes. Enough of them to reach `quorum` are picked.
+pub fn signature_targets<I>(dst: &DstLocation, our_elders: I, quorum: Quorum) -> Vec<Peer>
 where
     I: IntoIterator<Item = Peer>,
 {
@@ -165,6 +222,6 @@ where
         .into_iter()
         .sorted_by(|lhs, rhs| dst_name.cmp_distance(lhs.name(), rhs.name()))
         .collect();
-    list.truncate(cmp::min(list.len(), majority(ELDER_SIZE)));
+    list.truncate(cmp::min(list.len(), quorum.count(ELDER_SIZE)));
     list
 }
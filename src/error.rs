@@ -39,6 +39,18 @@ pub enum Error {
     FailedSend,
     #[error("Invalid vote.")]
     InvalidVote,
+    #[error("Message would be routed back to us.")]
+    MessageLoop,
+    #[error("Message is not trusted.")]
+    UntrustedMessage,
+    #[error("Address of the peer is unknown.")]
+    UnknownPeer,
+    #[error("Address is not a valid peer address.")]
+    InvalidPeerAddr,
+    #[error("Too many messages sent toward the destination section.")]
+    RateLimited,
+    #[error("Our name is already used by another member of the section.")]
+    NameCollision,
     #[error("Messaging protocol error: {0}")]
     Messaging(#[from] sn_messaging::Error),
 }
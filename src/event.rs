@@ -6,7 +6,10 @@
 // KIND, either express or implied. Please review the Licences for the specific language governing
 // permissions and limitations relating to use of the SAFE Network Software.
 
-use crate::location::{DstLocation, SrcLocation};
+use crate::{
+    consensus::DkgKey,
+    location::{DstLocation, SrcLocation},
+};
 
 use bytes::Bytes;
 use ed25519_dalek::Keypair;
@@ -18,6 +21,7 @@ use std::{
     fmt::{self, Debug, Formatter},
     net::SocketAddr,
     sync::Arc,
+    time::Duration,
 };
 use xor_name::{Prefix, XorName};
 
@@ -78,6 +82,10 @@ pub enum Event {
         key: bls::PublicKey,
         /// The set of elders of our section.
         elders: BTreeSet<XorName>,
+        /// The elders that were not elders before this change.
+        added: BTreeSet<XorName>,
+        /// The elders that are no longer elders after this change.
+        removed: BTreeSet<XorName>,
         /// Promoted, demoted or no change?
         self_status_change: NodeElderChange,
     },
@@ -94,6 +102,36 @@ pub enum Event {
         /// New keypair to be used after relocation.
         new_keypair: Arc<Keypair>,
     },
+    /// The age of this node changed. Raised once per change, after `Relocated`.
+    OurAgeChanged {
+        /// Age before the change.
+        old: u8,
+        /// Age after the change.
+        new: u8,
+    },
+    /// A DKG (distributed key generation) session we participate in made progress.
+    DkgProgress {
+        /// The identifier of the DKG session.
+        session_id: DkgKey,
+        /// The participants of the session.
+        participants: BTreeSet<XorName>,
+        /// Number of participants we've received a valid contribution from so far (including us).
+        contributions_received: usize,
+        /// Total number of participants.
+        total: usize,
+    },
+    /// A DKG session we participate in completed successfully.
+    DkgComplete {
+        /// The identifier of the DKG session.
+        session_id: DkgKey,
+        /// The generated BLS public key.
+        public_key: bls::PublicKey,
+    },
+    /// A DKG session we participate in failed.
+    DkgFailed {
+        /// The identifier of the DKG session.
+        session_id: DkgKey,
+    },
     /// Disconnected or failed to connect - restart required.
     RestartRequired,
     /// Received a message from a client node.
@@ -110,6 +148,98 @@ pub enum Event {
     },
     /// Failed in sending a message to client, or connection to client is lost
     ClientLost(SocketAddr),
+    /// An incoming message was dropped without being handled. For diagnostics only: not every
+    /// dropped message is necessarily reported.
+    MessageDropped {
+        /// Why the message was dropped.
+        reason: DropReason,
+        /// The source location of the message, or `None` if it couldn't be determined.
+        src: Option<SrcLocation>,
+    },
+    /// A node tried to join our section with the name of one of our current members. The join was
+    /// refused and the existing member kept.
+    NameCollision {
+        /// The name used by both nodes.
+        name: XorName,
+    },
+    /// The clock of one of our elders is off from ours by more than 30 seconds, as measured by
+    /// `Routing::measure_clock_skew`. Time based decisions might not work as expected between us.
+    ClockSkewDetected {
+        /// The name of the elder.
+        name: XorName,
+        /// The estimated difference between our clocks, in either direction.
+        skew: Duration,
+    },
+}
+
+/// Change of the membership of our section, as yielded by `MembershipEventStream`. These are also
+/// reported, in a less focused way, by the general `Event`s.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum MembershipEvent {
+    /// A new node joined our section.
+    Joined {
+        /// Name of the node.
+        name: XorName,
+        /// Age of the node.
+        age: u8,
+    },
+    /// A node left our section.
+    Left {
+        /// Name of the node.
+        name: XorName,
+        /// Age of the node.
+        age: u8,
+    },
+    /// The age of our node changed. Raised after we relocate.
+    AgeChanged {
+        /// Our name after the change.
+        name: XorName,
+        /// Age before the change.
+        old: u8,
+        /// Age after the change.
+        new: u8,
+    },
+    /// A member is being relocated out of our section. It is no longer our member.
+    RelocatingOut {
+        /// Name of the node.
+        name: XorName,
+        /// Age of the node.
+        age: u8,
+        /// Name the node is being relocated to. It will join the section matching it.
+        destination: XorName,
+    },
+    /// A node joined our section after being relocated from another one.
+    RelocatedIn {
+        /// Name of the node.
+        name: XorName,
+        /// Name of the node before the relocation.
+        previous_name: XorName,
+        /// Age of the node.
+        age: u8,
+    },
+}
+
+/// Reason why an incoming message was dropped (see `Event::MessageDropped`).
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum DropReason {
+    /// The message exceeds the maximum incoming message size.
+    TooLarge,
+    /// The message couldn't be deserialised.
+    Malformed,
+    /// The message came back to us after we relayed it without us being its destination.
+    Loop,
+    /// The message failed verification, or its proof chain isn't trusted.
+    FailedVerification,
+    /// The content of the message isn't signed by its source and only signed messages are
+    /// accepted.
+    Unsigned,
+    /// The message is from a section we don't know any key of and such messages are configured
+    /// to be dropped.
+    UnknownSection,
+    /// The message has already been handled.
+    Duplicate,
+    /// The message reached its hop limit before reaching its destination.
+    TtlExpired,
 }
 
 impl Debug for Event {
@@ -144,12 +274,16 @@ impl Debug for Event {
                 prefix,
                 key,
                 elders,
+                added,
+                removed,
                 self_status_change,
             } => formatter
                 .debug_struct("EldersChanged")
                 .field("prefix", prefix)
                 .field("key", key)
                 .field("elders", elders)
+                .field("added", added)
+                .field("removed", removed)
                 .field("self_status_change", self_status_change)
                 .finish(),
             Self::RelocationStarted { previous_name } => formatter
@@ -164,6 +298,35 @@ impl Debug for Event {
                 .field("previous_name", previous_name)
                 .field("new_keypair", new_keypair)
                 .finish(),
+            Self::OurAgeChanged { old, new } => formatter
+                .debug_struct("OurAgeChanged")
+                .field("old", old)
+                .field("new", new)
+                .finish(),
+            Self::DkgProgress {
+                session_id,
+                participants,
+                contributions_received,
+                total,
+            } => formatter
+                .debug_struct("DkgProgress")
+                .field("session_id", session_id)
+                .field("participants", participants)
+                .field("contributions_received", contributions_received)
+                .field("total", total)
+                .finish(),
+            Self::DkgComplete {
+                session_id,
+                public_key,
+            } => formatter
+                .debug_struct("DkgComplete")
+                .field("session_id", session_id)
+                .field("public_key", public_key)
+                .finish(),
+            Self::DkgFailed { session_id } => formatter
+                .debug_struct("DkgFailed")
+                .field("session_id", session_id)
+                .finish(),
             Self::RestartRequired => write!(formatter, "RestartRequired"),
             Self::ClientMessageReceived { content, src, .. } => write!(
                 formatter,
@@ -171,6 +334,20 @@ impl Debug for Event {
                 content, src,
             ),
             Self::ClientLost(addr) => write!(formatter, "ClientLost({:?})", addr),
+            Self::MessageDropped { reason, src } => formatter
+                .debug_struct("MessageDropped")
+                .field("reason", reason)
+                .field("src", src)
+                .finish(),
+            Self::NameCollision { name } => formatter
+                .debug_struct("NameCollision")
+                .field("name", name)
+                .finish(),
+            Self::ClockSkewDetected { name, skew } => formatter
+                .debug_struct("ClockSkewDetected")
+                .field("name", name)
+                .field("skew", skew)
+                .finish(),
         }
     }
 }
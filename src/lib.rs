@@ -70,12 +70,25 @@ extern crate tracing;
 // ############################################################################
 // Public API
 // ############################################################################
+#[cfg(feature = "testing")]
+pub use self::routing::{LinkImpairment, PresetMember, SectionOverride, SectionPreset};
 pub use self::{
+    consensus::{DkgKey, Quorum},
+    crypto::decrypt_payload,
     error::{Error, Result},
-    event::{Event, NodeElderChange, SendStream},
+    event::{DropReason, Event, MembershipEvent, NodeElderChange, SendStream},
     location::{DstLocation, SrcLocation},
-    routing::{Config, EventStream, Routing},
-    section::{SectionProofChain, MIN_AGE},
+    relocation::{AgePolicy, DefaultAgePolicy, RelocationRecord},
+    routing::{
+        CommandSummary, Config, ConnectionCacheStats, DeliveryReport, EventSink, EventStream,
+        IncomingInspector, InterceptDecision, JoinPhase, KeyRotationEntry, MembershipEventStream,
+        Metrics, OutgoingInterceptor, PeerConnectionEvent, PeerConnectionEventKind, QueryHandler,
+        RateLimit, RetryPolicy, Routing, RoutingDigest, SectionMetrics, SendFailureKind,
+        SendFailureRecord, UnknownSectionPolicy,
+    },
+    section::{
+        MembershipProof, SectionProofBlock, SectionProofChain, StructureError, TrustStatus, MIN_AGE,
+    },
 };
 pub use qp2p::Config as TransportConfig;
 
@@ -111,6 +124,7 @@ pub const ELDER_SIZE: usize = 5;
 
 /// Number of votes required to agree
 /// with a strict majority (i.e. > 50%)
+#[cfg(test)]
 #[inline]
 pub(crate) const fn majority(num_possible_voters: usize) -> usize {
     1 + (num_possible_voters / 2)
@@ -11,7 +11,7 @@ use crate::{
     messages::{Message, MessageHash},
 };
 use lru_time_cache::LruCache;
-use std::time::Duration;
+use std::{collections::BTreeSet, net::SocketAddr, time::Duration};
 use xor_name::XorName;
 
 const INCOMING_EXPIRY_DURATION: Duration = Duration::from_secs(20 * 60);
@@ -40,6 +40,8 @@ impl FilteringResult {
 pub(crate) struct MessageFilter {
     incoming: LruCache<MessageHash, ()>,
     outgoing: LruCache<(MessageHash, XorName), ()>,
+    // Messages we relayed (or sent) to someone else, with the addresses we relayed them to.
+    relayed: LruCache<MessageHash, BTreeSet<SocketAddr>>,
 }
 
 impl MessageFilter {
@@ -53,6 +55,10 @@ impl MessageFilter {
                 OUTGOING_EXPIRY_DURATION,
                 MAX_ENTRIES,
             ),
+            relayed: LruCache::with_expiry_duration_and_capacity(
+                OUTGOING_EXPIRY_DURATION,
+                MAX_ENTRIES,
+            ),
         }
     }
 
@@ -85,10 +91,33 @@ impl MessageFilter {
         }
     }
 
-    // Resets both incoming and outgoing filters.
+    // Returns whether we relayed the message to `addr` before.
+    pub fn was_relayed_to(&mut self, msg: &Message, addr: &SocketAddr) -> bool {
+        self.relayed
+            .get(msg.hash())
+            .map_or(false, |recipients| recipients.contains(addr))
+    }
+
+    pub fn insert_relayed(&mut self, msg: &Message, recipients: &[SocketAddr]) {
+        // Not filtering direct messages.
+        if let DstLocation::Direct = msg.dst() {
+            return;
+        }
+
+        if let Some(existing) = self.relayed.get_mut(msg.hash()) {
+            existing.extend(recipients.iter().copied());
+        } else {
+            let _ = self
+                .relayed
+                .insert(*msg.hash(), recipients.iter().copied().collect());
+        }
+    }
+
+    // Resets all the filters.
     pub fn reset(&mut self) {
         self.incoming.clear();
         self.outgoing.clear();
+        self.relayed.clear();
     }
 }
 
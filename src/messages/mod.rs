@@ -29,6 +29,10 @@ use std::fmt::{self, Debug, Formatter};
 use thiserror::Error;
 use xor_name::Prefix;
 
+/// Number of times a message can be relayed, unless the sender sets a different limit. Generous
+/// enough for any destination to be reached.
+pub(crate) const DEFAULT_HOP_LIMIT: u8 = 64;
+
 /// Message sent over the network.
 #[derive(Clone, Eq, Serialize, Deserialize)]
 pub(crate) struct Message {
@@ -47,6 +51,9 @@ pub(crate) struct Message {
     /// to determine the length of the proof of messages sent to the source so the source would
     /// trust it (the proof needs to start at this key).
     dst_key: Option<bls::PublicKey>,
+    /// Number of times the message can still be relayed. Decremented on every relay, so it's
+    /// neither signed nor hashed.
+    hop_limit: u8,
     /// Serialised message, this is a signed and fully serialised message ready to send.
     #[serde(skip)]
     serialized: Bytes,
@@ -87,8 +94,8 @@ impl Message {
             }
         }
 
-        msg.serialized = msg_bytes.clone();
-        msg.hash = MessageHash::from_bytes(&msg_bytes);
+        msg.hash = msg.compute_hash()?;
+        msg.serialized = msg_bytes;
 
         Ok(msg)
     }
@@ -112,12 +119,13 @@ impl Message {
             proof_chain,
             variant,
             dst_key,
+            hop_limit: DEFAULT_HOP_LIMIT,
             serialized: Default::default(),
             hash: Default::default(),
         };
 
         msg.serialized = bincode::serialize(&msg)?.into();
-        msg.hash = MessageHash::from_bytes(&msg.serialized);
+        msg.hash = msg.compute_hash()?;
 
         Ok(msg)
     }
@@ -238,6 +246,23 @@ impl Message {
         &self.hash
     }
 
+    /// Getter
+    pub fn hop_limit(&self) -> u8 {
+        self.hop_limit
+    }
+
+    /// Returns this message with the hop limit changed to `hop_limit`. The signature and the hash
+    /// stay the same.
+    pub(crate) fn with_hop_limit(&self, hop_limit: u8) -> Result<Self, CreateError> {
+        let mut msg = Self {
+            hop_limit,
+            ..self.clone()
+        };
+        msg.serialized = bincode::serialize(&msg)?.into();
+
+        Ok(msg)
+    }
+
     /// Returns the attached proof chain, if any.
     pub(crate) fn proof_chain(&self) -> Result<&SectionProofChain> {
         self.proof_chain.as_ref().ok_or(Error::InvalidMessage)
@@ -263,18 +288,35 @@ impl Message {
             return Err(ExtendProofChainError::NoProofChain);
         }
 
+        let hop_limit = self.hop_limit;
+
         Ok(Self::new_signed(
             self.src,
             self.dst,
             self.variant,
             self.proof_chain,
             self.dst_key,
-        )?)
+        )?
+        .with_hop_limit(hop_limit)?)
+    }
+
+    // Computes the hash of every field but the hop limit, so all the relayed copies of a message
+    // have the same hash.
+    fn compute_hash(&self) -> Result<MessageHash, CreateError> {
+        let bytes = bincode::serialize(&HashableView {
+            src: &self.src,
+            dst: &self.dst,
+            variant: &self.variant,
+            proof_chain: self.proof_chain.as_ref(),
+            dst_key: self.dst_key.as_ref(),
+        })?;
+
+        Ok(MessageHash::from_bytes(&bytes))
     }
 }
 
 // Ignore `serialized` and `hash` fields because they are only computed from the other fields and
-// in some cases might be even absent.
+// in some cases might be even absent. Ignore `hop_limit` too, as it changes on every relay.
 impl PartialEq for Message {
     fn eq(&self, other: &Self) -> bool {
         self.src == other.src
@@ -367,6 +409,16 @@ pub(crate) struct SignableView<'a> {
     pub variant: &'a Variant,
 }
 
+// View of a message that can be serialized for the purpose of hashing.
+#[derive(Serialize)]
+struct HashableView<'a> {
+    src: &'a SrcAuthority,
+    dst: &'a DstLocation,
+    variant: &'a Variant,
+    proof_chain: Option<&'a SectionProofChain>,
+    dst_key: Option<&'a bls::PublicKey>,
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -430,4 +482,25 @@ mod tests {
 
         Ok(())
     }
+
+    #[test]
+    fn with_hop_limit() -> Result<()> {
+        let node = Node::new(crypto::gen_keypair(), gen_addr());
+        let message = Message::single_src(
+            &node,
+            DstLocation::Section(rand::random()),
+            Variant::UserMessage(Bytes::from_static(b"hello")),
+            None,
+            None,
+        )?;
+        assert_eq!(message.hop_limit(), DEFAULT_HOP_LIMIT);
+
+        // The hop limit survives the round trip, while the signature and the hash are unaffected.
+        let relayed = Message::from_bytes(message.with_hop_limit(3)?.to_bytes())?;
+        assert_eq!(relayed.hop_limit(), 3);
+        assert_eq!(relayed.hash(), message.hash());
+        assert_eq!(relayed, message);
+
+        Ok(())
+    }
 }
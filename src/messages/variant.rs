@@ -66,6 +66,9 @@ pub(crate) enum Variant {
         elders_info: EldersInfo,
         section_key: bls::PublicKey,
     },
+    /// Response to a JoinRequest from a peer whose name is already used by another member of the
+    /// section.
+    JoinRejected,
     /// Sent from a node that can't establish the trust of the contained message to its original
     /// source in order for them to provide new proof that the node would trust.
     BouncedUntrustedMessage(Box<Message>),
@@ -116,6 +119,31 @@ pub(crate) enum Variant {
         nonce: [u8; 32],
         nonce_signature: Signature,
     },
+    /// First message of a dedicated stream carrying a user message in chunks. The chunks follow
+    /// on the same stream, terminated by an empty chunk.
+    UserMessageStream,
+    /// Sent to the elders of a neighbour section to ask them for their current `EldersInfo`. They
+    /// respond with `NeighbourInfo`.
+    NeighbourInfoRequest,
+    /// Sent by a member to the elders of its section to ask them to relocate it back into the same
+    /// section, under a new name, so it can rotate its keypair.
+    RelocateRequest,
+    /// Sent by a member to the elders of its section to ask them not to select it for relocation
+    /// on the given number of churn events.
+    RelocationDeferral(u32),
+    /// Sent by a member that requested a relocation deferral to the elders of its section to end
+    /// it early.
+    CancelRelocationDeferral,
+    /// User query sent on a dedicated bi-directional stream. The response is sent back as raw bytes
+    /// on the same stream.
+    UserQuery(Bytes),
+    /// Sent to an elder on a dedicated bi-directional stream to measure the offset between our
+    /// clocks. The elder responds on the same stream with its current time, as the duration since
+    /// the UNIX epoch.
+    ClockProbe,
+    /// Sent by a member that requested demotion to the elders of its section to let it be picked
+    /// as an elder again.
+    CancelDemotion,
 }
 
 impl Variant {
@@ -192,6 +220,7 @@ impl Debug for Variant {
                 .field("elders_info", elders_info)
                 .field("section_key", section_key)
                 .finish(),
+            Self::JoinRejected => write!(f, "JoinRejected"),
             Self::BouncedUntrustedMessage(message) => f
                 .debug_tuple("BouncedUntrustedMessage")
                 .field(message)
@@ -246,6 +275,16 @@ impl Debug for Variant {
                 .field("data_size", data_size)
                 .field("difficulty", difficulty)
                 .finish(),
+            Self::UserMessageStream => write!(f, "UserMessageStream"),
+            Self::NeighbourInfoRequest => write!(f, "NeighbourInfoRequest"),
+            Self::RelocateRequest => write!(f, "RelocateRequest"),
+            Self::RelocationDeferral(churn_events) => {
+                write!(f, "RelocationDeferral({})", churn_events)
+            }
+            Self::CancelRelocationDeferral => write!(f, "CancelRelocationDeferral"),
+            Self::UserQuery(payload) => write!(f, "UserQuery({:10})", HexFmt(payload)),
+            Self::ClockProbe => write!(f, "ClockProbe"),
+            Self::CancelDemotion => write!(f, "CancelDemotion"),
         }
     }
 }
@@ -17,6 +17,7 @@ use crate::{
     section::{EldersInfo, SectionProofChain},
 };
 
+use itertools::Itertools;
 use serde::{Deserialize, Serialize};
 use std::{collections::HashSet, iter};
 use xor_name::{Prefix, XorName};
@@ -48,6 +49,15 @@ impl Network {
             .min_by(|lhs, rhs| lhs.prefix.cmp_distance(&rhs.prefix, name))
     }
 
+    /// Returns up to `count` known sections closest to the given name, sorted by their distance to
+    /// it (closest first).
+    pub fn closest_n(&self, name: &XorName, count: usize) -> Vec<&EldersInfo> {
+        self.all()
+            .sorted_by(|lhs, rhs| lhs.prefix.cmp_distance(&rhs.prefix, name))
+            .take(count)
+            .collect()
+    }
+
     /// Returns iterator over all known sections.
     pub fn all(&self) -> impl Iterator<Item = &EldersInfo> + Clone {
         self.neighbours.iter().map(|info| &info.value)
@@ -154,6 +164,30 @@ impl Network {
         }
     }
 
+    /// Returns the prefixes of the sections we would keep track of if the known section with
+    /// `prefix` split into its two halves and we learned about both of them, sorted. The halves
+    /// that are not our neighbours would be pruned (see `prune_neighbours`). If we don't know a
+    /// section with `prefix`, returns the current prefixes.
+    pub fn prefixes_after_split(&self, our_prefix: &Prefix, prefix: &Prefix) -> Vec<Prefix> {
+        let mut known: Vec<_> = self.prefixes().copied().collect();
+        if !known.contains(prefix) {
+            known.sort();
+            return known;
+        }
+
+        known.retain(|known_prefix| known_prefix != prefix);
+        known.push(prefix.pushed(false));
+        known.push(prefix.pushed(true));
+
+        let mut prefixes: Vec<_> = known
+            .iter()
+            .filter(|known_prefix| !can_prune_neighbour(our_prefix, known_prefix, &known))
+            .copied()
+            .collect();
+        prefixes.sort();
+        prefixes
+    }
+
     /// Returns the known section keys.
     pub fn keys(&self) -> impl Iterator<Item = (&Prefix, &bls::PublicKey)> {
         self.keys
@@ -161,6 +195,21 @@ impl Network {
             .map(|entry| (&entry.value.0, &entry.value.1))
     }
 
+    /// Returns the latest known key of each neighbour section, from `keys`. Neighbours we don't
+    /// know any key of yet are skipped. (Their elders info is signed by our section, not by them,
+    /// so it tells nothing about their key.)
+    pub fn neighbour_keys(&self) -> impl Iterator<Item = (&Prefix, &bls::PublicKey)> {
+        self.neighbours.iter().filter_map(move |info| {
+            let prefix = &info.value.prefix;
+            self.keys.get(prefix).map(|entry| (prefix, &entry.value.1))
+        })
+    }
+
+    /// Returns the known section keys together with the proofs of our section agreeing on them.
+    pub fn proven_keys(&self) -> impl Iterator<Item = &Proven<(Prefix, bls::PublicKey)>> {
+        self.keys.iter()
+    }
+
     pub fn has_key(&self, key: &bls::PublicKey) -> bool {
         self.keys.iter().any(|entry| entry.value.1 == *key)
     }
@@ -170,6 +219,12 @@ impl Network {
         self.keys.get_matching(name).map(|entry| &entry.value.1)
     }
 
+    /// Returns the key of our section that signed our record of the latest key of the section with
+    /// the given prefix, or `None` if we don't know their key.
+    pub fn their_key_signer(&self, prefix: &Prefix) -> Option<&bls::PublicKey> {
+        self.keys.get(prefix).map(|entry| &entry.proof.public_key)
+    }
+
     /// Returns the elders_info and the latest known key for the prefix that matches `name`,
     /// excluding self section.
     pub fn section_by_name(&self, name: &XorName) -> (Option<bls::PublicKey>, Option<EldersInfo>) {
@@ -446,6 +501,36 @@ mod tests {
         assert_eq!(map.closest(&n11).map(|i| &i.prefix), Some(&p10));
     }
 
+    #[test]
+    fn closest_n() {
+        let sk = bls::SecretKey::random();
+
+        let p00: Prefix = "00".parse().unwrap();
+        let p01: Prefix = "01".parse().unwrap();
+        let p10: Prefix = "10".parse().unwrap();
+        let p11: Prefix = "11".parse().unwrap();
+
+        let mut map = Network::new();
+        for prefix in &[p00, p01, p10, p11] {
+            let _ = map.update_neighbour_info(gen_proven_elders_info(&sk, *prefix));
+        }
+
+        let name = p01.substituted_in(rand::thread_rng().gen());
+        let prefixes = |count| -> Vec<_> {
+            map.closest_n(&name, count)
+                .into_iter()
+                .map(|info| info.prefix)
+                .collect()
+        };
+
+        assert_eq!(prefixes(0), vec![]);
+        assert_eq!(prefixes(1), vec![p01]);
+        assert_eq!(prefixes(2), vec![p01, p00]);
+        assert_eq!(prefixes(3), vec![p01, p00, p11]);
+        // Fewer sections than requested are known.
+        assert_eq!(prefixes(10), vec![p01, p00, p11, p10]);
+    }
+
     #[test]
     fn prune_neighbours() {
         let sk = bls::SecretKey::random();
@@ -471,6 +556,34 @@ mod tests {
         assert!(map.prefixes().all(|&prefix| prefix != p1));
     }
 
+    #[test]
+    fn prefixes_after_split() {
+        let sk = bls::SecretKey::random();
+
+        let p00: Prefix = "00".parse().unwrap();
+        let p01: Prefix = "01".parse().unwrap();
+        let p010: Prefix = "010".parse().unwrap();
+        let p011: Prefix = "011".parse().unwrap();
+        let p1: Prefix = "1".parse().unwrap();
+        let p10: Prefix = "10".parse().unwrap();
+
+        // We are (00) and know our neighbours (01) and (1).
+        let mut map = Network::new();
+        for prefix in &[p01, p1] {
+            let _ = map.update_neighbour_info(gen_proven_elders_info(&sk, *prefix));
+        }
+        map.prune_neighbours(&p00);
+
+        // Both halves of (01) are our neighbours.
+        assert_eq!(map.prefixes_after_split(&p00, &p01), vec![p010, p011, p1]);
+        // (10) is our neighbour, but (11) is not.
+        assert_eq!(map.prefixes_after_split(&p00, &p1), vec![p01, p10]);
+        // Unknown section.
+        assert_eq!(map.prefixes_after_split(&p00, &p10), vec![p01, p1]);
+        // The network itself is not affected.
+        assert_eq!(map.prefixes().copied().collect::<Vec<_>>(), vec![p01, p1]);
+    }
+
     // Create a `Network` and apply a series of `update_keys` calls to it, then verify the stored
     // keys are as expected.
     //
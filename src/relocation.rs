@@ -11,7 +11,7 @@
 use crate::{
     crypto::{self, Keypair, Signature, Verifier},
     error::Error,
-    messages::{Message, Variant},
+    messages::{Message, Variant, VerifyStatus},
     network::Network,
     peer::Peer,
     section::{MemberInfo, Section},
@@ -19,22 +19,26 @@ use crate::{
 use bytes::Bytes;
 use serde::{de::Error as SerdeDeError, Deserialize, Deserializer, Serialize, Serializer};
 use sn_messaging::MessageType;
-use std::net::SocketAddr;
+use std::{collections::BTreeSet, fmt::Debug, net::SocketAddr};
 use tokio::sync::mpsc;
-use xor_name::XorName;
+use xor_name::{Prefix, XorName};
 
 /// Find all nodes to relocate after a churn event and create the relocate actions for them.
+/// The `deferred` nodes are not eligible for relocation.
 pub(crate) fn actions(
     section: &Section,
     network: &Network,
     churn_name: &XorName,
     churn_signature: &bls::Signature,
+    deferred: &BTreeSet<XorName>,
+    age_policy: &dyn AgePolicy,
 ) -> Vec<(MemberInfo, RelocateAction)> {
     // Find the peers that pass the relocation check and take only the oldest ones to avoid
     // relocating too many nodes at the same time.
     let candidates: Vec<_> = section
         .members()
         .joined()
+        .filter(|info| !deferred.contains(info.peer.name()))
         .filter(|info| check(info.peer.age(), churn_signature))
         .collect();
 
@@ -50,12 +54,47 @@ pub(crate) fn actions(
         .map(|info| {
             (
                 *info,
-                RelocateAction::new(section, network, &info.peer, churn_name),
+                RelocateAction::new(section, network, &info.peer, churn_name, age_policy),
             )
         })
         .collect()
 }
 
+/// Policy deciding the age a node gets when relocated due to churn.
+///
+/// The policy is a local setting. It's not exchanged between nodes nor checked when a node joins.
+/// The relocation details (including the new age) are signed by the elders of the source section
+/// collectively, so elders using different policies fail to sign them and the relocation never
+/// goes through. It's up to whoever runs the nodes to configure all of them with the same policy.
+pub trait AgePolicy: Debug + Send + Sync {
+    /// Returns the age of a node with the `current` age after being relocated. `churn_count` is
+    /// the number of elder changes the source section went through so far (that is, the index of
+    /// its current section key).
+    fn next_age(&self, current: u8, churn_count: u64) -> u8;
+}
+
+/// The default `AgePolicy` which increments the age by one on every relocation.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct DefaultAgePolicy;
+
+impl AgePolicy for DefaultAgePolicy {
+    fn next_age(&self, current: u8, _churn_count: u64) -> u8 {
+        post_relocation_age(current)
+    }
+}
+
+/// Relocation of one of our members computed by us on a churn event (see
+/// `Routing::relocation_history`).
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct RelocationRecord {
+    /// Name of the member being relocated.
+    pub relocating_name: XorName,
+    /// Name of the node whose joining or leaving triggered the relocation.
+    pub churn_name: XorName,
+    /// Name the destination section of the relocation is picked by.
+    pub destination: XorName,
+}
+
 /// Details of a relocation: which node to relocate, where to relocate it to and what age it should
 /// get once relocated.
 #[derive(Debug, Serialize, Deserialize, PartialEq, Eq, PartialOrd, Ord, Hash, Clone)]
@@ -77,14 +116,10 @@ impl RelocateDetails {
         network: &Network,
         peer: &Peer,
         destination: XorName,
+        age_policy: &dyn AgePolicy,
     ) -> Self {
-        Self::with_age(
-            section,
-            network,
-            peer,
-            destination,
-            peer.age().saturating_add(1),
-        )
+        let age = age_policy.next_age(peer.age(), section.chain().last_key_index());
+        Self::with_age(section, network, peer, destination, age)
     }
 
     pub(crate) fn with_age(
@@ -191,6 +226,30 @@ impl RelocatePayload {
             .is_ok()
     }
 
+    /// Verifies the whole payload: that the relocation details are signed by a section trusted
+    /// via `trusted_keys` and that the relocated node proved its identity by signing `new_name`
+    /// with its old key. Returns `Error::UntrustedMessage` if the signature of the details is
+    /// valid but not trusted and `Error::FailedSignature` if any signature is invalid.
+    pub fn verify(&self, new_name: &XorName, trusted_keys: &[bls::PublicKey]) -> Result<(), Error> {
+        let signed_msg = self.details.signed_msg();
+        signed_msg.src().check_is_section()?;
+
+        // The keys are not associated with any particular prefix, so trust them for any.
+        let any_prefix = Prefix::default();
+        let trusted_keys = trusted_keys.iter().map(|key| (&any_prefix, key));
+
+        match signed_msg.verify(trusted_keys)? {
+            VerifyStatus::Full => (),
+            VerifyStatus::Unknown => return Err(Error::UntrustedMessage),
+        }
+
+        if self.verify_identity(new_name) {
+            Ok(())
+        } else {
+            Err(Error::FailedSignature)
+        }
+    }
+
     pub fn relocate_details(&self) -> &RelocateDetails {
         self.details.relocate_details()
     }
@@ -222,7 +281,13 @@ pub(crate) enum RelocateAction {
 }
 
 impl RelocateAction {
-    pub fn new(section: &Section, network: &Network, peer: &Peer, churn_name: &XorName) -> Self {
+    pub fn new(
+        section: &Section,
+        network: &Network,
+        peer: &Peer,
+        churn_name: &XorName,
+        age_policy: &dyn AgePolicy,
+    ) -> Self {
         let destination = destination(peer.name(), churn_name);
 
         if section.is_elder(peer.name()) {
@@ -231,7 +296,13 @@ impl RelocateAction {
                 destination,
             })
         } else {
-            RelocateAction::Instant(RelocateDetails::new(section, network, peer, destination))
+            RelocateAction::Instant(RelocateDetails::new(
+                section,
+                network,
+                peer,
+                destination,
+                age_policy,
+            ))
         }
     }
 
@@ -259,9 +330,14 @@ pub(crate) fn check(age: u8, churn_signature: &bls::Signature) -> bool {
     trailing_zeros(&churn_signature.to_bytes()[..]) >= age as u32
 }
 
+// Age a node with `current_age` gets when relocated due to churn.
+pub(crate) fn post_relocation_age(current_age: u8) -> u8 {
+    current_age.saturating_add(1)
+}
+
 // Compute the destination for the node with `relocating_name` to be relocated to. `churn_name` is
 // the name of the joined/left node that triggered the relocation.
-fn destination(relocating_name: &XorName, churn_name: &XorName) -> XorName {
+pub(crate) fn destination(relocating_name: &XorName, churn_name: &XorName) -> XorName {
     let combined_name = xor(relocating_name, churn_name);
     XorName(crypto::sha3_256(&combined_name.0))
 }
@@ -296,8 +372,9 @@ fn trailing_zeros(bytes: &[u8]) -> u32 {
 mod tests {
     use super::*;
     use crate::{
-        consensus::test_utils::proven, peer::test_utils::arbitrary_unique_peers,
-        section::EldersInfo, SectionProofChain, ELDER_SIZE, MIN_AGE,
+        consensus::test_utils::proven, location::DstLocation, messages::PlainMessage,
+        peer::test_utils::arbitrary_unique_peers, section::EldersInfo, SectionProofChain,
+        ELDER_SIZE, MIN_AGE,
     };
     use anyhow::Result;
     use assert_matches::assert_matches;
@@ -318,6 +395,84 @@ mod tests {
         assert_eq!(trailing_zeros(&[2, 0]), 9);
     }
 
+    #[test]
+    fn verify_relocate_payload() -> Result<()> {
+        let section_sk = bls::SecretKey::random();
+        let old_keypair = crypto::gen_keypair();
+        let new_name = crypto::name(&crypto::gen_keypair().public);
+
+        let payload = create_relocate_payload(&section_sk, &old_keypair, &new_name)?;
+        payload.verify(&new_name, &[section_sk.public_key()])?;
+
+        Ok(())
+    }
+
+    #[test]
+    fn verify_relocate_payload_with_bad_identity_signature() -> Result<()> {
+        let section_sk = bls::SecretKey::random();
+        let old_keypair = crypto::gen_keypair();
+        let new_name = crypto::name(&crypto::gen_keypair().public);
+
+        let payload = create_relocate_payload(&section_sk, &old_keypair, &new_name)?;
+        let other_name = rand::random();
+
+        assert_matches!(
+            payload.verify(&other_name, &[section_sk.public_key()]),
+            Err(Error::FailedSignature)
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn verify_relocate_payload_with_untrusted_details() -> Result<()> {
+        let section_sk = bls::SecretKey::random();
+        let old_keypair = crypto::gen_keypair();
+        let new_name = crypto::name(&crypto::gen_keypair().public);
+
+        let payload = create_relocate_payload(&section_sk, &old_keypair, &new_name)?;
+        let other_key = bls::SecretKey::random().public_key();
+
+        assert_matches!(
+            payload.verify(&new_name, &[other_key]),
+            Err(Error::UntrustedMessage)
+        );
+
+        Ok(())
+    }
+
+    fn create_relocate_payload(
+        section_sk: &bls::SecretKey,
+        old_keypair: &Keypair,
+        new_name: &XorName,
+    ) -> Result<RelocatePayload> {
+        let section_key = section_sk.public_key();
+        let old_name = crypto::name(&old_keypair.public);
+
+        let plain = PlainMessage {
+            src: Prefix::default(),
+            dst: DstLocation::Node(old_name),
+            dst_key: section_key,
+            variant: Variant::Relocate(RelocateDetails {
+                pub_id: old_name,
+                destination: rand::random(),
+                destination_key: section_key,
+                age: MIN_AGE + 1,
+            }),
+        };
+        let signature = section_sk.sign(&bincode::serialize(&plain.as_signable())?);
+        let message = Message::section_src(plain, signature, SectionProofChain::new(section_key))?;
+        let details = SignedRelocateDetails::new(message)?;
+
+        Ok(RelocatePayload::new(details, new_name, old_keypair))
+    }
+
+    #[test]
+    fn relocation_age() {
+        assert_eq!(post_relocation_age(MIN_AGE), MIN_AGE + 1);
+        assert_eq!(post_relocation_age(u8::MAX), u8::MAX);
+    }
+
     const MAX_AGE: u8 = MIN_AGE + 4;
 
     proptest! {
@@ -369,7 +524,14 @@ mod tests {
         let churn_name = rng.gen();
         let churn_signature = signature_with_trailing_zeros(signature_trailing_zeros as u32);
 
-        let actions = actions(&section, &network, &churn_name, &churn_signature);
+        let actions = actions(
+            &section,
+            &network,
+            &churn_name,
+            &churn_signature,
+            &BTreeSet::new(),
+            &DefaultAgePolicy,
+        );
         let actions: Vec<_> = actions
             .into_iter()
             .map(|(_, action)| action)
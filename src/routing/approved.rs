@@ -6,31 +6,35 @@
 // KIND, either express or implied. Please review the Licences for the specific language governing
 // permissions and limitations relating to use of the SAFE Network Software.
 
-use super::{Command, SplitBarrier};
+use super::{
+    command, digest::RoutingDigest, event_stream::MembershipObservers,
+    neighbour_reconciliation::NeighbourReconciliation, Command, EventSink, IncomingInspector,
+    SplitBarrier,
+};
 use crate::{
     consensus::{
         DkgCommands, DkgFailureProof, DkgFailureProofSet, DkgKey, DkgVoter, Proof, ProofShare,
-        Proven, Vote, VoteAccumulationError, VoteAccumulator,
+        Proven, Quorum, Vote, VoteAccumulationError, VoteAccumulator,
     },
     crypto, delivery_group,
     error::{Error, Result},
-    event::{Event, NodeElderChange},
+    event::{DropReason, Event, MembershipEvent, NodeElderChange},
     location::{DstLocation, SrcLocation},
     message_filter::MessageFilter,
     messages::{
         JoinRequest, Message, MessageHash, MessageStatus, PlainMessage, ResourceProofResponse,
-        Variant, VerifyStatus,
+        Variant, VerifyStatus, DEFAULT_HOP_LIMIT,
     },
     network::Network,
     node::Node,
     peer::Peer,
     relocation::{
-        self, RelocateAction, RelocateDetails, RelocatePromise, RelocateState,
-        SignedRelocateDetails,
+        self, AgePolicy, DefaultAgePolicy, RelocateAction, RelocateDetails, RelocatePromise,
+        RelocateState, RelocationRecord, SignedRelocateDetails,
     },
     section::{
-        EldersInfo, MemberInfo, PeerState, Section, SectionKeyShare, SectionKeysProvider,
-        SectionProofChain, MIN_AGE,
+        EldersInfo, MemberInfo, MembershipProof, PeerState, Section, SectionKeyShare,
+        SectionKeysProvider, SectionProofChain, TrustStatus, MIN_AGE,
     },
     ELDER_SIZE, RECOMMENDED_SECTION_SIZE,
 };
@@ -38,19 +42,102 @@ use bls_dkg::key_gen::message::Message as DkgMessage;
 use bytes::Bytes;
 use ed25519_dalek::Verifier;
 use itertools::Itertools;
+use rand::seq::SliceRandom;
 use resource_proof::ResourceProof;
+use serde::{Deserialize, Serialize};
 use sn_messaging::{
     infrastructure::{GetSectionResponse, Query},
     node::NodeMessage,
     MessageType,
 };
-use std::{cmp, net::SocketAddr, slice};
+use std::{
+    cmp,
+    collections::{BTreeMap, BTreeSet, HashMap, VecDeque},
+    iter,
+    net::SocketAddr,
+    panic::{self, AssertUnwindSafe},
+    slice,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc,
+    },
+    time::{Duration, Instant},
+};
 use tokio::sync::mpsc;
 use xor_name::{Prefix, XorName};
 
 pub(crate) const RESOURCE_PROOF_DATA_SIZE: usize = 64;
 pub(crate) const RESOURCE_PROOF_DIFFICULTY: u8 = 2;
 const KEY_CACHE_SIZE: u8 = 5;
+// Number of most recently picked elders to avoid when picking an elder to send to.
+const RECENT_ELDERS_LEN: usize = 2;
+// Number of the most recent relocations computed by us to keep.
+const RELOCATION_HISTORY_LEN: usize = 64;
+// Number of the most recent elder sets of our section to keep.
+const ELDERS_HISTORY_LEN: usize = 64;
+// Number of the most recent churn events to estimate the churn rate from.
+const CHURN_HISTORY_LEN: usize = 32;
+// Minimal number of churn events needed to estimate the churn rate.
+const MIN_CHURN_SAMPLES: usize = 3;
+// Largest number of churn events a member's relocation can be deferred for. It's also for how many
+// churn events the member has to stay eligible for relocation after that before it can ask again.
+const MAX_RELOCATION_DEFERRAL: u32 = 16;
+
+/// How to handle user messages from a section we don't know any key of, so we can't establish trust
+/// in the message. Other messages from such sections are always bounced.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum UnknownSectionPolicy {
+    /// Bounce the message back to its source for it to resend it with a proof we can trust.
+    Bounce,
+    /// Drop the message.
+    Drop,
+    /// Accept the message if its signature matches the last key of its proof chain and the chain
+    /// itself is valid. Drop it otherwise. Note this doesn't establish trust: anyone can make up
+    /// such a chain.
+    VerifyThenAccept,
+    /// Accept the message without any verification.
+    AcceptUnverified,
+}
+
+impl Default for UnknownSectionPolicy {
+    fn default() -> Self {
+        Self::Bounce
+    }
+}
+
+/// Key our section held at some point, as listed by `Routing::key_rotation_log`.
+#[derive(Clone, Debug, Eq, PartialEq, Serialize, Deserialize)]
+pub struct KeyRotationEntry {
+    /// Index of the key in our section proof chain.
+    pub version: u64,
+    /// Prefix of our section while it held the key, or `None` if we don't remember the elders that
+    /// held it (see `Routing::elders_at_version`).
+    pub prefix: Option<Prefix>,
+    /// The section key.
+    pub key: bls::PublicKey,
+    /// Slice of our section proof chain from the key of the previous entry to this one. It proves
+    /// this key was signed by the previous one. `None` for the first entry and for the first entry
+    /// after keys removed by compaction, as we no longer have the blocks that would prove it.
+    pub proof_slice_from_previous: Option<SectionProofChain>,
+}
+
+// Settings of the approved state that come from the `Config`. They are carried over to the new
+// state when we relocate.
+#[derive(Clone)]
+pub(crate) struct ApprovedSettings {
+    pub unknown_section_policy: UnknownSectionPolicy,
+    pub incoming_inspector: Option<Arc<dyn IncomingInspector>>,
+    pub event_sink: Option<Arc<dyn EventSink>>,
+    pub age_policy: Arc<dyn AgePolicy>,
+    pub min_elder_age: u8,
+    pub strict_proof_verification: bool,
+    pub require_signed_messages: bool,
+    pub max_proof_chain_len: Option<usize>,
+    pub quorum: Quorum,
+    pub neighbour_reconciliation_interval: Option<Duration>,
+    #[cfg(any(test, feature = "testing"))]
+    pub section_override: Option<Arc<delivery_group::SectionOverride>>,
+}
 
 // The approved stage - node is a full member of a section and is performing its duties according
 // to its persona (adult or elder).
@@ -68,6 +155,55 @@ pub(crate) struct Approved {
     pub(super) event_tx: mpsc::UnboundedSender<Event>,
     joins_allowed: bool,
     resource_proof: ResourceProof,
+    // Elders most recently picked by `send_to_any_elder`, most recent last.
+    recent_elders: VecDeque<XorName>,
+    unknown_section_policy: UnknownSectionPolicy,
+    incoming_inspector: Option<Arc<dyn IncomingInspector>>,
+    event_sink: Option<Arc<dyn EventSink>>,
+    #[cfg(any(test, feature = "testing"))]
+    section_override: Option<Arc<delivery_group::SectionOverride>>,
+    // Elders that the section agreed to let step down. They are not picked as elders again until
+    // they cancel it or leave the section.
+    stepping_down: BTreeSet<XorName>,
+    // When we agreed to relocate each of our members whose relocation hasn't been carried out yet.
+    relocations_started: HashMap<XorName, Instant>,
+    // Members whose relocation deferral we agreed on, with the number of churn events until they
+    // can defer again. They are not relocated while this is above `MAX_RELOCATION_DEFERRAL`.
+    // Dropped whenever our elders change, as new elders don't know about them.
+    relocation_deferrals: HashMap<XorName, u32>,
+    // Timer that ends the relocation deferral we requested, if any.
+    relocation_deferral_timer: Option<u64>,
+    // When each of our current members joined (or relocated in), as observed by us. Members that
+    // joined before we did are not present.
+    joined_at: HashMap<XorName, Instant>,
+    // Number of churn events (members joining or leaving) in our section since we joined it. Each
+    // of them relocates us with an increased age with the probability of `2^-age`.
+    churn_count: u32,
+    // When the most recent churn events happened, oldest first.
+    churn_times: VecDeque<Instant>,
+    // The most recent elder sets of our section we saw becoming our elders, by the index of the
+    // key they hold in our chain. Local only: the proof chain doesn't carry them.
+    elders_history: BTreeMap<u64, EldersInfo>,
+    // Number of `Event::MessageDropped` raised so far.
+    messages_dropped: AtomicU64,
+    // The most recent relocations computed by us on churn, oldest first.
+    relocation_history: VecDeque<RelocationRecord>,
+    // Subscribers to the membership events.
+    membership_observers: Arc<MembershipObservers>,
+    age_policy: Arc<dyn AgePolicy>,
+    // If true, messages whose proof chain is not rooted in any of our trusted keys are dropped.
+    strict_proof_verification: bool,
+    // If true, user messages not signed by their claimed source are dropped.
+    require_signed_messages: bool,
+    // If set, our section proof chain is compacted to this many blocks after the first key.
+    max_proof_chain_len: Option<usize>,
+    // Fraction of the elders required to agree.
+    quorum: Quorum,
+    // Members younger than this are not promoted to elders unless there aren't enough members old
+    // enough.
+    min_elder_age: u8,
+    // Periodic querying of our neighbours for their current info or `None` if disabled.
+    neighbour_reconciliation: Option<NeighbourReconciliation>,
 }
 
 impl Approved {
@@ -86,7 +222,7 @@ impl Approved {
     ) -> Self {
         let section_keys_provider = SectionKeysProvider::new(KEY_CACHE_SIZE, section_key_share);
 
-        Self {
+        let mut state = Self {
             node,
             section,
             network: Network::new(),
@@ -99,9 +235,220 @@ impl Approved {
             event_tx,
             joins_allowed: true,
             resource_proof: ResourceProof::new(RESOURCE_PROOF_DATA_SIZE, RESOURCE_PROOF_DIFFICULTY),
+            recent_elders: VecDeque::with_capacity(RECENT_ELDERS_LEN),
+            unknown_section_policy: UnknownSectionPolicy::default(),
+            incoming_inspector: None,
+            event_sink: None,
+            #[cfg(any(test, feature = "testing"))]
+            section_override: None,
+            stepping_down: BTreeSet::new(),
+            relocations_started: HashMap::new(),
+            relocation_deferrals: HashMap::new(),
+            relocation_deferral_timer: None,
+            joined_at: HashMap::new(),
+            churn_count: 0,
+            churn_times: VecDeque::with_capacity(CHURN_HISTORY_LEN),
+            elders_history: BTreeMap::new(),
+            messages_dropped: AtomicU64::new(0),
+            relocation_history: VecDeque::with_capacity(RELOCATION_HISTORY_LEN),
+            membership_observers: Arc::new(MembershipObservers::default()),
+            age_policy: Arc::new(DefaultAgePolicy),
+            strict_proof_verification: false,
+            require_signed_messages: false,
+            max_proof_chain_len: None,
+            quorum: Quorum::default(),
+            min_elder_age: MIN_AGE,
+            neighbour_reconciliation: None,
+        };
+
+        state.record_elders();
+        state
+    }
+
+    pub fn with_settings(self, settings: ApprovedSettings) -> Self {
+        let state = self
+            .with_unknown_section_policy(settings.unknown_section_policy)
+            .with_incoming_inspector(settings.incoming_inspector)
+            .with_event_sink(settings.event_sink)
+            .with_age_policy(settings.age_policy)
+            .with_min_elder_age(settings.min_elder_age)
+            .with_strict_proof_verification(settings.strict_proof_verification)
+            .with_signed_messages_required(settings.require_signed_messages)
+            .with_max_proof_chain_len(settings.max_proof_chain_len)
+            .with_quorum(settings.quorum)
+            .with_neighbour_reconciliation(settings.neighbour_reconciliation_interval);
+        #[cfg(any(test, feature = "testing"))]
+        let state = state.with_section_override(settings.section_override);
+        state
+    }
+
+    pub fn settings(&self) -> ApprovedSettings {
+        ApprovedSettings {
+            unknown_section_policy: self.unknown_section_policy,
+            incoming_inspector: self.incoming_inspector.clone(),
+            event_sink: self.event_sink.clone(),
+            age_policy: self.age_policy.clone(),
+            min_elder_age: self.min_elder_age,
+            strict_proof_verification: self.strict_proof_verification,
+            require_signed_messages: self.require_signed_messages,
+            max_proof_chain_len: self.max_proof_chain_len,
+            quorum: self.quorum,
+            neighbour_reconciliation_interval: self
+                .neighbour_reconciliation
+                .as_ref()
+                .map(NeighbourReconciliation::interval),
+            #[cfg(any(test, feature = "testing"))]
+            section_override: self.section_override.clone(),
+        }
+    }
+
+    pub fn with_unknown_section_policy(mut self, policy: UnknownSectionPolicy) -> Self {
+        self.unknown_section_policy = policy;
+        self
+    }
+
+    pub fn with_incoming_inspector(
+        mut self,
+        inspector: Option<Arc<dyn IncomingInspector>>,
+    ) -> Self {
+        self.incoming_inspector = inspector;
+        self
+    }
+
+    pub fn with_event_sink(mut self, sink: Option<Arc<dyn EventSink>>) -> Self {
+        self.event_sink = sink;
+        self
+    }
+
+    #[cfg(any(test, feature = "testing"))]
+    pub fn with_section_override(
+        mut self,
+        section_override: Option<Arc<delivery_group::SectionOverride>>,
+    ) -> Self {
+        self.section_override = section_override;
+        self
+    }
+
+    pub fn with_age_policy(mut self, age_policy: Arc<dyn AgePolicy>) -> Self {
+        self.age_policy = age_policy;
+        self
+    }
+
+    pub fn with_membership_observers(mut self, observers: Arc<MembershipObservers>) -> Self {
+        self.membership_observers = observers;
+        self
+    }
+
+    pub fn membership_observers(&self) -> Arc<MembershipObservers> {
+        self.membership_observers.clone()
+    }
+
+    /// Returns the number of `Event::MessageDropped` raised so far.
+    pub fn messages_dropped(&self) -> u64 {
+        self.messages_dropped.load(Ordering::Relaxed)
+    }
+
+    /// Returns the most recent relocations computed by us on churn, oldest first.
+    pub fn relocation_history(&self) -> Vec<RelocationRecord> {
+        self.relocation_history.iter().copied().collect()
+    }
+
+    /// Returns the elders of our section that held the key at the given index of our chain, if we
+    /// saw them become our elders and still remember them.
+    pub fn elders_at_version(&self, version: u64) -> Option<EldersInfo> {
+        self.elders_history.get(&version).cloned()
+    }
+
+    pub fn key_rotation_log(&self) -> Vec<KeyRotationEntry> {
+        let chain = self.section.chain();
+        let mut previous_version = None;
+
+        chain
+            .indexed_keys()
+            .map(|(version, key)| {
+                // Keys are only missing from the chain where it was compacted, and we can't prove
+                // a key across that gap.
+                let proof_slice_from_previous = previous_version
+                    .filter(|previous_version| previous_version + 1 == version)
+                    .map(|previous_version| chain.slice(previous_version..=version));
+                previous_version = Some(version);
+
+                KeyRotationEntry {
+                    version,
+                    prefix: self
+                        .elders_history
+                        .get(&version)
+                        .map(|elders_info| elders_info.prefix),
+                    key: *key,
+                    proof_slice_from_previous,
+                }
+            })
+            .collect()
+    }
+
+    // Remembers our current elders as the holders of the key their `EldersInfo` is signed with.
+    fn record_elders(&mut self) {
+        let elders_info = self.section.proven_elders_info();
+        let index =
+            if let Some(index) = self.section.chain().index_of(&elders_info.proof.public_key) {
+                index
+            } else {
+                return;
+            };
+
+        let _ = self.elders_history.insert(index, elders_info.value.clone());
+
+        while self.elders_history.len() > ELDERS_HISTORY_LEN {
+            if let Some(oldest) = self.elders_history.keys().next().copied() {
+                let _ = self.elders_history.remove(&oldest);
+            }
         }
     }
 
+    pub fn with_strict_proof_verification(mut self, strict: bool) -> Self {
+        self.strict_proof_verification = strict;
+        self
+    }
+
+    pub fn with_signed_messages_required(mut self, required: bool) -> Self {
+        self.require_signed_messages = required;
+        self
+    }
+
+    pub fn signed_messages_required(&self) -> bool {
+        self.require_signed_messages
+    }
+
+    pub fn with_max_proof_chain_len(mut self, max_len: Option<usize>) -> Self {
+        self.max_proof_chain_len = max_len;
+        self
+    }
+
+    pub fn with_quorum(mut self, quorum: Quorum) -> Self {
+        self.quorum = quorum;
+        // Called right after construction, so there are no DKG sessions to lose yet.
+        self.dkg_voter = DkgVoter::new(quorum);
+        self
+    }
+
+    pub fn with_min_elder_age(mut self, min_elder_age: u8) -> Self {
+        self.min_elder_age = min_elder_age;
+        self
+    }
+
+    pub fn with_neighbour_reconciliation(mut self, interval: Option<Duration>) -> Self {
+        self.neighbour_reconciliation = interval.map(NeighbourReconciliation::new);
+        self
+    }
+
+    /// Returns the command scheduling the next round of the neighbour reconciliation, or `None`
+    /// if it's disabled.
+    pub fn schedule_neighbour_reconciliation(&mut self) -> Option<Command> {
+        self.neighbour_reconciliation
+            .as_mut()
+            .map(NeighbourReconciliation::schedule)
+    }
+
     pub fn node(&self) -> &Node {
         &self.node
     }
@@ -114,11 +461,123 @@ impl Approved {
         &self.network
     }
 
+    /// Returns the compact summary of our network view: our prefix and key and the keys of the
+    /// other sections we know.
+    pub fn routing_digest(&self) -> RoutingDigest {
+        RoutingDigest {
+            prefix: *self.section.prefix(),
+            key: *self.section.chain().last_key(),
+            keys: self.network.proven_keys().cloned().collect(),
+        }
+    }
+
+    /// Merges the section keys from a digest of another node into our network view. A key is
+    /// merged only if it's signed by a key of our section chain, and for a section we already know
+    /// a key of, only if it's signed by a later key than the current one. Keys signed by other
+    /// sections are skipped, as any of them could vouch for arbitrary prefixes otherwise. So are
+    /// the keys of our own section, of its sub-sections and of its ancestors. Returns
+    /// `Error::FailedSignature`, without merging anything, if any of the keys has an invalid
+    /// signature.
+    pub fn apply_digest(&mut self, digest: RoutingDigest) -> Result<()> {
+        if !digest.keys.iter().all(Proven::self_verify) {
+            return Err(Error::FailedSignature);
+        }
+
+        let our_prefix = *self.section.prefix();
+        let chain = self.section.chain();
+
+        for key in digest.keys {
+            let prefix = key.value.0;
+            if prefix.is_compatible(&our_prefix) {
+                continue;
+            }
+
+            let signer = &key.proof.public_key;
+            let trusted = if let Some(current_signer) = self.network.their_key_signer(&prefix) {
+                match (chain.index_of(current_signer), chain.index_of(signer)) {
+                    (Some(current), Some(new)) => new > current,
+                    _ => false,
+                }
+            } else {
+                chain.has_key(signer)
+            };
+
+            if trusted {
+                let _ = self.network.update_their_key(key);
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Returns the prefix of the section `dst` belongs to, to the best of our knowledge, or `None`
+    /// if `dst` is `Direct` or we don't know any section it could belong to.
+    pub fn destination_prefix(&self, dst: &DstLocation) -> Option<Prefix> {
+        let name = dst.name()?;
+        if self.section.prefix().matches(name) {
+            Some(*self.section.prefix())
+        } else {
+            self.network.closest(name).map(|info| info.prefix)
+        }
+    }
+
     /// Is this node an elder?
     pub fn is_elder(&self) -> bool {
         self.section.is_elder(&self.node.name())
     }
 
+    /// Returns the proof that we are a member of our section, with the part of our section chain
+    /// needed to verify it starting from the genesis key.
+    pub fn membership_proof(&self) -> Result<MembershipProof> {
+        let member_info = self
+            .section
+            .members()
+            .get_proven(&self.node.name())
+            .filter(|info| info.value.state == PeerState::Joined)
+            .ok_or(Error::InvalidState)?;
+        let index = self
+            .section
+            .chain()
+            .index_of(&member_info.proof.public_key)
+            .ok_or(Error::InvalidState)?;
+        let chain = self.section.chain().slice(..=index);
+
+        // The removed blocks can't be sent, so the proof couldn't be verified from the genesis key.
+        if chain.is_compacted() {
+            return Err(Error::InvalidState);
+        }
+
+        Ok(MembershipProof::new(member_info.clone(), chain))
+    }
+
+    /// Returns whether we are the elder of our section closest to `name`. Returns
+    /// `Error::InvalidState` if we are not an elder.
+    pub fn is_closest_elder_to(&self, name: &XorName) -> Result<bool> {
+        if !self.is_elder() {
+            return Err(Error::InvalidState);
+        }
+
+        let closest = self
+            .section
+            .elders_info()
+            .elders
+            .keys()
+            .min_by(|lhs, rhs| name.cmp_distance(lhs, rhs));
+
+        Ok(closest == Some(&self.node.name()))
+    }
+
+    /// Returns the ages of our current elders, as per their member info.
+    pub fn elder_ages(&self) -> impl Iterator<Item = u8> + '_ {
+        self.section.elders_info().peers().map(move |peer| {
+            self.section
+                .members()
+                .get(peer.name())
+                .map(|info| info.peer.age())
+                .unwrap_or_else(|| peer.age())
+        })
+    }
+
     /// Tries to sign with the secret corresponding to the provided BLS public key
     pub fn sign_with_section_key_share(
         &self,
@@ -128,6 +587,45 @@ impl Approved {
         self.section_keys_provider.sign_with(data, public_key)
     }
 
+    /// Signs `payload` with our share of the current section key.
+    pub fn sign_as_section(&self, payload: &[u8]) -> Result<bls::SignatureShare> {
+        Ok(self
+            .section_keys_provider
+            .key_share()?
+            .secret_key_share
+            .sign(payload))
+    }
+
+    /// Combines the given signature shares of `payload` (keyed by the index of the signer) into a
+    /// signature by the current section key. Invalid shares are ignored. Returns `None` if there
+    /// are not enough valid shares yet.
+    pub fn try_complete_section_signature(
+        &self,
+        payload: &[u8],
+        shares: &BTreeMap<usize, bls::SignatureShare>,
+    ) -> Result<Option<bls::Signature>> {
+        let public_key_set = &self.section_keys_provider.key_share()?.public_key_set;
+
+        let valid_shares: Vec<_> = shares
+            .iter()
+            .filter(|(index, share)| {
+                public_key_set
+                    .public_key_share(**index)
+                    .verify(share, payload)
+            })
+            .collect();
+
+        if valid_shares.len() <= public_key_set.threshold() {
+            return Ok(None);
+        }
+
+        let signature = public_key_set
+            .combine_signatures(valid_shares)
+            .map_err(|_| Error::InvalidSignatureShare)?;
+
+        Ok(Some(signature))
+    }
+
     /// Returns the current BLS public key set
     pub fn public_key_set(&self) -> Result<bls::PublicKeySet> {
         Ok(self
@@ -137,13 +635,325 @@ impl Approved {
             .clone())
     }
 
+    /// Returns the current BLS public key of our section if this node is a member of the BLS
+    /// group, or `Error::MissingSecretKeyShare` otherwise.
+    pub fn section_public_key(&self) -> Result<bls::PublicKey> {
+        Ok(*self.section_keys_provider.public_key()?)
+    }
+
+    /// Returns the age the member with the given name would get if relocated now, or `None` if
+    /// it's not a member of our section.
+    pub fn predict_relocation_age(&self, name: &XorName) -> Option<u8> {
+        self.section.members().get(name).map(|info| {
+            self.age_policy
+                .next_age(info.peer.age(), self.section.chain().last_key_index())
+        })
+    }
+
+    /// Returns the members whose relocation we agreed on but which still remain our elders (their
+    /// relocation is delayed until they are demoted), together with how long they've been
+    /// pending.
+    pub fn relocation_pending(&self) -> Vec<(XorName, Duration)> {
+        self.section
+            .members()
+            .all()
+            .filter(|info| matches!(info.state, PeerState::Relocated(_)))
+            .map(|info| info.peer.name())
+            .filter(|name| self.section.is_elder(name))
+            .map(|name| {
+                let pending_for = self
+                    .relocations_started
+                    .get(name)
+                    .map(Instant::elapsed)
+                    .unwrap_or_default();
+                (*name, pending_for)
+            })
+            .collect()
+    }
+
+    /// Returns the names and ages of the members that joined our section, including by relocating
+    /// into it, within the given time window.
+    pub fn recently_joined(&self, within: Duration) -> Vec<(XorName, u8)> {
+        self.joined_at
+            .iter()
+            .filter(|(_, joined_at)| joined_at.elapsed() <= within)
+            .filter_map(|(name, _)| self.section.members().get(name))
+            .filter(|info| info.state == PeerState::Joined)
+            .map(|info| (*info.peer.name(), info.peer.age()))
+            .collect()
+    }
+
+    /// Returns our current age, the number of churn events in our section since we joined it and
+    /// the number of churn events after which we can expect to be relocated with an increased age
+    /// (`2^age`). The latter is an expected value only, as the relocation is decided randomly on
+    /// every churn. Returns `Error::InvalidState` if we are not a member of our section.
+    pub fn age_progress(&self) -> Result<(u8, u32, u32)> {
+        if !self.section.members().is_joined(&self.node.name()) {
+            return Err(Error::InvalidState);
+        }
+
+        let age = self.node.age;
+        let threshold = 1u32.checked_shl(age.into()).unwrap_or(u32::MAX);
+
+        Ok((age, self.churn_count, threshold))
+    }
+
+    /// Records a churn event (a member joining or leaving) in our section that happened at `at`.
+    pub fn record_churn(&mut self, at: Instant) {
+        self.churn_count = self.churn_count.saturating_add(1);
+
+        if self.churn_times.len() >= CHURN_HISTORY_LEN {
+            let _ = self.churn_times.pop_front();
+        }
+        self.churn_times.push_back(at);
+    }
+
+    /// Roughly estimates how long it takes until we are promoted to elder. Assumes each churn
+    /// event moves us one place up among the elder candidates and that churn keeps happening at
+    /// the mean rate of the recent churn events. Returns `None` if we are an elder, not eligible
+    /// for promotion or too few churn events were observed to estimate their rate.
+    pub fn estimated_promotion_time(&self) -> Option<Duration> {
+        if self.is_elder() || self.churn_times.len() < MIN_CHURN_SAMPLES {
+            return None;
+        }
+
+        let our_name = self.node.name();
+        let position = self
+            .section
+            .members()
+            .elder_candidates(usize::MAX, self.min_elder_age, self.section.elders_info())
+            .iter()
+            .position(|peer| *peer.name() == our_name)?;
+        // We are promoted once we are among the first `ELDER_SIZE` candidates, on the next churn
+        // at the earliest.
+        let churns_needed = cmp::max(position + 1, ELDER_SIZE + 1) - ELDER_SIZE;
+
+        let first = self.churn_times.front()?;
+        let last = self.churn_times.back()?;
+        let mean_interval = last.duration_since(*first) / (self.churn_times.len() - 1) as u32;
+
+        Some(mean_interval * churns_needed as u32)
+    }
+
+    /// Returns whether we can currently take part in producing a section signature, that is, we
+    /// hold a key share for the current section key and there are enough elders to reach the
+    /// threshold of its key set.
+    pub fn can_sign(&self) -> bool {
+        self.section_keys_provider
+            .key_share()
+            .map(|share| {
+                share.public_key_set.public_key() == *self.section.chain().last_key()
+                    && self.section.elders_info().elders.len() > share.public_key_set.threshold()
+            })
+            .unwrap_or(false)
+    }
+
+    /// Returns whether the name derived from our keypair matches the name our section recorded for
+    /// the member at our address. Trivially true if our section has no member at our address.
+    pub fn verify_identity_consistency(&self) -> bool {
+        let name = crypto::name(&self.node.keypair.public);
+
+        self.section
+            .members()
+            .joined()
+            .filter(|info| *info.peer.addr() == self.node.addr)
+            .all(|info| *info.peer.name() == name)
+    }
+
+    /// Returns whether the key share we hold is for the current section key and is consistent with
+    /// its public key set. After a key change, this is false until we install our share of the
+    /// new key, as signature shares made with the old one wouldn't combine.
+    pub fn has_current_key_share(&self) -> bool {
+        self.section_keys_provider
+            .key_share()
+            .map(|share| {
+                share.public_key_set.public_key() == *self.section.chain().last_key()
+                    && share.public_key_set.public_key_share(share.index)
+                        == share.secret_key_share.public_key_share()
+            })
+            .unwrap_or(false)
+    }
+
+    /// Returns the number of sections we can route messages to: our own and the neighbours whose
+    /// elders we know. Neighbours we track without knowing any of their elders are not counted.
+    pub fn routable_section_count(&self) -> usize {
+        1 + self
+            .network
+            .all()
+            .filter(|info| !info.elders.is_empty())
+            .count()
+    }
+
+    /// Returns whether we hold a key share for the current section key and no key rotation is
+    /// underway, that is we don't hold the outcome of a DKG whose key our section hasn't switched
+    /// to yet and which is for our current or next elders. An outcome for elders that were
+    /// superseded by a different elder change doesn't count, as it will never be used.
+    pub fn has_complete_key_set(&self) -> bool {
+        if !self.has_current_key_share() {
+            return false;
+        }
+
+        let elders_info =
+            if let Some(elders_info) = self.section_keys_provider.pending_dkg_elders_info() {
+                elders_info
+            } else {
+                return true;
+            };
+
+        elders_info != self.section.elders_info()
+            && !self
+                .section
+                .promote_and_demote_elders_excluding(
+                    &self.node.name(),
+                    self.min_elder_age,
+                    self.quorum,
+                    &self.stepping_down,
+                )
+                .contains(elders_info)
+    }
+
     /// Returns our index in the current BLS group if this node is a member of one, or
     /// `Error::MissingSecretKeyShare` otherwise.
     pub fn our_index(&self) -> Result<usize> {
         Ok(self.section_keys_provider.key_share()?.index)
     }
 
+    // Estimates the number of hops a message to `dst` takes: one for each bit our prefix differs
+    // from the destination name in, plus one for the final delivery if `dst` is a single node.
+    pub fn estimated_hops(&self, dst: &DstLocation) -> usize {
+        let (name, to_node) = match dst {
+            DstLocation::Node(name) if *name == self.node.name() => return 0,
+            DstLocation::Node(name) => (name, true),
+            DstLocation::Section(name) => (name, false),
+            DstLocation::Direct => return 1,
+        };
+
+        let prefix = self.section.prefix();
+        let common_len = prefix.name().common_prefix(name).min(prefix.bit_count());
+
+        prefix.bit_count() - common_len + usize::from(to_node)
+    }
+
+    // Returns whether a message sent to `dst` is meant for us.
+    pub fn is_destination(&self, dst: &DstLocation) -> bool {
+        dst.contains(&self.node.name(), self.section.prefix())
+    }
+
+    // Runs the checks `handle_message` does, on a message received on a dedicated stream rather
+    // than as a regular message (e.g. the header of a user message stream): it must be for us,
+    // not handled before and trusted. Returns whether to handle it.
+    pub fn check_stream_message(&mut self, msg: &Message) -> bool {
+        if !self.is_destination(msg.dst()) {
+            return false;
+        }
+
+        if self.msg_filter.contains_incoming(msg) {
+            trace!("not handling message - already handled: {:?}", msg);
+            self.report_dropped(msg, DropReason::Duplicate);
+            return false;
+        }
+
+        match self.decide_message_status(msg) {
+            Ok(MessageStatus::Useful) => {
+                self.msg_filter.insert_incoming(msg);
+                true
+            }
+            Ok(_) => {
+                debug!("Not handling stream message {:?}", msg);
+                false
+            }
+            Err(_) => {
+                self.report_dropped(msg, DropReason::FailedVerification);
+                false
+            }
+        }
+    }
+
+    // Creates the header message of a stream carrying user message to `dst` and returns it together
+    // with the address of the recipient. Only destinations that are nodes we know the address of
+    // are supported.
+    pub fn create_user_message_stream_header(
+        &self,
+        dst: DstLocation,
+    ) -> Result<(SocketAddr, Message)> {
+        let name = dst.as_node()?;
+        let recipient = self.known_peer_addr(name).ok_or(Error::CannotRoute)?;
+        let msg = Message::single_src(&self.node, dst, Variant::UserMessageStream, None, None)?;
+
+        Ok((recipient, msg))
+    }
+
+    // Creates a query with the given content for each of our elders, except us, and returns them
+    // together with the names and addresses of the elders.
+    pub fn create_user_queries(
+        &self,
+        content: Bytes,
+    ) -> Result<Vec<(XorName, SocketAddr, Message)>> {
+        self.create_elder_requests(|| Variant::UserQuery(content.clone()))
+    }
+
+    // Creates a clock probe for each of our elders, except us, and returns them together with the
+    // names and addresses of the elders.
+    pub fn create_clock_probes(&self) -> Result<Vec<(XorName, SocketAddr, Message)>> {
+        self.create_elder_requests(|| Variant::ClockProbe)
+    }
+
+    fn create_elder_requests<F>(&self, variant: F) -> Result<Vec<(XorName, SocketAddr, Message)>>
+    where
+        F: Fn() -> Variant,
+    {
+        self.section
+            .elders_info()
+            .elders
+            .values()
+            .filter(|peer| *peer.name() != self.node.name())
+            .map(|peer| {
+                let msg = Message::single_src(
+                    &self.node,
+                    DstLocation::Node(*peer.name()),
+                    variant(),
+                    None,
+                    None,
+                )?;
+                Ok((*peer.name(), *peer.addr(), msg))
+            })
+            .collect()
+    }
+
+    // Address of the peer with the given name if it's one of our elders, a member of our section
+    // or an elder of a known section.
+    fn known_peer_addr(&self, name: &XorName) -> Option<SocketAddr> {
+        self.section
+            .elders_info()
+            .elders
+            .get(name)
+            .or_else(|| self.section.members().get(name).map(|info| &info.peer))
+            .or_else(|| self.network.get_elder(name))
+            .map(|peer| *peer.addr())
+    }
+
+    // Our position in the list of our section elders sorted by name or `Error::InvalidState` if
+    // we are not elder.
+    pub fn our_elder_index(&self) -> Result<usize> {
+        self.section
+            .elders_info()
+            .elders
+            .keys()
+            .position(|name| *name == self.node.name())
+            .ok_or(Error::InvalidState)
+    }
+
     pub fn send_event(&self, event: Event) {
+        if let Event::MessageDropped { .. } = event {
+            let _ = self.messages_dropped.fetch_add(1, Ordering::Relaxed);
+        }
+
+        if let Some(sink) = &self.event_sink {
+            if panic::catch_unwind(AssertUnwindSafe(|| sink.record(&event))).is_err() {
+                error!("Event sink failed to record {:?}", event);
+            }
+        }
+
         // Note: cloning the sender to avoid mutable access. Should have negligible cost.
         if self.event_tx.clone().send(event).is_err() {
             error!("Event receiver has been closed");
@@ -158,7 +968,21 @@ impl Approved {
         let mut commands = vec![];
 
         // Check if the message is for us.
-        let in_dst_location = msg.dst().contains(&self.node.name(), self.section.prefix());
+        let in_dst_location = self.is_destination(msg.dst());
+
+        // A message we already relayed that came back to us from one of the peers we relayed it to,
+        // without us being its destination, is looping. Other repeats are ordinary duplicates (e.g.
+        // relayed to us by several members of a delivery group) and are filtered when relaying.
+        if !in_dst_location
+            && sender.map_or(false, |sender| {
+                self.msg_filter.was_relayed_to(&msg, &sender)
+            })
+        {
+            error!("Dropping looping message from {:?}: {:?}", sender, msg);
+            self.report_dropped(&msg, DropReason::Loop);
+            return Err(Error::MessageLoop);
+        }
+
         if !in_dst_location || msg.dst().is_section() {
             // Relay closer to the destination or
             // broadcast to the rest of our section.
@@ -172,6 +996,7 @@ impl Approved {
         // Filter messages which were already handled
         if self.msg_filter.contains_incoming(&msg) {
             trace!("not handling message - already handled: {:?}", msg);
+            self.report_dropped(&msg, DropReason::Duplicate);
             return Ok(commands);
         }
 
@@ -250,11 +1075,54 @@ impl Approved {
     }
 
     pub fn handle_timeout(&mut self, token: u64) -> Result<Vec<Command>> {
+        if self.relocation_deferral_timer == Some(token) {
+            self.relocation_deferral_timer = None;
+            return self.cancel_relocation_deferral();
+        }
+
+        if self
+            .neighbour_reconciliation
+            .as_ref()
+            .map(|reconciliation| reconciliation.is_timer(token))
+            .unwrap_or(false)
+        {
+            return self.reconcile_neighbours();
+        }
+
         self.dkg_voter
             .handle_timeout(&self.node.keypair, token)
             .into_commands(&self.node)
     }
 
+    // Ask the neighbours for their current info, except those being backed off, and schedule the
+    // next round. Only elders handle the responses, so adults just wait for the next round.
+    fn reconcile_neighbours(&mut self) -> Result<Vec<Command>> {
+        let mut commands = vec![];
+
+        if self.is_elder() {
+            let our_prefix = *self.section.prefix();
+            let prefixes = self
+                .network
+                .prefixes()
+                .filter(|prefix| prefix.is_neighbour(&our_prefix))
+                .copied()
+                .collect();
+            let prefixes = if let Some(reconciliation) = &mut self.neighbour_reconciliation {
+                reconciliation.select(prefixes)
+            } else {
+                vec![]
+            };
+
+            for prefix in prefixes {
+                commands.extend(self.refresh_neighbour(&prefix)?);
+            }
+        }
+
+        commands.extend(self.schedule_neighbour_reconciliation());
+
+        Ok(commands)
+    }
+
     // Insert the vote into the vote accumulator and handle it if accumulated.
     pub fn handle_vote(&mut self, vote: Vote, proof_share: ProofShare) -> Result<Vec<Command>> {
         match self.vote_accumulator.add(vote, proof_share) {
@@ -298,6 +1166,16 @@ impl Approved {
                 self.joins_allowed = joins_allowed;
                 Ok(vec![])
             }
+            Vote::StepDown(name) => self.handle_step_down_event(name),
+            Vote::CancelStepDown(name) => self.handle_cancel_step_down_event(name),
+            Vote::DeferRelocation { name, churn_events } => {
+                self.handle_defer_relocation_event(name, churn_events);
+                Ok(vec![])
+            }
+            Vote::CancelRelocationDeferral(name) => {
+                self.handle_cancel_relocation_deferral_event(name);
+                Ok(vec![])
+            }
         }
     }
 
@@ -348,13 +1226,19 @@ impl Approved {
         elders_info: EldersInfo,
         key_share: SectionKeyShare,
     ) -> Result<Vec<Command>> {
-        let vote = Vote::SectionInfo(elders_info);
+        self.send_event(Event::DkgComplete {
+            session_id: DkgKey::new(&elders_info),
+            public_key: key_share.public_key_set.public_key(),
+        });
+
+        let vote = Vote::SectionInfo(elders_info.clone());
         let recipients: Vec<_> = self.section.elders_info().peers().copied().collect();
         let result = self.send_vote_with(&recipients, vote, &key_share);
 
         let public_key = key_share.public_key_set.public_key();
 
-        self.section_keys_provider.insert_dkg_outcome(key_share);
+        self.section_keys_provider
+            .insert_dkg_outcome(elders_info, key_share);
 
         if self.section.chain().has_key(&public_key) {
             self.section_keys_provider.finalise_dkg(&public_key)
@@ -368,6 +1252,10 @@ impl Approved {
         elders_info: EldersInfo,
         proofs: DkgFailureProofSet,
     ) -> Result<Command> {
+        self.send_event(Event::DkgFailed {
+            session_id: DkgKey::new(&elders_info),
+        });
+
         let variant = Variant::DKGFailureAgreement {
             elders_info,
             proofs,
@@ -479,8 +1367,27 @@ impl Approved {
     ////////////////////////////////////////////////////////////////////////////
 
     fn decide_message_status(&self, msg: &Message) -> Result<MessageStatus> {
+        // `NodeApproval`, `JoinRetry` and `JoinRejected` are verified by the bootstrap task against
+        // the keys of the section we are joining.
+        if self.strict_proof_verification
+            && !matches!(
+                msg.variant(),
+                Variant::NodeApproval { .. } | Variant::JoinRetry { .. } | Variant::JoinRejected
+            )
+            && !self.is_proof_chain_trusted(msg)
+        {
+            warn!("Dropping message with untrusted proof chain: {:?}", msg);
+            self.report_dropped(msg, DropReason::FailedVerification);
+            return Ok(MessageStatus::Useless);
+        }
+
         match msg.variant() {
-            Variant::NeighbourInfo { .. } => {
+            Variant::NeighbourInfo { .. }
+            | Variant::NeighbourInfoRequest
+            | Variant::RelocateRequest
+            | Variant::RelocationDeferral(_)
+            | Variant::CancelRelocationDeferral
+            | Variant::CancelDemotion => {
                 if !self.is_elder() {
                     return Ok(MessageStatus::Unknown);
                 }
@@ -505,7 +1412,7 @@ impl Approved {
                     return Ok(MessageStatus::Useless);
                 }
             }
-            Variant::NodeApproval { .. } | Variant::JoinRetry { .. } => {
+            Variant::NodeApproval { .. } | Variant::JoinRetry { .. } | Variant::JoinRejected => {
                 // Skip validation of these. We will validate them inside the bootstrap task.
                 return Ok(MessageStatus::Useful);
             }
@@ -541,6 +1448,35 @@ impl Approved {
             | Variant::DKGFailureObservation { .. }
             | Variant::DKGFailureAgreement { .. }
             | Variant::ResourceChallenge { .. } => {}
+            Variant::UserMessageStream | Variant::UserQuery(_) | Variant::ClockProbe => {
+                // Received on their dedicated connection stream and checked by
+                // `check_stream_message`. Handled there, never by `handle_useful_message`.
+            }
+        }
+
+        // The policy only covers user messages. Anything else from an unknown section goes through
+        // the regular verification, which bounces it if it's not trusted.
+        if matches!(msg.variant(), Variant::UserMessage(_)) && self.is_from_unknown_section(msg) {
+            match self.unknown_section_policy {
+                UnknownSectionPolicy::Bounce => (),
+                UnknownSectionPolicy::Drop => {
+                    trace!("Dropping message from unknown section: {:?}", msg);
+                    self.report_dropped(msg, DropReason::UnknownSection);
+                    return Ok(MessageStatus::Useless);
+                }
+                UnknownSectionPolicy::VerifyThenAccept => {
+                    // With no trusted keys, this checks the signature and the proof chain itself.
+                    return match msg.verify(iter::empty()) {
+                        Ok(_) => Ok(MessageStatus::Useful),
+                        Err(error) => {
+                            warn!("Verification of {:?} failed: {}", msg, error);
+                            self.report_dropped(msg, DropReason::FailedVerification);
+                            Ok(MessageStatus::Useless)
+                        }
+                    };
+                }
+                UnknownSectionPolicy::AcceptUnverified => return Ok(MessageStatus::Useful),
+            }
         }
 
         if self.verify_message(msg)? {
@@ -550,6 +1486,29 @@ impl Approved {
         }
     }
 
+    // Let the user know we dropped the message.
+    fn report_dropped(&self, msg: &Message, reason: DropReason) {
+        self.send_event(Event::MessageDropped {
+            reason,
+            src: Some(msg.src().src_location()),
+        })
+    }
+
+    // Is the message sent by a section we don't know any key of?
+    fn is_from_unknown_section(&self, msg: &Message) -> bool {
+        let prefix = if let Ok(prefix) = msg.src().as_section_prefix() {
+            prefix
+        } else {
+            return false;
+        };
+
+        !prefix.is_compatible(self.section.prefix())
+            && !self
+                .network
+                .keys()
+                .any(|(known_prefix, _)| prefix.is_compatible(known_prefix))
+    }
+
     async fn handle_useful_message(
         &mut self,
         sender: Option<SocketAddr>,
@@ -577,6 +1536,16 @@ impl Approved {
                 self.handle_join_request(msg.src().to_node_peer(sender)?, *join_request.clone())
             }
             Variant::UserMessage(content) => {
+                // The message might have been accepted without verification (see
+                // `UnknownSectionPolicy::AcceptUnverified`). Require a valid signature of the
+                // source node, or of the source section with a proof chain rooted in a key we
+                // trust. A chain the sender made up for a key of its own isn't enough.
+                if self.require_signed_messages && !matches!(self.verify_message(&msg), Ok(true)) {
+                    warn!("Dropping unsigned user message {:?}", msg);
+                    self.report_dropped(&msg, DropReason::Unsigned);
+                    return Ok(vec![]);
+                }
+
                 self.handle_user_message(msg.src().src_location(), *msg.dst(), content.clone());
                 Ok(vec![])
             }
@@ -634,6 +1603,7 @@ impl Approved {
             }
             Variant::NodeApproval { .. }
             | Variant::JoinRetry { .. }
+            | Variant::JoinRejected
             | Variant::ResourceChallenge { .. } => {
                 if let Some(RelocateState::InProgress(message_tx)) = &mut self.relocate_state {
                     if let Some(sender) = sender {
@@ -649,6 +1619,19 @@ impl Approved {
 
                 Ok(vec![])
             }
+            Variant::UserMessageStream | Variant::UserQuery(_) | Variant::ClockProbe => Ok(vec![]),
+            Variant::NeighbourInfoRequest => {
+                let src_name = msg.src().to_node_name()?;
+                self.handle_neighbour_info_request(&src_name, *msg.hash())
+            }
+            Variant::RelocateRequest => self.handle_relocate_request(&msg.src().to_node_name()?),
+            Variant::RelocationDeferral(churn_events) => {
+                self.handle_relocation_deferral(&msg.src().to_node_name()?, *churn_events)
+            }
+            Variant::CancelRelocationDeferral => {
+                self.handle_cancel_relocation_deferral(&msg.src().to_node_name()?)
+            }
+            Variant::CancelDemotion => self.handle_cancel_demotion(&msg.src().to_node_name()?),
         }
     }
 
@@ -718,6 +1701,24 @@ impl Approved {
         }
     }
 
+    // Whether the proof chain attached to the message, if any, is rooted in one of our trusted
+    // keys.
+    fn is_proof_chain_trusted(&self, msg: &Message) -> bool {
+        let proof_chain = if let Ok(proof_chain) = msg.proof_chain() {
+            proof_chain
+        } else {
+            return true;
+        };
+
+        let known_keys = self
+            .section
+            .chain()
+            .keys()
+            .chain(self.network.keys().map(|(_, key)| key));
+
+        proof_chain.check_trust(known_keys) == TrustStatus::Trusted
+    }
+
     /// Handle message whose trust we can't establish because its proof contains only keys we don't
     /// know.
     fn handle_untrusted_message(
@@ -840,56 +1841,180 @@ impl Approved {
             return Ok(vec![]);
         }
 
-        trace!(
-            "Received BouncedUnknownMessage({:?}) from {:?} \
-             - peer is lagging behind, resending with Sync",
-            MessageHash::from_bytes(&bounced_msg_bytes),
-            sender,
-        );
-        // First send Sync to update the peer, then resend the message itself. If the messages
-        // arrive in the same order they were sent, the Sync should update the peer so it will then
-        // be able to handle the resent message. If not, the peer will bounce the message again.
-        Ok(vec![
-            self.send_direct_message(
-                sender.addr(),
-                Variant::Sync {
-                    section: self.section.clone(),
-                    network: self.network.clone(),
-                },
-            )?,
-            Command::send_message_to_node(sender.addr(), bounced_msg_bytes),
-        ])
+        trace!(
+            "Received BouncedUnknownMessage({:?}) from {:?} \
+             - peer is lagging behind, resending with Sync",
+            MessageHash::from_bytes(&bounced_msg_bytes),
+            sender,
+        );
+        // First send Sync to update the peer, then resend the message itself. If the messages
+        // arrive in the same order they were sent, the Sync should update the peer so it will then
+        // be able to handle the resent message. If not, the peer will bounce the message again.
+        Ok(vec![
+            self.send_direct_message(
+                sender.addr(),
+                Variant::Sync {
+                    section: self.section.clone(),
+                    network: self.network.clone(),
+                },
+            )?,
+            Command::send_message_to_node(sender.addr(), bounced_msg_bytes),
+        ])
+    }
+
+    fn handle_neighbour_info(
+        &mut self,
+        elders_info: EldersInfo,
+        src_key: bls::PublicKey,
+    ) -> Result<Vec<Command>> {
+        let mut commands = vec![];
+
+        if let Some(reconciliation) = &mut self.neighbour_reconciliation {
+            reconciliation.record_response(&elders_info.prefix);
+        }
+
+        if !self.network.has_key(&src_key) {
+            commands.extend(self.vote(Vote::TheirKey {
+                prefix: elders_info.prefix,
+                key: src_key,
+            })?);
+        } else {
+            trace!(
+                "Ignore not new section key of {:?}: {:?}",
+                elders_info,
+                src_key
+            );
+            return Ok(commands);
+        }
+
+        if elders_info.prefix.is_neighbour(self.section.prefix()) {
+            commands.extend(self.vote(Vote::SectionInfo(elders_info))?);
+        }
+
+        Ok(commands)
+    }
+
+    fn handle_neighbour_info_request(
+        &mut self,
+        src_name: &XorName,
+        nonce: MessageHash,
+    ) -> Result<Vec<Command>> {
+        let prefix = if let Some(elders_info) = self.network.closest(src_name) {
+            elders_info.prefix
+        } else {
+            trace!("Ignore NeighbourInfoRequest from unknown section");
+            return Ok(vec![]);
+        };
+
+        if !prefix.matches(src_name) || !prefix.is_neighbour(self.section.prefix()) {
+            trace!(
+                "Ignore NeighbourInfoRequest from non-neighbour {:?}",
+                prefix
+            );
+            return Ok(vec![]);
+        }
+
+        let dst_key = self.network.key_by_name(&prefix.name()).cloned();
+        Ok(self
+            .send_neighbour_info(prefix, nonce, dst_key)?
+            .into_iter()
+            .collect())
+    }
+
+    // Relocate the member back into our section with the same age, so it gets a new name and
+    // keypair. The member proves it's the same node when rejoining, with the `RelocatePayload`.
+    fn handle_relocate_request(&mut self, src_name: &XorName) -> Result<Vec<Command>> {
+        let info = match self.section.members().get(src_name) {
+            Some(info) if info.state == PeerState::Joined => *info,
+            _ => {
+                trace!("Ignore RelocateRequest from non-member {}", src_name);
+                return Ok(vec![]);
+            }
+        };
+
+        if self.section.is_elder(src_name) {
+            // Elders can only be relocated once demoted.
+            trace!("Ignore RelocateRequest from elder {}", src_name);
+            return Ok(vec![]);
+        }
+
+        let details = RelocateDetails::with_age(
+            &self.section,
+            &self.network,
+            &info.peer,
+            *info.peer.name(),
+            info.peer.age(),
+        );
+
+        debug!("Relocating {:?} back to our section on request", info.peer);
+
+        let mut commands = self.vote(Vote::Offline(info.relocate(details.destination)))?;
+        commands.extend(self.send_relocate(&info.peer, details)?);
+
+        Ok(commands)
+    }
+
+    fn handle_cancel_demotion(&self, src_name: &XorName) -> Result<Vec<Command>> {
+        if !self.stepping_down.contains(src_name) {
+            trace!("Ignore CancelDemotion from {} not stepping down", src_name);
+            return Ok(vec![]);
+        }
+
+        self.vote(Vote::CancelStepDown(*src_name))
     }
 
-    fn handle_neighbour_info(
+    fn handle_relocation_deferral(
         &self,
-        elders_info: EldersInfo,
-        src_key: bls::PublicKey,
+        src_name: &XorName,
+        churn_events: u32,
     ) -> Result<Vec<Command>> {
-        let mut commands = vec![];
+        if !matches!(
+            self.section.members().get(src_name),
+            Some(info) if info.state == PeerState::Joined
+        ) {
+            trace!("Ignore RelocationDeferral from non-member {}", src_name);
+            return Ok(vec![]);
+        }
 
-        if !self.network.has_key(&src_key) {
-            commands.extend(self.vote(Vote::TheirKey {
-                prefix: elders_info.prefix,
-                key: src_key,
-            })?);
-        } else {
+        if churn_events == 0 || self.relocation_deferrals.contains_key(src_name) {
+            trace!("Ignore RelocationDeferral from {}", src_name);
+            return Ok(vec![]);
+        }
+
+        self.vote(Vote::DeferRelocation {
+            name: *src_name,
+            churn_events: churn_events.min(MAX_RELOCATION_DEFERRAL),
+        })
+    }
+
+    fn handle_cancel_relocation_deferral(&self, src_name: &XorName) -> Result<Vec<Command>> {
+        if !self.is_relocation_deferred(src_name) {
             trace!(
-                "Ignore not new section key of {:?}: {:?}",
-                elders_info,
-                src_key
+                "Ignore CancelRelocationDeferral from {} not deferred",
+                src_name
             );
-            return Ok(commands);
+            return Ok(vec![]);
         }
 
-        if elders_info.prefix.is_neighbour(self.section.prefix()) {
-            commands.extend(self.vote(Vote::SectionInfo(elders_info))?);
-        }
+        self.vote(Vote::CancelRelocationDeferral(*src_name))
+    }
 
-        Ok(commands)
+    fn is_relocation_deferred(&self, name: &XorName) -> bool {
+        self.relocation_deferrals
+            .get(name)
+            .map(|remaining| *remaining > MAX_RELOCATION_DEFERRAL)
+            .unwrap_or(false)
     }
 
-    fn handle_user_message(&self, src: SrcLocation, dst: DstLocation, content: Bytes) {
+    // Raise the user message as event unless the incoming inspector drops it.
+    pub fn handle_user_message(&self, src: SrcLocation, dst: DstLocation, content: Bytes) {
+        if let Some(inspector) = &self.incoming_inspector {
+            if !inspector.on_receive(&src, &content) {
+                trace!("Incoming message from {:?} dropped by the inspector", src);
+                return;
+            }
+        }
+
         self.send_event(Event::MessageReceived { content, src, dst })
     }
 
@@ -992,6 +2117,7 @@ impl Approved {
                 &self.network,
                 &info.peer,
                 promise.destination,
+                self.age_policy.as_ref(),
             );
             commands.extend(self.send_relocate(&info.peer, details)?);
         } else {
@@ -1029,12 +2155,30 @@ impl Approved {
             return Ok(vec![self.send_direct_message(peer.addr(), variant)?]);
         }
 
-        if self.section.members().is_joined(peer.name()) {
-            debug!(
-                "Ignoring JoinRequest from {} - already member of our section.",
-                peer
+        if let Some(member_info) = self
+            .section
+            .members()
+            .get(peer.name())
+            .filter(|info| info.state == PeerState::Joined)
+        {
+            if member_info.peer.addr() == peer.addr() {
+                debug!(
+                    "Ignoring JoinRequest from {} - already member of our section.",
+                    peer
+                );
+                return Ok(vec![]);
+            }
+
+            // Another node with the name of one of our members. We can't tell which one is
+            // legitimate, so we keep the existing member and refuse the newcomer.
+            warn!(
+                "Rejecting JoinRequest from {} - name already used by member {}.",
+                peer, member_info.peer
             );
-            return Ok(vec![]);
+            self.send_event(Event::NameCollision { name: *peer.name() });
+            return Ok(vec![
+                self.send_direct_message(peer.addr(), Variant::JoinRejected)?
+            ]);
         }
 
         // This joining node is being relocated to us.
@@ -1153,9 +2297,14 @@ impl Approved {
         key_index: u64,
     ) -> Result<Vec<Command>> {
         trace!("Received DKGStart for {}", new_elders_info);
-        self.dkg_voter
+        let old_contributions = self.dkg_contributions(&dkg_key);
+        let commands = self
+            .dkg_voter
             .start(&self.node.keypair, dkg_key, new_elders_info, key_index)
-            .into_commands(&self.node)
+            .into_commands(&self.node)?;
+        self.report_dkg_progress(&dkg_key, old_contributions);
+
+        Ok(commands)
     }
 
     fn handle_dkg_message(
@@ -1166,9 +2315,35 @@ impl Approved {
     ) -> Result<Vec<Command>> {
         trace!("handle DKG message {:?} from {}", message, sender);
 
+        let old_contributions = self.dkg_contributions(&dkg_key);
+        let commands = self
+            .dkg_voter
+            .process_message(&self.node.keypair, &dkg_key, &sender, message)
+            .into_commands(&self.node)?;
+        self.report_dkg_progress(&dkg_key, old_contributions);
+
+        Ok(commands)
+    }
+
+    fn dkg_contributions(&self, dkg_key: &DkgKey) -> usize {
         self.dkg_voter
-            .process_message(&self.node.keypair, &dkg_key, message)
-            .into_commands(&self.node)
+            .progress(dkg_key)
+            .map(|(_, contributions)| contributions)
+            .unwrap_or(0)
+    }
+
+    // Raise `DkgProgress` event if the number of contributions in the given DKG session increased.
+    fn report_dkg_progress(&self, dkg_key: &DkgKey, old_contributions: usize) {
+        if let Some((elders_info, contributions)) = self.dkg_voter.progress(dkg_key) {
+            if contributions > old_contributions {
+                self.send_event(Event::DkgProgress {
+                    session_id: *dkg_key,
+                    participants: elders_info.elders.keys().copied().collect(),
+                    contributions_received: contributions,
+                    total: elders_info.elders.len(),
+                })
+            }
+        }
     }
 
     fn handle_dkg_failure_observation(
@@ -1194,7 +2369,7 @@ impl Approved {
             .ok_or(Error::InvalidSrcLocation)?
             .peer;
 
-        if !proofs.verify(&elders_info) {
+        if !proofs.verify(&elders_info, self.quorum) {
             error!(
                 "Ignore DKG failure agreement with invalid proofs: {}",
                 elders_info
@@ -1204,7 +2379,12 @@ impl Approved {
 
         if !self
             .section
-            .promote_and_demote_elders(&self.node.name())
+            .promote_and_demote_elders_excluding(
+                &self.node.name(),
+                self.min_elder_age,
+                self.quorum,
+                &self.stepping_down,
+            )
             .contains(&elders_info)
         {
             trace!(
@@ -1222,12 +2402,79 @@ impl Approved {
         self.send_dkg_start_to(elders_info, slice::from_ref(sender))
     }
 
+    fn handle_step_down_event(&mut self, name: XorName) -> Result<Vec<Command>> {
+        if !self.section.is_elder(&name) {
+            trace!("Ignore step down of non-elder {}", name);
+            return Ok(vec![]);
+        }
+
+        info!("Elder {} is stepping down", name);
+        let _ = self.stepping_down.insert(name);
+
+        self.promote_and_demote_elders()
+    }
+
+    fn handle_cancel_step_down_event(&mut self, name: XorName) -> Result<Vec<Command>> {
+        if !self.stepping_down.remove(&name) {
+            trace!("Ignore cancelling step down of {}", name);
+            return Ok(vec![]);
+        }
+
+        info!("{} can be picked as elder again", name);
+
+        self.promote_and_demote_elders()
+    }
+
+    // Don't select the member for relocation on the next `churn_events` churn events. So that a
+    // member can't dodge relocation indefinitely, it has to stay eligible for
+    // `MAX_RELOCATION_DEFERRAL` more churn events after that before it can defer again.
+    fn handle_defer_relocation_event(&mut self, name: XorName, churn_events: u32) {
+        if !matches!(
+            self.section.members().get(&name),
+            Some(info) if info.state == PeerState::Joined
+        ) || self.relocation_deferrals.contains_key(&name)
+        {
+            trace!("Ignore relocation deferral of {}", name);
+            return;
+        }
+
+        debug!(
+            "Deferring relocation of {} for {} churn events",
+            name, churn_events
+        );
+
+        let _ = self.relocation_deferrals.insert(
+            name,
+            churn_events.min(MAX_RELOCATION_DEFERRAL) + MAX_RELOCATION_DEFERRAL,
+        );
+    }
+
+    // End the member's relocation deferral before its churn events run out. It still has to stay
+    // eligible for relocation for `MAX_RELOCATION_DEFERRAL` churn events before it can defer again.
+    fn handle_cancel_relocation_deferral_event(&mut self, name: XorName) {
+        if !self.is_relocation_deferred(&name) {
+            trace!("Ignore cancelling relocation deferral of {}", name);
+            return;
+        }
+
+        debug!("Relocation of {} no longer deferred", name);
+
+        let _ = self
+            .relocation_deferrals
+            .insert(name, MAX_RELOCATION_DEFERRAL);
+    }
+
     // Generate a new section info based on the current set of members and vote for it if it
     // changed.
     fn promote_and_demote_elders(&mut self) -> Result<Vec<Command>> {
         let mut commands = vec![];
 
-        for info in self.section.promote_and_demote_elders(&self.node.name()) {
+        for info in self.section.promote_and_demote_elders_excluding(
+            &self.node.name(),
+            self.min_elder_age,
+            self.quorum,
+            &self.stepping_down,
+        ) {
             commands.extend(self.send_dkg_start(info)?);
         }
 
@@ -1235,19 +2482,38 @@ impl Approved {
     }
 
     fn relocate_peers(
-        &self,
+        &mut self,
         churn_name: &XorName,
         churn_signature: &bls::Signature,
     ) -> Result<Vec<Command>> {
         let mut commands = vec![];
 
+        // Every agreed churn event counts towards the end of the deferrals, whether or not anyone
+        // is relocated on it, so all the elders that agreed on a deferral end it at the same time.
+        let deferred: BTreeSet<_> = self
+            .relocation_deferrals
+            .iter()
+            .filter(|(_, remaining)| **remaining > MAX_RELOCATION_DEFERRAL)
+            .map(|(name, _)| *name)
+            .collect();
+        self.relocation_deferrals.retain(|_, remaining| {
+            *remaining -= 1;
+            *remaining > 0
+        });
+
         // Do not carry out relocation when there is not enough elder nodes.
         if self.section.elders_info().elders.len() < ELDER_SIZE {
             return Ok(commands);
         }
 
-        let relocations =
-            relocation::actions(&self.section, &self.network, churn_name, churn_signature);
+        let relocations = relocation::actions(
+            &self.section,
+            &self.network,
+            churn_name,
+            churn_signature,
+            &deferred,
+            self.age_policy.as_ref(),
+        );
 
         for (info, action) in relocations {
             let peer = info.peer;
@@ -1264,6 +2530,15 @@ impl Approved {
                 churn_name
             );
 
+            if self.relocation_history.len() >= RELOCATION_HISTORY_LEN {
+                let _ = self.relocation_history.pop_front();
+            }
+            self.relocation_history.push_back(RelocationRecord {
+                relocating_name: *peer.name(),
+                churn_name: *churn_name,
+                destination: *action.destination(),
+            });
+
             commands.extend(self.vote(Vote::Offline(info.relocate(*action.destination())))?);
 
             match action {
@@ -1348,13 +2623,31 @@ impl Approved {
 
         info!("handle Online: {:?}", new_info.value.peer);
 
+        let _ = self
+            .joined_at
+            .insert(*new_info.value.peer.name(), Instant::now());
+
         self.send_event(Event::MemberJoined {
             name: *new_info.value.peer.name(),
             previous_name,
             age: new_info.value.peer.age(),
             startup_relocation: is_startup_phase,
         });
+        self.membership_observers
+            .notify(if let Some(previous_name) = previous_name {
+                MembershipEvent::RelocatedIn {
+                    name: *new_info.value.peer.name(),
+                    previous_name,
+                    age: new_info.value.peer.age(),
+                }
+            } else {
+                MembershipEvent::Joined {
+                    name: *new_info.value.peer.name(),
+                    age: new_info.value.peer.age(),
+                }
+            });
 
+        self.record_churn(Instant::now());
         commands
             .extend(self.relocate_peers(new_info.value.peer.name(), &new_info.proof.signature)?);
         commands.extend(self.promote_and_demote_elders()?);
@@ -1373,6 +2666,7 @@ impl Approved {
         let mut commands = vec![];
 
         let peer = member_info.peer;
+        let state = member_info.state;
         let age = peer.age();
         let signature = proof.signature.clone();
 
@@ -1386,6 +2680,17 @@ impl Approved {
 
         info!("handle Offline: {:?}", peer);
 
+        let _ = self.joined_at.remove(peer.name());
+        let _ = self.stepping_down.remove(peer.name());
+        let _ = self.relocation_deferrals.remove(peer.name());
+
+        if let PeerState::Relocated(_) = state {
+            let _ = self
+                .relocations_started
+                .insert(*peer.name(), Instant::now());
+        }
+
+        self.record_churn(Instant::now());
         commands.extend(self.relocate_peers(peer.name(), &signature)?);
         commands.extend(self.promote_and_demote_elders()?);
 
@@ -1393,6 +2698,19 @@ impl Approved {
             name: *peer.name(),
             age,
         });
+        self.membership_observers
+            .notify(if let PeerState::Relocated(destination) = state {
+                MembershipEvent::RelocatingOut {
+                    name: *peer.name(),
+                    age,
+                    destination,
+                }
+            } else {
+                MembershipEvent::Left {
+                    name: *peer.name(),
+                    age,
+                }
+            });
 
         Ok(commands)
     }
@@ -1413,7 +2731,12 @@ impl Approved {
             // Our section
             if self
                 .section
-                .promote_and_demote_elders(&self.node.name())
+                .promote_and_demote_elders_excluding(
+                    &self.node.name(),
+                    self.min_elder_age,
+                    self.quorum,
+                    &self.stepping_down,
+                )
                 .contains(&elders_info.value)
             {
                 if prefix_is_extension {
@@ -1425,9 +2748,15 @@ impl Approved {
 
                 commands.extend(self.vote(Vote::OurElders(elders_info))?);
             }
-        } else if self.network.update_neighbour_info(elders_info) {
+        } else {
             // Other section
-            self.network.prune_neighbours(self.section.prefix());
+            if let Some(reconciliation) = &mut self.neighbour_reconciliation {
+                reconciliation.record_response(&elders_info.value.prefix);
+            }
+
+            if self.network.update_neighbour_info(elders_info) {
+                self.network.prune_neighbours(self.section.prefix());
+            }
         }
 
         Ok(commands)
@@ -1530,9 +2859,14 @@ impl Approved {
         let old_is_elder = self.is_elder();
         let old_last_key = *self.section.chain().last_key();
         let old_prefix = *self.section.prefix();
+        let old_elders: BTreeSet<_> = self.section.elders_info().elders.keys().copied().collect();
 
         self.section.merge(section)?;
+        if let Some(max_len) = self.max_proof_chain_len {
+            self.section.compact_chain(max_len);
+        }
         self.network.merge(network, self.section.chain());
+        self.record_elders();
 
         self.section_keys_provider
             .finalise_dkg(self.section.chain().last_key());
@@ -1541,6 +2875,27 @@ impl Approved {
         let new_last_key = *self.section.chain().last_key();
         let new_prefix = *self.section.prefix();
 
+        if new_last_key != old_last_key {
+            // Our section switched to a key other than the one of our pending DKG outcome, if we
+            // hold any, and keys are only ever appended to the chain, so it won't be used anymore.
+            self.section_keys_provider.discard_dkg_outcome();
+
+            // Our elders changed. Those just promoted never saw the deferrals we agreed on, so we
+            // drop them too, to keep deciding relocations the same way as the new elders.
+            self.relocation_deferrals.clear();
+        }
+
+        // Those who stepped down stay excluded until they cancel it or leave. Members of our former
+        // sibling are not our concern anymore.
+        let section = &self.section;
+        self.stepping_down
+            .retain(|name| section.members().is_joined(name));
+        self.relocations_started
+            .retain(|name, _| section.is_elder(name));
+        // Members of our former sibling are not our members anymore.
+        self.joined_at
+            .retain(|name, _| section.members().is_joined(name));
+
         if new_prefix != old_prefix {
             info!("Split");
 
@@ -1592,10 +2947,16 @@ impl Approved {
                 NodeElderChange::None
             };
 
+            let elders: BTreeSet<_> = self.section.elders_info().elders.keys().copied().collect();
+            let added = elders.difference(&old_elders).copied().collect();
+            let removed = old_elders.difference(&elders).copied().collect();
+
             self.send_event(Event::EldersChanged {
                 prefix: *self.section.prefix(),
                 key: *self.section.chain().last_key(),
-                elders: self.section.elders_info().elders.keys().copied().collect(),
+                elders,
+                added,
+                removed,
                 self_status_change,
             });
         }
@@ -1804,14 +3165,38 @@ impl Approved {
         self.send_vote(recipients, vote)
     }
 
-    // Send message over the network.
+    // Send message over the network. Every relay, including the first send, uses up one hop of the
+    // message's hop limit.
     pub fn relay_message(&mut self, msg: &Message) -> Result<Option<Command>> {
-        let (targets, dg_size) = delivery_group::delivery_targets(
-            msg.dst(),
-            &self.node.name(),
-            &self.section,
-            &self.network,
-        )?;
+        let msg = if let Some(hop_limit) = msg.hop_limit().checked_sub(1) {
+            msg.with_hop_limit(hop_limit)?
+        } else {
+            debug!("Not relaying {:?}: hop limit reached", msg);
+            self.report_dropped(msg, DropReason::TtlExpired);
+            return Ok(None);
+        };
+        let msg = &msg;
+
+        let (targets, dg_size) = self.delivery_targets(msg.dst())?;
+
+        // Never relay to ourselves - that would make the message loop (e.g. when some peer in our
+        // view of the network has our address).
+        let (ours, targets): (Vec<_>, Vec<_>) = targets
+            .into_iter()
+            .partition(|peer| peer.name() == &self.node.name() || peer.addr() == &self.node.addr);
+
+        if !ours.is_empty() {
+            error!(
+                "Not relaying {:?} to {:?}: that's us ({})",
+                msg,
+                ours,
+                Error::MessageLoop
+            );
+
+            if targets.is_empty() {
+                return Err(Error::MessageLoop);
+            }
+        }
 
         let targets: Vec<_> = targets
             .into_iter()
@@ -1825,11 +3210,39 @@ impl Approved {
         trace!("relay {:?} to {:?}", msg, targets);
 
         let targets: Vec<_> = targets.into_iter().map(|node| *node.addr()).collect();
+        self.msg_filter.insert_relayed(msg, &targets);
         let command = Command::send_message_to_nodes(&targets, dg_size, msg.to_bytes());
 
         Ok(Some(command))
     }
 
+    // Recipients of a message for `dst`, together with the delivery group size (see
+    // `delivery_group::delivery_targets`). The section override, if set, takes precedence.
+    fn delivery_targets(&self, dst: &DstLocation) -> Result<(Vec<Peer>, usize)> {
+        #[cfg(any(test, feature = "testing"))]
+        {
+            if let Some(targets) = self.section_override.as_ref().and_then(|section_override| {
+                delivery_group::overridden_delivery_targets(
+                    section_override,
+                    dst,
+                    &self.node.name(),
+                    &self.section,
+                    &self.network,
+                )
+            }) {
+                return Ok(targets);
+            }
+        }
+
+        delivery_group::delivery_targets(
+            dst,
+            &self.node.name(),
+            &self.section,
+            &self.network,
+            self.quorum,
+        )
+    }
+
     // Setting the JoinsAllowed triggers a round Vote::SetJoinsAllowed to update the flag.
     pub fn set_joins_allowed(&mut self, joins_allowed: bool) -> Result<Vec<Command>> {
         let mut commands = Vec::new();
@@ -1839,11 +3252,96 @@ impl Approved {
         Ok(commands)
     }
 
+    pub fn request_key_rotation(&self) -> Result<Vec<Command>> {
+        if self.is_elder() || self.relocate_state.is_some() {
+            return Err(Error::InvalidState);
+        }
+
+        let msg = Message::single_src(
+            &self.node,
+            DstLocation::Direct,
+            Variant::RelocateRequest,
+            None,
+            None,
+        )?;
+
+        Ok(vec![self.send_message_to_our_elders(msg.to_bytes())])
+    }
+
+    // Ask our elders to defer our relocation for as many churn events as they allow, and to end
+    // it once `duration` elapses.
+    pub fn request_relocation_deferral(&mut self, duration: Duration) -> Result<Vec<Command>> {
+        if self.relocate_state.is_some() {
+            return Err(Error::InvalidState);
+        }
+
+        let msg = Message::single_src(
+            &self.node,
+            DstLocation::Direct,
+            Variant::RelocationDeferral(MAX_RELOCATION_DEFERRAL),
+            None,
+            None,
+        )?;
+
+        let token = command::next_timer_token();
+        self.relocation_deferral_timer = Some(token);
+
+        Ok(vec![
+            self.send_message_to_our_elders(msg.to_bytes()),
+            Command::ScheduleTimeout { duration, token },
+        ])
+    }
+
+    fn cancel_relocation_deferral(&self) -> Result<Vec<Command>> {
+        let msg = Message::single_src(
+            &self.node,
+            DstLocation::Direct,
+            Variant::CancelRelocationDeferral,
+            None,
+            None,
+        )?;
+
+        Ok(vec![self.send_message_to_our_elders(msg.to_bytes())])
+    }
+
+    pub fn request_demotion(&mut self) -> Result<Vec<Command>> {
+        if !self.is_elder() {
+            return Err(Error::InvalidState);
+        }
+
+        self.vote(Vote::StepDown(self.node.name()))
+    }
+
+    pub fn cancel_demotion(&self) -> Result<Vec<Command>> {
+        let msg = Message::single_src(
+            &self.node,
+            DstLocation::Direct,
+            Variant::CancelDemotion,
+            None,
+            None,
+        )?;
+
+        Ok(vec![self.send_message_to_our_elders(msg.to_bytes())])
+    }
+
     pub fn send_user_message(
         &mut self,
         src: SrcLocation,
         dst: DstLocation,
         content: Bytes,
+    ) -> Result<Vec<Command>> {
+        self.send_user_message_with_hop_limit(src, dst, content, DEFAULT_HOP_LIMIT)
+    }
+
+    // Like `send_user_message`, but the message can be relayed at most `hop_limit` times. Section
+    // messages are assembled and sent by whichever elder accumulates their signature, so they can
+    // only use the default limit.
+    pub fn send_user_message_with_hop_limit(
+        &mut self,
+        src: SrcLocation,
+        dst: DstLocation,
+        content: Bytes,
+        hop_limit: u8,
     ) -> Result<Vec<Command>> {
         if !src.contains(&self.node.name()) {
             error!(
@@ -1866,10 +3364,11 @@ impl Approved {
         match src {
             SrcLocation::Node(_) => {
                 // If the source is a single node, we don't even need to vote, so let's cut this short.
-                let msg = Message::single_src(&self.node, dst, variant, None, None)?;
+                let msg = Message::single_src(&self.node, dst, variant, None, None)?
+                    .with_hop_limit(hop_limit)?;
                 let mut commands = vec![];
 
-                if dst.contains(&self.node.name(), self.section.prefix()) {
+                if self.is_destination(&dst) {
                     commands.push(Command::HandleMessage {
                         sender: Some(self.node.addr),
                         message: msg.clone(),
@@ -1881,16 +3380,127 @@ impl Approved {
                 Ok(commands)
             }
             SrcLocation::Section(_) => {
+                if hop_limit != DEFAULT_HOP_LIMIT {
+                    error!(
+                        "Not sending user message {:?} -> {:?}: hop limit not supported for \
+                         section src",
+                        src, dst
+                    );
+                    return Err(Error::InvalidSrcLocation);
+                }
+
                 let vote = self.create_send_message_vote(dst, variant, None)?;
                 let recipients = delivery_group::signature_targets(
                     &dst,
                     self.section.elders_info().peers().copied(),
+                    self.quorum,
                 );
                 self.send_vote(&recipients, vote)
             }
         }
     }
 
+    // Picks a single elder of our section at random, weighted by age, to send a `UserMessage` to.
+    // The recently picked elders are avoided (if possible) to spread the load more evenly.
+    pub fn pick_any_elder(&mut self) -> Result<XorName> {
+        let elders: Vec<_> = self.section.elders_info().peers().copied().collect();
+        let candidates: Vec<_> = elders
+            .iter()
+            .filter(|peer| !self.recent_elders.contains(peer.name()))
+            .copied()
+            .collect();
+        let candidates = if candidates.is_empty() {
+            elders
+        } else {
+            candidates
+        };
+
+        let elder = *candidates
+            .choose_weighted(&mut rand::thread_rng(), |peer| peer.age() as u32)
+            .map_err(|_| Error::InvalidState)?;
+
+        if self.recent_elders.len() >= RECENT_ELDERS_LEN {
+            let _ = self.recent_elders.pop_front();
+        }
+        self.recent_elders.push_back(*elder.name());
+
+        Ok(*elder.name())
+    }
+
+    // Send `UserMessage` directly to the node with the given name, resolving its address from our
+    // view of the network.
+    pub fn send_to_name(&self, name: &XorName, content: Bytes) -> Result<Vec<Command>> {
+        let msg = Message::single_src(
+            &self.node,
+            DstLocation::Node(*name),
+            Variant::UserMessage(content),
+            None,
+            None,
+        )?;
+
+        if name == &self.node.name() {
+            return Ok(vec![Command::HandleMessage {
+                sender: Some(self.node.addr),
+                message: msg,
+            }]);
+        }
+
+        let recipient = self.known_peer_addr(name).ok_or(Error::UnknownPeer)?;
+
+        Ok(vec![Command::send_message_to_node(
+            &recipient,
+            msg.to_bytes(),
+        )])
+    }
+
+    /// Asks the elders of the neighbour section with the given prefix for their current
+    /// `EldersInfo`. The response is a regular `NeighbourInfo` which updates our view of the
+    /// neighbour once it is verified and agreed on.
+    pub fn refresh_neighbour(&self, prefix: &Prefix) -> Result<Vec<Command>> {
+        let elders_info = self
+            .network
+            .get(prefix)
+            .filter(|elders_info| elders_info.prefix.is_neighbour(self.section.prefix()))
+            .ok_or(Error::InvalidDstLocation)?;
+
+        let recipients: Vec<_> = elders_info.peers().map(Peer::addr).copied().collect();
+        let msg = Message::single_src(
+            &self.node,
+            DstLocation::Section(prefix.name()),
+            Variant::NeighbourInfoRequest,
+            None,
+            self.network.key_by_name(&prefix.name()).cloned(),
+        )?;
+
+        Ok(vec![Command::send_message_to_nodes(
+            &recipients,
+            recipients.len(),
+            msg.to_bytes(),
+        )])
+    }
+
+    // Returns the destinations of a `UserMessage` sent to the elders of the `fanout` known sections
+    // (including ours) closest to `target`.
+    pub fn toward_destinations(&self, target: &XorName, fanout: usize) -> Vec<DstLocation> {
+        let mut prefixes: Vec<_> = iter::once(*self.section.prefix())
+            .chain(
+                self.network
+                    .closest_n(target, fanout)
+                    .into_iter()
+                    .map(|info| info.prefix),
+            )
+            .collect();
+        prefixes.sort_by(|lhs, rhs| lhs.cmp_distance(rhs, target));
+        prefixes.truncate(fanout);
+
+        // Pick the name within each section closest to the target so the message is routed to
+        // that section and not to the section the target itself belongs to.
+        prefixes
+            .into_iter()
+            .map(|prefix| DstLocation::Section(prefix.substituted_in(*target)))
+            .collect()
+    }
+
     fn create_send_message_vote(
         &self,
         dst: DstLocation,
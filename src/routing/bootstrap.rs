@@ -38,6 +38,20 @@ use xor_name::{Prefix, XorName};
 
 const BACKLOG_CAPACITY: usize = 100;
 
+/// Phase of joining the network, reported while `Routing::new` is in progress (see
+/// `Config::join_phase_tx`).
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum JoinPhase {
+    /// Asking the bootstrap contacts which section we belong to.
+    Connecting,
+    /// Found the section to join.
+    Bootstrapped,
+    /// Asked the section to let us join and waiting for the approval.
+    Joining,
+    /// Approved by the section. This is the last phase.
+    Joined,
+}
+
 /// Bootstrap into the network as new node.
 ///
 /// NOTE: It's not guaranteed this function ever returns. This can happen due to messages being
@@ -48,13 +62,14 @@ pub(crate) async fn initial(
     comm: &Comm,
     incoming_conns: &mut mpsc::Receiver<ConnectionEvent>,
     bootstrap_addr: SocketAddr,
+    join_phase_tx: Option<mpsc::UnboundedSender<JoinPhase>>,
 ) -> Result<(Node, Section, Vec<(Message, SocketAddr)>)> {
     let (send_tx, send_rx) = mpsc::channel(1);
     let recv_rx = MessageReceiver::Raw(incoming_conns);
 
     let span = trace_span!("bootstrap::initial", name = %node.name());
 
-    let state = State::new(node, send_tx, recv_rx)?;
+    let state = State::new(node, send_tx, recv_rx)?.with_join_phase_tx(join_phase_tx);
 
     future::join(
         state.run(vec![bootstrap_addr], None),
@@ -101,6 +116,8 @@ struct State<'a> {
     node: Node,
     // Backlog for unknown messages
     backlog: VecDeque<(Message, SocketAddr)>,
+    // Sender for the progress updates, if anyone is interested in them.
+    join_phase_tx: Option<mpsc::UnboundedSender<JoinPhase>>,
 }
 
 impl<'a> State<'a> {
@@ -114,14 +131,29 @@ impl<'a> State<'a> {
             recv_rx,
             node,
             backlog: VecDeque::with_capacity(BACKLOG_CAPACITY),
+            join_phase_tx: None,
         })
     }
 
+    fn with_join_phase_tx(mut self, tx: Option<mpsc::UnboundedSender<JoinPhase>>) -> Self {
+        self.join_phase_tx = tx;
+        self
+    }
+
+    fn report_phase(&self, phase: JoinPhase) {
+        if let Some(tx) = &self.join_phase_tx {
+            // The receiver might have been dropped, but that should not stop us from joining.
+            let _ = tx.send(phase);
+        }
+    }
+
     async fn run(
         mut self,
         bootstrap_addrs: Vec<SocketAddr>,
         relocate_details: Option<SignedRelocateDetails>,
     ) -> Result<(Node, Section, Vec<(Message, SocketAddr)>)> {
+        self.report_phase(JoinPhase::Connecting);
+
         let (prefix, section_key, elders) = self
             .bootstrap(bootstrap_addrs, relocate_details.as_ref())
             .await?;
@@ -158,6 +190,7 @@ impl<'a> State<'a> {
                         "Joining a section ({:b}), key: {:?}, elders: {:?} (given by {:?})",
                         prefix, key, elders, sender
                     );
+                    self.report_phase(JoinPhase::Bootstrapped);
                     return Ok((prefix, key, elders));
                 }
                 GetSectionResponse::Redirect(new_bootstrap_addrs) => {
@@ -260,6 +293,7 @@ impl<'a> State<'a> {
     // Send `JoinRequest` and wait for the response. If the response is `Rejoin`, repeat with the
     // new info. If it is `Approval`, returns the initial `Section` value to use by this node,
     // completing the bootstrap. If it is `Challenge`, carries out a resource proof calculation.
+    // If it is `Rejected`, fails with `Error::NameCollision`.
     async fn join(
         mut self,
         mut section_key: bls::PublicKey,
@@ -273,6 +307,7 @@ impl<'a> State<'a> {
         };
         let recipients = elders.into_iter().map(|(_, addr)| addr).collect();
         self.send_join_requests(join_request, recipients).await?;
+        self.report_phase(JoinPhase::Joining);
 
         loop {
             let (response, sender) = self
@@ -285,6 +320,7 @@ impl<'a> State<'a> {
                     age,
                     section_chain,
                 } => {
+                    self.report_phase(JoinPhase::Joined);
                     return Ok((
                         self.node.with_age(age),
                         Section::new(section_chain, elders_info)?,
@@ -343,6 +379,10 @@ impl<'a> State<'a> {
                     let recipients = vec![sender];
                     self.send_join_requests(join_request, recipients).await?;
                 }
+                JoinResponse::Rejected => {
+                    error!("Join rejected by {} - our name is already taken.", sender);
+                    return Err(Error::NameCollision);
+                }
             }
         }
     }
@@ -397,6 +437,13 @@ impl<'a> State<'a> {
                         sender,
                     ));
                 }
+                Variant::JoinRejected => {
+                    if !self.verify_message(&message, None) {
+                        continue;
+                    }
+
+                    return Ok((JoinResponse::Rejected, sender));
+                }
                 Variant::ResourceChallenge {
                     data_size,
                     difficulty,
@@ -513,6 +560,7 @@ enum JoinResponse {
         nonce: [u8; 32],
         nonce_signature: Signature,
     },
+    Rejected,
 }
 
 // Receiver of incoming messages that can be backed either by a raw `qp2p::ConnectionEvent` receiver
@@ -665,6 +713,88 @@ mod tests {
         Ok(())
     }
 
+    #[tokio::test]
+    async fn join_phases() -> Result<()> {
+        let (send_tx, mut send_rx) = mpsc::channel(1);
+        let (mut recv_tx, recv_rx) = mpsc::channel(1);
+        let recv_rx = MessageReceiver::Deserialized(recv_rx);
+        let (join_phase_tx, mut join_phase_rx) = mpsc::unbounded_channel();
+
+        let (elders_info, mut nodes) = gen_elders_info(Default::default(), ELDER_SIZE);
+        let bootstrap_node = nodes.remove(0);
+        let bootstrap_addr = bootstrap_node.addr;
+
+        let sk = bls::SecretKey::random();
+        let pk = sk.public_key();
+
+        let node = Node::new(crypto::gen_keypair(), gen_addr());
+        let peer = node.peer();
+        let state = State::new(node, send_tx, recv_rx)?.with_join_phase_tx(Some(join_phase_tx));
+
+        let bootstrap = async move {
+            state
+                .run(vec![bootstrap_addr], None)
+                .await
+                .map_err(Error::from)
+        };
+
+        let others = async {
+            task::yield_now().await;
+
+            // Receive GetSectionRequest
+            let _ = send_rx.try_recv()?;
+            assert_eq!(join_phase_rx.try_recv()?, JoinPhase::Connecting);
+            assert_matches!(join_phase_rx.try_recv(), Err(TryRecvError::Empty));
+
+            // Send GetSectionResponse::Success
+            let message = Query::GetSectionResponse(GetSectionResponse::Success {
+                prefix: elders_info.prefix,
+                key: pk,
+                elders: elders_info
+                    .peers()
+                    .map(|peer| (*peer.name(), *peer.addr()))
+                    .collect(),
+            });
+            recv_tx.try_send((MessageType::InfrastructureQuery(message), bootstrap_addr))?;
+            task::yield_now().await;
+
+            // Receive JoinRequest
+            let _ = send_rx.try_recv()?;
+            assert_eq!(join_phase_rx.try_recv()?, JoinPhase::Bootstrapped);
+            assert_eq!(join_phase_rx.try_recv()?, JoinPhase::Joining);
+            assert_matches!(join_phase_rx.try_recv(), Err(TryRecvError::Empty));
+
+            // Send NodeApproval
+            let elders_info = proven(&sk, elders_info.clone())?;
+            let member_info = proven(&sk, MemberInfo::joined(peer.with_age(MIN_AGE + 1)))?;
+            let proof_chain = SectionProofChain::new(pk);
+            let message = Message::single_src(
+                &bootstrap_node,
+                DstLocation::Direct,
+                Variant::NodeApproval {
+                    elders_info,
+                    member_info,
+                },
+                Some(proof_chain),
+                None,
+            )?;
+
+            recv_tx.try_send((
+                MessageType::NodeMessage(NodeMessage::new(message.to_bytes())),
+                bootstrap_addr,
+            ))?;
+
+            Ok(())
+        };
+
+        let _ = future::try_join(bootstrap, others).await?;
+
+        assert_eq!(join_phase_rx.try_recv()?, JoinPhase::Joined);
+        assert_matches!(join_phase_rx.try_recv(), Err(TryRecvError::Closed));
+
+        Ok(())
+    }
+
     #[tokio::test]
     async fn receive_get_section_response_redirect() -> Result<()> {
         let (send_tx, mut send_rx) = mpsc::channel(1);
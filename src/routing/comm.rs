@@ -6,27 +6,92 @@
 // KIND, either express or implied. Please review the Licences for the specific language governing
 // permissions and limitations relating to use of the SAFE Network Software.
 
+use super::stage::jittered;
 use crate::error::{Error, Result};
-use bytes::Bytes;
-use futures::stream::{FuturesUnordered, StreamExt};
+use bytes::{Buf, BufMut, Bytes, BytesMut};
+use futures::stream::{self, FuturesUnordered, Stream, StreamExt};
 use qp2p::{Connection, Endpoint, QuicP2p};
+#[cfg(any(test, feature = "testing"))]
+use rand::{rngs::StdRng, Rng, SeedableRng};
+use serde::Serialize;
+use sn_messaging::MessageType;
 use std::{
+    collections::{HashMap, HashSet, VecDeque},
     fmt::{self, Debug, Formatter},
+    future::Future,
     net::SocketAddr,
-    sync::RwLock,
+    sync::{
+        atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering},
+        Arc, Mutex, RwLock, Weak,
+    },
+    time::{Duration, Instant, SystemTime},
 };
 use thiserror::Error;
-use tokio::{sync::mpsc, task};
+use tokio::{
+    sync::{mpsc, oneshot, Mutex as AsyncMutex, Semaphore, SemaphorePermit},
+    task, time,
+};
+
+// Default number of the most recent send failures to keep.
+pub(crate) const DEFAULT_SEND_FAILURE_HISTORY_LEN: usize = 64;
+// Default time sends to a peer are skipped for after its circuit breaker opens.
+pub(crate) const DEFAULT_CIRCUIT_BREAKER_COOLDOWN: Duration = Duration::from_secs(30);
+
+// Marks a batch of messages sent together in a single stream.
+const BATCH_MAGIC: &[u8] = b"SNBATCH\0";
+// Each message in a batch is prefixed with its length as big-endian u32.
+const BATCH_ITEM_HEADER_LEN: usize = 4;
+
+// How often `drain` checks whether the in-flight sends completed.
+const DRAIN_POLL_INTERVAL: Duration = Duration::from_millis(10);
 
 // Communication component of the node to interact with other nodes.
 pub(crate) struct Comm {
     _quic_p2p: QuicP2p,
     endpoint: Endpoint,
+    // Whether `endpoint` is bound to an IPv6 address.
+    endpoint_is_ipv6: bool,
+    // Additional endpoints we listen on, for example to accept connections of the other IP family.
+    extra_endpoints: Vec<ExtraEndpoint>,
     // Sender for connection events. Kept here so we can clone it and pass it to the incoming
     // messages handler every time we establish new connection. It's kept in an `Option` so we can
     // take it out and drop it on `terminate` which together with all the incoming message handlers
     // terminating closes the corresponding receiver.
     event_tx: RwLock<Option<mpsc::Sender<ConnectionEvent>>>,
+    // Time of the last successful send to each peer.
+    last_sent: RwLock<HashMap<SocketAddr, Instant>>,
+    // Time of the last failed send to each peer that hasn't been followed by a successful one.
+    last_failed: RwLock<HashMap<SocketAddr, Instant>>,
+    // How long a peer is considered unreachable after a failed send.
+    unreachable_cooldown: Duration,
+    // Number of times an existing connection was reused / a new one had to be opened.
+    cache_hits: AtomicU64,
+    cache_misses: AtomicU64,
+    // The most recent send failures, oldest first.
+    send_failures: RwLock<VecDeque<SendFailureRecord>>,
+    send_failure_history_len: usize,
+    // Batching of messages to the same recipient or `None` if disabled.
+    batching: Option<Batching>,
+    // Limits the number of connection attempts in progress at the same time.
+    connect_limiter: ConnectLimiter,
+    // Skips sends to peers that keep failing or `None` if disabled.
+    circuit_breaker: Option<CircuitBreaker>,
+    // Bytes received but not yet handled, per connection.
+    connection_buffers: Arc<ConnectionBuffers>,
+    // Subscribers to the low-level connection events.
+    connection_observers: Arc<ConnectionObservers>,
+    // Per-peer locks making sends to the same peer happen one after another, in order, or `None`
+    // if sends may happen concurrently. A peer's lock is removed once no send to it is pending.
+    send_locks: Option<Mutex<HashMap<SocketAddr, Arc<AsyncMutex<()>>>>>,
+    // Peers whose connections are kept alive, or `None` if keep-alives are disabled.
+    pinned_peers: Option<Arc<Mutex<HashSet<SocketAddr>>>>,
+    // Set once `drain` is called. New sends are refused from then on.
+    draining: AtomicBool,
+    // Number of sends currently in progress.
+    sends_in_flight: AtomicUsize,
+    // Simulated loss and latency applied to every send or `None` if disabled.
+    #[cfg(any(test, feature = "testing"))]
+    link_impairment: Option<ActiveLinkImpairment>,
 }
 
 impl Comm {
@@ -34,21 +99,46 @@ impl Comm {
         transport_config: qp2p::Config,
         event_tx: mpsc::Sender<ConnectionEvent>,
     ) -> Result<Self> {
+        let endpoint_is_ipv6 = is_ipv6(&transport_config);
         let quic_p2p = QuicP2p::with_config(Some(transport_config), Default::default(), true)?;
 
         // Don't bootstrap, just create an endpoint where to listen to
         // the incoming messages from other nodes.
         let endpoint = quic_p2p.new_endpoint()?;
+        let connection_buffers = Arc::new(ConnectionBuffers::new(endpoint.clone()));
+        let connection_observers = Arc::new(ConnectionObservers::default());
 
         let _ = task::spawn(handle_incoming_connections(
             endpoint.listen(),
             event_tx.clone(),
+            connection_buffers.clone(),
+            connection_observers.clone(),
         ));
 
         Ok(Self {
             _quic_p2p: quic_p2p,
             endpoint,
+            endpoint_is_ipv6,
+            extra_endpoints: Vec::new(),
             event_tx: RwLock::new(Some(event_tx)),
+            last_sent: RwLock::new(HashMap::new()),
+            last_failed: RwLock::new(HashMap::new()),
+            unreachable_cooldown: DEFAULT_CIRCUIT_BREAKER_COOLDOWN,
+            cache_hits: AtomicU64::new(0),
+            cache_misses: AtomicU64::new(0),
+            send_failures: RwLock::new(VecDeque::new()),
+            send_failure_history_len: DEFAULT_SEND_FAILURE_HISTORY_LEN,
+            batching: None,
+            connect_limiter: ConnectLimiter::default(),
+            circuit_breaker: None,
+            connection_buffers,
+            connection_observers,
+            send_locks: None,
+            pinned_peers: None,
+            draining: AtomicBool::new(false),
+            sends_in_flight: AtomicUsize::new(0),
+            #[cfg(any(test, feature = "testing"))]
+            link_impairment: None,
         })
     }
 
@@ -56,34 +146,223 @@ impl Comm {
         transport_config: qp2p::Config,
         event_tx: mpsc::Sender<ConnectionEvent>,
     ) -> Result<(Self, SocketAddr)> {
+        let endpoint_is_ipv6 = is_ipv6(&transport_config);
         let quic_p2p = QuicP2p::with_config(Some(transport_config), Default::default(), true)?;
 
         // Bootstrap to the network returning the connection to a node.
         let (endpoint, conn, incoming_messages) = quic_p2p.bootstrap().await?;
         let addr = conn.remote_address();
+        let connection_buffers = Arc::new(ConnectionBuffers::new(endpoint.clone()));
+        let connection_observers = Arc::new(ConnectionObservers::default());
 
         let _ = task::spawn(handle_incoming_connections(
             endpoint.listen(),
             event_tx.clone(),
+            connection_buffers.clone(),
+            connection_observers.clone(),
         ));
         let _ = task::spawn(handle_incoming_messages(
             incoming_messages,
             event_tx.clone(),
+            connection_buffers.clone(),
+            connection_observers.clone(),
         ));
 
         Ok((
             Self {
                 _quic_p2p: quic_p2p,
                 endpoint,
+                endpoint_is_ipv6,
+                extra_endpoints: Vec::new(),
                 event_tx: RwLock::new(Some(event_tx)),
+                last_sent: RwLock::new(HashMap::new()),
+                last_failed: RwLock::new(HashMap::new()),
+                unreachable_cooldown: DEFAULT_CIRCUIT_BREAKER_COOLDOWN,
+                cache_hits: AtomicU64::new(0),
+                cache_misses: AtomicU64::new(0),
+                send_failures: RwLock::new(VecDeque::new()),
+                send_failure_history_len: DEFAULT_SEND_FAILURE_HISTORY_LEN,
+                batching: None,
+                connect_limiter: ConnectLimiter::default(),
+                circuit_breaker: None,
+                connection_buffers,
+                connection_observers,
+                send_locks: None,
+                pinned_peers: None,
+                draining: AtomicBool::new(false),
+                sends_in_flight: AtomicUsize::new(0),
+                #[cfg(any(test, feature = "testing"))]
+                link_impairment: None,
             },
             addr,
         ))
     }
 
+    /// Sets how many of the most recent send failures to keep (see `recent_send_failures`).
+    pub fn with_send_failure_history_len(mut self, len: usize) -> Self {
+        self.send_failure_history_len = len;
+        self
+    }
+
+    /// Enables batching: a message to a recipient nothing else is being sent to is sent right
+    /// away. Otherwise it's queued and the messages queued within `window` are sent together in a
+    /// single stream, as long as their total size doesn't exceed `max_size`.
+    pub fn with_batching(mut self, window: Duration, max_size: usize) -> Self {
+        self.batching = Some(Batching {
+            window,
+            max_size,
+            pending: Mutex::new(HashMap::new()),
+            busy: Mutex::new(HashMap::new()),
+            next_id: AtomicU64::new(0),
+        });
+        self
+    }
+
+    /// Limits the number of connections being established at the same time to `limit`. Further
+    /// connection attempts wait until some of the in-progress ones complete.
+    pub fn with_max_concurrent_connects(mut self, limit: usize) -> Self {
+        self.connect_limiter.semaphore = Some(Semaphore::new(limit.max(1)));
+        self
+    }
+
+    /// Enables the circuit breaker: after `threshold` consecutive failed sends to a peer, further
+    /// sends to it fail immediately for `cooldown`. After that, a single probe send is let through
+    /// which either closes the breaker again (on success) or reopens it for another `cooldown`.
+    pub fn with_circuit_breaker(mut self, threshold: usize, cooldown: Duration) -> Self {
+        self.unreachable_cooldown = cooldown;
+        self.circuit_breaker = Some(CircuitBreaker {
+            threshold: threshold.max(1),
+            cooldown,
+            peers: Mutex::new(HashMap::new()),
+        });
+        self
+    }
+
+    /// Closes any connection whose received but not yet handled messages take more than
+    /// `max_size` bytes in total. Zero means no limit.
+    pub fn with_max_connection_buffer(self, max_size: usize) -> Self {
+        self.connection_buffers
+            .max_size
+            .store(max_size, Ordering::Relaxed);
+        self
+    }
+
+    /// Makes the messages to the same recipient to be sent one at a time, in the order the sends
+    /// were started, so they arrive in that order too. This holds also when a send has to be
+    /// retried, at the cost of lower throughput.
+    pub fn with_ordered_delivery(mut self) -> Self {
+        self.send_locks = Some(Mutex::new(HashMap::new()));
+        self
+    }
+
+    /// Enables keep-alives: every `interval`, a ping is sent on the existing connection to each of
+    /// the pinned peers (see `set_pinned_peers`), so the connection isn't dropped as idle by us,
+    /// the peer or any NAT in between. Connections to the other peers are not affected. Each
+    /// interval is randomly shifted by up to `jitter_percent` of its length.
+    pub fn with_keepalive(mut self, interval: Duration, jitter_percent: u8) -> Self {
+        let pinned_peers = Arc::new(Mutex::new(HashSet::new()));
+        let _ = task::spawn(send_keepalives(
+            self.endpoint.clone(),
+            Arc::downgrade(&pinned_peers),
+            interval,
+            jitter_percent,
+        ));
+
+        self.pinned_peers = Some(pinned_peers);
+        self
+    }
+
+    /// Simulates a lossy, slow link: every send is delayed by `impairment.latency` and then fails
+    /// with probability `impairment.drop_probability`, as if the peer was unreachable. For tests
+    /// only.
+    #[cfg(any(test, feature = "testing"))]
+    pub fn with_link_impairment(mut self, impairment: LinkImpairment) -> Self {
+        self.link_impairment = Some(ActiveLinkImpairment {
+            drop_probability: impairment.drop_probability.max(0.0).min(1.0),
+            latency: impairment.latency,
+            rng: Mutex::new(StdRng::seed_from_u64(impairment.seed)),
+        });
+        self
+    }
+
+    /// Returns whether keep-alives are enabled.
+    pub fn is_keepalive_enabled(&self) -> bool {
+        self.pinned_peers.is_some()
+    }
+
+    /// Sets the peers whose connections are kept alive, replacing the previous ones. Has no effect
+    /// if keep-alives are disabled.
+    pub fn set_pinned_peers(&self, addrs: impl IntoIterator<Item = SocketAddr>) {
+        if let Some(pinned_peers) = &self.pinned_peers {
+            *pinned_peers.lock().unwrap_or_else(|err| err.into_inner()) =
+                addrs.into_iter().collect();
+        }
+    }
+
+    /// Returns the peers whose connections are kept alive.
+    pub fn pinned_peers(&self) -> Vec<SocketAddr> {
+        self.pinned_peers
+            .as_ref()
+            .map(|pinned_peers| {
+                pinned_peers
+                    .lock()
+                    .unwrap_or_else(|err| err.into_inner())
+                    .iter()
+                    .copied()
+                    .collect()
+            })
+            .unwrap_or_default()
+    }
+
+    /// Closes the connection to the given peer, if any. A later send to the peer connects again.
+    pub fn disconnect(&self, addr: &SocketAddr) {
+        if let Some(conn) = self.endpoint_for(addr).get_connection(addr) {
+            conn.close();
+        }
+    }
+
+    /// Additionally listen on an endpoint for each of the given configs, for example to accept
+    /// both IPv4 and IPv6 connections. Messages received on any endpoint are handled the same way.
+    /// Sends use the endpoint bound to the same IP family as the recipient, if there is any.
+    pub fn with_extra_endpoints(mut self, transport_configs: Vec<qp2p::Config>) -> Result<Self> {
+        let event_tx = self
+            .event_tx
+            .read()
+            .unwrap_or_else(|err| err.into_inner())
+            .clone();
+        let event_tx = if let Some(event_tx) = event_tx {
+            event_tx
+        } else {
+            return Ok(self);
+        };
+
+        for transport_config in transport_configs {
+            let is_ipv6 = is_ipv6(&transport_config);
+            let quic_p2p = QuicP2p::with_config(Some(transport_config), Default::default(), true)?;
+            let endpoint = quic_p2p.new_endpoint()?;
+
+            self.connection_buffers.add_endpoint(endpoint.clone());
+
+            let _ = task::spawn(handle_incoming_connections(
+                endpoint.listen(),
+                event_tx.clone(),
+                self.connection_buffers.clone(),
+                self.connection_observers.clone(),
+            ));
+
+            self.extra_endpoints.push(ExtraEndpoint {
+                _quic_p2p: quic_p2p,
+                endpoint,
+                is_ipv6,
+            });
+        }
+
+        Ok(self)
+    }
+
     // Close all existing connections and stop accepting new ones.
     pub fn terminate(&self) {
-        self.endpoint.close();
+        self.close_endpoints();
         let _ = self
             .event_tx
             .write()
@@ -91,6 +370,27 @@ impl Comm {
             .take();
     }
 
+    // Stops accepting new sends, waits for the ones in progress to complete, but at most `timeout`,
+    // then terminates. Unlike `terminate`, this doesn't cut short the sends already underway.
+    pub async fn drain(&self, timeout: Duration) {
+        self.draining.store(true, Ordering::SeqCst);
+
+        let in_flight = async {
+            while self.sends_in_flight.load(Ordering::SeqCst) > 0 {
+                time::delay_for(DRAIN_POLL_INTERVAL).await
+            }
+        };
+
+        if time::timeout(timeout, in_flight).await.is_err() {
+            debug!(
+                "Terminating with {} sends still in progress",
+                self.sends_in_flight.load(Ordering::SeqCst)
+            );
+        }
+
+        self.terminate()
+    }
+
     pub async fn our_connection_info(&self) -> Result<SocketAddr> {
         self.endpoint.socket_addr().await.map_err(|err| {
             error!("Failed to retrieve our connection info: {:?}", err);
@@ -98,22 +398,208 @@ impl Comm {
         })
     }
 
+    /// Returns the connection info of all our endpoints, the main one first.
+    pub async fn our_connection_infos(&self) -> Result<Vec<SocketAddr>> {
+        let mut addrs = vec![self.our_connection_info().await?];
+
+        for extra in &self.extra_endpoints {
+            addrs.push(extra.endpoint.socket_addr().await.map_err(|err| {
+                error!("Failed to retrieve our connection info: {:?}", err);
+                Error::from(err)
+            })?);
+        }
+
+        Ok(addrs)
+    }
+
     /// Sends a message on an existing connection. If no such connection exists, returns an error.
     pub async fn send_on_existing_connection(
         &self,
         recipient: &SocketAddr,
         msg: Bytes,
     ) -> Result<(), SendError> {
-        if let Some(conn) = self.endpoint.get_connection(recipient) {
+        let _in_flight = self.start_send()?;
+
+        if let Some(conn) = self.endpoint_for(recipient).get_connection(recipient) {
             if let Err(err) = conn.send_uni(msg).await {
                 error!("Sending message to {} failed: {}", recipient, err);
+                self.record_failure(recipient, SendFailureKind::Send);
             } else {
+                self.record_sent(recipient);
                 return Ok(());
             }
         }
         Err(SendError)
     }
 
+    /// Sends a message to a single recipient without keeping the connection around afterwards.
+    /// Useful for one-off sends to ephemeral addresses which would otherwise just pollute the
+    /// connection pool. If a connection to the recipient already exists, it is reused and left
+    /// open.
+    pub async fn send_uncached(&self, recipient: &SocketAddr, msg: Bytes) -> Result<(), SendError> {
+        let _in_flight = self.start_send()?;
+
+        if let Some(conn) = self.endpoint_for(recipient).get_connection(recipient) {
+            conn.send_uni(msg).await.map_err(|err| {
+                error!("Sending message to {} failed: {}", recipient, err);
+                self.record_failure(recipient, SendFailureKind::Send);
+                SendError
+            })?;
+            self.record_sent(recipient);
+            return Ok(());
+        }
+
+        let (conn, _) = self.connect_limited(recipient).await.map_err(|err| {
+            error!("Failed to connect to {}: {}", recipient, err);
+            self.record_failure(recipient, SendFailureKind::Connect);
+            SendError
+        })?;
+
+        let result = conn.send_uni(msg).await.map_err(|err| {
+            error!("Sending message to {} failed: {}", recipient, err);
+            self.record_failure(recipient, SendFailureKind::Send);
+            SendError
+        });
+
+        conn.close();
+        self.connection_observers
+            .notify(recipient, PeerConnectionEventKind::Closed);
+
+        if result.is_ok() {
+            self.record_sent(recipient);
+        }
+
+        result
+    }
+
+    /// Opens a new bi-directional stream to `recipient` and sends `msg` on it. Returns the sending
+    /// half of the stream which can be used to send further messages.
+    pub async fn open_stream(
+        &self,
+        recipient: &SocketAddr,
+        msg: Bytes,
+    ) -> Result<qp2p::SendStream, SendError> {
+        let _in_flight = self.start_send()?;
+
+        let conn = self.connect_to(recipient).await.map_err(|err| {
+            error!("Failed to connect to {}: {}", recipient, err);
+            self.record_failure(recipient, SendFailureKind::Connect);
+            SendError
+        })?;
+        let (send, _) = conn.send_bi(msg).await.map_err(|err| {
+            error!("Opening stream to {} failed: {}", recipient, err);
+            self.record_failure(recipient, SendFailureKind::Send);
+            SendError
+        })?;
+        self.record_sent(recipient);
+
+        Ok(send)
+    }
+
+    /// Opens a new bi-directional stream to `recipient`, sends `msg` on it and waits for a single
+    /// response on the same stream.
+    pub async fn request(&self, recipient: &SocketAddr, msg: Bytes) -> Result<Bytes, SendError> {
+        let _in_flight = self.start_send()?;
+
+        let conn = self.connect_to(recipient).await.map_err(|err| {
+            error!("Failed to connect to {}: {}", recipient, err);
+            self.record_failure(recipient, SendFailureKind::Connect);
+            SendError
+        })?;
+        // Keep the sending half around until the response arrives.
+        let (_send, mut recv) = conn.send_bi(msg).await.map_err(|err| {
+            error!("Opening stream to {} failed: {}", recipient, err);
+            self.record_failure(recipient, SendFailureKind::Send);
+            SendError
+        })?;
+        self.record_sent(recipient);
+
+        recv.next().await.map_err(|err| {
+            debug!("Receiving response from {} failed: {}", recipient, err);
+            SendError
+        })
+    }
+
+    /// Establishes a connection to `addr`, or reuses the existing one, and runs `f` with it. The
+    /// connection is kept open at least until the future returned by `f` completes, so all the
+    /// sends done by `f` share it.
+    pub async fn with_connection<F, Fut, R>(&self, addr: &SocketAddr, f: F) -> Result<R, SendError>
+    where
+        F: FnOnce(Connection) -> Fut,
+        Fut: Future<Output = R>,
+    {
+        let _in_flight = self.start_send()?;
+
+        let conn = self.connect_to(addr).await.map_err(|err| {
+            error!("Failed to connect to {}: {}", addr, err);
+            self.record_failure(addr, SendFailureKind::Connect);
+            SendError
+        })?;
+
+        Ok(f(conn).await)
+    }
+
+    /// Returns the time of the last successful send to `addr`, or `None` if we haven't sent
+    /// anything to it yet.
+    pub fn last_sent(&self, addr: &SocketAddr) -> Option<Instant> {
+        self.last_sent
+            .read()
+            .unwrap_or_else(|err| err.into_inner())
+            .get(addr)
+            .copied()
+    }
+
+    /// Returns the peers whose last send failed within the cooldown period (the circuit breaker
+    /// cooldown if enabled, otherwise the default one). A peer is removed once a send to it
+    /// succeeds.
+    pub fn unreachable_peers(&self) -> Vec<SocketAddr> {
+        let mut last_failed = self
+            .last_failed
+            .write()
+            .unwrap_or_else(|err| err.into_inner());
+        last_failed.retain(|_, failed_at| failed_at.elapsed() < self.unreachable_cooldown);
+        last_failed.keys().copied().collect()
+    }
+
+    /// Returns how many times sending reused an existing connection vs had to open a new one.
+    pub fn connection_cache_stats(&self) -> ConnectionCacheStats {
+        ConnectionCacheStats {
+            hits: self.cache_hits.load(Ordering::Relaxed),
+            misses: self.cache_misses.load(Ordering::Relaxed),
+        }
+    }
+
+    /// Returns the number of bytes received but not yet handled for every connection that has any.
+    /// Only tracked if the buffer size is limited (see `with_max_connection_buffer`).
+    pub fn connection_buffer_usage(&self) -> HashMap<SocketAddr, usize> {
+        self.connection_buffers
+            .sizes
+            .lock()
+            .unwrap_or_else(|err| err.into_inner())
+            .clone()
+    }
+
+    /// Returns a stream of the low-level events about connections to other peers being opened,
+    /// closed or reset. Every call returns a new independent stream which receives only the events
+    /// that happen after the call.
+    pub fn connection_events(&self) -> impl Stream<Item = PeerConnectionEvent> {
+        let rx = self.connection_observers.subscribe();
+        stream::unfold(rx, |mut rx| async move {
+            let event = rx.recv().await?;
+            Some((event, rx))
+        })
+    }
+
+    /// Returns the most recent send failures, oldest first.
+    pub fn recent_send_failures(&self) -> Vec<SendFailureRecord> {
+        self.send_failures
+            .read()
+            .unwrap_or_else(|err| err.into_inner())
+            .iter()
+            .copied()
+            .collect()
+    }
+
     /// Sends a message to multiple recipients. Attempts to send to `delivery_group_size`
     /// recipients out of the `recipients` list. If a send fails, attempts to send to the next peer
     /// until `delivery_goup_size` successful sends complete or there are no more recipients to
@@ -128,6 +614,11 @@ impl Comm {
         delivery_group_size: usize,
         msg: Bytes,
     ) -> (Result<(), SendError>, Vec<SocketAddr>) {
+        let _in_flight = match self.start_send() {
+            Ok(in_flight) => in_flight,
+            Err(error) => return (Err(error), vec![]),
+        };
+
         trace!(
             "Sending message ({} bytes) to {} of {:?}",
             msg.len(),
@@ -148,7 +639,14 @@ impl Comm {
         // Run all the sends concurrently (using `FuturesUnordered`). If any of them fails, pick
         // the next recipient and try to send to them. Proceed until the needed number of sends
         // succeeds or if there are no more recipients to pick.
-        let send = |recipient, msg| async move { (self.send_to(recipient, msg).await, recipient) };
+        let send = |recipient, msg| async move {
+            if !self.circuit_allows(recipient) {
+                trace!("Not sending to {} - circuit breaker open", recipient);
+                return (None, recipient);
+            }
+
+            (Some(self.send_to(recipient, msg).await), recipient)
+        };
 
         let mut tasks: FuturesUnordered<_> = recipients[0..delivery_group_size]
             .iter()
@@ -161,13 +659,13 @@ impl Comm {
 
         while let Some((result, addr)) = tasks.next().await {
             match result {
-                Ok(()) => successes += 1,
-                Err(qp2p::Error::Connection(qp2p::ConnectionError::LocallyClosed)) => {
+                Some(Ok(())) => successes += 1,
+                Some(Err(qp2p::Error::Connection(qp2p::ConnectionError::LocallyClosed))) => {
                     // The connection was closed by us which means we are terminating so let's cut
                     // this short.
                     return (Err(SendError), vec![]);
                 }
-                Err(_) => {
+                Some(Err(_)) | None => {
                     failed_recipients.push(*addr);
 
                     if next < recipients.len() {
@@ -197,166 +695,1351 @@ impl Comm {
 
     // Low-level send
     async fn send_to(&self, recipient: &SocketAddr, msg: Bytes) -> Result<(), qp2p::Error> {
-        let conn = self.connect_to(recipient).await?;
-
-        if conn.send_uni(msg.clone()).await.is_ok() {
-            return Ok(());
+        if let Some(batching) = &self.batching {
+            self.send_batched(batching, recipient, msg).await
+        } else {
+            self.send_unbatched(recipient, msg).await
         }
-
-        self.connect_to(recipient).await?.send_uni(msg).await
     }
 
-    async fn connect_to(&self, addr: &SocketAddr) -> Result<Connection, qp2p::Error> {
-        let (conn, incoming_messages) = self.endpoint.connect_to(addr).await?;
-        let event_tx = self.event_tx.read().ok().and_then(|tx| tx.clone());
-
-        if let (Some(incoming_messages), Some(event_tx)) = (incoming_messages, event_tx) {
-            trace!(
-                "New outgoing connection to {}",
-                incoming_messages.remote_addr()
-            );
-            let _ = task::spawn(handle_incoming_messages(incoming_messages, event_tx));
+    // Sends the message right away if nothing else is being sent or queued to `recipient`.
+    // Otherwise adds it to the pending batch for `recipient` and waits until the batch is sent.
+    // The first message of a batch waits for the batching window to pass and then sends the whole
+    // batch.
+    async fn send_batched(
+        &self,
+        batching: &Batching,
+        recipient: &SocketAddr,
+        msg: Bytes,
+    ) -> Result<(), qp2p::Error> {
+        if let Some(_busy) = batching.mark_busy_if_idle(recipient) {
+            return self.send_unbatched(recipient, msg).await;
         }
 
-        Ok(conn)
-    }
-}
+        let (result_tx, result_rx) = oneshot::channel();
+
+        let (full_batch, leader_id) = {
+            let mut pending = batching
+                .pending
+                .lock()
+                .unwrap_or_else(|err| err.into_inner());
+            let size = BATCH_ITEM_HEADER_LEN + msg.len();
+
+            // If the message doesn't fit into the pending batch, send that batch right away and
+            // start a new one.
+            let full_batch = if pending
+                .get(recipient)
+                .map(|batch| batch.size + size > batching.max_size)
+                .unwrap_or(false)
+            {
+                pending.remove(recipient)
+            } else {
+                None
+            };
+
+            let batch = pending.entry(*recipient).or_insert_with(|| PendingBatch {
+                id: batching.next_id.fetch_add(1, Ordering::Relaxed),
+                messages: vec![],
+                size: BATCH_MAGIC.len(),
+                result_txs: vec![],
+            });
+            let leader_id = if batch.messages.is_empty() {
+                Some(batch.id)
+            } else {
+                None
+            };
 
-impl Drop for Comm {
-    fn drop(&mut self) {
-        self.endpoint.close()
-    }
-}
+            batch.messages.push(msg.clone());
+            batch.size += size;
+            batch.result_txs.push(result_tx);
 
-#[derive(Debug, Error)]
-#[error("Send failed")]
-pub struct SendError;
+            (full_batch, leader_id)
+        };
 
-impl From<SendError> for Error {
-    fn from(_: SendError) -> Self {
-        Error::FailedSend
-    }
-}
+        if let Some(batch) = full_batch {
+            self.send_batch(batching, recipient, batch).await;
+        }
 
-pub(crate) enum ConnectionEvent {
-    Received(qp2p::Message),
-    Disconnected(SocketAddr),
-}
+        if let Some(id) = leader_id {
+            let leader = BatchLeader {
+                batching,
+                recipient: *recipient,
+                id,
+            };
 
-impl Debug for ConnectionEvent {
-    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
-        match self {
-            Self::Received(qp2p::Message::UniStream { src, .. }) => {
-                write!(f, "Received(UniStream {{ src: {}, .. }})", src)
-            }
-            Self::Received(qp2p::Message::BiStream { src, .. }) => {
-                write!(f, "Received(BiStream {{ src: {}, .. }})", src)
+            time::delay_for(batching.window).await;
+
+            // The batch might have been sent already because it got full.
+            if let Some(batch) = leader.take_batch() {
+                self.send_batch(batching, recipient, batch).await;
             }
-            Self::Disconnected(addr) => write!(f, "Disconnected({})", addr),
         }
-    }
-}
 
-async fn handle_incoming_connections(
-    mut incoming_conns: qp2p::IncomingConnections,
-    event_tx: mpsc::Sender<ConnectionEvent>,
-) {
-    while let Some(incoming_msgs) = incoming_conns.next().await {
-        trace!("New incoming connection to {}", incoming_msgs.remote_addr());
-        let _ = task::spawn(handle_incoming_messages(incoming_msgs, event_tx.clone()));
+        if let Ok(result) = result_rx.await {
+            result
+        } else {
+            // The batch was dropped without being sent. Send the message on its own.
+            self.send_unbatched(recipient, msg).await
+        }
     }
-}
 
-async fn handle_incoming_messages(
-    mut incoming_msgs: qp2p::IncomingMessages,
-    mut event_tx: mpsc::Sender<ConnectionEvent>,
-) {
-    while let Some(msg) = incoming_msgs.next().await {
-        let _ = event_tx.send(ConnectionEvent::Received(msg)).await;
-    }
+    async fn send_batch(&self, batching: &Batching, recipient: &SocketAddr, batch: PendingBatch) {
+        let _busy = batching.mark_busy(recipient);
 
-    let _ = event_tx
-        .send(ConnectionEvent::Disconnected(incoming_msgs.remote_addr()))
-        .await;
-}
+        let results = if let [msg] = &batch.messages[..] {
+            vec![self.send_unbatched(recipient, msg.clone()).await]
+        } else {
+            trace!(
+                "Sending batch of {} messages to {}",
+                batch.messages.len(),
+                recipient
+            );
 
-#[cfg(test)]
-mod tests {
-    use super::*;
-    use anyhow::Result;
-    use assert_matches::assert_matches;
-    use futures::future;
-    use qp2p::Config;
-    use std::{net::Ipv4Addr, slice, time::Duration};
-    use tokio::{net::UdpSocket, sync::mpsc, time};
+            if self
+                .send_unbatched(recipient, encode_batch(&batch.messages))
+                .await
+                .is_ok()
+            {
+                batch.messages.iter().map(|_| Ok(())).collect()
+            } else {
+                // Retry the messages of the failed batch one by one, so each of them is reported
+                // as delivered or failed on its own.
+                let mut results = Vec::with_capacity(batch.messages.len());
+                for msg in batch.messages {
+                    results.push(self.send_unbatched(recipient, msg).await);
+                }
+                results
+            }
+        };
 
-    const TIMEOUT: Duration = Duration::from_secs(1);
+        for (result_tx, result) in batch.result_txs.into_iter().zip(results) {
+            let _ = result_tx.send(result);
+        }
+    }
 
-    #[tokio::test]
-    async fn successful_send() -> Result<()> {
-        let (tx, _rx) = mpsc::channel(1);
-        let comm = Comm::new(transport_config(), tx)?;
+    async fn send_unbatched(&self, recipient: &SocketAddr, msg: Bytes) -> Result<(), qp2p::Error> {
+        let send_lock = self.send_lock(recipient);
+        let _guard = if let Some(send_lock) = &send_lock {
+            Some(send_lock.lock.lock().await)
+        } else {
+            None
+        };
 
-        let mut peer0 = Peer::new().await?;
-        let mut peer1 = Peer::new().await?;
+        #[cfg(any(test, feature = "testing"))]
+        {
+            if let Some(impairment) = &self.link_impairment {
+                if impairment.apply().await {
+                    trace!("Simulating loss of message to {}", recipient);
+                    return Err(self.check_failure(
+                        recipient,
+                        SendFailureKind::Send,
+                        qp2p::Error::Connection(qp2p::ConnectionError::TimedOut),
+                    ));
+                }
+            }
+        }
 
-        let message = Bytes::from_static(b"hello world");
-        comm.send(&[peer0.addr, peer1.addr], 2, message.clone())
+        let conn = self
+            .connect_to(recipient)
             .await
-            .0?;
+            .map_err(|err| self.check_failure(recipient, SendFailureKind::Connect, err))?;
 
-        assert_eq!(peer0.rx.recv().await, Some(message.clone()));
-        assert_eq!(peer1.rx.recv().await, Some(message));
+        if conn.send_uni(msg.clone()).await.is_ok() {
+            self.record_sent(recipient);
+            return Ok(());
+        }
+
+        self.connection_observers
+            .notify(recipient, PeerConnectionEventKind::Reset);
+
+        self.connect_to(recipient)
+            .await
+            .map_err(|err| self.check_failure(recipient, SendFailureKind::Connect, err))?
+            .send_uni(msg)
+            .await
+            .map_err(|err| self.check_failure(recipient, SendFailureKind::Send, err))?;
+        self.record_sent(recipient);
 
         Ok(())
     }
 
-    #[tokio::test]
-    async fn successful_send_to_subset() -> Result<()> {
-        let (tx, _rx) = mpsc::channel(1);
-        let comm = Comm::new(transport_config(), tx)?;
+    // Records the failure unless it's caused by us terminating. Returns the error back.
+    fn check_failure(
+        &self,
+        recipient: &SocketAddr,
+        kind: SendFailureKind,
+        error: qp2p::Error,
+    ) -> qp2p::Error {
+        if !matches!(
+            error,
+            qp2p::Error::Connection(qp2p::ConnectionError::LocallyClosed)
+        ) {
+            self.record_failure(recipient, kind);
+        }
 
-        let mut peer0 = Peer::new().await?;
-        let mut peer1 = Peer::new().await?;
+        error
+    }
 
-        let message = Bytes::from_static(b"hello world");
-        comm.send(&[peer0.addr, peer1.addr], 1, message.clone())
-            .await
-            .0?;
+    fn record_failure(&self, recipient: &SocketAddr, kind: SendFailureKind) {
+        if let Some(circuit_breaker) = &self.circuit_breaker {
+            circuit_breaker.record_failure(recipient);
+        }
 
-        assert_eq!(peer0.rx.recv().await, Some(message));
+        let _ = self
+            .last_failed
+            .write()
+            .unwrap_or_else(|err| err.into_inner())
+            .insert(*recipient, Instant::now());
 
-        assert!(time::timeout(TIMEOUT, peer1.rx.recv())
-            .await
-            .unwrap_or_default()
-            .is_none());
+        if self.send_failure_history_len == 0 {
+            return;
+        }
 
-        Ok(())
-    }
+        let mut send_failures = self
+            .send_failures
+            .write()
+            .unwrap_or_else(|err| err.into_inner());
 
-    #[tokio::test]
-    async fn failed_send() -> Result<()> {
+        while send_failures.len() >= self.send_failure_history_len {
+            let _ = send_failures.pop_front();
+        }
+
+        send_failures.push_back(SendFailureRecord {
+            addr: *recipient,
+            timestamp: SystemTime::now(),
+            kind,
+        });
+    }
+
+    fn record_sent(&self, recipient: &SocketAddr) {
+        if let Some(circuit_breaker) = &self.circuit_breaker {
+            circuit_breaker.record_success(recipient);
+        }
+
+        let _ = self
+            .last_failed
+            .write()
+            .unwrap_or_else(|err| err.into_inner())
+            .remove(recipient);
+
+        let _ = self
+            .last_sent
+            .write()
+            .unwrap_or_else(|err| err.into_inner())
+            .insert(*recipient, Instant::now());
+    }
+
+    async fn connect_to(&self, addr: &SocketAddr) -> Result<Connection, qp2p::Error> {
+        let (conn, incoming_messages) = self.connect_limited(addr).await?;
+
+        // `incoming_messages` is only returned for newly established connections.
+        if incoming_messages.is_some() {
+            let _ = self.cache_misses.fetch_add(1, Ordering::Relaxed);
+        } else {
+            let _ = self.cache_hits.fetch_add(1, Ordering::Relaxed);
+        }
+
+        let event_tx = self.event_tx.read().ok().and_then(|tx| tx.clone());
+
+        if let (Some(incoming_messages), Some(event_tx)) = (incoming_messages, event_tx) {
+            trace!(
+                "New outgoing connection to {}",
+                incoming_messages.remote_addr()
+            );
+            let _ = task::spawn(handle_incoming_messages(
+                incoming_messages,
+                event_tx,
+                self.connection_buffers.clone(),
+                self.connection_observers.clone(),
+            ));
+        }
+
+        Ok(conn)
+    }
+}
+
+impl Comm {
+    // Returns the lock to hold while sending to `addr` if ordered delivery is enabled.
+    fn send_lock(&self, addr: &SocketAddr) -> Option<SendLock<'_>> {
+        let send_locks = self.send_locks.as_ref()?;
+        let lock = send_locks
+            .lock()
+            .unwrap_or_else(|err| err.into_inner())
+            .entry(*addr)
+            .or_default()
+            .clone();
+
+        Some(SendLock {
+            send_locks,
+            addr: *addr,
+            lock,
+        })
+    }
+
+    // Returns whether sending to `addr` should be attempted according to the circuit breaker.
+    fn circuit_allows(&self, addr: &SocketAddr) -> bool {
+        self.circuit_breaker
+            .as_ref()
+            .map(|circuit_breaker| circuit_breaker.allows(addr))
+            .unwrap_or(true)
+    }
+
+    // Returns the endpoint to use for connections to `addr`: the first one bound to the same IP
+    // family as `addr`, or the main one if there is none.
+    fn endpoint_for(&self, addr: &SocketAddr) -> &Endpoint {
+        if self.endpoint_is_ipv6 == addr.is_ipv6() {
+            return &self.endpoint;
+        }
+
+        self.extra_endpoints
+            .iter()
+            .find(|extra| extra.is_ipv6 == addr.is_ipv6())
+            .map(|extra| &extra.endpoint)
+            .unwrap_or(&self.endpoint)
+    }
+
+    fn close_endpoints(&self) {
+        self.endpoint.close();
+
+        for extra in &self.extra_endpoints {
+            extra.endpoint.close();
+        }
+    }
+
+    // Registers a send as in progress until the returned guard is dropped. Returns `SendError` if
+    // we are draining.
+    fn start_send(&self) -> Result<InFlightSend, SendError> {
+        let in_flight = InFlightSend::new(&self.sends_in_flight);
+
+        if self.draining.load(Ordering::SeqCst) {
+            trace!("Not sending - draining");
+            return Err(SendError);
+        }
+
+        Ok(in_flight)
+    }
+
+    // Connects to `addr`, waiting for a free slot first if the number of concurrent connection
+    // attempts is limited. Reusing an existing connection doesn't count against the limit.
+    async fn connect_limited(
+        &self,
+        addr: &SocketAddr,
+    ) -> Result<(Connection, Option<qp2p::IncomingMessages>), qp2p::Error> {
+        let endpoint = self.endpoint_for(addr);
+        let _permit = if endpoint.get_connection(addr).is_some() {
+            None
+        } else {
+            self.connect_limiter.acquire().await
+        };
+
+        #[cfg(test)]
+        let _in_flight = self.connect_limiter.track();
+        let (conn, incoming_messages) = endpoint.connect_to(addr).await?;
+
+        if incoming_messages.is_some() {
+            self.connection_observers
+                .notify(addr, PeerConnectionEventKind::Opened);
+        }
+
+        Ok((conn, incoming_messages))
+    }
+}
+
+// Limits the number of connection attempts in progress at the same time.
+#[derive(Default)]
+struct ConnectLimiter {
+    // `None` if the number of concurrent connection attempts is unlimited.
+    semaphore: Option<Semaphore>,
+    // Number of connection attempts currently in progress and the highest such number so far.
+    #[cfg(test)]
+    in_flight: AtomicUsize,
+    #[cfg(test)]
+    max_in_flight: AtomicUsize,
+}
+
+impl ConnectLimiter {
+    async fn acquire(&self) -> Option<SemaphorePermit<'_>> {
+        if let Some(semaphore) = &self.semaphore {
+            Some(semaphore.acquire().await)
+        } else {
+            None
+        }
+    }
+
+    #[cfg(test)]
+    fn track(&self) -> InFlightConnect {
+        let in_flight = self.in_flight.fetch_add(1, Ordering::Relaxed) + 1;
+        let _ = self.max_in_flight.fetch_max(in_flight, Ordering::Relaxed);
+
+        InFlightConnect(&self.in_flight)
+    }
+}
+
+// Decrements the number of in-flight connection attempts when dropped.
+#[cfg(test)]
+struct InFlightConnect<'a>(&'a AtomicUsize);
+
+#[cfg(test)]
+impl Drop for InFlightConnect<'_> {
+    fn drop(&mut self) {
+        let _ = self.0.fetch_sub(1, Ordering::Relaxed);
+    }
+}
+
+// Marks a send as in progress while alive.
+struct InFlightSend<'a>(&'a AtomicUsize);
+
+impl<'a> InFlightSend<'a> {
+    fn new(counter: &'a AtomicUsize) -> Self {
+        let _ = counter.fetch_add(1, Ordering::SeqCst);
+        Self(counter)
+    }
+}
+
+impl Drop for InFlightSend<'_> {
+    fn drop(&mut self) {
+        let _ = self.0.fetch_sub(1, Ordering::SeqCst);
+    }
+}
+
+impl Drop for Comm {
+    fn drop(&mut self) {
+        self.close_endpoints()
+    }
+}
+
+// Endpoint listening in addition to the main one.
+struct ExtraEndpoint {
+    _quic_p2p: QuicP2p,
+    endpoint: Endpoint,
+    is_ipv6: bool,
+}
+
+// Returns whether an endpoint created with the given config is bound to an IPv6 address.
+fn is_ipv6(transport_config: &qp2p::Config) -> bool {
+    transport_config.ip.map(|ip| ip.is_ipv6()).unwrap_or(false)
+}
+
+/// Statistics of the reuse of cached connections when sending messages.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq, Serialize)]
+pub struct ConnectionCacheStats {
+    /// Number of sends that reused an existing connection.
+    pub hits: u64,
+    /// Number of sends that had to open a new connection.
+    pub misses: u64,
+}
+
+struct Batching {
+    window: Duration,
+    max_size: usize,
+    pending: Mutex<HashMap<SocketAddr, PendingBatch>>,
+    // Number of sends (of single messages or whole batches) in progress to each recipient.
+    busy: Mutex<HashMap<SocketAddr, usize>>,
+    next_id: AtomicU64,
+}
+
+impl Batching {
+    // Marks `recipient` as being sent to, unless it already is or there is a batch pending for it.
+    fn mark_busy_if_idle(&self, recipient: &SocketAddr) -> Option<BusyRecipient> {
+        let pending = self.pending.lock().unwrap_or_else(|err| err.into_inner());
+        let mut busy = self.busy.lock().unwrap_or_else(|err| err.into_inner());
+
+        if pending.contains_key(recipient) || busy.contains_key(recipient) {
+            None
+        } else {
+            let _ = busy.insert(*recipient, 1);
+            Some(BusyRecipient {
+                batching: self,
+                recipient: *recipient,
+            })
+        }
+    }
+
+    fn mark_busy(&self, recipient: &SocketAddr) -> BusyRecipient {
+        *self
+            .busy
+            .lock()
+            .unwrap_or_else(|err| err.into_inner())
+            .entry(*recipient)
+            .or_default() += 1;
+
+        BusyRecipient {
+            batching: self,
+            recipient: *recipient,
+        }
+    }
+}
+
+// The send of the first message of a pending batch, which sends the batch once the batching window
+// passes. If the send is cancelled before that, the batch is dropped when this is, so it doesn't
+// stay pending forever. The senders of the other messages of the batch then send them on their
+// own.
+struct BatchLeader<'a> {
+    batching: &'a Batching,
+    recipient: SocketAddr,
+    id: u64,
+}
+
+impl BatchLeader<'_> {
+    // Takes the batch out of `pending`, unless it was taken already.
+    fn take_batch(&self) -> Option<PendingBatch> {
+        let mut pending = self
+            .batching
+            .pending
+            .lock()
+            .unwrap_or_else(|err| err.into_inner());
+
+        if pending.get(&self.recipient).map(|batch| batch.id) == Some(self.id) {
+            pending.remove(&self.recipient)
+        } else {
+            None
+        }
+    }
+}
+
+impl Drop for BatchLeader<'_> {
+    fn drop(&mut self) {
+        let _ = self.take_batch();
+    }
+}
+
+// Lock ordering the sends to `addr` (see `Comm::send_lock`). When dropped, it's removed from
+// `send_locks` unless another send to `addr` holds or waits for it, so the map doesn't keep an
+// entry for every address ever sent to.
+struct SendLock<'a> {
+    send_locks: &'a Mutex<HashMap<SocketAddr, Arc<AsyncMutex<()>>>>,
+    addr: SocketAddr,
+    lock: Arc<AsyncMutex<()>>,
+}
+
+impl Drop for SendLock<'_> {
+    fn drop(&mut self) {
+        let mut send_locks = self
+            .send_locks
+            .lock()
+            .unwrap_or_else(|err| err.into_inner());
+
+        // One reference is ours and the other one is in the map.
+        if Arc::strong_count(&self.lock) <= 2 {
+            let _ = send_locks.remove(&self.addr);
+        }
+    }
+}
+
+// Marks a recipient as being sent to while alive.
+struct BusyRecipient<'a> {
+    batching: &'a Batching,
+    recipient: SocketAddr,
+}
+
+impl Drop for BusyRecipient<'_> {
+    fn drop(&mut self) {
+        let mut busy = self
+            .batching
+            .busy
+            .lock()
+            .unwrap_or_else(|err| err.into_inner());
+
+        if let Some(count) = busy.get_mut(&self.recipient) {
+            *count -= 1;
+            if *count == 0 {
+                let _ = busy.remove(&self.recipient);
+            }
+        }
+    }
+}
+
+/// Simulated impairment of the links to all peers (see `Config::link_impairment`). For tests only.
+#[cfg(any(test, feature = "testing"))]
+#[derive(Clone, Copy, Debug)]
+pub struct LinkImpairment {
+    /// Probability (between 0 and 1) that a send is dropped.
+    pub drop_probability: f64,
+    /// Delay added to every send, dropped or not.
+    pub latency: Duration,
+    /// Seed of the random generator deciding which sends are dropped, so the outcome of a test is
+    /// reproducible.
+    pub seed: u64,
+}
+
+#[cfg(any(test, feature = "testing"))]
+struct ActiveLinkImpairment {
+    drop_probability: f64,
+    latency: Duration,
+    rng: Mutex<StdRng>,
+}
+
+#[cfg(any(test, feature = "testing"))]
+impl ActiveLinkImpairment {
+    // Waits for the simulated latency, then returns whether the send should be dropped.
+    async fn apply(&self) -> bool {
+        time::delay_for(self.latency).await;
+        self.rng
+            .lock()
+            .unwrap_or_else(|err| err.into_inner())
+            .gen_bool(self.drop_probability)
+    }
+}
+
+struct CircuitBreaker {
+    threshold: usize,
+    cooldown: Duration,
+    peers: Mutex<HashMap<SocketAddr, CircuitState>>,
+}
+
+#[derive(Default)]
+struct CircuitState {
+    consecutive_failures: usize,
+    // If set, the breaker is open and sends are skipped until this time.
+    open_until: Option<Instant>,
+}
+
+impl CircuitBreaker {
+    fn allows(&self, addr: &SocketAddr) -> bool {
+        let mut peers = self.peers.lock().unwrap_or_else(|err| err.into_inner());
+        let state = if let Some(state) = peers.get_mut(addr) {
+            state
+        } else {
+            return true;
+        };
+
+        match state.open_until {
+            None => true,
+            Some(open_until) if Instant::now() < open_until => false,
+            Some(_) => {
+                // Cooldown elapsed. Let a single probe through and re-arm the cooldown right away,
+                // so the breaker stays open for another cooldown unless the probe succeeds, even if
+                // its failure is never recorded.
+                state.open_until = Some(Instant::now() + self.cooldown);
+                true
+            }
+        }
+    }
+
+    fn record_success(&self, addr: &SocketAddr) {
+        let _ = self
+            .peers
+            .lock()
+            .unwrap_or_else(|err| err.into_inner())
+            .remove(addr);
+    }
+
+    fn record_failure(&self, addr: &SocketAddr) {
+        let mut peers = self.peers.lock().unwrap_or_else(|err| err.into_inner());
+        let state = peers.entry(*addr).or_default();
+
+        state.consecutive_failures += 1;
+
+        if state.consecutive_failures >= self.threshold {
+            if state.open_until.is_none() {
+                debug!(
+                    "Opening circuit breaker for {} after {} failed sends",
+                    addr, state.consecutive_failures
+                );
+            }
+
+            state.open_until = Some(Instant::now() + self.cooldown);
+        }
+    }
+}
+
+struct PendingBatch {
+    id: u64,
+    messages: Vec<Bytes>,
+    // Size of the encoded batch.
+    size: usize,
+    // For notifying the senders of the messages of the results of sending them.
+    result_txs: Vec<oneshot::Sender<Result<(), qp2p::Error>>>,
+}
+
+fn encode_batch(messages: &[Bytes]) -> Bytes {
+    let size = messages
+        .iter()
+        .map(|msg| BATCH_ITEM_HEADER_LEN + msg.len())
+        .sum::<usize>();
+    let mut bytes = BytesMut::with_capacity(BATCH_MAGIC.len() + size);
+    bytes.put_slice(BATCH_MAGIC);
+
+    for msg in messages {
+        bytes.put_u32(msg.len() as u32);
+        bytes.put_slice(msg);
+    }
+
+    bytes.freeze()
+}
+
+// Splits a batch of messages back into the individual messages, in the order they were sent.
+// Returns `None` if `bytes` is not a batch.
+pub(crate) fn split_batch(bytes: &Bytes) -> Option<Vec<Bytes>> {
+    if !bytes.starts_with(BATCH_MAGIC) {
+        return None;
+    }
+
+    let mut rest = bytes.slice(BATCH_MAGIC.len()..);
+    let mut messages = vec![];
+
+    while !rest.is_empty() {
+        if rest.len() < BATCH_ITEM_HEADER_LEN {
+            warn!("Dropping malformed tail of a message batch");
+            break;
+        }
+
+        let len = rest.get_u32() as usize;
+        if rest.len() < len {
+            warn!("Dropping malformed tail of a message batch");
+            break;
+        }
+
+        messages.push(rest.split_to(len));
+    }
+
+    Some(messages)
+}
+
+/// Record of a failed attempt to send a message to a peer.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct SendFailureRecord {
+    /// Address of the peer.
+    pub addr: SocketAddr,
+    /// Time of the failure.
+    pub timestamp: SystemTime,
+    /// What went wrong.
+    pub kind: SendFailureKind,
+}
+
+/// Kind of a send failure.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum SendFailureKind {
+    /// Failed to connect to the peer.
+    Connect,
+    /// Connected to the peer, but failed to send the message to it.
+    Send,
+}
+
+#[derive(Debug, Error)]
+#[error("Send failed")]
+pub struct SendError;
+
+impl From<SendError> for Error {
+    fn from(_: SendError) -> Self {
+        Error::FailedSend
+    }
+}
+
+pub(crate) enum ConnectionEvent {
+    Received(qp2p::Message),
+    Disconnected(SocketAddr),
+}
+
+impl Debug for ConnectionEvent {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        match self {
+            Self::Received(qp2p::Message::UniStream { src, .. }) => {
+                write!(f, "Received(UniStream {{ src: {}, .. }})", src)
+            }
+            Self::Received(qp2p::Message::BiStream { src, .. }) => {
+                write!(f, "Received(BiStream {{ src: {}, .. }})", src)
+            }
+            Self::Disconnected(addr) => write!(f, "Disconnected({})", addr),
+        }
+    }
+}
+
+// Periodically pings the pinned peers on their existing connections, until `Comm` is dropped.
+async fn send_keepalives(
+    endpoint: Endpoint,
+    pinned_peers: Weak<Mutex<HashSet<SocketAddr>>>,
+    interval: Duration,
+    jitter_percent: u8,
+) {
+    let ping = match MessageType::Ping.serialize() {
+        Ok(ping) => ping,
+        Err(error) => {
+            error!("Failed to serialize keep-alive ping: {}", error);
+            return;
+        }
+    };
+
+    loop {
+        let delay = jittered(interval, jitter_percent, &mut rand::thread_rng());
+        time::delay_for(delay).await;
+
+        let addrs: Vec<_> = if let Some(pinned_peers) = pinned_peers.upgrade() {
+            let pinned_peers = pinned_peers.lock().unwrap_or_else(|err| err.into_inner());
+            pinned_peers.iter().copied().collect()
+        } else {
+            break;
+        };
+
+        for addr in addrs {
+            if let Some(conn) = endpoint.get_connection(&addr) {
+                if let Err(error) = conn.send_uni(ping.clone()).await {
+                    trace!("Failed to send keep-alive to {}: {}", addr, error);
+                }
+            }
+        }
+    }
+}
+
+async fn handle_incoming_connections(
+    mut incoming_conns: qp2p::IncomingConnections,
+    event_tx: mpsc::Sender<ConnectionEvent>,
+    connection_buffers: Arc<ConnectionBuffers>,
+    connection_observers: Arc<ConnectionObservers>,
+) {
+    while let Some(incoming_msgs) = incoming_conns.next().await {
+        trace!("New incoming connection to {}", incoming_msgs.remote_addr());
+        connection_observers.notify(
+            &incoming_msgs.remote_addr(),
+            PeerConnectionEventKind::Opened,
+        );
+        let _ = task::spawn(handle_incoming_messages(
+            incoming_msgs,
+            event_tx.clone(),
+            connection_buffers.clone(),
+            connection_observers.clone(),
+        ));
+    }
+}
+
+async fn handle_incoming_messages(
+    mut incoming_msgs: qp2p::IncomingMessages,
+    mut event_tx: mpsc::Sender<ConnectionEvent>,
+    connection_buffers: Arc<ConnectionBuffers>,
+    connection_observers: Arc<ConnectionObservers>,
+) {
+    let remote_addr = incoming_msgs.remote_addr();
+
+    if connection_buffers.max_size.load(Ordering::Relaxed) == 0 {
+        while let Some(msg) = incoming_msgs.next().await {
+            let _ = event_tx.send(ConnectionEvent::Received(msg)).await;
+        }
+    } else {
+        // Keep reading from the connection while the previous messages wait to be handled, so
+        // we can tell when they take too much memory.
+        let (buffer_tx, mut buffer_rx) = mpsc::unbounded_channel();
+        let buffers = connection_buffers.clone();
+
+        let _ = task::spawn(async move {
+            while let Some(msg) = incoming_msgs.next().await {
+                if !buffers.add(&remote_addr, message_size(&msg)) {
+                    buffers.evict(&remote_addr);
+                    break;
+                }
+
+                if buffer_tx.send(msg).is_err() {
+                    break;
+                }
+            }
+        });
+
+        while let Some(msg) = buffer_rx.recv().await {
+            let size = message_size(&msg);
+            let _ = event_tx.send(ConnectionEvent::Received(msg)).await;
+            connection_buffers.remove(&remote_addr, size);
+        }
+
+        connection_buffers.clear(&remote_addr);
+    }
+
+    // If the connection was reset and re-established meanwhile, the new one is still open.
+    if !connection_buffers.is_cached(&remote_addr) {
+        connection_observers.notify(&remote_addr, PeerConnectionEventKind::Closed);
+    }
+
+    let _ = event_tx
+        .send(ConnectionEvent::Disconnected(remote_addr))
+        .await;
+}
+
+/// Low-level event about a connection to another peer.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct PeerConnectionEvent {
+    /// Address of the peer.
+    pub addr: SocketAddr,
+    /// What happened to the connection.
+    pub kind: PeerConnectionEventKind,
+}
+
+/// Kind of a `PeerConnectionEvent`.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum PeerConnectionEventKind {
+    /// New connection, either incoming or outgoing, has been established.
+    Opened,
+    /// The connection has been closed, by either side.
+    Closed,
+    /// Sending on the connection failed, so it's going to be re-established.
+    Reset,
+}
+
+#[derive(Default)]
+struct ConnectionObservers {
+    txs: Mutex<Vec<mpsc::UnboundedSender<PeerConnectionEvent>>>,
+    // Peers whose connection was reported as opened and not yet as closed or reset. Used to report
+    // every connection closing only once, even though several code paths can notice it.
+    open: Mutex<HashSet<SocketAddr>>,
+}
+
+impl ConnectionObservers {
+    fn subscribe(&self) -> mpsc::UnboundedReceiver<PeerConnectionEvent> {
+        let (tx, rx) = mpsc::unbounded_channel();
+        self.txs
+            .lock()
+            .unwrap_or_else(|err| err.into_inner())
+            .push(tx);
+        rx
+    }
+
+    fn notify(&self, addr: &SocketAddr, kind: PeerConnectionEventKind) {
+        {
+            let mut open = self.open.lock().unwrap_or_else(|err| err.into_inner());
+            match kind {
+                PeerConnectionEventKind::Opened => {
+                    if !open.insert(*addr) {
+                        return;
+                    }
+                }
+                PeerConnectionEventKind::Closed => {
+                    if !open.remove(addr) {
+                        return;
+                    }
+                }
+                PeerConnectionEventKind::Reset => {
+                    let _ = open.remove(addr);
+                }
+            }
+        }
+
+        let event = PeerConnectionEvent { addr: *addr, kind };
+
+        // Drop the subscribers that are no longer interested.
+        self.txs
+            .lock()
+            .unwrap_or_else(|err| err.into_inner())
+            .retain(|tx| tx.send(event).is_ok());
+    }
+}
+
+fn message_size(msg: &qp2p::Message) -> usize {
+    match msg {
+        qp2p::Message::UniStream { bytes, .. } | qp2p::Message::BiStream { bytes, .. } => {
+            bytes.len()
+        }
+    }
+}
+
+// Tracks the size of the messages received on each connection but not yet handled.
+struct ConnectionBuffers {
+    endpoints: RwLock<Vec<Endpoint>>,
+    // Maximum total size of the buffered messages per connection, zero meaning unlimited.
+    max_size: AtomicUsize,
+    sizes: Mutex<HashMap<SocketAddr, usize>>,
+}
+
+impl ConnectionBuffers {
+    fn new(endpoint: Endpoint) -> Self {
+        Self {
+            endpoints: RwLock::new(vec![endpoint]),
+            max_size: AtomicUsize::new(0),
+            sizes: Mutex::new(HashMap::new()),
+        }
+    }
+
+    fn add_endpoint(&self, endpoint: Endpoint) {
+        self.endpoints
+            .write()
+            .unwrap_or_else(|err| err.into_inner())
+            .push(endpoint)
+    }
+
+    // Adds `size` bytes to the buffer of the connection to `addr`. Returns whether the buffer is
+    // still within the limit.
+    fn add(&self, addr: &SocketAddr, size: usize) -> bool {
+        let mut sizes = self.sizes.lock().unwrap_or_else(|err| err.into_inner());
+        let buffered = sizes.entry(*addr).or_default();
+        *buffered += size;
+
+        let max_size = self.max_size.load(Ordering::Relaxed);
+        if max_size > 0 && *buffered > max_size {
+            warn!(
+                "Connection to {} exceeded its buffer limit ({} > {} bytes)",
+                addr, buffered, max_size
+            );
+            false
+        } else {
+            true
+        }
+    }
+
+    fn remove(&self, addr: &SocketAddr, size: usize) {
+        let mut sizes = self.sizes.lock().unwrap_or_else(|err| err.into_inner());
+        if let Some(buffered) = sizes.get_mut(addr) {
+            *buffered = buffered.saturating_sub(size);
+        }
+    }
+
+    fn clear(&self, addr: &SocketAddr) {
+        let _ = self
+            .sizes
+            .lock()
+            .unwrap_or_else(|err| err.into_inner())
+            .remove(addr);
+    }
+
+    // Returns whether there is a connection to `addr` in the connection pool.
+    fn is_cached(&self, addr: &SocketAddr) -> bool {
+        self.endpoints
+            .read()
+            .unwrap_or_else(|err| err.into_inner())
+            .iter()
+            .any(|endpoint| endpoint.get_connection(addr).is_some())
+    }
+
+    // Closes the connection to `addr` and removes it from the connection pool.
+    fn evict(&self, addr: &SocketAddr) {
+        let endpoints = self.endpoints.read().unwrap_or_else(|err| err.into_inner());
+        for endpoint in endpoints.iter() {
+            if let Some(conn) = endpoint.get_connection(addr) {
+                conn.close();
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use anyhow::Result;
+    use assert_matches::assert_matches;
+    use futures::future;
+    use qp2p::Config;
+    use std::{
+        iter,
+        net::{IpAddr, Ipv4Addr, Ipv6Addr},
+        slice,
+        time::Duration,
+    };
+    use tokio::{net::UdpSocket, sync::mpsc, time};
+
+    const TIMEOUT: Duration = Duration::from_secs(1);
+
+    #[tokio::test]
+    async fn successful_send() -> Result<()> {
+        let (tx, _rx) = mpsc::channel(1);
+        let comm = Comm::new(transport_config(), tx)?;
+
+        let mut peer0 = Peer::new().await?;
+        let mut peer1 = Peer::new().await?;
+
+        let message = Bytes::from_static(b"hello world");
+        comm.send(&[peer0.addr, peer1.addr], 2, message.clone())
+            .await
+            .0?;
+
+        assert_eq!(peer0.rx.recv().await, Some(message.clone()));
+        assert_eq!(peer1.rx.recv().await, Some(message));
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn successful_send_to_subset() -> Result<()> {
+        let (tx, _rx) = mpsc::channel(1);
+        let comm = Comm::new(transport_config(), tx)?;
+
+        let mut peer0 = Peer::new().await?;
+        let mut peer1 = Peer::new().await?;
+
+        let message = Bytes::from_static(b"hello world");
+        comm.send(&[peer0.addr, peer1.addr], 1, message.clone())
+            .await
+            .0?;
+
+        assert_eq!(peer0.rx.recv().await, Some(message));
+
+        assert!(time::timeout(TIMEOUT, peer1.rx.recv())
+            .await
+            .unwrap_or_default()
+            .is_none());
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn failed_send() -> Result<()> {
+        let (tx, _rx) = mpsc::channel(1);
+        let comm = Comm::new(
+            Config {
+                // This makes this test faster.
+                idle_timeout_msec: Some(1),
+                ..transport_config()
+            },
+            tx,
+        )?;
+        let invalid_addr = get_invalid_addr().await?;
+
+        let message = Bytes::from_static(b"hello world");
+        let (result, failed_recipients) = comm.send(&[invalid_addr], 1, message.clone()).await;
+        assert!(result.is_err());
+        assert_eq!(failed_recipients, [invalid_addr]);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn successful_send_after_failed_attempts() -> Result<()> {
+        let (tx, _rx) = mpsc::channel(1);
+        let comm = Comm::new(
+            Config {
+                idle_timeout_msec: Some(1),
+                ..transport_config()
+            },
+            tx,
+        )?;
+        let mut peer = Peer::new().await?;
+        let invalid_addr = get_invalid_addr().await?;
+
+        let message = Bytes::from_static(b"hello world");
+        comm.send(&[invalid_addr, peer.addr], 1, message.clone())
+            .await
+            .0?;
+
+        assert_eq!(peer.rx.recv().await, Some(message));
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn partially_successful_send() -> Result<()> {
+        let (tx, _rx) = mpsc::channel(1);
+        let comm = Comm::new(
+            Config {
+                idle_timeout_msec: Some(1),
+                ..transport_config()
+            },
+            tx,
+        )?;
+        let mut peer = Peer::new().await?;
+        let invalid_addr = get_invalid_addr().await?;
+
+        let message = Bytes::from_static(b"hello world");
+        let (result, failed_recipients) = comm
+            .send(&[invalid_addr, peer.addr], 2, message.clone())
+            .await;
+
+        assert!(result.is_err());
+        assert_eq!(failed_recipients, [invalid_addr]);
+        assert_eq!(peer.rx.recv().await, Some(message));
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn send_after_reconnect() -> Result<()> {
+        let (tx, _rx) = mpsc::channel(1);
+        let send_comm = Comm::new(transport_config(), tx)?;
+
+        let recv_transport = QuicP2p::with_config(Some(transport_config()), &[], false)?;
+        let recv_endpoint = recv_transport.new_endpoint()?;
+        let recv_addr = recv_endpoint.socket_addr().await?;
+        let mut recv_incoming_connections = recv_endpoint.listen();
+
+        // Send the first message.
+        let msg0 = Bytes::from_static(b"zero");
+        send_comm
+            .send(slice::from_ref(&recv_addr), 1, msg0.clone())
+            .await
+            .0?;
+
+        let mut msg0_received = false;
+
+        // Receive one message and drop the incoming stream.
+        {
+            if let Some(mut incoming_msgs) =
+                time::timeout(TIMEOUT, recv_incoming_connections.next()).await?
+            {
+                if let Some(msg) = time::timeout(TIMEOUT, incoming_msgs.next()).await? {
+                    assert_eq!(msg.get_message_data(), msg0);
+                    msg0_received = true;
+                }
+            }
+
+            assert!(msg0_received);
+        }
+
+        // Send the second message.
+        let msg1 = Bytes::from_static(b"one");
+        send_comm
+            .send(slice::from_ref(&recv_addr), 1, msg1.clone())
+            .await
+            .0?;
+
+        let mut msg1_received = false;
+
+        // Expect to receive the second message on a re-established connection.
+        if let Some(mut incoming_msgs) =
+            time::timeout(TIMEOUT, recv_incoming_connections.next()).await?
+        {
+            if let Some(msg) = time::timeout(TIMEOUT, incoming_msgs.next()).await? {
+                assert_eq!(msg.get_message_data(), msg1);
+                msg1_received = true;
+            }
+        }
+
+        assert!(msg1_received);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn send_uncached() -> Result<()> {
+        let (tx, _rx) = mpsc::channel(1);
+        let comm = Comm::new(transport_config(), tx)?;
+        let mut peer = Peer::new().await?;
+
+        let message = Bytes::from_static(b"hello world");
+        comm.send_uncached(&peer.addr, message.clone()).await?;
+
+        assert_eq!(peer.rx.recv().await, Some(message));
+        assert!(comm.endpoint.get_connection(&peer.addr).is_none());
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn last_sent() -> Result<()> {
+        let (tx, _rx) = mpsc::channel(1);
+        let comm = Comm::new(transport_config(), tx)?;
+        let mut peer0 = Peer::new().await?;
+        let peer1 = Peer::new().await?;
+
+        let message = Bytes::from_static(b"hello world");
+        comm.send(slice::from_ref(&peer0.addr), 1, message.clone())
+            .await
+            .0?;
+        assert_eq!(peer0.rx.recv().await, Some(message));
+
+        assert_matches!(comm.last_sent(&peer0.addr), Some(instant) => {
+            assert!(instant.elapsed() < TIMEOUT)
+        });
+        assert!(comm.last_sent(&peer1.addr).is_none());
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn connection_cache_stats() -> Result<()> {
+        let (tx, _rx) = mpsc::channel(1);
+        let comm = Comm::new(transport_config(), tx)?;
+        let mut peer0 = Peer::new().await?;
+        let mut peer1 = Peer::new().await?;
+
+        let message = Bytes::from_static(b"hello world");
+
+        // Repeated sends to the same peer reuse the connection after the first one.
+        for _ in 0..3 {
+            comm.send(slice::from_ref(&peer0.addr), 1, message.clone())
+                .await
+                .0?;
+            assert_eq!(peer0.rx.recv().await, Some(message.clone()));
+        }
+
+        assert_eq!(
+            comm.connection_cache_stats(),
+            ConnectionCacheStats { hits: 2, misses: 1 }
+        );
+
+        // Sending to a different peer needs a new connection.
+        comm.send(slice::from_ref(&peer1.addr), 1, message.clone())
+            .await
+            .0?;
+        assert_eq!(peer1.rx.recv().await, Some(message));
+
+        assert_eq!(
+            comm.connection_cache_stats(),
+            ConnectionCacheStats { hits: 2, misses: 2 }
+        );
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn with_connection() -> Result<()> {
+        let (tx, _rx) = mpsc::channel(1);
+        let comm = Comm::new(transport_config(), tx)?;
+        let mut peer = Peer::new().await?;
+
+        let messages = vec![
+            Bytes::from_static(b"one"),
+            Bytes::from_static(b"two"),
+            Bytes::from_static(b"three"),
+        ];
+
+        let sends = messages.clone();
+        comm.with_connection(&peer.addr, |conn| async move {
+            for message in sends {
+                conn.send_uni(message).await?;
+            }
+
+            Ok::<_, qp2p::Error>(())
+        })
+        .await??;
+
+        // Separate streams might arrive in any order.
+        let mut received = Vec::new();
+        for _ in 0..messages.len() {
+            received.extend(peer.rx.recv().await);
+        }
+        received.sort();
+        let mut expected = messages;
+        expected.sort();
+        assert_eq!(received, expected);
+
+        // The connection was established once and all the sends went through it.
+        assert_eq!(
+            comm.connection_cache_stats(),
+            ConnectionCacheStats { hits: 0, misses: 1 }
+        );
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn recent_send_failures() -> Result<()> {
         let (tx, _rx) = mpsc::channel(1);
         let comm = Comm::new(
             Config {
-                // This makes this test faster.
                 idle_timeout_msec: Some(1),
                 ..transport_config()
             },
             tx,
-        )?;
-        let invalid_addr = get_invalid_addr().await?;
+        )?
+        .with_send_failure_history_len(2);
+
+        let invalid_addrs = vec![
+            get_invalid_addr().await?,
+            get_invalid_addr().await?,
+            get_invalid_addr().await?,
+        ];
 
         let message = Bytes::from_static(b"hello world");
-        let (result, failed_recipients) = comm.send(&[invalid_addr], 1, message.clone()).await;
-        assert!(result.is_err());
-        assert_eq!(failed_recipients, [invalid_addr]);
+        for addr in &invalid_addrs {
+            let (result, _) = comm.send(slice::from_ref(addr), 1, message.clone()).await;
+            assert!(result.is_err());
+        }
+
+        // Only the two most recent failures are kept, oldest first.
+        let failures = comm.recent_send_failures();
+        let addrs: Vec<_> = failures.iter().map(|record| record.addr).collect();
+        assert_eq!(addrs, &invalid_addrs[1..]);
+        assert!(failures[0].timestamp <= failures[1].timestamp);
 
         Ok(())
     }
 
     #[tokio::test]
-    async fn successful_send_after_failed_attempts() -> Result<()> {
+    async fn unreachable_peers() -> Result<()> {
+        // Failed sends make the peer unreachable.
         let (tx, _rx) = mpsc::channel(1);
         let comm = Comm::new(
             Config {
@@ -365,21 +2048,139 @@ mod tests {
             },
             tx,
         )?;
-        let mut peer = Peer::new().await?;
-        let invalid_addr = get_invalid_addr().await?;
 
+        let invalid_addr = get_invalid_addr().await?;
         let message = Bytes::from_static(b"hello world");
-        comm.send(&[invalid_addr, peer.addr], 1, message.clone())
+        let (result, _) = comm
+            .send(slice::from_ref(&invalid_addr), 1, message.clone())
+            .await;
+        assert!(result.is_err());
+        assert_eq!(comm.unreachable_peers(), [invalid_addr]);
+
+        // A successful send makes the peer reachable again.
+        let (tx, _rx) = mpsc::channel(1);
+        let comm = Comm::new(transport_config(), tx)?;
+        let mut peer = Peer::new().await?;
+
+        comm.record_failure(&peer.addr, SendFailureKind::Send);
+        assert_eq!(comm.unreachable_peers(), [peer.addr]);
+
+        comm.send(slice::from_ref(&peer.addr), 1, message.clone())
             .await
             .0?;
+        assert_eq!(peer.rx.recv().await, Some(message));
+        assert!(comm.unreachable_peers().is_empty());
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn batching() -> Result<()> {
+        let (tx, _rx) = mpsc::channel(1);
+        let comm = Comm::new(transport_config(), tx)?
+            .with_batching(Duration::from_millis(100), 1024 * 1024);
+        let mut peer = Peer::new().await?;
+
+        let messages = vec![
+            Bytes::from_static(b"one"),
+            Bytes::from_static(b"two"),
+            Bytes::from_static(b"three"),
+        ];
+
+        let (result0, result1, result2) = future::join3(
+            comm.send(slice::from_ref(&peer.addr), 1, messages[0].clone()),
+            comm.send(slice::from_ref(&peer.addr), 1, messages[1].clone()),
+            comm.send(slice::from_ref(&peer.addr), 1, messages[2].clone()),
+        )
+        .await;
+        result0.0?;
+        result1.0?;
+        result2.0?;
+
+        // Nothing else is being sent to the peer, so the first message is sent right away. The
+        // other two are queued meanwhile and arrive in a single stream, in order.
+        assert_eq!(peer.rx.recv().await, Some(messages[0].clone()));
+        let batch = peer.rx.recv().await.expect("batch not received");
+        assert_eq!(split_batch(&batch), Some(messages[1..].to_vec()));
+
+        assert!(time::timeout(TIMEOUT, peer.rx.recv())
+            .await
+            .unwrap_or_default()
+            .is_none());
 
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn batching_cancelled_leader() -> Result<()> {
+        let (tx, _rx) = mpsc::channel(1);
+        let comm =
+            Comm::new(transport_config(), tx)?.with_batching(Duration::from_secs(60), 1024 * 1024);
+        let mut peer = Peer::new().await?;
+        let batching = comm.batching.as_ref().expect("batching not enabled");
+
+        // While something else is being sent to the peer, a message starts a new batch and waits
+        // for the batching window to pass. Its send is cancelled meanwhile.
+        let busy = batching.mark_busy(&peer.addr);
+        assert!(time::timeout(
+            Duration::from_millis(100),
+            comm.send(
+                slice::from_ref(&peer.addr),
+                1,
+                Bytes::from_static(b"cancelled")
+            )
+        )
+        .await
+        .is_err());
+        drop(busy);
+
+        // The abandoned batch doesn't hold up later sends to the peer.
+        let message = Bytes::from_static(b"hello world");
+        time::timeout(
+            TIMEOUT,
+            comm.send(slice::from_ref(&peer.addr), 1, message.clone()),
+        )
+        .await?
+        .0?;
         assert_eq!(peer.rx.recv().await, Some(message));
 
         Ok(())
     }
 
+    #[test]
+    fn split_non_batch() {
+        assert_eq!(split_batch(&Bytes::from_static(b"hello world")), None);
+    }
+
     #[tokio::test]
-    async fn partially_successful_send() -> Result<()> {
+    async fn max_concurrent_connects() -> Result<()> {
+        let (tx, _rx) = mpsc::channel(1);
+        let comm = Comm::new(transport_config(), tx)?.with_max_concurrent_connects(2);
+
+        let mut peers = vec![];
+        for _ in 0..8 {
+            peers.push(Peer::new().await?);
+        }
+        let addrs: Vec<_> = peers.iter().map(|peer| peer.addr).collect();
+
+        let message = Bytes::from_static(b"hello world");
+        comm.send(&addrs, addrs.len(), message.clone()).await.0?;
+
+        for peer in &mut peers {
+            assert_eq!(peer.rx.recv().await, Some(message.clone()));
+        }
+
+        let max_in_flight = comm.connect_limiter.max_in_flight.load(Ordering::Relaxed);
+        assert!(max_in_flight > 0);
+        assert!(max_in_flight <= 2);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn circuit_breaker() -> Result<()> {
+        let cooldown = Duration::from_millis(500);
+
         let (tx, _rx) = mpsc::channel(1);
         let comm = Comm::new(
             Config {
@@ -387,75 +2188,213 @@ mod tests {
                 ..transport_config()
             },
             tx,
-        )?;
-        let mut peer = Peer::new().await?;
-        let invalid_addr = get_invalid_addr().await?;
+        )?
+        .with_circuit_breaker(2, cooldown);
 
         let message = Bytes::from_static(b"hello world");
+
+        // Trip the breaker on a dead peer.
+        let invalid_addr = get_invalid_addr().await?;
+        for _ in 0..2 {
+            let (result, _) = comm
+                .send(slice::from_ref(&invalid_addr), 1, message.clone())
+                .await;
+            assert!(result.is_err());
+        }
+        assert_eq!(comm.recent_send_failures().len(), 2);
+
+        // Further sends fail without even being attempted.
         let (result, failed_recipients) = comm
-            .send(&[invalid_addr, peer.addr], 2, message.clone())
+            .send(slice::from_ref(&invalid_addr), 1, message.clone())
             .await;
-
         assert!(result.is_err());
         assert_eq!(failed_recipients, [invalid_addr]);
-        assert_eq!(peer.rx.recv().await, Some(message));
+        assert_eq!(comm.recent_send_failures().len(), 2);
+
+        // Once the cooldown elapses, a single probe is attempted. It fails, so the sends are
+        // skipped again for another cooldown.
+        time::delay_for(cooldown).await;
+
+        for _ in 0..2 {
+            let (result, _) = comm
+                .send(slice::from_ref(&invalid_addr), 1, message.clone())
+                .await;
+            assert!(result.is_err());
+            assert_eq!(comm.recent_send_failures().len(), 3);
+        }
+
+        // A peer whose breaker is open doesn't receive anything until the cooldown elapses.
+        let mut peer = Peer::new().await?;
+        comm.record_failure(&peer.addr, SendFailureKind::Send);
+        comm.record_failure(&peer.addr, SendFailureKind::Send);
+
+        let (result, _) = comm
+            .send(slice::from_ref(&peer.addr), 1, message.clone())
+            .await;
+        assert!(result.is_err());
+        assert!(time::timeout(TIMEOUT, peer.rx.recv())
+            .await
+            .unwrap_or_default()
+            .is_none());
+
+        time::delay_for(cooldown).await;
+
+        // The probe succeeds and closes the breaker again.
+        for _ in 0..2 {
+            comm.send(slice::from_ref(&peer.addr), 1, message.clone())
+                .await
+                .0?;
+            assert_eq!(peer.rx.recv().await, Some(message.clone()));
+        }
 
         Ok(())
     }
 
     #[tokio::test]
-    async fn send_after_reconnect() -> Result<()> {
+    async fn max_connection_buffer() -> Result<()> {
+        // Nobody handles the incoming messages so they pile up in the buffer.
         let (tx, _rx) = mpsc::channel(1);
-        let send_comm = Comm::new(transport_config(), tx)?;
+        let comm0 = Comm::new(transport_config(), tx)?.with_max_connection_buffer(100);
+        let addr0 = comm0.our_connection_info().await?;
 
-        let recv_transport = QuicP2p::with_config(Some(transport_config()), &[], false)?;
-        let recv_endpoint = recv_transport.new_endpoint()?;
-        let recv_addr = recv_endpoint.socket_addr().await?;
-        let mut recv_incoming_connections = recv_endpoint.listen();
+        let (tx, _rx) = mpsc::channel(1);
+        let comm1 = Comm::new(transport_config(), tx)?;
+        let addr1 = comm1.our_connection_info().await?;
 
-        // Send the first message.
-        let msg0 = Bytes::from_static(b"zero");
-        send_comm
-            .send(slice::from_ref(&recv_addr), 1, msg0.clone())
+        let message = Bytes::from(vec![0; 64]);
+        comm1
+            .send(slice::from_ref(&addr0), 1, message.clone())
             .await
             .0?;
 
-        let mut msg0_received = false;
+        for _ in 0..4 {
+            let _ = comm1
+                .send(slice::from_ref(&addr0), 1, message.clone())
+                .await;
+        }
 
-        // Receive one message and drop the incoming stream.
-        {
-            if let Some(mut incoming_msgs) =
-                time::timeout(TIMEOUT, recv_incoming_connections.next()).await?
-            {
-                if let Some(msg) = time::timeout(TIMEOUT, incoming_msgs.next()).await? {
-                    assert_eq!(msg.get_message_data(), msg0);
-                    msg0_received = true;
-                }
+        // The connection gets closed and evicted.
+        time::timeout(TIMEOUT, async {
+            while comm0.endpoint.get_connection(&addr1).is_some() {
+                time::delay_for(Duration::from_millis(10)).await;
             }
+        })
+        .await?;
 
-            assert!(msg0_received);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn connection_events() -> Result<()> {
+        let (tx, _rx) = mpsc::channel(1);
+        let comm = Comm::new(transport_config(), tx)?;
+        let mut events = Box::pin(comm.connection_events());
+        let mut peer = Peer::new().await?;
+
+        let message = Bytes::from_static(b"hello world");
+        comm.send_uncached(&peer.addr, message.clone()).await?;
+        assert_eq!(peer.rx.recv().await, Some(message));
+
+        let expected = [
+            PeerConnectionEvent {
+                addr: peer.addr,
+                kind: PeerConnectionEventKind::Opened,
+            },
+            PeerConnectionEvent {
+                addr: peer.addr,
+                kind: PeerConnectionEventKind::Closed,
+            },
+        ];
+
+        for expected in &expected {
+            assert_eq!(
+                time::timeout(TIMEOUT, events.next()).await?,
+                Some(*expected)
+            );
         }
 
-        // Send the second message.
-        let msg1 = Bytes::from_static(b"one");
-        send_comm
-            .send(slice::from_ref(&recv_addr), 1, msg1.clone())
-            .await
-            .0?;
+        // The connection closing is reported only once.
+        assert!(time::timeout(TIMEOUT, events.next()).await.is_err());
 
-        let mut msg1_received = false;
+        Ok(())
+    }
 
-        // Expect to receive the second message on a re-established connection.
-        if let Some(mut incoming_msgs) =
-            time::timeout(TIMEOUT, recv_incoming_connections.next()).await?
-        {
-            if let Some(msg) = time::timeout(TIMEOUT, incoming_msgs.next()).await? {
-                assert_eq!(msg.get_message_data(), msg1);
-                msg1_received = true;
-            }
+    #[tokio::test]
+    async fn ordered_delivery() -> Result<()> {
+        let (tx, _rx) = mpsc::channel(1);
+        let comm = Comm::new(transport_config(), tx)?.with_ordered_delivery();
+        let mut peer = Peer::new().await?;
+
+        let messages: Vec<_> = (0..20u8).map(|index| Bytes::from(vec![index])).collect();
+        let results = future::join_all(
+            messages
+                .iter()
+                .map(|msg| comm.send(slice::from_ref(&peer.addr), 1, msg.clone())),
+        )
+        .await;
+
+        for (result, _) in results {
+            result?;
         }
 
-        assert!(msg1_received);
+        for msg in messages {
+            assert_eq!(peer.rx.recv().await, Some(msg));
+        }
+
+        // The lock of the peer is released once all the sends to it are done.
+        assert!(comm
+            .send_locks
+            .as_ref()
+            .expect("ordered delivery not enabled")
+            .lock()
+            .unwrap()
+            .is_empty());
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn extra_endpoints() -> Result<()> {
+        let (tx, mut rx) = mpsc::channel(1);
+        let comm = Comm::new(transport_config(), tx)?.with_extra_endpoints(vec![Config {
+            ip: Some(Ipv6Addr::LOCALHOST.into()),
+            ..Default::default()
+        }])?;
+
+        let addrs = comm.our_connection_infos().await?;
+        assert_eq!(addrs.len(), 2);
+        assert!(addrs[0].is_ipv4());
+        assert!(addrs[1].is_ipv6());
+
+        for (index, ip) in [
+            IpAddr::from(Ipv4Addr::LOCALHOST),
+            Ipv6Addr::LOCALHOST.into(),
+        ]
+        .iter()
+        .enumerate()
+        {
+            let (tx, _rx) = mpsc::channel(1);
+            let sender = Comm::new(
+                Config {
+                    ip: Some(*ip),
+                    ..Default::default()
+                },
+                tx,
+            )?;
+            let sender_addr = sender.our_connection_info().await?;
+
+            let msg = Bytes::from(vec![index as u8]);
+            sender
+                .send(slice::from_ref(&addrs[index]), 1, msg.clone())
+                .await
+                .0?;
+
+            assert_matches!(
+                rx.recv().await,
+                Some(ConnectionEvent::Received(qp2p::Message::UniStream { bytes, src, .. }))
+                    if bytes == msg && src == sender_addr
+            );
+        }
 
         Ok(())
     }
@@ -488,6 +2427,124 @@ mod tests {
         Ok(())
     }
 
+    #[tokio::test]
+    async fn keepalive() -> Result<()> {
+        let idle_timeout = Duration::from_millis(500);
+        let transport_config = || Config {
+            idle_timeout_msec: Some(idle_timeout.as_millis() as u64),
+            ..transport_config()
+        };
+
+        let (tx, mut rx0) = mpsc::channel(1);
+        let comm0 = Comm::new(transport_config(), tx)?;
+        let addr0 = comm0.our_connection_info().await?;
+
+        let (tx, _rx) = mpsc::channel(1);
+        let comm1 = Comm::new(transport_config(), tx)?.with_keepalive(idle_timeout / 5, 0);
+        let addr1 = comm1.our_connection_info().await?;
+        comm1.set_pinned_peers(iter::once(addr0));
+
+        let (tx, _rx) = mpsc::channel(1);
+        let comm2 = Comm::new(transport_config(), tx)?.with_keepalive(idle_timeout / 5, 0);
+        let addr2 = comm2.our_connection_info().await?;
+
+        for comm in &[&comm1, &comm2] {
+            comm.send(slice::from_ref(&addr0), 1, Bytes::from_static(b"hello"))
+                .await
+                .0?;
+        }
+
+        // The pings keep arriving, so wait until a deadline instead of until the events stop.
+        let deadline = Instant::now() + idle_timeout * 3;
+        let mut disconnected = vec![];
+        while let Ok(event) = time::timeout(
+            deadline.saturating_duration_since(Instant::now()),
+            rx0.recv(),
+        )
+        .await
+        {
+            match event {
+                Some(ConnectionEvent::Disconnected(addr)) => disconnected.push(addr),
+                Some(ConnectionEvent::Received(_)) => (),
+                None => break,
+            }
+        }
+
+        assert!(!disconnected.contains(&addr1));
+        assert!(disconnected.contains(&addr2));
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn drain() -> Result<()> {
+        let (tx, _rx) = mpsc::channel(1);
+        // Batching holds a send back for the duration of the window while another send to the same
+        // peer is in progress, making it long enough to start draining while it's still in
+        // progress.
+        let window = Duration::from_millis(500);
+        let comm = Comm::new(transport_config(), tx)?.with_batching(window, 1024 * 1024);
+        let mut peer = Peer::new().await?;
+
+        let first_message = Bytes::from_static(b"first");
+        let in_flight_message = Bytes::from_static(b"in flight");
+        let new_message = Bytes::from_static(b"new");
+
+        let (first_result, in_flight_result, (), new_result) = future::join4(
+            comm.send(slice::from_ref(&peer.addr), 1, first_message.clone()),
+            comm.send(slice::from_ref(&peer.addr), 1, in_flight_message.clone()),
+            comm.drain(TIMEOUT * 5),
+            async {
+                time::delay_for(window / 5).await;
+                comm.send(slice::from_ref(&peer.addr), 1, new_message).await
+            },
+        )
+        .await;
+
+        first_result.0?;
+        in_flight_result.0?;
+        assert_matches!(new_result.0, Err(SendError));
+
+        assert_eq!(peer.rx.recv().await, Some(first_message));
+        assert_eq!(peer.rx.recv().await, Some(in_flight_message));
+        assert!(time::timeout(TIMEOUT, peer.rx.recv())
+            .await
+            .unwrap_or_default()
+            .is_none());
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn link_impairment() -> Result<()> {
+        // The peer is given as every recipient, so each dropped send falls back to sending to it
+        // again, up to this many times in total.
+        const ATTEMPTS: usize = 16;
+
+        let (tx, _rx) = mpsc::channel(1);
+        let latency = Duration::from_millis(10);
+        let comm = Comm::new(transport_config(), tx)?.with_link_impairment(LinkImpairment {
+            drop_probability: 0.5,
+            latency,
+            seed: 42,
+        });
+        let mut peer = Peer::new().await?;
+
+        let message = Bytes::from_static(b"hello world");
+        let start = Instant::now();
+        let (result, failed_recipients) = comm
+            .send(&vec![peer.addr; ATTEMPTS], 1, message.clone())
+            .await;
+        result?;
+
+        assert!(failed_recipients.len() < ATTEMPTS);
+        assert_eq!(comm.recent_send_failures().len(), failed_recipients.len());
+        assert!(start.elapsed() >= latency * (failed_recipients.len() as u32 + 1));
+        assert_eq!(peer.rx.recv().await, Some(message));
+
+        Ok(())
+    }
+
     fn transport_config() -> Config {
         Config {
             ip: Some(Ipv4Addr::LOCALHOST.into()),
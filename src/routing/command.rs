@@ -16,15 +16,16 @@ use crate::{
 use bls_signature_aggregator::Proof;
 use bytes::Bytes;
 use hex_fmt::HexFmt;
-use sn_messaging::{infrastructure::Query, node::NodeMessage, MessageType};
+use sn_messaging::{client::MsgEnvelope, infrastructure::Query, node::NodeMessage, MessageType};
 use std::{
     fmt::{self, Debug, Formatter},
     net::SocketAddr,
     slice,
     sync::atomic::{AtomicU64, Ordering},
-    time::Duration,
+    time::{Duration, SystemTime},
 };
 use tokio::sync::mpsc;
+use xor_name::{Prefix, XorName};
 
 /// Command for node.
 #[allow(clippy::large_enum_variant)]
@@ -65,12 +66,32 @@ pub(crate) enum Command {
         delivery_group_size: usize,
         message: MessageType,
     },
+    /// Send `message` to the client at `recipient`, encrypted to the client's `client_key`.
+    SendEncryptedToClient {
+        recipient: SocketAddr,
+        client_key: bls::PublicKey,
+        message: MsgEnvelope,
+    },
     /// Send `UserMessage` with the given source and destination.
     SendUserMessage {
         src: SrcLocation,
         dst: DstLocation,
         content: Bytes,
     },
+    /// Send `UserMessage` with the given content to a single elder of our section.
+    SendToAnyElder { content: Bytes },
+    /// Send `UserMessage` with the given content directly to the node with the given name.
+    SendToName { name: XorName, content: Bytes },
+    /// Send `UserMessage` with the given content to the elders of the `fanout` known sections
+    /// closest to `target`.
+    SendToward {
+        target: XorName,
+        fanout: usize,
+        content: Bytes,
+    },
+    /// Ask the elders of the neighbour section with the given prefix for their current
+    /// `EldersInfo`.
+    RefreshNeighbour(Prefix),
     /// Schedule a timeout after the given duration. When the timeout expires, a `HandleTimeout`
     /// command is raised. The token is used to identify the timeout.
     ScheduleTimeout { duration: Duration, token: u64 },
@@ -85,6 +106,14 @@ pub(crate) enum Command {
     },
     /// Attempt to set JoinsAllowed flag.
     SetJoinsAllowed(bool),
+    /// Ask our section to demote us from the elder set.
+    RequestDemotion,
+    /// Ask our section to let us be picked as an elder again after requesting demotion.
+    CancelDemotion,
+    /// Ask our section to relocate us back into it under a new name, to rotate our keypair.
+    RequestKeyRotation,
+    /// Ask our section not to relocate us for the given duration.
+    RequestRelocationDeferral(Duration),
 }
 
 impl Command {
@@ -106,6 +135,115 @@ impl Command {
             message: MessageType::NodeMessage(node_msg),
         }
     }
+
+    /// Short description of this command for debugging, without the full payloads.
+    pub fn summary(&self) -> CommandSummary {
+        let (kind, details) = match self {
+            Self::HandleMessage { sender, .. } => {
+                ("HandleMessage", format!("sender: {:?}", sender))
+            }
+            Self::HandleInfrastructureQuery { sender, .. } => {
+                ("HandleInfrastructureQuery", format!("sender: {}", sender))
+            }
+            Self::HandleTimeout(token) => ("HandleTimeout", format!("token: {}", token)),
+            Self::HandleConnectionLost(addr) => ("HandleConnectionLost", format!("addr: {}", addr)),
+            Self::HandlePeerLost(addr) => ("HandlePeerLost", format!("addr: {}", addr)),
+            Self::HandleVote { proof_share, .. } => (
+                "HandleVote",
+                format!("proof_share.index: {}", proof_share.index),
+            ),
+            Self::HandleConsensus { proof, .. } => (
+                "HandleConsensus",
+                format!("proof.public_key: {:?}", proof.public_key),
+            ),
+            Self::HandleDkgOutcome { elders_info, .. } => (
+                "HandleDkgOutcome",
+                format!("prefix: {:?}", elders_info.prefix),
+            ),
+            Self::HandleDkgFailure { elders_info, .. } => (
+                "HandleDkgFailure",
+                format!("prefix: {:?}", elders_info.prefix),
+            ),
+            Self::SendMessage {
+                recipients,
+                delivery_group_size,
+                ..
+            } => (
+                "SendMessage",
+                format!(
+                    "recipients: {:?}, delivery_group_size: {}",
+                    recipients, delivery_group_size
+                ),
+            ),
+            Self::SendEncryptedToClient { recipient, .. } => {
+                ("SendEncryptedToClient", format!("recipient: {}", recipient))
+            }
+            Self::SendUserMessage { src, dst, content } => (
+                "SendUserMessage",
+                format!("src: {:?}, dst: {:?}, len: {}", src, dst, content.len()),
+            ),
+            Self::SendToAnyElder { content } => {
+                ("SendToAnyElder", format!("len: {}", content.len()))
+            }
+            Self::SendToName { name, content } => (
+                "SendToName",
+                format!("name: {}, len: {}", name, content.len()),
+            ),
+            Self::SendToward {
+                target,
+                fanout,
+                content,
+            } => (
+                "SendToward",
+                format!(
+                    "target: {}, fanout: {}, len: {}",
+                    target,
+                    fanout,
+                    content.len()
+                ),
+            ),
+            Self::RefreshNeighbour(prefix) => ("RefreshNeighbour", format!("prefix: {:?}", prefix)),
+            Self::ScheduleTimeout { duration, token } => (
+                "ScheduleTimeout",
+                format!("duration: {:?}, token: {}", duration, token),
+            ),
+            Self::Relocate {
+                bootstrap_addrs, ..
+            } => (
+                "Relocate",
+                format!("bootstrap_addrs: {:?}", bootstrap_addrs),
+            ),
+            Self::SetJoinsAllowed(joins_allowed) => (
+                "SetJoinsAllowed",
+                format!("joins_allowed: {}", joins_allowed),
+            ),
+            Self::RequestDemotion => ("RequestDemotion", String::new()),
+            Self::CancelDemotion => ("CancelDemotion", String::new()),
+            Self::RequestKeyRotation => ("RequestKeyRotation", String::new()),
+            Self::RequestRelocationDeferral(duration) => (
+                "RequestRelocationDeferral",
+                format!("duration: {:?}", duration),
+            ),
+        };
+
+        CommandSummary {
+            kind,
+            details,
+            timestamp: SystemTime::now(),
+        }
+    }
+}
+
+/// Summary of a command processed by the node, recorded for debugging (see
+/// `Routing::recent_commands`).
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct CommandSummary {
+    /// Kind of the command, for example `"SendUserMessage"`.
+    pub kind: &'static str,
+    /// Short description of the command's arguments, without the full payloads.
+    pub details: String,
+    /// When the command started being handled.
+    pub timestamp: SystemTime,
 }
 
 impl Debug for Command {
@@ -162,12 +300,44 @@ impl Debug for Command {
                 .field("delivery_group_size", delivery_group_size)
                 .field("message", message)
                 .finish(),
+            Self::SendEncryptedToClient {
+                recipient,
+                client_key,
+                message,
+            } => f
+                .debug_struct("SendEncryptedToClient")
+                .field("recipient", recipient)
+                .field("client_key", client_key)
+                .field("message", message)
+                .finish(),
             Self::SendUserMessage { src, dst, content } => f
                 .debug_struct("SendUserMessage")
                 .field("src", src)
                 .field("dst", dst)
                 .field("content", &format_args!("{:10}", HexFmt(content)))
                 .finish(),
+            Self::SendToAnyElder { content } => f
+                .debug_struct("SendToAnyElder")
+                .field("content", &format_args!("{:10}", HexFmt(content)))
+                .finish(),
+            Self::SendToName { name, content } => f
+                .debug_struct("SendToName")
+                .field("name", name)
+                .field("content", &format_args!("{:10}", HexFmt(content)))
+                .finish(),
+            Self::SendToward {
+                target,
+                fanout,
+                content,
+            } => f
+                .debug_struct("SendToward")
+                .field("target", target)
+                .field("fanout", fanout)
+                .field("content", &format_args!("{:10}", HexFmt(content)))
+                .finish(),
+            Self::RefreshNeighbour(prefix) => {
+                f.debug_tuple("RefreshNeighbour").field(prefix).finish()
+            }
             Self::ScheduleTimeout { duration, token } => f
                 .debug_struct("ScheduleTimeout")
                 .field("duration", duration)
@@ -186,6 +356,13 @@ impl Debug for Command {
                 .debug_tuple("SetJoinsAllowed")
                 .field(joins_allowed)
                 .finish(),
+            Self::RequestDemotion => f.debug_tuple("RequestDemotion").finish(),
+            Self::CancelDemotion => f.debug_tuple("CancelDemotion").finish(),
+            Self::RequestKeyRotation => f.debug_tuple("RequestKeyRotation").finish(),
+            Self::RequestRelocationDeferral(duration) => f
+                .debug_tuple("RequestRelocationDeferral")
+                .field(duration)
+                .finish(),
         }
     }
 }
@@ -0,0 +1,25 @@
+// Copyright 2020 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under The General Public License (GPL), version 3.
+// Unless required by applicable law or agreed to in writing, the SAFE Network Software distributed
+// under the GPL Licence is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied. Please review the Licences for the specific language governing
+// permissions and limitations relating to use of the SAFE Network Software.
+
+use crate::consensus::Proven;
+use serde::{Deserialize, Serialize};
+use xor_name::Prefix;
+
+/// Compact summary of a node's view of the network, meant to be gossiped between nodes (see
+/// `Routing::routing_digest` and `Routing::apply_digest`). Contains only the prefixes and keys of
+/// the sections, not their elders.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct RoutingDigest {
+    /// Prefix of the section of the node the digest comes from.
+    pub prefix: Prefix,
+    /// Latest key of the section of the node the digest comes from.
+    pub key: bls::PublicKey,
+    /// Latest known key of every other section, each signed by the section of the node the digest
+    /// comes from.
+    pub keys: Vec<Proven<(Prefix, bls::PublicKey)>>,
+}
@@ -6,7 +6,8 @@
 // KIND, either express or implied. Please review the Licences for the specific language governing
 // permissions and limitations relating to use of the SAFE Network Software.
 
-use crate::event::Event;
+use crate::event::{Event, MembershipEvent};
+use std::sync::Mutex;
 use tokio::sync::mpsc;
 
 /// Stream of routing node events
@@ -24,3 +25,38 @@ impl EventStream {
         self.events_rx.recv().await
     }
 }
+
+/// Stream of the membership events of our section (see `Routing::membership_events`).
+pub struct MembershipEventStream {
+    events_rx: mpsc::UnboundedReceiver<MembershipEvent>,
+}
+
+impl MembershipEventStream {
+    /// Returns next membership event
+    pub async fn next(&mut self) -> Option<MembershipEvent> {
+        self.events_rx.recv().await
+    }
+}
+
+// Subscribers to the membership events.
+#[derive(Default)]
+pub(crate) struct MembershipObservers(Mutex<Vec<mpsc::UnboundedSender<MembershipEvent>>>);
+
+impl MembershipObservers {
+    pub fn subscribe(&self) -> MembershipEventStream {
+        let (events_tx, events_rx) = mpsc::unbounded_channel();
+        self.0
+            .lock()
+            .unwrap_or_else(|err| err.into_inner())
+            .push(events_tx);
+        MembershipEventStream { events_rx }
+    }
+
+    pub fn notify(&self, event: MembershipEvent) {
+        // Drop the subscribers that are no longer interested.
+        self.0
+            .lock()
+            .unwrap_or_else(|err| err.into_inner())
+            .retain(|events_tx| events_tx.send(event).is_ok());
+    }
+}
@@ -0,0 +1,55 @@
+// Copyright 2020 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under The General Public License (GPL), version 3.
+// Unless required by applicable law or agreed to in writing, the SAFE Network Software distributed
+// under the GPL Licence is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied. Please review the Licences for the specific language governing
+// permissions and limitations relating to use of the SAFE Network Software.
+
+use crate::{
+    error::Error,
+    event::Event,
+    location::{DstLocation, SrcLocation},
+};
+use bytes::Bytes;
+use std::fmt::Debug;
+
+/// Hook to observe, reject or modify the outgoing user messages before they are sent.
+pub trait OutgoingInterceptor: Debug + Send + Sync {
+    /// Called for every outgoing user message sent to other nodes, except the chunks of
+    /// `Routing::send_stream` and the queries of `Routing::query_elders`.
+    fn on_send(&self, src: &SrcLocation, dst: &DstLocation, content: &Bytes) -> InterceptDecision;
+}
+
+/// Hook to observe or drop the incoming user messages before they are raised as
+/// `Event::MessageReceived`. Doesn't see any of the routing internal messages.
+pub trait IncomingInspector: Debug + Send + Sync {
+    /// Called for every incoming user message. Returning `false` drops the message.
+    fn on_receive(&self, src: &SrcLocation, content: &Bytes) -> bool;
+}
+
+/// Hook answering the queries sent by other nodes with `Routing::query_elders`.
+pub trait QueryHandler: Debug + Send + Sync {
+    /// Called for every incoming query. Returns the response to send back to the querying node, or
+    /// `None` to not respond.
+    fn on_query(&self, src: &SrcLocation, content: &Bytes) -> Option<Bytes>;
+}
+
+/// Hook observing every event raised to the user, in addition to the `EventStream`, for example to
+/// keep an audit trail of the events independent of the consumer of the stream.
+pub trait EventSink: Debug + Send + Sync {
+    /// Called for every event just before it's pushed to the `EventStream`. It delays the delivery
+    /// of the event, so it should return quickly. A panic is logged and otherwise ignored.
+    fn record(&self, event: &Event);
+}
+
+/// Decision of an `OutgoingInterceptor` about an outgoing message.
+#[derive(Debug)]
+pub enum InterceptDecision {
+    /// Send the message unchanged.
+    Allow,
+    /// Don't send the message and fail the send with the given error.
+    Reject(Error),
+    /// Send the given content instead of the original one.
+    Replace(Bytes),
+}
@@ -0,0 +1,49 @@
+// Copyright 2020 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under The General Public License (GPL), version 3.
+// Unless required by applicable law or agreed to in writing, the SAFE Network Software distributed
+// under the GPL Licence is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied. Please review the Licences for the specific language governing
+// permissions and limitations relating to use of the SAFE Network Software.
+
+use super::ConnectionCacheStats;
+use serde::Serialize;
+
+/// Snapshot of the observable state of a node, as returned by `Routing::metrics_snapshot`.
+#[derive(Clone, Debug, Serialize)]
+pub struct Metrics {
+    /// Reuse of cached connections when sending messages (see `Routing::connection_cache_stats`).
+    pub connection_cache: ConnectionCacheStats,
+    /// Number of failed sends currently kept in the history (see
+    /// `Routing::recent_send_failures`).
+    pub send_failures: usize,
+    /// Number of peers currently considered unreachable (see `Routing::unreachable_peers`).
+    pub unreachable_peers: usize,
+    /// Number of incoming connections with received but not yet handled messages (see
+    /// `Routing::connection_buffer_usage`).
+    pub buffered_connections: usize,
+    /// Total size (in bytes) of the received but not yet handled messages of all connections.
+    pub buffered_bytes: usize,
+    /// Number of messages dropped since we joined our current section, that is the number of
+    /// `Event::MessageDropped` raised.
+    pub messages_dropped: u64,
+    /// Size and age aggregates of our section.
+    pub section: SectionMetrics,
+}
+
+/// Size and age aggregates of our section, part of `Metrics`.
+#[derive(Clone, Debug, Serialize)]
+pub struct SectionMetrics {
+    /// Number of joined members, including the elders.
+    pub members: usize,
+    /// Number of elders.
+    pub elders: usize,
+    /// Number of adults (mature members that are not elders).
+    pub adults: usize,
+    /// Age of the youngest joined member.
+    pub min_age: u8,
+    /// Age of the oldest joined member.
+    pub max_age: u8,
+    /// Mean age of the joined members.
+    pub mean_age: f64,
+}
@@ -11,40 +11,93 @@ pub(crate) mod command;
 mod approved;
 mod bootstrap;
 mod comm;
+mod digest;
 mod event_stream;
+mod interceptor;
+mod metrics;
+mod neighbour_reconciliation;
+#[cfg(any(test, feature = "testing"))]
+mod preset;
+mod rate_limit;
 mod split_barrier;
 mod stage;
 #[cfg(test)]
 mod tests;
 
-pub use self::event_stream::EventStream;
 use self::{
-    approved::Approved,
-    comm::{Comm, ConnectionEvent},
+    approved::{Approved, ApprovedSettings},
+    comm::{
+        Comm, ConnectionEvent, DEFAULT_CIRCUIT_BREAKER_COOLDOWN, DEFAULT_SEND_FAILURE_HISTORY_LEN,
+    },
     command::Command,
     split_barrier::SplitBarrier,
     stage::Stage,
 };
+pub use self::{
+    approved::{KeyRotationEntry, UnknownSectionPolicy},
+    bootstrap::JoinPhase,
+    comm::{
+        ConnectionCacheStats, PeerConnectionEvent, PeerConnectionEventKind, SendFailureKind,
+        SendFailureRecord,
+    },
+    command::CommandSummary,
+    digest::RoutingDigest,
+    event_stream::{EventStream, MembershipEventStream},
+    interceptor::{
+        EventSink, IncomingInspector, InterceptDecision, OutgoingInterceptor, QueryHandler,
+    },
+    metrics::{Metrics, SectionMetrics},
+    rate_limit::RateLimit,
+    stage::{DeliveryReport, RetryPolicy},
+};
+#[cfg(any(test, feature = "testing"))]
+pub use self::{
+    comm::LinkImpairment,
+    preset::{PresetMember, SectionPreset},
+};
+#[cfg(any(test, feature = "testing"))]
+pub use crate::delivery_group::SectionOverride;
 use crate::{
+    consensus::Quorum,
     crypto,
-    error::Result,
-    event::{Event, NodeElderChange},
+    error::{Error, Result},
+    event::{DropReason, Event, NodeElderChange},
     location::{DstLocation, SrcLocation},
-    messages::Message,
+    messages::{Message, Variant},
     node::Node,
     peer::Peer,
-    section::{EldersInfo, SectionProofChain},
+    relocation::{AgePolicy, DefaultAgePolicy, RelocationRecord},
+    section::{EldersInfo, MembershipProof, SectionProofChain},
     TransportConfig, MIN_AGE,
 };
-use bytes::Bytes;
+use bytes::{Bytes, BytesMut};
 use ed25519_dalek::{Keypair, PublicKey, Signature, Signer};
+use futures::{
+    future,
+    stream::{FuturesUnordered, Stream, StreamExt},
+};
 use itertools::Itertools;
 use qp2p::{Message as Qp2pMessage, RecvStream, SendStream};
 use sn_messaging::{client::MsgEnvelope, node::NodeMessage, MessageType, WireMsg};
-use std::{net::SocketAddr, sync::Arc};
-use tokio::{sync::mpsc, task};
+use std::{
+    collections::{BTreeMap, BTreeSet, HashMap},
+    iter,
+    net::SocketAddr,
+    sync::Arc,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+use tokio::{sync::mpsc, task, time};
 use xor_name::{Prefix, XorName};
 
+// Default limit on the size of a single incoming message.
+const DEFAULT_MAX_INCOMING_MESSAGE_SIZE: usize = 10 * 1024 * 1024;
+
+// How long to wait for an elder to answer a clock probe.
+const CLOCK_PROBE_TIMEOUT: Duration = Duration::from_secs(5);
+
+// Clock skew with an elder above which `Event::ClockSkewDetected` is raised.
+const CLOCK_SKEW_WARNING_THRESHOLD: Duration = Duration::from_secs(30);
+
 /// Routing configuration.
 #[derive(Debug)]
 pub struct Config {
@@ -54,6 +107,127 @@ pub struct Config {
     pub keypair: Option<Keypair>,
     /// Configuration for the underlying network transport.
     pub transport_config: TransportConfig,
+    /// Maximum size (in bytes) of an incoming message. Larger messages are dropped without being
+    /// deserialised.
+    pub max_incoming_message_size: usize,
+    /// Maximum random deviation (in percent, capped at 100) applied to the intervals of periodic
+    /// tasks so that the timers of different nodes don't fire in sync. Zero disables the jitter.
+    pub timer_jitter_percent: u8,
+    /// How to handle messages from sections we don't know any key of.
+    pub unknown_section_policy: UnknownSectionPolicy,
+    /// Hook called for every outgoing user message before it is sent, or `None` for no hook. Not
+    /// called for `Routing::send_stream` and `Routing::query_elders`.
+    pub outgoing_interceptor: Option<Arc<dyn OutgoingInterceptor>>,
+    /// Hook called for every incoming user message before it is raised as
+    /// `Event::MessageReceived`, or `None` for no hook.
+    pub incoming_inspector: Option<Box<dyn IncomingInspector>>,
+    /// Hook called for every event raised, in addition to pushing it to the `EventStream`, or
+    /// `None` for no hook.
+    pub event_sink: Option<Box<dyn EventSink>>,
+    /// If set, messages sent to a peer while another send to it is in progress are queued and
+    /// those queued within this window are sent together in a single stream, to reduce the
+    /// per-message overhead. A message to an otherwise idle peer is sent right away. The combined
+    /// size of the batch is limited by `max_incoming_message_size`. `None` disables batching.
+    pub batch_window: Option<Duration>,
+    /// Number of the most recent send failures to keep for diagnostics (see
+    /// `Routing::recent_send_failures`).
+    pub send_failure_history_len: usize,
+    /// Maximum number of connections being established at the same time. Further connection
+    /// attempts are queued until some of the in-progress ones complete. `None` means no limit.
+    pub max_concurrent_connects: Option<usize>,
+    /// How long to keep sending the messages still in progress after `Routing` is dropped, before
+    /// closing all connections. This is best-effort only: the remaining work continues in a
+    /// detached task which is cut short if the async runtime shuts down first. Zero closes the
+    /// connections immediately.
+    pub drop_grace_period: Duration,
+    /// Number of consecutive failed sends to a peer after which further sends to it fail
+    /// immediately, without being attempted, for `circuit_breaker_cooldown`. `None` disables this.
+    pub circuit_breaker_threshold: Option<usize>,
+    /// How long sends to a peer are skipped for once its circuit breaker opens. After that, one
+    /// send is attempted again. Its success resumes normal sending to the peer, its failure starts
+    /// another cooldown.
+    pub circuit_breaker_cooldown: Duration,
+    /// Maximum total size (in bytes) of the messages received on a single connection that are
+    /// still waiting to be handled. Connections exceeding it are closed, which protects against
+    /// peers trying to exhaust our memory. `None` means no limit.
+    pub max_connection_buffer: Option<usize>,
+    /// If true, messages to the same peer are sent one at a time, in the order they were sent by
+    /// us, so they are also received in that order. This trades some throughput for ordering.
+    pub ordered_delivery: bool,
+    /// Policy deciding the ages of the nodes relocated due to churn. This is a local setting which
+    /// is not exchanged or checked between nodes. Elders using different policies fail to sign
+    /// relocations, so all the nodes of the network need to be configured with the same one.
+    pub age_policy: Arc<dyn AgePolicy>,
+    /// If true, summaries of the most recently handled internal commands are recorded and can be
+    /// retrieved with `Routing::recent_commands`. Meant for debugging only, as it adds overhead
+    /// to every command.
+    pub debug_command_trace: bool,
+    /// Configs of additional endpoints to listen on besides the one from `transport_config`. For
+    /// example, a dual-stack node can listen on both an IPv4 and an IPv6 address this way.
+    pub extra_transport_configs: Vec<TransportConfig>,
+    /// If set, our elders ask the elders of every neighbour section for their current info this
+    /// often, so our view of the neighbours doesn't go stale. Neighbours that don't respond are
+    /// asked less and less often. `None` disables this.
+    pub neighbour_reconciliation_interval: Option<Duration>,
+    /// Minimum age of the members promoted to elders. Current elders are not demoted for being
+    /// younger, and if there aren't enough members old enough, the remaining elder seats are
+    /// filled with the oldest of the younger members.
+    /// All the nodes of the network must use the same value, otherwise they fail to agree on the
+    /// elders.
+    pub min_elder_age: u8,
+    /// If set, the phases of joining the network are reported through this channel while
+    /// `Routing::new` is in progress, for example to show the progress to the user.
+    pub join_phase_tx: Option<mpsc::UnboundedSender<JoinPhase>>,
+    /// If true, every incoming message carrying a proof chain is dropped unless the chain is
+    /// rooted in one of the keys we trust, instead of being bounced back for resend with a longer
+    /// chain or, for some messages, accepted without checking the chain at all.
+    pub strict_proof_verification: bool,
+    /// If true, a user message is delivered only if its content is signed by its claimed source:
+    /// by the key of the source node, whose name must derive from that key, or by the key of the
+    /// source section, proven by a chain rooted in a key we trust. Messages that can't be verified
+    /// that way, including those accepted from unknown sections without verification and those
+    /// received via `Routing::send_stream`, are dropped with `DropReason::Unsigned`.
+    pub require_signed_messages: bool,
+    /// If set, our section proof chain keeps only this many of its most recent blocks, plus the
+    /// first key. The older blocks are replaced with a link that lets us keep trusting the chain
+    /// from the first key, because we checked them before removing them. The link is local: we
+    /// send only the part of the chain starting at the last removed key, so others can trust it
+    /// only from one of the remaining keys. `None` keeps the whole chain.
+    pub max_proof_chain_len: Option<usize>,
+    /// Fraction of the elders of a section required to agree for the section to sign or reach
+    /// consensus: it sets the threshold of the section keys generated by DKG and the number of
+    /// observations needed to agree on a DKG failure. Every node of the network must use the same
+    /// quorum. Defaults to a strict majority.
+    pub quorum: Quorum,
+    /// If set, a keep-alive is sent this often on the connections to the elders of our section, so
+    /// they are not dropped as idle, for example by a NAT, between messages. Connections to other
+    /// peers are not kept alive. `None` disables keep-alives.
+    pub keepalive_interval: Option<Duration>,
+    /// If true, once we are demoted from elder, the connections to the other elders kept alive
+    /// while we were an elder (see `keepalive_interval`) are no longer kept alive, until we are
+    /// an elder again, and those to the peers that are no longer elders are closed, to free the
+    /// resources they hold. They are reopened on demand. Nodes that were never elders are not
+    /// affected. Without keep-alives this has no effect.
+    pub drop_connections_on_demotion: bool,
+    /// Hook answering the queries sent to us with `Routing::query_elders`, or `None` to not answer
+    /// them.
+    pub query_handler: Option<Arc<dyn QueryHandler>>,
+    /// If true, `Routing::validate_peer_addr` accepts loopback addresses. Meant for tests and local
+    /// networks running on a single machine.
+    pub allow_loopback_addrs: bool,
+    /// Limit on the rate of the user messages and streams sent toward any single section. Messages
+    /// exceeding it are rejected with `Error::RateLimited`. `None` means no limit. The queries of
+    /// `Routing::query_elders` are not limited.
+    pub per_prefix_rate_limit: Option<RateLimit>,
+    /// Simulated loss and latency of the links to all peers, or `None` for none. For tests only.
+    #[cfg(any(test, feature = "testing"))]
+    pub link_impairment: Option<LinkImpairment>,
+    /// Mapping from destination names to the prefixes of the sections messages to them are sent
+    /// to, used instead of the prefix matching when targeting a `DstLocation::Section`. Lets tests
+    /// route messages to a specific section regardless of the destination name. `None` means
+    /// regular routing. For tests only.
+    #[cfg(any(test, feature = "testing"))]
+    pub section_override: Option<SectionOverride>,
 }
 
 impl Default for Config {
@@ -62,6 +236,39 @@ impl Default for Config {
             first: false,
             keypair: None,
             transport_config: TransportConfig::default(),
+            max_incoming_message_size: DEFAULT_MAX_INCOMING_MESSAGE_SIZE,
+            timer_jitter_percent: 0,
+            unknown_section_policy: UnknownSectionPolicy::default(),
+            outgoing_interceptor: None,
+            incoming_inspector: None,
+            event_sink: None,
+            batch_window: None,
+            send_failure_history_len: DEFAULT_SEND_FAILURE_HISTORY_LEN,
+            max_concurrent_connects: None,
+            drop_grace_period: Duration::from_secs(0),
+            circuit_breaker_threshold: None,
+            circuit_breaker_cooldown: DEFAULT_CIRCUIT_BREAKER_COOLDOWN,
+            max_connection_buffer: None,
+            ordered_delivery: false,
+            age_policy: Arc::new(DefaultAgePolicy),
+            debug_command_trace: false,
+            extra_transport_configs: Vec::new(),
+            neighbour_reconciliation_interval: None,
+            min_elder_age: MIN_AGE,
+            join_phase_tx: None,
+            strict_proof_verification: false,
+            require_signed_messages: false,
+            max_proof_chain_len: None,
+            quorum: Quorum::default(),
+            keepalive_interval: None,
+            drop_connections_on_demotion: false,
+            query_handler: None,
+            allow_loopback_addrs: false,
+            per_prefix_rate_limit: None,
+            #[cfg(any(test, feature = "testing"))]
+            link_impairment: None,
+            #[cfg(any(test, feature = "testing"))]
+            section_override: None,
         }
     }
 }
@@ -88,43 +295,179 @@ impl Routing {
     /// lost in transit during bootstrapping, or other reasons. It's the responsibility of the
     /// caller to handle this case, for example by using a timeout.
     pub async fn new(config: Config) -> Result<(Self, EventStream)> {
+        let start_mode = if config.first {
+            StartMode::First
+        } else {
+            StartMode::Join
+        };
+
+        Self::start(config, start_mode).await
+    }
+
+    /// Creates new node using the given config, as a member of the given preset section. The node
+    /// doesn't bootstrap nor join, but starts in the section right away, which speeds up tests that
+    /// need a specific section. `config.first` is ignored. For tests only.
+    #[cfg(any(test, feature = "testing"))]
+    pub async fn new_with_preset_section(
+        config: Config,
+        preset: SectionPreset,
+    ) -> Result<(Self, EventStream)> {
+        Self::start(config, StartMode::Preset(preset)).await
+    }
+
+    async fn start(config: Config, start_mode: StartMode) -> Result<(Self, EventStream)> {
         let keypair = config.keypair.unwrap_or_else(crypto::gen_keypair);
+        let max_incoming_message_size = config.max_incoming_message_size;
+        let timer_jitter_percent = config.timer_jitter_percent;
+        let send_failure_history_len = config.send_failure_history_len;
+        let batch_window = config.batch_window;
+        let max_concurrent_connects = config.max_concurrent_connects;
+        let drop_grace_period = config.drop_grace_period;
+        let allow_loopback_addrs = config.allow_loopback_addrs;
+        let per_prefix_rate_limit = config.per_prefix_rate_limit;
+        let circuit_breaker_threshold = config.circuit_breaker_threshold;
+        let circuit_breaker_cooldown = config.circuit_breaker_cooldown;
+        let max_connection_buffer = config.max_connection_buffer.unwrap_or(0);
+        let ordered_delivery = config.ordered_delivery;
+        let keepalive_interval = config.keepalive_interval;
+        let drop_connections_on_demotion = config.drop_connections_on_demotion;
+        #[cfg(any(test, feature = "testing"))]
+        let link_impairment = config.link_impairment;
+        let debug_command_trace = config.debug_command_trace;
+        let extra_transport_configs = config.extra_transport_configs;
+        let outgoing_interceptor = config.outgoing_interceptor;
+        let query_handler = config.query_handler;
+        let settings = ApprovedSettings {
+            unknown_section_policy: config.unknown_section_policy,
+            incoming_inspector: config.incoming_inspector.map(Arc::from),
+            event_sink: config.event_sink.map(Arc::from),
+            age_policy: config.age_policy,
+            min_elder_age: config.min_elder_age,
+            strict_proof_verification: config.strict_proof_verification,
+            require_signed_messages: config.require_signed_messages,
+            max_proof_chain_len: config.max_proof_chain_len,
+            quorum: config.quorum,
+            neighbour_reconciliation_interval: config.neighbour_reconciliation_interval,
+            #[cfg(any(test, feature = "testing"))]
+            section_override: config.section_override.map(Arc::new),
+        };
         let node_name = crypto::name(&keypair.public);
 
         let (event_tx, event_rx) = mpsc::unbounded_channel();
         let (connection_event_tx, mut connection_event_rx) = mpsc::channel(1);
 
-        let (state, comm, backlog) = if config.first {
-            info!("{} Starting a new network as the seed node.", node_name);
-            let mut zero_config = config.transport_config;
-            zero_config.ip = Some(std::net::Ipv4Addr::new(0, 0, 0, 0).into());
-            zero_config.forward_port = true;
-            let comm = Comm::new(zero_config, connection_event_tx)?;
-            let node = Node::new(keypair, comm.our_connection_info().await?).with_age(MIN_AGE + 1);
-            let state = Approved::first_node(node, event_tx)?;
+        let first = matches!(start_mode, StartMode::First);
+        let (state, comm, backlog) = match start_mode {
+            StartMode::First => {
+                info!("{} Starting a new network as the seed node.", node_name);
+                let mut zero_config = config.transport_config;
+                zero_config.ip = Some(std::net::Ipv4Addr::new(0, 0, 0, 0).into());
+                zero_config.forward_port = true;
+                let comm = Comm::new(zero_config, connection_event_tx)?
+                    .with_send_failure_history_len(send_failure_history_len)
+                    .with_max_connection_buffer(max_connection_buffer);
+                let node =
+                    Node::new(keypair, comm.our_connection_info().await?).with_age(MIN_AGE + 1);
+                let state = Approved::first_node(node, event_tx)?;
+
+                (state, comm, vec![])
+            }
+            StartMode::Join => {
+                info!("{} Bootstrapping a new node.", node_name);
+                let (comm, bootstrap_addr) =
+                    Comm::bootstrap(config.transport_config, connection_event_tx).await?;
+                let comm = comm
+                    .with_send_failure_history_len(send_failure_history_len)
+                    .with_max_connection_buffer(max_connection_buffer);
+                let node =
+                    Node::new(keypair, comm.our_connection_info().await?).with_age(MIN_AGE + 1);
+                let (node, section, backlog) = bootstrap::initial(
+                    node,
+                    &comm,
+                    &mut connection_event_rx,
+                    bootstrap_addr,
+                    config.join_phase_tx,
+                )
+                .await?;
+                let state = Approved::new(node, section, None, event_tx);
+
+                (state, comm, backlog)
+            }
+            #[cfg(any(test, feature = "testing"))]
+            StartMode::Preset(preset) => {
+                info!("{} Starting in a preset section.", node_name);
+                let comm = Comm::new(config.transport_config, connection_event_tx)?
+                    .with_send_failure_history_len(send_failure_history_len)
+                    .with_max_connection_buffer(max_connection_buffer);
+                let node = Node::new(keypair, comm.our_connection_info().await?);
+                let (node, section, section_key_share) = preset.build(node)?;
+                let state = Approved::new(node, section, section_key_share, event_tx);
+
+                (state, comm, vec![])
+            }
+        };
+        let state = state.with_settings(settings);
+
+        if first {
+            // Sent only once the settings are applied, so the event sink sees it too.
             let section = state.section();
+            let elders: BTreeSet<_> = section.elders_info().elders.keys().copied().collect();
 
             state.send_event(Event::EldersChanged {
                 prefix: *section.prefix(),
                 key: *section.chain().last_key(),
-                elders: section.elders_info().elders.keys().copied().collect(),
+                elders: elders.clone(),
+                added: elders,
+                removed: BTreeSet::new(),
                 self_status_change: NodeElderChange::Promoted,
             });
+        }
 
-            (state, comm, vec![])
+        let comm = if let Some(batch_window) = batch_window {
+            comm.with_batching(batch_window, max_incoming_message_size)
         } else {
-            info!("{} Bootstrapping a new node.", node_name);
-            let (comm, bootstrap_addr) =
-                Comm::bootstrap(config.transport_config, connection_event_tx).await?;
-            let node = Node::new(keypair, comm.our_connection_info().await?).with_age(MIN_AGE + 1);
-            let (node, section, backlog) =
-                bootstrap::initial(node, &comm, &mut connection_event_rx, bootstrap_addr).await?;
-            let state = Approved::new(node, section, None, event_tx);
-
-            (state, comm, backlog)
+            comm
+        };
+        let comm = if let Some(limit) = max_concurrent_connects {
+            comm.with_max_concurrent_connects(limit)
+        } else {
+            comm
+        };
+        let comm = if let Some(threshold) = circuit_breaker_threshold {
+            comm.with_circuit_breaker(threshold, circuit_breaker_cooldown)
+        } else {
+            comm
+        };
+        let comm = if ordered_delivery {
+            comm.with_ordered_delivery()
+        } else {
+            comm
+        };
+        let comm = if let Some(interval) = keepalive_interval {
+            comm.with_keepalive(interval, timer_jitter_percent)
+        } else {
+            comm
+        };
+        #[cfg(any(test, feature = "testing"))]
+        let comm = if let Some(impairment) = link_impairment {
+            comm.with_link_impairment(impairment)
+        } else {
+            comm
+        };
+        let comm = comm.with_extra_endpoints(extra_transport_configs)?;
+        let stage = Stage::new(state, comm)
+            .with_timer_jitter(timer_jitter_percent)
+            .with_outgoing_interceptor(outgoing_interceptor)
+            .with_query_handler(query_handler)
+            .with_drop_grace_period(drop_grace_period)
+            .with_loopback_addrs_allowed(allow_loopback_addrs)
+            .with_drop_connections_on_demotion(drop_connections_on_demotion)
+            .with_per_prefix_rate_limit(per_prefix_rate_limit);
+        let stage = if debug_command_trace {
+            Arc::new(stage.with_command_trace())
+        } else {
+            Arc::new(stage)
         };
-
-        let stage = Arc::new(Stage::new(state, comm));
         let event_stream = EventStream::new(event_rx);
 
         // Process message backlog
@@ -138,8 +481,18 @@ impl Routing {
                 .await?;
         }
 
+        // Start the periodic neighbour reconciliation.
+        let command = stage.state.lock().await.schedule_neighbour_reconciliation();
+        if let Some(command) = command {
+            let _ = task::spawn(stage.clone().handle_commands(command));
+        }
+
         // Start listening to incoming connections.
-        let _ = task::spawn(handle_connection_events(stage.clone(), connection_event_rx));
+        let _ = task::spawn(handle_connection_events(
+            stage.clone(),
+            connection_event_rx,
+            max_incoming_message_size,
+        ));
 
         let routing = Self { stage };
 
@@ -152,6 +505,55 @@ impl Routing {
         self.stage.clone().handle_commands(command).await
     }
 
+    /// Asks our section to gracefully demote us from the elder set and promote the next best
+    /// candidate instead. Once the section completes the change, `Event::EldersChanged` with
+    /// `NodeElderChange::Demoted` is raised. Returns `Error::InvalidState` if we are not an elder.
+    pub async fn request_demotion(&self) -> Result<()> {
+        self.stage
+            .clone()
+            .handle_commands(Command::RequestDemotion)
+            .await
+    }
+
+    /// Asks our section to let us be picked as an elder again after `request_demotion`. Until
+    /// then, or until we leave the section, we are not promoted back even if we are the best
+    /// candidate.
+    pub async fn cancel_demotion(&self) -> Result<()> {
+        self.stage
+            .clone()
+            .handle_commands(Command::CancelDemotion)
+            .await
+    }
+
+    /// Rotates our keypair by asking our section to relocate us back into it under a new name,
+    /// keeping our age. The new name is signed with the old key when rejoining, so the section
+    /// knows it's the same node. Returns the new name once we've rejoined. Returns
+    /// `Error::InvalidState` if we are an elder (see `request_demotion`) or already relocating.
+    ///
+    /// NOTE: like `new`, it's not guaranteed this function ever returns, for example if the
+    /// request is lost in transit. It's the responsibility of the caller to handle this case.
+    pub async fn rotate_keypair(&self) -> Result<XorName> {
+        let relocated_rx = self.stage.wait_for_relocation().await;
+
+        self.stage
+            .clone()
+            .handle_commands(Command::RequestKeyRotation)
+            .await?;
+
+        relocated_rx.await.map_err(|_| Error::InvalidState)
+    }
+
+    /// Asks our section not to select us for relocation for `duration`, for example to finish some
+    /// local work first. The elders agree on the deferral by voting, and we ask them to end it once
+    /// `duration` elapses. So that we can't dodge relocation indefinitely, it also ends after 16
+    /// churn events (members joining or leaving) at most, and after it we stay eligible for
+    /// relocation for 16 more churn events before our section accepts another one. It ends early
+    /// if our elders change. Returns `Error::InvalidState` if we are already relocating.
+    pub async fn defer_relocation(&self, duration: Duration) -> Result<()> {
+        let command = Command::RequestRelocationDeferral(duration);
+        self.stage.clone().handle_commands(command).await
+    }
+
     /// Returns the current age of this node.
     pub async fn age(&self) -> u8 {
         self.stage.state.lock().await.node().age
@@ -162,6 +564,12 @@ impl Routing {
         self.stage.state.lock().await.node().keypair.public
     }
 
+    /// Returns the canonical 32-byte encoding of the ed25519 public key of this node. Useful as a
+    /// stable, fixed-length identifier of the node.
+    pub async fn public_key_bytes(&self) -> [u8; ed25519_dalek::PUBLIC_KEY_LENGTH] {
+        self.public_key().await.to_bytes()
+    }
+
     /// Signs `data` with the ed25519 key of this node.
     pub async fn sign_as_node(&self, data: &[u8]) -> Signature {
         self.stage.state.lock().await.node().keypair.sign(data)
@@ -181,6 +589,30 @@ impl Routing {
             .sign_with_section_key_share(data, public_key)
     }
 
+    /// Signs `payload` with our share of the current section key, as one step of collaboratively
+    /// producing a section signature. The shares of the individual elders (keyed by their
+    /// `our_index`) can then be combined using `try_complete_section_signature`. Returns
+    /// `Error::MissingSecretKeyShare` if we don't have a key share.
+    pub async fn sign_as_section(&self, payload: &[u8]) -> Result<bls::SignatureShare> {
+        self.stage.state.lock().await.sign_as_section(payload)
+    }
+
+    /// Combines the signature shares of `payload` produced by `sign_as_section`, keyed by the
+    /// index of the elder that produced them. Returns the signature by the current section key
+    /// once there are more valid shares than the signature threshold, `None` otherwise. Invalid
+    /// shares are ignored.
+    pub async fn try_complete_section_signature(
+        &self,
+        payload: &[u8],
+        shares: &BTreeMap<usize, bls::SignatureShare>,
+    ) -> Result<Option<bls::Signature>> {
+        self.stage
+            .state
+            .lock()
+            .await
+            .try_complete_section_signature(payload, shares)
+    }
+
     /// Verifies `signature` on `data` with the ed25519 public key of this node.
     pub async fn verify(&self, data: &[u8], signature: &Signature) -> bool {
         self.stage
@@ -203,6 +635,12 @@ impl Routing {
         self.stage.comm.our_connection_info().await
     }
 
+    /// Returns connection info of all the endpoints of this node, including the ones from
+    /// `Config::extra_transport_configs`. The first one is the same as `our_connection_info`.
+    pub async fn our_connection_infos(&self) -> Result<Vec<SocketAddr>> {
+        self.stage.comm.our_connection_infos().await
+    }
+
     /// Prefix of our section
     pub async fn our_prefix(&self) -> Prefix {
         *self.stage.state.lock().await.section().prefix()
@@ -231,6 +669,108 @@ impl Routing {
             .collect()
     }
 
+    /// Returns the addresses of all the current section elders, in the same order as
+    /// `our_elders`. Handy for example as a bootstrap list for clients.
+    pub async fn elder_addresses(&self) -> Vec<SocketAddr> {
+        self.stage
+            .state
+            .lock()
+            .await
+            .section()
+            .elders_info()
+            .peers()
+            .map(Peer::addr)
+            .copied()
+            .collect()
+    }
+
+    /// Returns a proof that we are a member of our section with our current age, verifiable with
+    /// the genesis key of the network (see `MembershipProof::verify`). Returns
+    /// `Error::InvalidState` if we are not a member or if our section chain doesn't reach back to
+    /// the key our membership is signed with, or no longer has all the blocks up to it (see
+    /// `Config::max_proof_chain_len`).
+    pub async fn membership_proof(&self) -> Result<MembershipProof> {
+        self.stage.state.lock().await.membership_proof()
+    }
+
+    /// Returns whether, as far as we know, the node named `name` is a current member of our section
+    /// with exactly the given age. This checks the claim against our own view only, it doesn't
+    /// prove anything cryptographically.
+    pub async fn verify_member_claim(&self, name: XorName, age: u8) -> bool {
+        self.stage
+            .state
+            .lock()
+            .await
+            .section()
+            .members()
+            .joined()
+            .any(|info| *info.peer.name() == name && info.peer.age() == age)
+    }
+
+    /// Returns the most recent relocations of our members computed by us on churn, oldest first.
+    /// Only the relocations since we joined our current section are included.
+    pub async fn relocation_history(&self) -> Vec<RelocationRecord> {
+        self.stage.state.lock().await.relocation_history()
+    }
+
+    /// Returns the elders of our section that held the section key at index `version` of our
+    /// section proof chain, for example to find out who signed with a past key.
+    ///
+    /// The proof chain only carries the keys, not the elders that held them, so this is our own
+    /// record and not something other nodes can verify or are guaranteed to agree with. We
+    /// remember only the elder sets we saw become our elders since we joined our current section,
+    /// up to the 64 most recent ones. `None` is returned for older versions, for versions we
+    /// skipped (for example when a sync brought several new keys at once) and for versions we
+    /// don't know yet.
+    pub async fn elders_at_version(&self, version: u64) -> Option<EldersInfo> {
+        self.stage.state.lock().await.elders_at_version(version)
+    }
+
+    /// Returns a compact, serializable summary of our view of the network: our prefix and key
+    /// and the latest known key of every other section, without their elders. Meant to be
+    /// gossiped to other nodes, which merge it with `apply_digest`.
+    pub async fn routing_digest(&self) -> RoutingDigest {
+        self.stage.state.lock().await.routing_digest()
+    }
+
+    /// Merges the section keys from the digest of another node (see `routing_digest`) into our
+    /// view of the network. Only the keys signed by our own section are merged, and none for our
+    /// own section, its sub-sections or its ancestors. Returns `Error::FailedSignature` if the
+    /// digest contains an invalid signature.
+    pub async fn apply_digest(&self, digest: RoutingDigest) -> Result<()> {
+        self.stage.state.lock().await.apply_digest(digest)
+    }
+
+    /// Returns whether we are the elder of our section closest to `name`, for example to pick a
+    /// single elder responsible for `name`. Returns `Error::InvalidState` if we are not an elder.
+    pub async fn is_closest_elder_to(&self, name: &XorName) -> Result<bool> {
+        self.stage.state.lock().await.is_closest_elder_to(name)
+    }
+
+    /// Returns the age of the youngest of our elders, or `None` if we don't know any elders.
+    pub async fn min_elder_age(&self) -> Option<u8> {
+        self.stage.state.lock().await.elder_ages().min()
+    }
+
+    /// Returns the age of the oldest of our elders, or `None` if we don't know any elders.
+    pub async fn max_elder_age(&self) -> Option<u8> {
+        self.stage.state.lock().await.elder_ages().max()
+    }
+
+    /// Returns whether a message sent to `dst` would be delivered to us, that is whether `dst` is
+    /// our name, a name within our section or `Direct`.
+    pub async fn is_destination(&self, dst: &DstLocation) -> bool {
+        self.stage.state.lock().await.is_destination(dst)
+    }
+
+    /// Returns an estimate of the number of hops a message sent from us to `dst` would take, based
+    /// on how many bits of our prefix differ from the destination name. A message to our own
+    /// section takes 0 hops and one to another node in it takes 1. This is only an estimate: the
+    /// actual route depends on the sections known along the way.
+    pub async fn estimated_hops(&self, dst: &DstLocation) -> usize {
+        self.stage.state.lock().await.estimated_hops(dst)
+    }
+
     /// Returns the elders of our section sorted by their distance to `name` (closest first).
     pub async fn our_elders_sorted_by_distance_to(&self, name: &XorName) -> Vec<Peer> {
         self.our_elders()
@@ -240,6 +780,68 @@ impl Routing {
             .collect()
     }
 
+    /// Returns the time elapsed since the last successful send to each of our current elders, or
+    /// `None` for the elders we haven't sent anything to yet.
+    pub async fn elder_last_contact(&self) -> Vec<(XorName, Option<Duration>)> {
+        self.our_elders()
+            .await
+            .into_iter()
+            .map(|peer| {
+                let elapsed = self
+                    .stage
+                    .comm
+                    .last_sent(peer.addr())
+                    .map(|instant| instant.elapsed());
+                (*peer.name(), elapsed)
+            })
+            .collect()
+    }
+
+    /// Returns how many times sending a message reused an already open connection (hits) vs had to
+    /// open a new one (misses). A low hit rate indicates high churn among the peers we talk to.
+    pub fn connection_cache_stats(&self) -> ConnectionCacheStats {
+        self.stage.comm.connection_cache_stats()
+    }
+
+    /// Returns a snapshot of the metrics of this node: the connection and send statistics together
+    /// with the size and age aggregates of our section. They are all collected while holding the
+    /// node state, so they are consistent with each other.
+    pub async fn metrics_snapshot(&self) -> Metrics {
+        let state = self.stage.state.lock().await;
+        let comm = &self.stage.comm;
+
+        let buffer_usage = comm.connection_buffer_usage();
+
+        let section = state.section();
+        let ages: Vec<_> = section
+            .members()
+            .joined()
+            .map(|info| info.peer.age())
+            .collect();
+        let mean_age = if ages.is_empty() {
+            0.0
+        } else {
+            ages.iter().map(|age| f64::from(*age)).sum::<f64>() / ages.len() as f64
+        };
+
+        Metrics {
+            connection_cache: comm.connection_cache_stats(),
+            send_failures: comm.recent_send_failures().len(),
+            unreachable_peers: comm.unreachable_peers().len(),
+            buffered_connections: buffer_usage.len(),
+            buffered_bytes: buffer_usage.values().sum(),
+            messages_dropped: state.messages_dropped(),
+            section: SectionMetrics {
+                members: ages.len(),
+                elders: section.elders_info().elders.len(),
+                adults: section.adults().count(),
+                min_age: ages.iter().copied().min().unwrap_or(0),
+                max_age: ages.iter().copied().max().unwrap_or(0),
+                mean_age,
+            },
+        }
+    }
+
     /// Returns the information of all the current section adults.
     pub async fn our_adults(&self) -> Vec<Peer> {
         self.stage
@@ -262,6 +864,24 @@ impl Routing {
             .collect()
     }
 
+    /// Partitions the joined members of our section between the two halves our section would split
+    /// into, without actually splitting. Returns the prefix extended by `0` with the names of its
+    /// members, followed by the same for the prefix extended by `1`.
+    pub async fn simulate_split(&self) -> (Prefix, Vec<XorName>, Prefix, Vec<XorName>) {
+        let state = self.stage.state.lock().await;
+        let section = state.section();
+        let prefix0 = section.prefix().pushed(false);
+        let prefix1 = section.prefix().pushed(true);
+
+        let (names0, names1) = section
+            .members()
+            .joined()
+            .map(|info| *info.peer.name())
+            .partition(|name| prefix0.matches(name));
+
+        (prefix0, names0, prefix1, names1)
+    }
+
     /// Returns the info about our section or `None` if we are not joined yet.
     pub async fn our_section(&self) -> EldersInfo {
         self.stage
@@ -273,6 +893,52 @@ impl Routing {
             .clone()
     }
 
+    /// Returns the total size (in bytes) of the received but not yet handled messages of every
+    /// connection that has any. Only tracked if `Config::max_connection_buffer` is set.
+    pub fn connection_buffer_usage(&self) -> HashMap<SocketAddr, usize> {
+        self.stage.comm.connection_buffer_usage()
+    }
+
+    /// Returns a stream of the changes of the membership of our section: members joining, leaving
+    /// and relocating in or out, and our age changing. Only changes that happen after this call are
+    /// yielded. The same changes are still reported by the general event stream too.
+    pub async fn membership_events(&self) -> MembershipEventStream {
+        self.stage
+            .state
+            .lock()
+            .await
+            .membership_observers()
+            .subscribe()
+    }
+
+    /// Returns a stream of low-level events about connections to other peers being opened, closed
+    /// or reset. Unlike `Event::ClientLost` or the detection of lost peers, these are raised for
+    /// every connection, whether it belongs to a member of our section or not. Only events that
+    /// happen after this call are yielded.
+    pub fn connection_events(&self) -> impl Stream<Item = PeerConnectionEvent> {
+        self.stage.comm.connection_events()
+    }
+
+    /// Returns the most recent failed attempts to send a message, oldest first. The number of
+    /// records kept is limited by `Config::send_failure_history_len`.
+    pub fn recent_send_failures(&self) -> Vec<SendFailureRecord> {
+        self.stage.comm.recent_send_failures()
+    }
+
+    /// Returns the peers we currently consider unreachable because our last send to them failed
+    /// recently: within `Config::circuit_breaker_cooldown` if the circuit breaker is enabled, or
+    /// within the default cooldown otherwise. A peer is removed from the list once a send to it
+    /// succeeds.
+    pub fn unreachable_peers(&self) -> Vec<SocketAddr> {
+        self.stage.comm.unreachable_peers()
+    }
+
+    /// Returns the summaries of the most recently handled internal commands, oldest first. Only
+    /// recorded if `Config::debug_command_trace` is set, otherwise empty.
+    pub fn recent_commands(&self) -> Vec<CommandSummary> {
+        self.stage.recent_commands()
+    }
+
     /// Returns the info about our neighbour sections.
     pub async fn neighbour_sections(&self) -> Vec<EldersInfo> {
         self.stage
@@ -285,6 +951,81 @@ impl Routing {
             .collect()
     }
 
+    /// Returns the number of sections we can currently route messages to, that is our own section
+    /// and the neighbours whose elders we know. Unlike `neighbour_sections`, neighbours we still
+    /// track but whose elders we don't know are excluded.
+    pub async fn routable_section_count(&self) -> usize {
+        self.stage.state.lock().await.routable_section_count()
+    }
+
+    /// Returns the info about the neighbour section (that is, excluding ours) whose prefix is the
+    /// closest to `name`, or `None` if we don't know any neighbours.
+    pub async fn closest_neighbour(&self, name: &XorName) -> Option<EldersInfo> {
+        self.stage
+            .state
+            .lock()
+            .await
+            .network()
+            .closest(name)
+            .cloned()
+    }
+
+    /// Returns the latest section key of each of our known neighbour sections, as per our view of
+    /// the network. Useful to verify signatures claimed to be from a neighbour. Neighbours whose
+    /// key we don't know yet are left out.
+    pub async fn neighbour_keys(&self) -> HashMap<Prefix, bls::PublicKey> {
+        self.stage
+            .state
+            .lock()
+            .await
+            .network()
+            .neighbour_keys()
+            .map(|(prefix, key)| (*prefix, *key))
+            .collect()
+    }
+
+    /// Returns the slice of our section proof chain that proves the latest known key of the
+    /// neighbour section with the given prefix. The slice starts at the first key we know of and
+    /// ends at the key that signed our record of the neighbour's key, so it can be checked against
+    /// a trusted key using `SectionProofChain::check_trust`. Returns `None` if we don't know the
+    /// key of the section with the given prefix.
+    pub async fn neighbour_proof_slice(&self, prefix: &Prefix) -> Option<SectionProofChain> {
+        let state = self.stage.state.lock().await;
+        let signer = state.network().their_key_signer(prefix)?;
+        let chain = state.section().chain();
+        let index = chain.index_of(signer)?;
+
+        Some(chain.slice(..=index))
+    }
+
+    /// Returns the prefixes of the sections we would keep track of and route to if our neighbour
+    /// with the given prefix split into its two halves, for example to prepare for it in advance.
+    /// The halves that wouldn't be our neighbours are not included. If we don't know a neighbour
+    /// with the given prefix, returns the prefixes of our current neighbours.
+    pub async fn responsibility_after_neighbour_split(&self, neighbour: &Prefix) -> Vec<Prefix> {
+        let state = self.stage.state.lock().await;
+        state
+            .network()
+            .prefixes_after_split(state.section().prefix(), neighbour)
+    }
+
+    /// Returns the fraction of the name space our section is responsible for, that is
+    /// `2^-prefix_len`.
+    pub async fn address_space_fraction(&self) -> f64 {
+        address_space_fraction(self.stage.state.lock().await.section().prefix())
+    }
+
+    /// Returns the fraction of the name space each known section, including ours, is responsible
+    /// for. Useful to spot imbalances in the network. As our knowledge of the network is
+    /// incomplete, the fractions don't necessarily add up to one.
+    pub async fn coverage_by_prefix(&self) -> BTreeMap<Prefix, f64> {
+        let state = self.stage.state.lock().await;
+        iter::once(state.section().prefix())
+            .chain(state.network().prefixes())
+            .map(|prefix| (*prefix, address_space_fraction(prefix)))
+            .collect()
+    }
+
     /// Returns the info about the section matches the name.
     pub async fn match_section(
         &self,
@@ -315,6 +1056,252 @@ impl Routing {
         self.stage.clone().handle_commands(command).await
     }
 
+    /// Send a message like `send_message` does, but let it be relayed at most `hop_limit` times
+    /// (including the first send) on its way to `dst`. A node that receives it with no hops left
+    /// drops it, raising `Event::MessageDropped` with `DropReason::TtlExpired`, unless it is the
+    /// destination. Messages sent by `send_message` can be relayed up to 64 times. Only node
+    /// sources are supported, returns `Error::InvalidSrcLocation` for a section source.
+    pub async fn send_message_with_ttl(
+        &self,
+        src: SrcLocation,
+        dst: DstLocation,
+        content: Bytes,
+        hop_limit: u8,
+    ) -> Result<()> {
+        self.stage
+            .clone()
+            .send_user_message_with_hop_limit(src, dst, content, hop_limit)
+            .await
+    }
+
+    /// Send a message like `send_message` does, but wait until it's sent. If it isn't delivered to
+    /// all the recipients it needs to reach, resolve `dst` again, in case the section it belongs to
+    /// changed its elders or split in the meantime, and retry as set by `policy`.
+    /// Returns `Error::FailedSend` if the last attempt fails too.
+    pub async fn send_message_with_retry(
+        &self,
+        src: SrcLocation,
+        dst: DstLocation,
+        content: Bytes,
+        policy: RetryPolicy,
+    ) -> Result<()> {
+        self.stage
+            .clone()
+            .send_user_message_with_retry(src, dst, content, policy)
+            .await
+    }
+
+    /// Send a query to each of our elders, except us, and return the responses that arrive within
+    /// `timeout`, together with the names of the elders that sent them. The elders answer using
+    /// their `Config::query_handler`. Elders that don't answer in time, can't be reached or have
+    /// no handler are left out, so the result can have fewer entries than there are elders.
+    pub async fn query_elders(&self, content: Bytes, timeout: Duration) -> Vec<(XorName, Bytes)> {
+        let queries = match self.stage.state.lock().await.create_user_queries(content) {
+            Ok(queries) => queries,
+            Err(error) => {
+                error!("Failed to create user queries: {}", error);
+                return vec![];
+            }
+        };
+
+        let responses = queries.into_iter().map(|(name, addr, msg)| async move {
+            let bytes = MessageType::NodeMessage(NodeMessage::new(msg.to_bytes()))
+                .serialize()
+                .ok()?;
+            let response = time::timeout(timeout, self.stage.comm.request(&addr, bytes))
+                .await
+                .ok()?
+                .ok()?;
+            Some((name, response))
+        });
+
+        future::join_all(responses)
+            .await
+            .into_iter()
+            .flatten()
+            .collect()
+    }
+
+    /// Estimate the offset between our clock and the clock of each of our elders, except us. Each
+    /// elder is asked for its current time, which is compared with our time halfway through the
+    /// round trip. The result is the absolute offset, so it doesn't tell whose clock is ahead.
+    /// Elders that don't answer in time or can't be reached are left out. For every elder whose
+    /// offset exceeds 30 seconds, `Event::ClockSkewDetected` is raised as well.
+    pub async fn measure_clock_skew(&self) -> Vec<(XorName, Duration)> {
+        let probes = match self.stage.state.lock().await.create_clock_probes() {
+            Ok(probes) => probes,
+            Err(error) => {
+                error!("Failed to create clock probes: {}", error);
+                return vec![];
+            }
+        };
+
+        let skews = probes.into_iter().map(|(name, addr, msg)| async move {
+            let bytes = MessageType::NodeMessage(NodeMessage::new(msg.to_bytes()))
+                .serialize()
+                .ok()?;
+            let sent = SystemTime::now();
+            let response =
+                time::timeout(CLOCK_PROBE_TIMEOUT, self.stage.comm.request(&addr, bytes))
+                    .await
+                    .ok()?
+                    .ok()?;
+            let received = SystemTime::now();
+
+            let their_time: Duration = match bincode::deserialize(&response) {
+                Ok(time) => time,
+                Err(error) => {
+                    error!("Malformed clock probe response from {}: {}", name, error);
+                    return None;
+                }
+            };
+            let round_trip = received.duration_since(sent).unwrap_or_default();
+            let our_time = (sent + round_trip / 2).duration_since(UNIX_EPOCH).ok()?;
+            let skew = if their_time > our_time {
+                their_time - our_time
+            } else {
+                our_time - their_time
+            };
+
+            Some((name, skew))
+        });
+
+        let skews: Vec<_> = future::join_all(skews)
+            .await
+            .into_iter()
+            .flatten()
+            .collect();
+
+        for (name, skew) in &skews {
+            if *skew > CLOCK_SKEW_WARNING_THRESHOLD {
+                warn!("Clock of elder {} is off from ours by {:?}", name, skew);
+                self.stage
+                    .send_event(Event::ClockSkewDetected {
+                        name: *name,
+                        skew: *skew,
+                    })
+                    .await;
+            }
+        }
+
+        skews
+    }
+
+    /// Send a message to a single node in chunks, over a dedicated stream. The recipient receives
+    /// the whole message as a single `Event::MessageReceived` once all the chunks arrive. If the
+    /// stream fails midway, an error is returned and the recipient discards what it received so
+    /// far.
+    ///
+    /// Only `DstLocation::Node` destinations that are members of our section or elders of a known
+    /// section are supported. The stream counts as a single message toward
+    /// `Config::per_prefix_rate_limit`, but its chunks are not passed to
+    /// `Config::outgoing_interceptor`, as they are not known up front.
+    pub async fn send_stream<S>(&self, dst: DstLocation, chunks: S) -> Result<()>
+    where
+        S: Stream<Item = Bytes>,
+    {
+        let (recipient, header) = {
+            let state = self.stage.state.lock().await;
+            self.stage.check_rate_limit(&state, &dst)?;
+            state.create_user_message_stream_header(dst)?
+        };
+        let header = MessageType::NodeMessage(NodeMessage::new(header.to_bytes())).serialize()?;
+
+        let mut stream = self.stage.comm.open_stream(&recipient, header).await?;
+
+        futures::pin_mut!(chunks);
+        // An empty chunk terminates the stream, so don't send any of those from the input.
+        let mut chunks = chunks.filter(|chunk| futures::future::ready(!chunk.is_empty()));
+
+        while let Some(chunk) = chunks.next().await {
+            stream.send_user_msg(chunk).await.map_err(|error| {
+                error!("Sending stream chunk to {} failed: {}", recipient, error);
+                Error::FailedSend
+            })?;
+        }
+
+        stream.send_user_msg(Bytes::new()).await.map_err(|error| {
+            error!("Finishing stream to {} failed: {}", recipient, error);
+            Error::FailedSend
+        })?;
+        stream.finish().await.map_err(|error| {
+            error!("Finishing stream to {} failed: {}", recipient, error);
+            Error::FailedSend
+        })
+    }
+
+    /// Send a message to a single elder of our section. The elder is picked at random, weighted by
+    /// age, while avoiding the most recently picked ones, so that repeated calls spread the load
+    /// across all the elders.
+    pub async fn send_to_any_elder(&self, content: Bytes) -> Result<()> {
+        let command = Command::SendToAnyElder { content };
+        self.stage.clone().handle_commands(command).await
+    }
+
+    /// Send a message directly to the node with the given name, which must be one of our elders, a
+    /// member of our section or an elder of a known section. Returns `Error::UnknownPeer` if we
+    /// don't know the address of the node.
+    pub async fn send_message_to_name(&self, name: XorName, content: Bytes) -> Result<()> {
+        let command = Command::SendToName { name, content };
+        self.stage.clone().handle_commands(command).await
+    }
+
+    /// Send a message to the elders of the `fanout` known sections (including our own) closest to
+    /// `target`. If we know fewer than `fanout` sections, the message is sent to all of them.
+    pub async fn send_toward(&self, target: &XorName, fanout: usize, content: Bytes) -> Result<()> {
+        let command = Command::SendToward {
+            target: *target,
+            fanout,
+            content,
+        };
+        self.stage.clone().handle_commands(command).await
+    }
+
+    /// Send a message to the elders of every section we know, including our own, concurrently.
+    /// Yields the delivery report of each section as the sends to it complete. A section the
+    /// message couldn't be prepared for (for example because of the rate limit) is reported with
+    /// no recipients.
+    pub async fn announce_network_wide(
+        &self,
+        content: Bytes,
+    ) -> impl Stream<Item = (Prefix, DeliveryReport)> {
+        self.stage
+            .announce_network_wide(content)
+            .await
+            .into_iter()
+            .map(|(prefix, commands)| {
+                let stage = self.stage.clone();
+                async move {
+                    let report = match commands {
+                        Ok(commands) => stage.handle_commands_reported(commands).await,
+                        Err(error) => {
+                            error!("Failed to announce to section {:?}: {}", prefix, error);
+                            DeliveryReport::default()
+                        }
+                    };
+                    (prefix, report)
+                }
+            })
+            .collect::<FuturesUnordered<_>>()
+    }
+
+    /// Ask the elders of the neighbour section with the given prefix for their current
+    /// `EldersInfo`, in case our view of it went stale. Our view is updated once the response is
+    /// verified against the neighbour's proof chain and agreed on by our elders. Returns
+    /// `Error::InvalidDstLocation` if `prefix` is not one of our known neighbours.
+    pub async fn refresh_neighbour(&self, prefix: Prefix) -> Result<()> {
+        let command = Command::RefreshNeighbour(prefix);
+        self.stage.clone().handle_commands(command).await
+    }
+
+    /// Checks that `addr`, for example one exchanged out-of-band, is usable as the address of a
+    /// peer and returns it if so. Unspecified, multicast and broadcast addresses and port zero are
+    /// rejected with `Error::InvalidPeerAddr`, so are loopback addresses unless
+    /// `Config::allow_loopback_addrs` is set.
+    pub fn validate_peer_addr(&self, addr: SocketAddr) -> Result<SocketAddr> {
+        self.stage.validate_peer_addr(addr)
+    }
+
     /// Send a message to a client peer.
     /// Messages sent to a client are not signed or validated as part of the
     /// routing library.
@@ -331,34 +1318,175 @@ impl Routing {
         self.stage.clone().handle_commands(command).await
     }
 
+    /// Send a message to a client peer like `send_message_to_client` does, but encrypted so that
+    /// only the holder of the secret key corresponding to `client_key` can read it. The message is
+    /// serialised the same way as by `send_message_to_client` and then encrypted, so the client
+    /// reads it with `decrypt_payload` followed by `WireMsg::deserialize`.
+    pub async fn send_encrypted_to_client(
+        &self,
+        recipient: SocketAddr,
+        client_key: bls::PublicKey,
+        message: MsgEnvelope,
+    ) -> Result<()> {
+        let command = Command::SendEncryptedToClient {
+            recipient,
+            client_key,
+            message,
+        };
+        self.stage.clone().handle_commands(command).await
+    }
+
     /// Returns the current BLS public key set if this node has one, or
     /// `Error::InvalidState` otherwise.
     pub async fn public_key_set(&self) -> Result<bls::PublicKeySet> {
         self.stage.state.lock().await.public_key_set()
     }
 
+    /// Returns the current BLS public key of our section (the combined public key of the current
+    /// public key set) if this node has one, or `Error::MissingSecretKeyShare` otherwise. The key
+    /// is cached, so this is cheaper than `public_key_set().await?.public_key()`.
+    pub async fn section_public_key(&self) -> Result<bls::PublicKey> {
+        self.stage.state.lock().await.section_public_key()
+    }
+
+    /// Returns the threshold of the current BLS public key set if this node has one, or
+    /// `Error::InvalidState` otherwise. A valid section signature requires signature shares from
+    /// more than `threshold` elders.
+    pub async fn signature_threshold(&self) -> Result<usize> {
+        Ok(self.public_key_set().await?.threshold())
+    }
+
+    /// Returns the age the member of our section with the given name would get if it was relocated
+    /// now, or `None` if there is no such member.
+    pub async fn predict_relocation_age(&self, name: &XorName) -> Option<u8> {
+        self.stage.state.lock().await.predict_relocation_age(name)
+    }
+
+    /// Returns the members of our section that should have relocated but haven't yet, together
+    /// with how long they've been pending. These are elders whose relocation is delayed until
+    /// they get demoted, so a member staying here for long indicates a stuck elder change.
+    pub async fn relocation_pending(&self) -> Vec<(XorName, Duration)> {
+        self.stage.state.lock().await.relocation_pending()
+    }
+
+    /// Returns the names and ages of the members that joined our section, including by relocating
+    /// into it, within the given time window. Only joins observed by this node are included.
+    pub async fn recently_joined(&self, within: Duration) -> Vec<(XorName, u8)> {
+        self.stage.state.lock().await.recently_joined(within)
+    }
+
+    /// Returns our current age, the number of churn events (members joining or leaving) in our
+    /// section since we joined it and the number of churn events after which we can expect to be
+    /// relocated with an increased age. As the relocation is decided randomly on every churn, this
+    /// is only an estimate of our progress towards the next age. Returns `Error::InvalidState` if
+    /// we are not a member of our section, for example while relocating.
+    pub async fn age_progress(&self) -> Result<(u8, u32, u32)> {
+        self.stage.state.lock().await.age_progress()
+    }
+
+    /// Returns a rough estimate of how long it takes until we are promoted to elder, based on our
+    /// rank among the elder candidates and the rate of the recent churn events in our section.
+    /// This is a heuristic only: it assumes each churn moves us one place closer to the elders and
+    /// that the churn rate stays the same. Returns `None` if we are an elder, not eligible for
+    /// promotion or too few churn events were observed yet.
+    pub async fn estimated_promotion_time(&self) -> Option<Duration> {
+        self.stage.state.lock().await.estimated_promotion_time()
+    }
+
+    /// Returns whether this node can currently contribute to a section signature: it holds a key
+    /// share for the current section key and our section has enough elders to reach the
+    /// signature threshold. Unlike checking the elder count alone, this is accurate during key
+    /// transitions too.
+    pub async fn can_sign(&self) -> bool {
+        self.stage.state.lock().await.can_sign()
+    }
+
+    /// Cheap self-check of our identity, useful after importing or rotating it: re-derives our
+    /// name from our keypair and checks it matches the name our section recorded for the member
+    /// at our address. Returns false on mismatch, which means our section knows us under a
+    /// different identity than the one we sign with.
+    pub async fn verify_identity_consistency(&self) -> bool {
+        self.stage.state.lock().await.verify_identity_consistency()
+    }
+
+    /// Returns whether we hold a key share for the current section key. Right after a key change,
+    /// `secret_key_share` might still return our share of the previous key, whose signature shares
+    /// won't combine with those of the other elders, until the share of the new one is installed.
+    pub async fn has_current_key_share(&self) -> bool {
+        self.stage.state.lock().await.has_current_key_share()
+    }
+
+    /// Returns whether our section is keyed and settled from our point of view: we hold a share of
+    /// the current section key and no key rotation is underway. Unlike `public_key_set().is_ok()`,
+    /// this is false while we hold a stale key set (the section switched to a key whose DKG we
+    /// haven't completed yet) and while we hold a new one the section hasn't switched to yet.
+    pub async fn has_complete_key_set(&self) -> bool {
+        self.stage.state.lock().await.has_complete_key_set()
+    }
+
     /// Returns our section proof chain.
     pub async fn our_history(&self) -> SectionProofChain {
         self.stage.state.lock().await.section().chain().clone()
     }
 
+    /// Returns every key of our section proof chain, oldest first, each with the slice of the
+    /// chain proving it was signed by the key before it. Anyone trusting the first key can audit
+    /// the whole history of our section keys by checking each slice against the key of the previous
+    /// entry. Keys removed from the chain by compaction are not listed and the first key after them
+    /// has no slice, so the audit can only resume from that key.
+    pub async fn key_rotation_log(&self) -> Vec<KeyRotationEntry> {
+        self.stage.state.lock().await.key_rotation_log()
+    }
+
+    /// Returns the section proof chain starting at the first key we know of (the genesis key,
+    /// unless our chain has been trimmed) and ending at the key our current elders were approved
+    /// with. A client that trusts the genesis key can verify it using
+    /// `SectionProofChain::check_trust`.
+    pub async fn proof_to_current_key(&self) -> SectionProofChain {
+        self.stage
+            .state
+            .lock()
+            .await
+            .section()
+            .create_proof_chain_for_our_info(Some(0))
+    }
+
     /// Returns our index in the current BLS group if this node is a member of one, or
     /// `Error::MissingSecretKeyShare` otherwise.
     pub async fn our_index(&self) -> Result<usize> {
         self.stage.state.lock().await.our_index()
     }
+
+    /// Returns our position in the current elder set sorted by name, or `Error::InvalidState` if
+    /// this node is not an elder. Unlike `our_index`, this doesn't depend on the BLS key shares and
+    /// so is the same for every observer of the same elder set.
+    pub async fn our_elder_index(&self) -> Result<usize> {
+        self.stage.state.lock().await.our_elder_index()
+    }
 }
 
 impl Drop for Routing {
     fn drop(&mut self) {
-        self.stage.terminate()
+        self.stage.clone().terminate_gracefully()
     }
 }
 
+// How a new node enters the network.
+enum StartMode {
+    // Start a new network as its first node.
+    First,
+    // Bootstrap to an existing network and join it.
+    Join,
+    // Start as a member of a preset section.
+    #[cfg(any(test, feature = "testing"))]
+    Preset(SectionPreset),
+}
+
 // Listen for incoming connection events and handle them.
 async fn handle_connection_events(
     stage: Arc<Stage>,
     mut incoming_conns: mpsc::Receiver<ConnectionEvent>,
+    max_message_size: usize,
 ) {
     while let Some(event) = incoming_conns.recv().await {
         match event {
@@ -368,7 +1496,27 @@ async fn handle_connection_events(
                     bytes.len(),
                     src
                 );
-                handle_message(stage.clone(), bytes, src, recv, None).await;
+                if !check_message_size(&stage, &bytes, src, max_message_size).await {
+                    continue;
+                }
+
+                if let Some(messages) = comm::split_batch(&bytes) {
+                    trace!("Received batch of {} messages from {}", messages.len(), src);
+                    for bytes in messages {
+                        handle_message(stage.clone(), bytes, src, None, None, max_message_size)
+                            .await;
+                    }
+                } else {
+                    handle_message(
+                        stage.clone(),
+                        bytes,
+                        src,
+                        Some(recv),
+                        None,
+                        max_message_size,
+                    )
+                    .await;
+                }
             }
             ConnectionEvent::Received(Qp2pMessage::BiStream {
                 bytes,
@@ -381,7 +1529,17 @@ async fn handle_connection_events(
                     bytes.len(),
                     src
                 );
-                handle_message(stage.clone(), bytes, src, recv, Some(send)).await;
+                if check_message_size(&stage, &bytes, src, max_message_size).await {
+                    handle_message(
+                        stage.clone(),
+                        bytes,
+                        src,
+                        Some(recv),
+                        Some(send),
+                        max_message_size,
+                    )
+                    .await;
+                }
             }
             ConnectionEvent::Disconnected(addr) => {
                 trace!("Lost connection to {:?}", addr);
@@ -394,17 +1552,57 @@ async fn handle_connection_events(
     }
 }
 
+// Fraction of the name space covered by `prefix`.
+fn address_space_fraction(prefix: &Prefix) -> f64 {
+    0.5f64.powi(prefix.bit_count() as i32)
+}
+
+// Returns whether the message is within the size limit. Oversized messages are logged and reported
+// and should be dropped without deserialising them.
+async fn check_message_size(
+    stage: &Stage,
+    bytes: &Bytes,
+    sender: SocketAddr,
+    max_message_size: usize,
+) -> bool {
+    if bytes.len() > max_message_size {
+        warn!(
+            "Dropping message from {}: size {} bytes exceeds the limit of {} bytes",
+            sender,
+            bytes.len(),
+            max_message_size
+        );
+        stage
+            .send_event(Event::MessageDropped {
+                reason: DropReason::TooLarge,
+                src: None,
+            })
+            .await;
+        false
+    } else {
+        true
+    }
+}
+
+// `recv` is `None` if the message was received as part of a batch.
 async fn handle_message(
     stage: Arc<Stage>,
     bytes: Bytes,
     sender: SocketAddr,
-    recv: RecvStream,
+    recv: Option<RecvStream>,
     send: Option<SendStream>,
+    max_message_size: usize,
 ) {
     let message_type = match WireMsg::deserialize(bytes) {
         Ok(message_type) => message_type,
         Err(error) => {
             error!("Failed to deserialize message from {}: {}", sender, error);
+            stage
+                .send_event(Event::MessageDropped {
+                    reason: DropReason::Malformed,
+                    src: None,
+                })
+                .await;
             return;
         }
     };
@@ -419,6 +1617,39 @@ async fn handle_message(
         }
         MessageType::NodeMessage(NodeMessage(msg_bytes)) => {
             match Message::from_bytes(Bytes::from(msg_bytes)) {
+                Ok(message) if matches!(message.variant(), Variant::UserMessageStream) => {
+                    if let Some(recv) = recv {
+                        let _ = task::spawn(receive_user_message_stream(
+                            stage,
+                            message,
+                            sender,
+                            recv,
+                            max_message_size,
+                        ));
+                    } else {
+                        error!("Ignoring batched user message stream from {}", sender);
+                    }
+                }
+                Ok(message) if matches!(message.variant(), Variant::UserQuery(_)) => {
+                    if let Some(send) = send {
+                        let _ = task::spawn(respond_to_user_query(stage, message, sender, send));
+                    } else {
+                        error!(
+                            "Ignoring user query not sent on a bi-stream from {}",
+                            sender
+                        );
+                    }
+                }
+                Ok(message) if matches!(message.variant(), Variant::ClockProbe) => {
+                    if let Some(send) = send {
+                        let _ = task::spawn(respond_to_clock_probe(stage, message, sender, send));
+                    } else {
+                        error!(
+                            "Ignoring clock probe not sent on a bi-stream from {}",
+                            sender
+                        );
+                    }
+                }
                 Ok(message) => {
                     let command = Command::HandleMessage {
                         message,
@@ -436,6 +1667,13 @@ async fn handle_message(
             }
         }
         MessageType::ClientMessage(msg_envelope) => {
+            let recv = if let Some(recv) = recv {
+                recv
+            } else {
+                error!("Ignoring batched client message from {}", sender);
+                return;
+            };
+
             let event = Event::ClientMessageReceived {
                 content: Box::new(msg_envelope),
                 src: sender,
@@ -447,3 +1685,170 @@ async fn handle_message(
         }
     }
 }
+
+// Answer a clock probe sent via `Routing::measure_clock_skew` with our current time.
+async fn respond_to_clock_probe(
+    stage: Arc<Stage>,
+    probe: Message,
+    sender: SocketAddr,
+    mut send: SendStream,
+) {
+    let src = probe.src().src_location();
+    let dst = *probe.dst();
+
+    if !matches!(src, SrcLocation::Node(_))
+        || !stage.state.lock().await.check_stream_message(&probe)
+    {
+        error!(
+            "Ignoring clock probe {:?} -> {:?} from {}",
+            src, dst, sender
+        );
+        return;
+    }
+
+    let time = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default();
+    let response = match bincode::serialize(&time) {
+        Ok(response) => response,
+        Err(error) => {
+            error!("Failed to answer clock probe from {}: {}", sender, error);
+            return;
+        }
+    };
+
+    if let Err(error) = send.send_user_msg(Bytes::from(response)).await {
+        error!(
+            "Sending response to clock probe to {} failed: {}",
+            sender, error
+        );
+        return;
+    }
+
+    if let Err(error) = send.finish().await {
+        error!(
+            "Finishing response to clock probe to {} failed: {}",
+            sender, error
+        );
+    }
+}
+
+// Receive the chunks of a user message sent via `Routing::send_stream` and raise the reassembled
+// message as a single `Event::MessageReceived`. Nothing is raised if the stream fails or exceeds
+// the size limit.
+async fn receive_user_message_stream(
+    stage: Arc<Stage>,
+    header: Message,
+    sender: SocketAddr,
+    mut recv: RecvStream,
+    max_message_size: usize,
+) {
+    let src = header.src().src_location();
+    let dst = *header.dst();
+
+    if !matches!(src, SrcLocation::Node(_))
+        || !stage.state.lock().await.check_stream_message(&header)
+    {
+        error!(
+            "Ignoring user message stream {:?} -> {:?} from {}",
+            src, dst, sender
+        );
+        return;
+    }
+
+    // Only the header of the stream is signed, not the content.
+    if stage.state.lock().await.signed_messages_required() {
+        warn!(
+            "Dropping user message stream from {}: content is not signed",
+            sender
+        );
+        stage
+            .send_event(Event::MessageDropped {
+                reason: DropReason::Unsigned,
+                src: Some(src),
+            })
+            .await;
+        return;
+    }
+
+    let mut content = BytesMut::new();
+
+    loop {
+        match recv.next().await {
+            Ok(chunk) if chunk.is_empty() => break,
+            Ok(chunk) => {
+                if content.len() + chunk.len() > max_message_size {
+                    warn!(
+                        "Dropping user message stream from {}: size exceeds the limit of {} bytes",
+                        sender, max_message_size
+                    );
+                    stage
+                        .send_event(Event::MessageDropped {
+                            reason: DropReason::TooLarge,
+                            src: Some(src),
+                        })
+                        .await;
+                    return;
+                }
+
+                content.extend_from_slice(&chunk);
+            }
+            Err(error) => {
+                error!("User message stream from {} failed: {}", sender, error);
+                return;
+            }
+        }
+    }
+
+    stage
+        .state
+        .lock()
+        .await
+        .handle_user_message(src, dst, content.freeze());
+}
+
+// Answer a user query sent via `Routing::query_elders` using our query handler, if any, on the
+// stream it arrived on.
+async fn respond_to_user_query(
+    stage: Arc<Stage>,
+    query: Message,
+    sender: SocketAddr,
+    mut send: SendStream,
+) {
+    let src = query.src().src_location();
+    let dst = *query.dst();
+
+    let content = match query.variant() {
+        Variant::UserQuery(content) => content,
+        _ => return,
+    };
+
+    if !matches!(src, SrcLocation::Node(_))
+        || !stage.state.lock().await.check_stream_message(&query)
+    {
+        error!("Ignoring user query {:?} -> {:?} from {}", src, dst, sender);
+        return;
+    }
+
+    let response = if let Some(response) = stage.handle_user_query(&src, content) {
+        response
+    } else {
+        trace!("Not answering user query from {}", sender);
+        return;
+    };
+
+    if let Err(error) = send.send_user_msg(response).await {
+        error!(
+            "Sending response to user query to {} failed: {}",
+            sender, error
+        );
+        return;
+    }
+
+    if let Err(error) = send.finish().await {
+        error!(
+            "Finishing response to user query to {} failed: {}",
+            sender, error
+        );
+    }
+}
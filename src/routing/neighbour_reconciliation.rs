@@ -0,0 +1,100 @@
+// Copyright 2020 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under The General Public License (GPL), version 3.
+// Unless required by applicable law or agreed to in writing, the SAFE Network Software distributed
+// under the GPL Licence is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied. Please review the Licences for the specific language governing
+// permissions and limitations relating to use of the SAFE Network Software.
+
+use super::command::{self, Command};
+use std::{cmp, collections::HashMap, time::Duration};
+use xor_name::Prefix;
+
+// Maximum number of rounds to skip a neighbour for after its requests went unanswered.
+const MAX_SKIPPED_ROUNDS: u32 = 15;
+
+/// Helper to periodically ask our neighbours for their current `EldersInfo`, so our view of them
+/// doesn't drift.
+///
+/// # Usage
+///
+/// Call `schedule` to get the command that schedules the next round. When a timeout for which
+/// `is_timer` returns true fires, call `select` to get the neighbours to query in this round,
+/// query them and then `schedule` the next round. Call `record_response` whenever a neighbour
+/// responds.
+///
+/// Neighbours that don't respond are backed off: the `n`-th request in a row a neighbour leaves
+/// unanswered is followed by `2^(n-1) - 1` rounds (capped at `MAX_SKIPPED_ROUNDS`) in which the
+/// neighbour is not queried.
+pub(crate) struct NeighbourReconciliation {
+    interval: Duration,
+    timer_token: Option<u64>,
+    backoffs: HashMap<Prefix, Backoff>,
+}
+
+impl NeighbourReconciliation {
+    pub fn new(interval: Duration) -> Self {
+        Self {
+            interval,
+            timer_token: None,
+            backoffs: HashMap::new(),
+        }
+    }
+
+    pub fn interval(&self) -> Duration {
+        self.interval
+    }
+
+    pub fn schedule(&mut self) -> Command {
+        let token = command::next_timer_token();
+        self.timer_token = Some(token);
+
+        Command::ScheduleTimeout {
+            duration: self.interval,
+            token,
+        }
+    }
+
+    pub fn is_timer(&self, token: u64) -> bool {
+        self.timer_token == Some(token)
+    }
+
+    // Returns the neighbours out of `prefixes` to query in this round and records that they were
+    // queried.
+    pub fn select(&mut self, prefixes: Vec<Prefix>) -> Vec<Prefix> {
+        self.backoffs.retain(|prefix, _| prefixes.contains(prefix));
+
+        prefixes
+            .into_iter()
+            .filter(|prefix| {
+                let backoff = self.backoffs.entry(*prefix).or_default();
+
+                if backoff.skip > 0 {
+                    backoff.skip -= 1;
+                    return false;
+                }
+
+                // Assume the request goes unanswered until `record_response` says otherwise.
+                backoff.skip = cmp::min(
+                    2u32.saturating_pow(backoff.unanswered) - 1,
+                    MAX_SKIPPED_ROUNDS,
+                );
+                backoff.unanswered = backoff.unanswered.saturating_add(1);
+
+                true
+            })
+            .collect()
+    }
+
+    pub fn record_response(&mut self, prefix: &Prefix) {
+        let _ = self.backoffs.remove(prefix);
+    }
+}
+
+#[derive(Default)]
+struct Backoff {
+    // Number of consecutive requests the neighbour hasn't responded to.
+    unanswered: u32,
+    // Number of rounds to skip the neighbour for.
+    skip: u32,
+}
@@ -0,0 +1,118 @@
+// Copyright 2020 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under The General Public License (GPL), version 3.
+// Unless required by applicable law or agreed to in writing, the SAFE Network Software distributed
+// under the GPL Licence is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied. Please review the Licences for the specific language governing
+// permissions and limitations relating to use of the SAFE Network Software.
+
+use crate::{
+    consensus::Proven,
+    error::{Error, Result},
+    node::Node,
+    peer::Peer,
+    section::{EldersInfo, MemberInfo, Section, SectionKeyShare, SectionProofChain},
+};
+use bls_signature_aggregator::Proof;
+use serde::Serialize;
+use std::net::SocketAddr;
+use xor_name::{Prefix, XorName};
+
+/// Section to start a node in directly, without bootstrapping and joining (see
+/// `Routing::new_with_preset_section`). Meant for tests that need a section with a precise set of
+/// members without growing it organically.
+#[derive(Clone)]
+pub struct SectionPreset {
+    /// Prefix of the section.
+    pub prefix: Prefix,
+    /// Polynomial of the section key set. Its secret key signs the section info and the membership
+    /// of every member, so its public key must be the last key of `chain`. If our node is one of the
+    /// elders, it gets the key share at its position among the elders sorted by name.
+    pub secret_key_poly: bls::poly::Poly,
+    /// Proof chain of the section.
+    pub chain: SectionProofChain,
+    /// Members of the section. Must include our node, whose address is taken from our endpoint
+    /// instead.
+    pub members: Vec<PresetMember>,
+}
+
+/// Member of a `SectionPreset`.
+#[derive(Clone, Copy, Debug)]
+pub struct PresetMember {
+    /// Name of the member.
+    pub name: XorName,
+    /// Address of the member.
+    pub addr: SocketAddr,
+    /// Age of the member.
+    pub age: u8,
+    /// Whether the member is one of the elders.
+    pub is_elder: bool,
+}
+
+impl SectionPreset {
+    // Creates the section with `node` as one of its members and returns it together with `node`
+    // updated to the preset age and our key share, if we are one of the elders. Returns
+    // `Error::InvalidState` if the preset doesn't include us or its key set doesn't match its
+    // chain.
+    pub(crate) fn build(self, node: Node) -> Result<(Node, Section, Option<SectionKeyShare>)> {
+        let our_name = node.name();
+        let our_age = self
+            .members
+            .iter()
+            .find(|member| member.name == our_name)
+            .map(|member| member.age)
+            .ok_or(Error::InvalidState)?;
+        let node = node.with_age(our_age);
+
+        let secret_key = bls::SecretKey::from_mut(&mut self.secret_key_poly.evaluate(0));
+        let secret_key_set = bls::SecretKeySet::from(self.secret_key_poly);
+        if *self.chain.last_key() != secret_key.public_key() {
+            return Err(Error::InvalidState);
+        }
+
+        let to_peer = |member: &PresetMember| {
+            if member.name == our_name {
+                node.peer()
+            } else {
+                Peer::new(member.name, member.addr, member.age)
+            }
+        };
+
+        let elders_info = EldersInfo::new(
+            self.members
+                .iter()
+                .filter(|member| member.is_elder)
+                .map(&to_peer),
+            self.prefix,
+        );
+        let mut section = Section::new(self.chain, sign(&secret_key, elders_info)?)?;
+
+        for member in &self.members {
+            let member_info = MemberInfo::joined(to_peer(member));
+            let _ = section.update_member(sign(&secret_key, member_info)?);
+        }
+
+        let key_share = section
+            .elders_info()
+            .elders
+            .keys()
+            .position(|name| *name == our_name)
+            .map(|index| SectionKeyShare {
+                public_key_set: secret_key_set.public_keys(),
+                index,
+                secret_key_share: secret_key_set.secret_key_share(index),
+            });
+
+        Ok((node, section, key_share))
+    }
+}
+
+fn sign<T: Serialize>(secret_key: &bls::SecretKey, value: T) -> Result<Proven<T>> {
+    let bytes = bincode::serialize(&value)?;
+    let proof = Proof {
+        public_key: secret_key.public_key(),
+        signature: secret_key.sign(&bytes),
+    };
+
+    Ok(Proven::new(value, proof))
+}
@@ -0,0 +1,64 @@
+// Copyright 2020 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under The General Public License (GPL), version 3.
+// Unless required by applicable law or agreed to in writing, the SAFE Network Software distributed
+// under the GPL Licence is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied. Please review the Licences for the specific language governing
+// permissions and limitations relating to use of the SAFE Network Software.
+
+use std::{collections::HashMap, sync::Mutex, time::Instant};
+use xor_name::Prefix;
+
+/// Limit on the rate of the user messages sent toward a single section (see
+/// `Config::per_prefix_rate_limit`).
+#[derive(Clone, Copy, Debug)]
+pub struct RateLimit {
+    /// Maximum number of messages that can be sent toward a section at once, after not sending
+    /// anything to it for a while.
+    pub burst: u32,
+    /// Number of messages per second that can be sent toward a section in the long run.
+    pub per_second: f64,
+}
+
+// Enforces a `RateLimit` using a token bucket per destination prefix.
+pub(crate) struct PrefixRateLimiter {
+    limit: RateLimit,
+    buckets: Mutex<HashMap<Prefix, TokenBucket>>,
+}
+
+impl PrefixRateLimiter {
+    pub fn new(limit: RateLimit) -> Self {
+        Self {
+            limit,
+            buckets: Mutex::new(HashMap::new()),
+        }
+    }
+
+    // Takes a token from the bucket of `prefix`. Returns whether there was any left.
+    pub fn try_acquire(&self, prefix: &Prefix) -> bool {
+        let capacity = f64::from(self.limit.burst);
+        let now = Instant::now();
+
+        let mut buckets = self.buckets.lock().unwrap_or_else(|err| err.into_inner());
+        let bucket = buckets.entry(*prefix).or_insert(TokenBucket {
+            tokens: capacity,
+            refilled_at: now,
+        });
+
+        let elapsed = now.duration_since(bucket.refilled_at).as_secs_f64();
+        bucket.tokens = (bucket.tokens + elapsed * self.limit.per_second).min(capacity);
+        bucket.refilled_at = now;
+
+        if bucket.tokens >= 1.0 {
+            bucket.tokens -= 1.0;
+            true
+        } else {
+            false
+        }
+    }
+}
+
+struct TokenBucket {
+    tokens: f64,
+    refilled_at: Instant,
+}
@@ -6,15 +6,78 @@
 // KIND, either express or implied. Please review the Licences for the specific language governing
 // permissions and limitations relating to use of the SAFE Network Software.
 
-use super::{bootstrap, Approved, Comm, Command};
-use crate::{error::Result, event::Event, relocation::SignedRelocateDetails};
-use sn_messaging::MessageType;
-use std::{net::SocketAddr, sync::Arc, time::Duration};
+use super::{
+    bootstrap,
+    command::CommandSummary,
+    rate_limit::{PrefixRateLimiter, RateLimit},
+    Approved, Comm, Command, InterceptDecision, OutgoingInterceptor, QueryHandler,
+};
+use crate::{
+    crypto,
+    error::{Error, Result},
+    event::{Event, MembershipEvent},
+    location::{DstLocation, SrcLocation},
+    messages::Message,
+    node::Node,
+    relocation::SignedRelocateDetails,
+    section::Section,
+};
+use bytes::Bytes;
+use rand::Rng;
+use sn_messaging::{MessageType, WireMsg};
+use std::{
+    collections::{HashSet, VecDeque},
+    iter,
+    net::{IpAddr, SocketAddr},
+    slice,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc, RwLock,
+    },
+    time::Duration,
+};
 use tokio::{
-    sync::{mpsc, watch, Mutex},
+    runtime::Handle,
+    sync::{mpsc, oneshot, watch, Mutex},
     time,
 };
 use tracing::Instrument;
+use xor_name::{Prefix, XorName};
+
+// How often to check whether all commands completed when flushing before termination.
+const FLUSH_POLL_INTERVAL: Duration = Duration::from_millis(10);
+// Number of the most recently handled commands kept when the command trace is enabled.
+const COMMAND_TRACE_LEN: usize = 256;
+
+/// How `Routing::send_message_with_retry` retries a send that failed completely.
+#[derive(Clone, Copy, Debug)]
+pub struct RetryPolicy {
+    /// Maximum number of attempts to send the message, including the first one.
+    pub max_rounds: usize,
+    /// Time to wait before the second attempt. It doubles before every further attempt.
+    pub initial_backoff: Duration,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            max_rounds: 3,
+            initial_backoff: Duration::from_millis(500),
+        }
+    }
+}
+
+/// Outcome of sending a message to the elders of a section, as reported by
+/// `Routing::announce_network_wide`.
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct DeliveryReport {
+    /// Number of recipients the message had to be delivered to.
+    pub recipients: usize,
+    /// Number of recipients the message was delivered to.
+    pub delivered: usize,
+    /// Recipients the message failed to be sent to.
+    pub failed: Vec<SocketAddr>,
+}
 
 // Node's current stage which is responsible
 // for accessing current info and trigger operations.
@@ -24,6 +87,40 @@ pub(crate) struct Stage {
 
     cancel_timer_tx: watch::Sender<bool>,
     cancel_timer_rx: watch::Receiver<bool>,
+    // Maximum random deviation of scheduled timeouts, in percent of their duration.
+    timer_jitter_percent: u8,
+    // Hook called for every outgoing user message.
+    outgoing_interceptor: Option<Arc<dyn OutgoingInterceptor>>,
+    // Hook answering the incoming user queries.
+    query_handler: Option<Arc<dyn QueryHandler>>,
+    // Number of spawned commands that haven't completed yet.
+    commands_in_flight: AtomicUsize,
+    // How long to keep handling the in-flight commands after termination is requested.
+    drop_grace_period: Duration,
+    // Whether loopback addresses are valid peer addresses.
+    allow_loopback_addrs: bool,
+    // Limits the rate of user messages sent toward each section or `None` if unlimited.
+    prefix_rate_limiter: Option<PrefixRateLimiter>,
+    // Summaries of the most recently handled commands, or `None` if not tracing commands.
+    command_trace: Option<RwLock<VecDeque<CommandSummary>>>,
+    // Notified with our new name once the next relocation completes.
+    relocation_waiters: Mutex<Vec<oneshot::Sender<XorName>>>,
+    // Whether to release the connections to the other elders once we are no longer an elder.
+    drop_connections_on_demotion: bool,
+    // Our elders as of the last update of the pinned peers, so they are recomputed only when our
+    // elders change.
+    pinned_elders: Mutex<Option<PinnedElders>>,
+}
+
+// Our elders as of an update of the pinned peers.
+#[derive(Clone, Copy)]
+struct PinnedElders {
+    // Key held by the elders.
+    key: bls::PublicKey,
+    // Whether we were one of them.
+    elder: bool,
+    // Whether we released the connections to them, due to our demotion.
+    released: bool,
 }
 
 impl Stage {
@@ -38,9 +135,123 @@ impl Stage {
             comm,
             cancel_timer_tx,
             cancel_timer_rx,
+            timer_jitter_percent: 0,
+            outgoing_interceptor: None,
+            query_handler: None,
+            commands_in_flight: AtomicUsize::new(0),
+            drop_grace_period: Duration::from_secs(0),
+            allow_loopback_addrs: false,
+            prefix_rate_limiter: None,
+            command_trace: None,
+            relocation_waiters: Mutex::new(Vec::new()),
+            drop_connections_on_demotion: false,
+            pinned_elders: Mutex::new(None),
         }
     }
 
+    /// Randomly shift every scheduled timeout by up to `percent` of its duration (in either
+    /// direction) so that timers of different nodes don't fire in sync.
+    pub fn with_timer_jitter(mut self, percent: u8) -> Self {
+        self.timer_jitter_percent = percent.min(100);
+        self
+    }
+
+    /// Pass every outgoing user message through the given interceptor before sending it.
+    pub fn with_outgoing_interceptor(
+        mut self,
+        interceptor: Option<Arc<dyn OutgoingInterceptor>>,
+    ) -> Self {
+        self.outgoing_interceptor = interceptor;
+        self
+    }
+
+    /// Answer the incoming user queries with the given handler. Without a handler, queries are not
+    /// answered.
+    pub fn with_query_handler(mut self, handler: Option<Arc<dyn QueryHandler>>) -> Self {
+        self.query_handler = handler;
+        self
+    }
+
+    /// Returns the response to the given user query, or `None` if it's not to be answered.
+    pub fn handle_user_query(&self, src: &SrcLocation, content: &Bytes) -> Option<Bytes> {
+        self.query_handler.as_ref()?.on_query(src, content)
+    }
+
+    /// On termination, give the commands still in progress (for example message sends) up to
+    /// `grace_period` to complete before closing all connections.
+    pub fn with_drop_grace_period(mut self, grace_period: Duration) -> Self {
+        self.drop_grace_period = grace_period;
+        self
+    }
+
+    /// Once we are demoted, stop keeping the connections to the other elders alive and close the
+    /// ones to the peers that are no longer elders.
+    pub fn with_drop_connections_on_demotion(mut self, drop: bool) -> Self {
+        self.drop_connections_on_demotion = drop;
+        self
+    }
+
+    /// Accept loopback addresses in `validate_peer_addr`.
+    pub fn with_loopback_addrs_allowed(mut self, allow: bool) -> Self {
+        self.allow_loopback_addrs = allow;
+        self
+    }
+
+    /// Reject the user messages sent toward a section faster than `limit` allows with
+    /// `Error::RateLimited`. `None` means no limit.
+    pub fn with_per_prefix_rate_limit(mut self, limit: Option<RateLimit>) -> Self {
+        self.prefix_rate_limiter = limit.map(PrefixRateLimiter::new);
+        self
+    }
+
+    /// Returns `addr` if it's usable as the address of a peer, `Error::InvalidPeerAddr` otherwise.
+    pub fn validate_peer_addr(&self, addr: SocketAddr) -> Result<SocketAddr> {
+        let ip = addr.ip();
+        let is_broadcast = match ip {
+            IpAddr::V4(ip) => ip.is_broadcast(),
+            IpAddr::V6(_) => false,
+        };
+
+        if ip.is_unspecified()
+            || ip.is_multicast()
+            || is_broadcast
+            || addr.port() == 0
+            || (ip.is_loopback() && !self.allow_loopback_addrs)
+        {
+            Err(Error::InvalidPeerAddr)
+        } else {
+            Ok(addr)
+        }
+    }
+
+    /// Record a summary of every handled command (see `recent_commands`). For debugging only.
+    pub fn with_command_trace(mut self) -> Self {
+        self.command_trace = Some(RwLock::new(VecDeque::with_capacity(COMMAND_TRACE_LEN)));
+        self
+    }
+
+    /// Returns the summaries of the most recently handled commands, oldest first. Empty if the
+    /// command trace is not enabled.
+    pub fn recent_commands(&self) -> Vec<CommandSummary> {
+        if let Some(command_trace) = &self.command_trace {
+            command_trace
+                .read()
+                .unwrap_or_else(|err| err.into_inner())
+                .iter()
+                .cloned()
+                .collect()
+        } else {
+            vec![]
+        }
+    }
+
+    /// Returns a receiver of our new name, notified once the next relocation completes.
+    pub async fn wait_for_relocation(&self) -> oneshot::Receiver<XorName> {
+        let (tx, rx) = oneshot::channel();
+        self.relocation_waiters.lock().await.push(tx);
+        rx
+    }
+
     /// Send provided Event to the user which shall receive it through the EventStream
     pub async fn send_event(&self, event: Event) {
         self.state.lock().await.send_event(event)
@@ -59,6 +270,8 @@ impl Stage {
 
     /// Handles a single command.
     pub async fn handle_command(&self, command: Command) -> Result<Vec<Command>> {
+        self.trace_command(&command);
+
         // Create a tracing span containing info about the current node. This is very useful when
         // analyzing logs produced by running multiple nodes within the same process, for example
         // from integration tests.
@@ -73,7 +286,7 @@ impl Stage {
             )
         };
 
-        async {
+        let result = async {
             trace!(?command);
 
             self.try_handle_command(command).await.map_err(|error| {
@@ -82,7 +295,65 @@ impl Stage {
             })
         }
         .instrument(span)
-        .await
+        .await;
+
+        self.update_pinned_peers().await;
+
+        result
+    }
+
+    // If keep-alives are enabled, keep the connections to the other elders of our section alive.
+    // If `drop_connections_on_demotion` is set, stop doing so once we are demoted, until we are
+    // an elder again. Does nothing unless our elders changed since the last update.
+    async fn update_pinned_peers(&self) {
+        if !self.comm.is_keepalive_enabled() {
+            return;
+        }
+
+        let state = self.state.lock().await;
+        let key = state.section().proven_elders_info().proof.public_key;
+        let elder = state.is_elder();
+        let mut pinned_elders = self.pinned_elders.lock().await;
+        let previous = *pinned_elders;
+        if previous.map(|previous| previous.key) == Some(key) {
+            return;
+        }
+
+        let demoted = previous.map_or(false, |previous| previous.elder) && !elder;
+        let released = self.drop_connections_on_demotion
+            && !elder
+            && (demoted || previous.map_or(false, |previous| previous.released));
+        *pinned_elders = Some(PinnedElders {
+            key,
+            elder,
+            released,
+        });
+
+        let our_name = state.node().name();
+        let elders: HashSet<_> = state
+            .section()
+            .elders_info()
+            .elders
+            .values()
+            .filter(|peer| *peer.name() != our_name)
+            .map(|peer| *peer.addr())
+            .collect();
+
+        if !released {
+            self.comm.set_pinned_peers(elders);
+            return;
+        }
+
+        if demoted {
+            // As an adult we still talk to the current elders, so only close the connections to
+            // the peers that are no longer elders.
+            let pinned_peers = self.comm.pinned_peers();
+            self.comm.set_pinned_peers(iter::empty());
+
+            for addr in pinned_peers.iter().filter(|addr| !elders.contains(addr)) {
+                self.comm.disconnect(addr);
+            }
+        }
     }
 
     // Terminate this routing instance - cancel all scheduled timers including any future ones,
@@ -92,6 +363,46 @@ impl Stage {
         self.comm.terminate()
     }
 
+    // Same as `terminate` but if the drop grace period is non-zero, closes the connections only
+    // once all the in-flight commands complete or the grace period elapses, whichever comes first.
+    // This is best-effort only: it happens in a detached task which is not awaited by anyone, so
+    // it is cut short if the runtime shuts down in the meantime.
+    pub fn terminate_gracefully(self: Arc<Self>) {
+        if self.drop_grace_period == Duration::from_secs(0) {
+            return self.terminate();
+        }
+
+        let handle = if let Ok(handle) = Handle::try_current() {
+            handle
+        } else {
+            return self.terminate();
+        };
+
+        // Cancel the timers right away so they don't hold up the flush.
+        let _ = self.cancel_timer_tx.broadcast(true);
+
+        let _ = handle.spawn(async move {
+            if time::timeout(self.drop_grace_period, self.flush())
+                .await
+                .is_err()
+            {
+                debug!(
+                    "Terminating with {} commands still in progress",
+                    self.commands_in_flight.load(Ordering::Acquire)
+                );
+            }
+
+            self.comm.terminate()
+        });
+    }
+
+    // Waits until all the spawned commands complete.
+    async fn flush(&self) {
+        while self.commands_in_flight.load(Ordering::Acquire) > 0 {
+            time::delay_for(FLUSH_POLL_INTERVAL).await
+        }
+    }
+
     async fn try_handle_command(&self, command: Command) -> Result<Vec<Command>> {
         match command {
             Command::HandleMessage { sender, message } => {
@@ -147,8 +458,45 @@ impl Stage {
                 self.send_message(&recipients, delivery_group_size, message)
                     .await
             }
+            Command::SendEncryptedToClient {
+                recipient,
+                client_key,
+                message,
+            } => {
+                let msg_bytes = WireMsg::serialize_client_msg(&message)?;
+                let encrypted = crypto::encrypt_payload(&client_key, &msg_bytes)?;
+                self.send_to_clients(slice::from_ref(&recipient), encrypted)
+                    .await;
+                Ok(vec![])
+            }
             Command::SendUserMessage { src, dst, content } => {
-                self.state.lock().await.send_user_message(src, dst, content)
+                let content = self.intercept_outgoing(&src, &dst, content)?;
+                let mut state = self.state.lock().await;
+                self.check_rate_limit(&state, &dst)?;
+                state.send_user_message(src, dst, content)
+            }
+            Command::SendToAnyElder { content } => {
+                let name = self.state.lock().await.pick_any_elder()?;
+                self.send_to_name(name, content).await
+            }
+            Command::SendToName { name, content } => self.send_to_name(name, content).await,
+            Command::RefreshNeighbour(prefix) => self.state.lock().await.refresh_neighbour(&prefix),
+            Command::SendToward {
+                target,
+                fanout,
+                content,
+            } => {
+                let mut state = self.state.lock().await;
+                let src = SrcLocation::Node(state.node().name());
+                let mut commands = vec![];
+
+                for dst in state.toward_destinations(&target, fanout) {
+                    let content = self.intercept_outgoing(&src, &dst, content.clone())?;
+                    self.check_rate_limit(&state, &dst)?;
+                    commands.extend(state.send_user_message(src, dst, content)?);
+                }
+
+                Ok(commands)
             }
             Command::ScheduleTimeout { duration, token } => Ok(self
                 .handle_schedule_timeout(duration, token)
@@ -166,13 +514,194 @@ impl Stage {
             Command::SetJoinsAllowed(joins_allowed) => {
                 self.state.lock().await.set_joins_allowed(joins_allowed)
             }
+            Command::RequestDemotion => self.state.lock().await.request_demotion(),
+            Command::CancelDemotion => self.state.lock().await.cancel_demotion(),
+            Command::RequestKeyRotation => self.state.lock().await.request_key_rotation(),
+            Command::RequestRelocationDeferral(duration) => self
+                .state
+                .lock()
+                .await
+                .request_relocation_deferral(duration),
+        }
+    }
+
+    // Records the command in the debug command trace, if enabled.
+    fn trace_command(&self, command: &Command) {
+        let command_trace = if let Some(command_trace) = &self.command_trace {
+            command_trace
+        } else {
+            return;
+        };
+
+        let mut command_trace = command_trace.write().unwrap_or_else(|err| err.into_inner());
+
+        while command_trace.len() >= COMMAND_TRACE_LEN {
+            let _ = command_trace.pop_front();
+        }
+
+        command_trace.push_back(command.summary());
+    }
+
+    /// Sends a user message like `Command::SendUserMessage` does, but it can be relayed at most
+    /// `hop_limit` times on its way to `dst`.
+    pub async fn send_user_message_with_hop_limit(
+        self: Arc<Self>,
+        src: SrcLocation,
+        dst: DstLocation,
+        content: Bytes,
+        hop_limit: u8,
+    ) -> Result<()> {
+        let content = self.intercept_outgoing(&src, &dst, content)?;
+        let commands = {
+            let mut state = self.state.lock().await;
+            self.check_rate_limit(&state, &dst)?;
+            state.send_user_message_with_hop_limit(src, dst, content, hop_limit)?
+        };
+
+        for command in commands {
+            self.clone().spawn_handle_commands(command)
+        }
+
+        Ok(())
+    }
+
+    /// Sends a user message like `Command::SendUserMessage` does, but waits for the sends to
+    /// complete. If the message isn't delivered to its delivery group, resolves the destination
+    /// again, in case our knowledge of the network changed in the meantime (for example due to a
+    /// split), and retries, up to `policy.max_rounds` attempts in total.
+    pub async fn send_user_message_with_retry(
+        self: Arc<Self>,
+        src: SrcLocation,
+        dst: DstLocation,
+        content: Bytes,
+        policy: RetryPolicy,
+    ) -> Result<()> {
+        let content = self.intercept_outgoing(&src, &dst, content)?;
+        let mut backoff = policy.initial_backoff;
+        let mut round = 1;
+
+        loop {
+            let commands = {
+                let mut state = self.state.lock().await;
+                self.check_rate_limit(&state, &dst)?;
+                state.send_user_message(src, dst, content.clone())?
+            };
+
+            if self.clone().handle_commands_confirmed(commands).await {
+                return Ok(());
+            }
+
+            if round >= policy.max_rounds {
+                return Err(Error::FailedSend);
+            }
+
+            debug!(
+                "Sending user message {:?} -> {:?} failed (attempt {}/{}), retrying in {:?}",
+                src, dst, round, policy.max_rounds, backoff
+            );
+
+            time::delay_for(backoff).await;
+            backoff = backoff.checked_mul(2).unwrap_or(backoff);
+            round += 1;
+        }
+    }
+
+    /// Sends a user message from us to the elders of every section we know, including our own.
+    /// Returns the prefix of each section together with the commands sending the message to it,
+    /// or the error preparing them.
+    pub async fn announce_network_wide(
+        &self,
+        content: Bytes,
+    ) -> Vec<(Prefix, Result<Vec<Command>>)> {
+        let mut state = self.state.lock().await;
+        let src = SrcLocation::Node(state.node().name());
+        let prefixes: Vec<_> = iter::once(*state.section().prefix())
+            .chain(state.network().prefixes().copied())
+            .collect();
+
+        prefixes
+            .into_iter()
+            .map(|prefix| {
+                let dst = DstLocation::Section(prefix.name());
+                let commands = self
+                    .intercept_outgoing(&src, &dst, content.clone())
+                    .and_then(|content| {
+                        self.check_rate_limit(&state, &dst)?;
+                        state.send_user_message(src, dst, content)
+                    });
+                (prefix, commands)
+            })
+            .collect()
+    }
+
+    // Handles the given commands, but unlike `handle_commands`, waits for the node message sends
+    // among them to complete. Returns whether all of them were delivered to their delivery
+    // groups.
+    async fn handle_commands_confirmed(self: Arc<Self>, commands: Vec<Command>) -> bool {
+        let report = self.handle_commands_reported(commands).await;
+        report.delivered == report.recipients
+    }
+
+    /// Handles the given commands, but unlike `handle_commands`, waits for the node message sends
+    /// among them to complete and reports how many of their recipients they were delivered to.
+    pub async fn handle_commands_reported(
+        self: Arc<Self>,
+        commands: Vec<Command>,
+    ) -> DeliveryReport {
+        let mut report = DeliveryReport::default();
+
+        for command in commands {
+            match command {
+                Command::SendMessage {
+                    recipients,
+                    delivery_group_size,
+                    message: message @ MessageType::NodeMessage(_),
+                } => {
+                    let group_size = delivery_group_size.min(recipients.len());
+                    report.recipients += group_size;
+
+                    let msg_bytes = match message.serialize() {
+                        Ok(msg_bytes) => msg_bytes,
+                        Err(error) => {
+                            error!("Failed to serialize message: {}", error);
+                            continue;
+                        }
+                    };
+
+                    let (result, failed_recipients) = self
+                        .comm
+                        .send(&recipients, delivery_group_size, msg_bytes)
+                        .await;
+                    report.delivered += match result {
+                        Ok(()) => group_size,
+                        // The send was aborted before trying any recipient.
+                        Err(_) if failed_recipients.is_empty() => 0,
+                        // Every recipient was tried before giving up.
+                        Err(_) => recipients.len() - failed_recipients.len(),
+                    };
+
+                    for addr in failed_recipients {
+                        report.failed.push(addr);
+                        self.clone()
+                            .spawn_handle_commands(Command::HandlePeerLost(addr))
+                    }
+                }
+                command => self.clone().spawn_handle_commands(command),
+            }
         }
+
+        report
     }
 
     // Note: this indirecton is needed. Trying to call `spawn(self.handle_commands(...))` directly
     // inside `handle_commands` causes compile error about type check cycle.
     fn spawn_handle_commands(self: Arc<Self>, command: Command) {
-        let _ = tokio::spawn(self.handle_commands(command));
+        let _ = self.commands_in_flight.fetch_add(1, Ordering::AcqRel);
+        let _ = tokio::spawn(async move {
+            let result = self.clone().handle_commands(command).await;
+            let _ = self.commands_in_flight.fetch_sub(1, Ordering::AcqRel);
+            result
+        });
     }
 
     async fn send_message(
@@ -193,16 +722,7 @@ impl Stage {
                 .map(Command::HandlePeerLost)
                 .collect(),
             MessageType::ClientMessage(_) => {
-                for recipient in recipients {
-                    if self
-                        .comm
-                        .send_on_existing_connection(recipient, msg_bytes.clone())
-                        .await
-                        .is_err()
-                    {
-                        self.send_event(Event::ClientLost(*recipient)).await;
-                    }
-                }
+                self.send_to_clients(recipients, msg_bytes).await;
                 vec![]
             }
             MessageType::InfrastructureQuery(_) => {
@@ -219,6 +739,21 @@ impl Stage {
         Ok(cmds)
     }
 
+    // Sends the serialized message to each of the given clients, over the connection the client
+    // opened to us. Clients that can't be reached are reported with `Event::ClientLost`.
+    async fn send_to_clients(&self, recipients: &[SocketAddr], msg_bytes: Bytes) {
+        for recipient in recipients {
+            if self
+                .comm
+                .send_on_existing_connection(recipient, msg_bytes.clone())
+                .await
+                .is_err()
+            {
+                self.send_event(Event::ClientLost(*recipient)).await;
+            }
+        }
+    }
+
     async fn handle_schedule_timeout(&self, duration: Duration, token: u64) -> Option<Command> {
         let mut cancel_rx = self.cancel_timer_rx.clone();
 
@@ -227,12 +762,72 @@ impl Stage {
             return None;
         }
 
+        let duration = jittered(duration, self.timer_jitter_percent, &mut rand::thread_rng());
+
         tokio::select! {
             _ = time::delay_for(duration) => Some(Command::HandleTimeout(token)),
             _ = cancel_rx.recv() => None,
         }
     }
 
+    // Sends a `UserMessage` directly to the node with the given name, after passing it through the
+    // interceptor and the rate limit.
+    async fn send_to_name(&self, name: XorName, content: Bytes) -> Result<Vec<Command>> {
+        let src = SrcLocation::Node(self.state.lock().await.node().name());
+        let dst = DstLocation::Node(name);
+        let content = self.intercept_outgoing(&src, &dst, content)?;
+        let state = self.state.lock().await;
+        self.check_rate_limit(&state, &dst)?;
+        state.send_to_name(&name, content)
+    }
+
+    // Returns `Error::RateLimited` if sending another user message toward the section `dst`
+    // belongs to exceeds the per-prefix rate limit. Messages toward a section we don't know are
+    // not limited.
+    pub(super) fn check_rate_limit(&self, state: &Approved, dst: &DstLocation) -> Result<()> {
+        let limiter = if let Some(limiter) = &self.prefix_rate_limiter {
+            limiter
+        } else {
+            return Ok(());
+        };
+
+        if let Some(prefix) = state.destination_prefix(dst) {
+            if !limiter.try_acquire(&prefix) {
+                debug!("Not sending user message to {:?}: rate limited", dst);
+                return Err(Error::RateLimited);
+            }
+        }
+
+        Ok(())
+    }
+
+    // Passes the outgoing user message through the interceptor, if any. Returns the content to
+    // send or the error the interceptor rejected the message with.
+    fn intercept_outgoing(
+        &self,
+        src: &SrcLocation,
+        dst: &DstLocation,
+        content: Bytes,
+    ) -> Result<Bytes> {
+        let interceptor = if let Some(interceptor) = &self.outgoing_interceptor {
+            interceptor
+        } else {
+            return Ok(content);
+        };
+
+        match interceptor.on_send(src, dst, &content) {
+            InterceptDecision::Allow => Ok(content),
+            InterceptDecision::Reject(error) => {
+                debug!(
+                    "Outgoing message {:?} -> {:?} rejected by the interceptor: {}",
+                    src, dst, error
+                );
+                Err(error)
+            }
+            InterceptDecision::Replace(new_content) => Ok(new_content),
+        }
+    }
+
     async fn handle_relocate(
         &self,
         bootstrap_addrs: Vec<SocketAddr>,
@@ -240,28 +835,72 @@ impl Stage {
         message_rx: mpsc::Receiver<(MessageType, SocketAddr)>,
     ) -> Result<Vec<Command>> {
         let node = self.state.lock().await.node().clone();
-        let previous_name = node.name();
 
         let (node, section, backlog) =
             bootstrap::relocate(node, &self.comm, message_rx, bootstrap_addrs, details).await?;
 
+        Ok(self.complete_relocation(node, section, backlog).await)
+    }
+
+    // Switches to the state of a member of the section we got relocated to.
+    pub(super) async fn complete_relocation(
+        &self,
+        node: Node,
+        section: Section,
+        backlog: Vec<(Message, SocketAddr)>,
+    ) -> Vec<Command> {
         let mut state = self.state.lock().await;
+        let previous_name = state.node().name();
+        let previous_age = state.node().age;
         let event_tx = state.event_tx.clone();
+        let settings = state.settings();
+        let membership_observers = state.membership_observers();
         let new_keypair = node.keypair.clone();
-        *state = Approved::new(node, section, None, event_tx);
+        *state = Approved::new(node, section, None, event_tx)
+            .with_settings(settings)
+            .with_membership_observers(membership_observers);
 
         state.send_event(Event::Relocated {
             previous_name,
             new_keypair,
         });
 
-        let commands = backlog
+        if state.node().age != previous_age {
+            state.send_event(Event::OurAgeChanged {
+                old: previous_age,
+                new: state.node().age,
+            });
+            state
+                .membership_observers()
+                .notify(MembershipEvent::AgeChanged {
+                    name: state.node().name(),
+                    old: previous_age,
+                    new: state.node().age,
+                });
+        }
+
+        let new_name = state.node().name();
+        for waiter in self.relocation_waiters.lock().await.drain(..) {
+            let _ = waiter.send(new_name);
+        }
+
+        backlog
             .into_iter()
             .map(|(message, sender)| Command::HandleMessage {
                 message,
                 sender: Some(sender),
             })
-            .collect();
-        Ok(commands)
+            .chain(state.schedule_neighbour_reconciliation())
+            .collect()
     }
 }
+
+// Returns `duration` randomly shifted by up to `percent` of it in either direction.
+pub(super) fn jittered<R: Rng>(duration: Duration, percent: u8, rng: &mut R) -> Duration {
+    if percent == 0 {
+        return duration;
+    }
+
+    let max_offset = f64::from(percent.min(100)) / 100.0;
+    duration.mul_f64(1.0 + rng.gen_range(-max_offset, max_offset))
+}
@@ -8,29 +8,33 @@
 
 use super::{
     approved::{RESOURCE_PROOF_DATA_SIZE, RESOURCE_PROOF_DIFFICULTY},
-    Approved, Comm, Command, Stage,
+    comm::ConnectionEvent,
+    handle_connection_events, stage, Approved, Comm, Command, EventSink, IncomingInspector,
+    InterceptDecision, OutgoingInterceptor, PresetMember, QueryHandler, RateLimit, RetryPolicy,
+    Routing, RoutingDigest, SectionOverride, SectionPreset, Stage, UnknownSectionPolicy,
 };
 use crate::{
-    consensus::{test_utils::*, Proven, Vote},
+    consensus::{test_utils::*, DkgKey, Proven, Quorum, Vote},
     crypto,
-    event::Event,
+    event::{DropReason, Event, MembershipEvent},
     location::{DstLocation, SrcLocation},
     majority,
     messages::{JoinRequest, Message, PlainMessage, ResourceProofResponse, Variant, VerifyStatus},
     network::Network,
     node::Node,
     peer::Peer,
-    relocation::{self, RelocateDetails, RelocatePayload, SignedRelocateDetails},
+    relocation::{self, AgePolicy, RelocateDetails, RelocatePayload, SignedRelocateDetails},
     section::{
         test_utils::*, EldersInfo, MemberInfo, PeerState, Section, SectionKeyShare,
-        SectionProofChain, MIN_AGE,
+        SectionProofChain, TrustStatus, MIN_AGE,
     },
-    Error, ELDER_SIZE,
+    Config, Error, ELDER_SIZE,
 };
 use anyhow::Result;
 use assert_matches::assert_matches;
 use bls_signature_aggregator::Proof;
 use bytes::Bytes;
+use futures::stream::StreamExt;
 use resource_proof::ResourceProof;
 use sn_messaging::{
     infrastructure::{GetSectionResponse, Query},
@@ -38,10 +42,13 @@ use sn_messaging::{
     MessageType,
 };
 use std::{
-    collections::{BTreeSet, HashSet},
+    cmp,
+    collections::{BTreeMap, BTreeSet, HashMap, HashSet},
     iter,
-    net::Ipv4Addr,
+    net::{Ipv4Addr, SocketAddr},
     ops::Deref,
+    sync::Arc,
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
 };
 use tokio::sync::mpsc;
 use xor_name::{Prefix, XorName};
@@ -236,6 +243,67 @@ async fn receive_join_request_with_resource_proof_response() -> Result<()> {
     Ok(())
 }
 
+#[tokio::test]
+async fn receive_join_request_with_name_of_existing_member() -> Result<()> {
+    let node = create_node();
+    let (event_tx, mut event_rx) = mpsc::unbounded_channel();
+    let state = Approved::first_node(node.clone(), event_tx)?;
+    let stage = Stage::new(state, create_comm()?);
+
+    // Same keypair, and so the same name, but a different node.
+    let new_node = Node::new(node.keypair.clone(), gen_addr());
+    let section_key = *stage.state.lock().await.section().chain().last_key();
+
+    let message = Message::single_src(
+        &new_node,
+        DstLocation::Direct,
+        Variant::JoinRequest(Box::new(JoinRequest {
+            section_key,
+            relocate_payload: None,
+            resource_proof_response: None,
+        })),
+        None,
+        None,
+    )?;
+    let mut commands = stage
+        .handle_command(Command::HandleMessage {
+            sender: Some(new_node.addr),
+            message,
+        })
+        .await?
+        .into_iter();
+
+    let (recipients, response_message) = assert_matches!(
+        commands.next(),
+        Some(Command::SendMessage {
+            recipients,
+            message: MessageType::NodeMessage(NodeMessage(message)),
+            ..
+        }) => (recipients, message)
+    );
+    assert_eq!(recipients, [new_node.addr]);
+    let response_message = Message::from_bytes(Bytes::from(response_message))?;
+    assert_matches!(response_message.variant(), Variant::JoinRejected);
+    assert!(commands.next().is_none());
+
+    let mut collisions = iter::from_fn(|| event_rx.try_recv().ok())
+        .filter(|event| matches!(event, Event::NameCollision { .. }));
+    assert_matches!(collisions.next(), Some(Event::NameCollision { name }) => {
+        assert_eq!(name, node.name());
+    });
+
+    // The existing member is kept.
+    let state = stage.state.lock().await;
+    let member_info = state
+        .section()
+        .members()
+        .get(&node.name())
+        .expect("member not found");
+    assert_eq!(*member_info.peer.addr(), node.addr);
+
+    Ok(())
+}
+
 #[tokio::test]
 async fn receive_join_request_from_relocated_node() -> Result<()> {
     let (elders_info, mut nodes) = create_elders_info();
@@ -1372,6 +1440,12 @@ async fn relocation(relocated_peer_role: RelocatedPeerRole) -> Result<()> {
         RelocatedPeerRole::NonElder => &non_elder_peer,
     };
 
+    let predicted_age = stage
+        .state
+        .lock()
+        .await
+        .predict_relocation_age(relocated_peer.name());
+
     let (vote, proof) = create_relocation_trigger(sk_set.secret_key(), relocated_peer.age())?;
     let commands = stage
         .handle_command(Command::HandleConsensus { vote, proof })
@@ -1410,6 +1484,7 @@ async fn relocation(relocated_peer_role: RelocatedPeerRole) -> Result<()> {
 
                 assert_eq!(details.pub_id, *relocated_peer.name());
                 assert_eq!(details.age, relocated_peer.age() + 1);
+                assert_eq!(Some(details.age), predicted_age);
             }
             RelocatedPeerRole::Elder => {
                 let promise = match &message.variant {
@@ -1581,9 +1656,11 @@ async fn handle_elders_update() -> Result<()> {
 
     assert_matches!(
         event_rx.try_recv(),
-        Ok(Event::EldersChanged { key, elders, .. }) => {
+        Ok(Event::EldersChanged { key, elders, added, removed, .. }) => {
             assert_eq!(key, pk1);
             assert_eq!(elders, elder_names1);
+            assert_eq!(added, iter::once(*promoted_peer.name()).collect());
+            assert_eq!(removed, iter::once(*demoted_peer.name()).collect());
         }
     );
 
@@ -1728,6 +1805,4297 @@ async fn handle_demote_during_split() -> Result<()> {
     Ok(())
 }
 
+#[tokio::test]
+async fn send_to_any_elder_spreads_load() -> Result<()> {
+    let (event_tx, _) = mpsc::unbounded_channel();
+    let (elders_info, mut nodes) = gen_elders_info(Prefix::default(), ELDER_SIZE);
+    let sk_set = SecretKeySet::random();
+    let (section, section_key_share) = create_section(&sk_set, &elders_info)?;
+    let node = nodes.remove(0);
+    let node_addr = node.addr;
+    let state = Approved::new(node, section, Some(section_key_share), event_tx);
+    let stage = Stage::new(state, create_comm()?);
+
+    let sends = 100 * ELDER_SIZE;
+    let mut counts: HashMap<SocketAddr, usize> = HashMap::new();
+
+    for _ in 0..sends {
+        let commands = stage
+            .handle_command(Command::SendToAnyElder {
+                content: Bytes::from_static(b"hello"),
+            })
+            .await?;
+
+        let addr = match &commands[..] {
+            [Command::SendMessage { recipients, .. }] => recipients[0],
+            [Command::HandleMessage { .. }] => node_addr,
+            _ => panic!("unexpected commands: {:?}", commands),
+        };
+        *counts.entry(addr).or_default() += 1;
+    }
+
+    // All elders have the same age, so each should get roughly an equal share.
+    assert_eq!(counts.len(), ELDER_SIZE);
+    for count in counts.values() {
+        assert!(
+            *count > sends / ELDER_SIZE / 2 && *count < sends / ELDER_SIZE * 3 / 2,
+            "uneven distribution: {:?}",
+            counts
+        );
+    }
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn proof_to_current_key() -> Result<()> {
+    let genesis_sk = bls::SecretKey::random();
+    let genesis_pk = genesis_sk.public_key();
+
+    let sk_set = SecretKeySet::random();
+    let pk = sk_set.secret_key().public_key();
+    let signature = genesis_sk.sign(&bincode::serialize(&pk)?);
+
+    let mut chain = SectionProofChain::new(genesis_pk);
+    assert!(chain.push(pk, signature));
+
+    let (elders_info, mut nodes) = create_elders_info();
+    let proven_elders_info = proven(sk_set.secret_key(), elders_info)?;
+    let section = Section::new(chain, proven_elders_info)?;
+
+    let node = nodes.remove(0);
+    let state = Approved::new(node, section, None, mpsc::unbounded_channel().0);
+    let routing = Routing {
+        stage: Arc::new(Stage::new(state, create_comm()?)),
+    };
+
+    let proof = routing.proof_to_current_key().await;
+    assert_eq!(proof.first_key(), &genesis_pk);
+    assert_eq!(proof.last_key(), &pk);
+    assert_eq!(
+        proof.check_trust(iter::once(&genesis_pk)),
+        TrustStatus::Trusted
+    );
+
+    let unrelated_pk = bls::SecretKey::random().public_key();
+    assert_eq!(
+        proof.check_trust(iter::once(&unrelated_pk)),
+        TrustStatus::Unknown
+    );
+
+    Ok(())
+}
+
+#[test]
+fn our_elder_index() -> Result<()> {
+    let sk_set = SecretKeySet::random();
+    let (elders_info, nodes) = create_elders_info();
+    let (section, _) = create_section(&sk_set, &elders_info)?;
+
+    let indices: BTreeSet<_> = nodes
+        .into_iter()
+        .map(|node| {
+            let name = node.name();
+            let state = Approved::new(node, section.clone(), None, mpsc::unbounded_channel().0);
+            let index = state.our_elder_index()?;
+            assert_eq!(elders_info.elders.keys().nth(index), Some(&name));
+            Ok(index)
+        })
+        .collect::<Result<_>>()?;
+    assert_eq!(indices, (0..ELDER_SIZE).collect());
+
+    let state = Approved::new(create_node(), section, None, mpsc::unbounded_channel().0);
+    assert_matches!(state.our_elder_index(), Err(Error::InvalidState));
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn signature_threshold() -> Result<()> {
+    let sk_set = SecretKeySet::random();
+    let (elders_info, mut nodes) = create_elders_info();
+    let (section, section_key_share) = create_section(&sk_set, &elders_info)?;
+
+    let node = nodes.remove(0);
+    let state = Approved::new(
+        node,
+        section.clone(),
+        Some(section_key_share),
+        mpsc::unbounded_channel().0,
+    );
+    let routing = Routing {
+        stage: Arc::new(Stage::new(state, create_comm()?)),
+    };
+
+    assert_eq!(routing.signature_threshold().await?, THRESHOLD);
+    assert_eq!(THRESHOLD, majority(elders_info.elders.len()) - 1);
+
+    // Adult has no key share and so no threshold.
+    let state = Approved::new(create_node(), section, None, mpsc::unbounded_channel().0);
+    let routing = Routing {
+        stage: Arc::new(Stage::new(state, create_comm()?)),
+    };
+
+    assert!(routing.signature_threshold().await.is_err());
+
+    Ok(())
+}
+
+#[test]
+fn timer_jitter() {
+    let mut rng = rand::thread_rng();
+    let base = Duration::from_secs(10);
+
+    assert_eq!(stage::jittered(base, 0, &mut rng), base);
+
+    // Timers with the same base interval end up firing at different times.
+    let durations: HashSet<_> = iter::repeat_with(|| stage::jittered(base, 20, &mut rng))
+        .take(100)
+        .collect();
+    assert!(durations.len() > 1);
+
+    for duration in durations {
+        assert!(duration >= Duration::from_secs(8));
+        assert!(duration <= Duration::from_secs(12));
+    }
+}
+
+#[test]
+fn is_destination() -> Result<()> {
+    let sk_set = SecretKeySet::random();
+    let prefix = Prefix::default().pushed(false);
+    let (elders_info, mut nodes) = gen_elders_info(prefix, ELDER_SIZE);
+    let (section, _) = create_section(&sk_set, &elders_info)?;
+
+    let node = nodes.remove(0);
+    let our_name = node.name();
+    let state = Approved::new(node, section, None, mpsc::unbounded_channel().0);
+
+    assert!(state.is_destination(&DstLocation::Node(our_name)));
+    assert!(!state.is_destination(&DstLocation::Node(nodes[0].name())));
+
+    assert!(state.is_destination(&DstLocation::Section(our_name)));
+    assert!(state.is_destination(&DstLocation::Section(prefix.substituted_in(rand::random()))));
+    assert!(!state.is_destination(&DstLocation::Section(
+        prefix.sibling().substituted_in(rand::random())
+    )));
+
+    assert!(state.is_destination(&DstLocation::Direct));
+
+    Ok(())
+}
+
+#[test]
+fn estimated_hops() -> Result<()> {
+    let sk_set = SecretKeySet::random();
+    let prefix = Prefix::default().pushed(false).pushed(false).pushed(false);
+    let (elders_info, mut nodes) = gen_elders_info(prefix, ELDER_SIZE);
+    let (section, _) = create_section(&sk_set, &elders_info)?;
+
+    let node = nodes.remove(0);
+    let our_name = node.name();
+    let state = Approved::new(node, section, None, mpsc::unbounded_channel().0);
+
+    let name_in = |prefix: Prefix| prefix.substituted_in(rand::random());
+
+    // Our own section.
+    assert_eq!(state.estimated_hops(&DstLocation::Node(our_name)), 0);
+    assert_eq!(
+        state.estimated_hops(&DstLocation::Section(name_in(prefix))),
+        0
+    );
+    assert_eq!(state.estimated_hops(&DstLocation::Node(name_in(prefix))), 1);
+    assert_eq!(state.estimated_hops(&DstLocation::Direct), 1);
+
+    // Progressively more distant sections.
+    let mut last_hops = 0;
+    for bit in (0..3u8).rev() {
+        let other = prefix.with_flipped_bit(bit);
+        let hops = state.estimated_hops(&DstLocation::Section(name_in(other)));
+        assert_eq!(hops, 3 - usize::from(bit));
+        assert!(hops > last_hops);
+        assert_eq!(
+            state.estimated_hops(&DstLocation::Node(name_in(other))),
+            hops + 1
+        );
+        last_hops = hops;
+    }
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn handle_dkg_outcome_raises_event() -> Result<()> {
+    let sk_set0 = SecretKeySet::random();
+    let (elders_info, mut nodes) = create_elders_info();
+    let (section, section_key_share) = create_section(&sk_set0, &elders_info)?;
+
+    let (event_tx, mut event_rx) = mpsc::unbounded_channel();
+    let node = nodes.remove(0);
+    let state = Approved::new(node, section, Some(section_key_share), event_tx);
+    let stage = Stage::new(state, create_comm()?);
+
+    let sk_set1 = SecretKeySet::random();
+    let _ = stage
+        .handle_command(Command::HandleDkgOutcome {
+            elders_info: elders_info.clone(),
+            outcome: create_section_key_share(&sk_set1, 0),
+        })
+        .await?;
+
+    assert_matches!(
+        event_rx.try_recv(),
+        Ok(Event::DkgComplete { session_id, public_key }) => {
+            assert_eq!(session_id, DkgKey::new(&elders_info));
+            assert_eq!(public_key, sk_set1.public_keys().public_key());
+        }
+    );
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn section_public_key_after_key_change() -> Result<()> {
+    let sk_set0 = SecretKeySet::random();
+    let pk0 = sk_set0.secret_key().public_key();
+    let (elders_info, mut nodes) = create_elders_info();
+    let (section, section_key_share) = create_section(&sk_set0, &elders_info)?;
+
+    let node = nodes.remove(0);
+    let state = Approved::new(
+        node,
+        section,
+        Some(section_key_share),
+        mpsc::unbounded_channel().0,
+    );
+    let routing = Routing {
+        stage: Arc::new(Stage::new(state, create_comm()?)),
+    };
+
+    assert_eq!(routing.section_public_key().await?, pk0);
+    assert_eq!(routing.our_history().await.last_key(), &pk0);
+
+    // Complete DKG and reach consensus on the new key.
+    let sk_set1 = SecretKeySet::random();
+    let pk1 = sk_set1.secret_key().public_key();
+
+    let _ = routing
+        .stage
+        .handle_command(Command::HandleDkgOutcome {
+            elders_info: elders_info.clone(),
+            outcome: create_section_key_share(&sk_set1, 0),
+        })
+        .await?;
+
+    // The new key is not used until it becomes part of our section chain.
+    assert_eq!(routing.section_public_key().await?, pk0);
+
+    let vote = Vote::OurElders(proven(sk_set1.secret_key(), elders_info)?);
+    let signature = sk_set0
+        .secret_key()
+        .sign(&bincode::serialize(&vote.as_signable())?);
+    let proof = Proof {
+        signature,
+        public_key: pk0,
+    };
+
+    let _ = routing
+        .stage
+        .handle_command(Command::HandleConsensus { vote, proof })
+        .await?;
+
+    assert_eq!(routing.section_public_key().await?, pk1);
+    assert_eq!(routing.our_history().await.last_key(), &pk1);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn has_current_key_share() -> Result<()> {
+    let sk_set0 = SecretKeySet::random();
+    let pk0 = sk_set0.secret_key().public_key();
+    let (elders_info, mut nodes) = create_elders_info();
+    let (section, section_key_share) = create_section(&sk_set0, &elders_info)?;
+
+    let state = Approved::new(
+        nodes.remove(0),
+        section,
+        Some(section_key_share),
+        mpsc::unbounded_channel().0,
+    );
+    let routing = Routing {
+        stage: Arc::new(Stage::new(state, create_comm()?)),
+    };
+    assert!(routing.has_current_key_share().await);
+
+    // Consensus on the new key is reached before our DKG completes.
+    let sk_set1 = SecretKeySet::random();
+    let vote = Vote::OurElders(proven(sk_set1.secret_key(), elders_info.clone())?);
+    let signature = sk_set0
+        .secret_key()
+        .sign(&bincode::serialize(&vote.as_signable())?);
+    let proof = Proof {
+        signature,
+        public_key: pk0,
+    };
+    let _ = routing
+        .stage
+        .handle_command(Command::HandleConsensus { vote, proof })
+        .await?;
+
+    // We still hold the share of the old key only.
+    assert!(!routing.has_current_key_share().await);
+    assert_eq!(routing.section_public_key().await?, pk0);
+
+    let _ = routing
+        .stage
+        .handle_command(Command::HandleDkgOutcome {
+            elders_info,
+            outcome: create_section_key_share(&sk_set1, 0),
+        })
+        .await?;
+
+    assert!(routing.has_current_key_share().await);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn elder_addresses() -> Result<()> {
+    let sk_set = SecretKeySet::random();
+    let (elders_info, mut nodes) = create_elders_info();
+    let (section, section_key_share) = create_section(&sk_set, &elders_info)?;
+
+    let state = Approved::new(
+        nodes.remove(0),
+        section,
+        Some(section_key_share),
+        mpsc::unbounded_channel().0,
+    );
+    let routing = Routing {
+        stage: Arc::new(Stage::new(state, create_comm()?)),
+    };
+
+    let expected: Vec<_> = routing
+        .our_elders()
+        .await
+        .iter()
+        .map(Peer::addr)
+        .copied()
+        .collect();
+    assert_eq!(expected.len(), ELDER_SIZE);
+    assert_eq!(routing.elder_addresses().await, expected);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn drop_connections_on_demotion() -> Result<()> {
+    for &drop_connections in &[false, true] {
+        let sk_set0 = SecretKeySet::random();
+        let pk0 = sk_set0.secret_key().public_key();
+        let (elders_info0, mut nodes) = create_elders_info();
+        let (section, section_key_share) = create_section(&sk_set0, &elders_info0)?;
+
+        let node = nodes.remove(0);
+        let our_name = node.name();
+        let state = Approved::new(
+            node,
+            section,
+            Some(section_key_share),
+            mpsc::unbounded_channel().0,
+        );
+        let comm = create_comm()?.with_keepalive(Duration::from_secs(10), 0);
+        let stage = Stage::new(state, comm).with_drop_connections_on_demotion(drop_connections);
+
+        let _ = stage
+            .handle_command(Command::SetJoinsAllowed(false))
+            .await?;
+        let other_elders: BTreeSet<_> = elders_info0
+            .peers()
+            .filter(|peer| *peer.name() != our_name)
+            .map(Peer::addr)
+            .copied()
+            .collect();
+        assert_eq!(
+            stage
+                .comm
+                .pinned_peers()
+                .into_iter()
+                .collect::<BTreeSet<_>>(),
+            other_elders
+        );
+
+        // The pinned peers are not recomputed while our elders stay the same.
+        stage.comm.set_pinned_peers(iter::empty());
+        let _ = stage.handle_command(Command::SetJoinsAllowed(true)).await?;
+        assert!(stage.comm.pinned_peers().is_empty());
+
+        // We are replaced as elder.
+        let new_elder = Node::new(crypto::gen_keypair(), gen_addr()).with_age(MIN_AGE + 1);
+        let elders_info1 = EldersInfo::new(
+            elders_info0
+                .peers()
+                .filter(|peer| *peer.name() != our_name)
+                .copied()
+                .chain(iter::once(new_elder.peer())),
+            elders_info0.prefix,
+        );
+        let sk_set1 = SecretKeySet::random();
+        let vote = Vote::OurElders(proven(sk_set1.secret_key(), elders_info1)?);
+        let signature = sk_set0
+            .secret_key()
+            .sign(&bincode::serialize(&vote.as_signable())?);
+        let proof = Proof {
+            signature,
+            public_key: pk0,
+        };
+        let _ = stage
+            .handle_command(Command::HandleConsensus { vote, proof })
+            .await?;
+        assert!(!stage.state.lock().await.is_elder());
+
+        if drop_connections {
+            assert!(stage.comm.pinned_peers().is_empty());
+        } else {
+            assert_eq!(stage.comm.pinned_peers().len(), ELDER_SIZE);
+        }
+    }
+
+    // Adults that were never elders keep the connections to the elders alive.
+    let (elders_info, _) = create_elders_info();
+    let (section, _) = create_section(&SecretKeySet::random(), &elders_info)?;
+    let state = Approved::new(create_node(), section, None, mpsc::unbounded_channel().0);
+    let comm = create_comm()?.with_keepalive(Duration::from_secs(10), 0);
+    let stage = Stage::new(state, comm).with_drop_connections_on_demotion(true);
+
+    let _ = stage.handle_command(Command::SetJoinsAllowed(false)).await;
+    assert_eq!(stage.comm.pinned_peers().len(), ELDER_SIZE);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn has_complete_key_set() -> Result<()> {
+    let sk_set0 = SecretKeySet::random();
+    let pk0 = sk_set0.secret_key().public_key();
+    let (elders_info, mut nodes) = create_elders_info();
+    let (section, section_key_share) = create_section(&sk_set0, &elders_info)?;
+
+    let state = Approved::new(
+        nodes.remove(0),
+        section,
+        Some(section_key_share),
+        mpsc::unbounded_channel().0,
+    );
+    let routing = Routing {
+        stage: Arc::new(Stage::new(state, create_comm()?)),
+    };
+    assert!(routing.has_complete_key_set().await);
+
+    // Our DKG completes before consensus on the new key is reached.
+    let sk_set1 = SecretKeySet::random();
+    let _ = routing
+        .stage
+        .handle_command(Command::HandleDkgOutcome {
+            elders_info: elders_info.clone(),
+            outcome: create_section_key_share(&sk_set1, 0),
+        })
+        .await?;
+
+    // The old key share is still current, but the rotation is underway.
+    assert!(routing.has_current_key_share().await);
+    assert!(!routing.has_complete_key_set().await);
+
+    let vote = Vote::OurElders(proven(sk_set1.secret_key(), elders_info)?);
+    let signature = sk_set0
+        .secret_key()
+        .sign(&bincode::serialize(&vote.as_signable())?);
+    let proof = Proof {
+        signature,
+        public_key: pk0,
+    };
+    let _ = routing
+        .stage
+        .handle_command(Command::HandleConsensus { vote, proof })
+        .await?;
+
+    assert!(routing.has_complete_key_set().await);
+    assert_eq!(
+        routing.section_public_key().await?,
+        sk_set1.secret_key().public_key()
+    );
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn has_complete_key_set_with_superseded_dkg_outcome() -> Result<()> {
+    let sk_set = SecretKeySet::random();
+    let (elders_info, mut nodes) = create_elders_info();
+    let (mut section, section_key_share) = create_section(&sk_set, &elders_info)?;
+
+    let adult = create_peer();
+    let _ = section.update_member(proven(sk_set.secret_key(), MemberInfo::joined(adult))?);
+
+    // Pick an elder to go offline other than us.
+    let remove_peer = *elders_info
+        .elders
+        .values()
+        .rev()
+        .next()
+        .expect("elders_info is empty");
+    let remove_member_info = section
+        .members()
+        .get(remove_peer.name())
+        .expect("member not found")
+        .leave()?;
+    let adult_member_info = section
+        .members()
+        .get(adult.name())
+        .expect("member not found")
+        .leave()?;
+
+    let state = Approved::new(
+        nodes.remove(0),
+        section,
+        Some(section_key_share),
+        mpsc::unbounded_channel().0,
+    );
+    let routing = Routing {
+        stage: Arc::new(Stage::new(state, create_comm()?)),
+    };
+
+    // The elder goes offline, so the adult is to replace it.
+    let vote = Vote::Offline(remove_member_info);
+    let proof = prove(sk_set.secret_key(), &vote.as_signable())?;
+    let _ = routing
+        .stage
+        .handle_command(Command::HandleConsensus { vote, proof })
+        .await?;
+
+    let new_elders_info = EldersInfo::new(
+        elders_info
+            .elders
+            .values()
+            .filter(|peer| peer.name() != remove_peer.name())
+            .copied()
+            .chain(iter::once(adult)),
+        Prefix::default(),
+    );
+    let _ = routing
+        .stage
+        .handle_command(Command::HandleDkgOutcome {
+            elders_info: new_elders_info,
+            outcome: create_section_key_share(&SecretKeySet::random(), 0),
+        })
+        .await?;
+    assert!(!routing.has_complete_key_set().await);
+
+    // The adult goes offline too before the new elders are agreed on, so the outcome of the DKG
+    // including it will never be used.
+    let vote = Vote::Offline(adult_member_info);
+    let proof = prove(sk_set.secret_key(), &vote.as_signable())?;
+    let _ = routing
+        .stage
+        .handle_command(Command::HandleConsensus { vote, proof })
+        .await?;
+
+    assert!(routing.has_current_key_share().await);
+    assert!(routing.has_complete_key_set().await);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn verify_identity_consistency() -> Result<()> {
+    let sk_set = SecretKeySet::random();
+    let (elders_info, mut nodes) = create_elders_info();
+    let (section, _) = create_section(&sk_set, &elders_info)?;
+    let node = nodes.remove(0);
+
+    let state = Approved::new(
+        node.clone(),
+        section.clone(),
+        None,
+        mpsc::unbounded_channel().0,
+    );
+    let routing = Routing {
+        stage: Arc::new(Stage::new(state, create_comm()?)),
+    };
+    assert!(routing.verify_identity_consistency().await);
+
+    // Our section knows the member at our address under a name not derived from our keypair.
+    let corrupted = Node::new(crypto::gen_keypair(), node.addr).with_age(node.age);
+    let state = Approved::new(corrupted, section, None, mpsc::unbounded_channel().0);
+    let routing = Routing {
+        stage: Arc::new(Stage::new(state, create_comm()?)),
+    };
+    assert!(!routing.verify_identity_consistency().await);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn estimated_promotion_time() -> Result<()> {
+    let sk_set = SecretKeySet::random();
+    let (elders_info, mut nodes) = create_elders_info();
+    let (mut section, section_key_share) = create_section(&sk_set, &elders_info)?;
+
+    // An adult as old as the elders, so ranked right after them, and us, younger still.
+    let adult = Peer::new(rand::random(), gen_addr(), MIN_AGE + 1);
+    let _ = section.update_member(proven(sk_set.secret_key(), MemberInfo::joined(adult))?);
+    let node = create_node();
+    let _ = section.update_member(proven(
+        sk_set.secret_key(),
+        MemberInfo::joined(node.peer()),
+    )?);
+
+    let mut state = Approved::new(node, section.clone(), None, mpsc::unbounded_channel().0);
+
+    // No churn observed yet.
+    assert_eq!(state.estimated_promotion_time(), None);
+
+    let now = Instant::now();
+    for secs in &[30, 20, 10, 0] {
+        state.record_churn(now - Duration::from_secs(*secs));
+    }
+
+    let routing = Routing {
+        stage: Arc::new(Stage::new(state, create_comm()?)),
+    };
+
+    // Two churns, ten seconds apart, to get past the adult and into the elders.
+    let estimate = routing
+        .estimated_promotion_time()
+        .await
+        .expect("no estimate");
+    assert!(estimate >= Duration::from_secs(15) && estimate <= Duration::from_secs(25));
+
+    // Elders are not promoted anymore.
+    let mut state = Approved::new(
+        nodes.remove(0),
+        section,
+        Some(section_key_share),
+        mpsc::unbounded_channel().0,
+    );
+    for secs in &[30, 20, 10, 0] {
+        state.record_churn(now - Duration::from_secs(*secs));
+    }
+    assert_eq!(state.estimated_promotion_time(), None);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn elders_at_version() -> Result<()> {
+    let sk_set0 = SecretKeySet::random();
+    let pk0 = sk_set0.secret_key().public_key();
+    let (elders_info0, mut nodes) = create_elders_info();
+    let (section, section_key_share) = create_section(&sk_set0, &elders_info0)?;
+
+    let state = Approved::new(
+        nodes.remove(0),
+        section,
+        Some(section_key_share),
+        mpsc::unbounded_channel().0,
+    );
+    let routing = Routing {
+        stage: Arc::new(Stage::new(state, create_comm()?)),
+    };
+
+    // One of the elders is replaced and the section key changes with it.
+    let new_elder = Node::new(crypto::gen_keypair(), gen_addr()).with_age(MIN_AGE + 1);
+    let elders_info1 = EldersInfo::new(
+        elders_info0
+            .elders
+            .values()
+            .take(ELDER_SIZE - 1)
+            .copied()
+            .chain(iter::once(new_elder.peer())),
+        elders_info0.prefix,
+    );
+    let sk_set1 = SecretKeySet::random();
+    let vote = Vote::OurElders(proven(sk_set1.secret_key(), elders_info1.clone())?);
+    let signature = sk_set0
+        .secret_key()
+        .sign(&bincode::serialize(&vote.as_signable())?);
+    let proof = Proof {
+        signature,
+        public_key: pk0,
+    };
+    let _ = routing
+        .stage
+        .handle_command(Command::HandleConsensus { vote, proof })
+        .await?;
+
+    assert_eq!(routing.our_history().await.last_key_index(), 1);
+    assert_eq!(routing.elders_at_version(0).await, Some(elders_info0));
+    assert_eq!(routing.elders_at_version(1).await, Some(elders_info1));
+    assert_eq!(routing.elders_at_version(2).await, None);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn refresh_neighbour() -> Result<()> {
+    let prefix0 = Prefix::default().pushed(false);
+    let prefix1 = Prefix::default().pushed(true);
+
+    let gen_elders_info_in = |prefix: Prefix| {
+        let nodes: Vec<_> = iter::repeat_with(|| {
+            let keypair = crypto::gen_keypair_within_range(&prefix.range_inclusive());
+            Node::new(keypair, gen_addr()).with_age(MIN_AGE + 1)
+        })
+        .take(ELDER_SIZE)
+        .collect();
+        let elders_info = EldersInfo::new(nodes.iter().map(Node::peer), prefix);
+        (elders_info, nodes)
+    };
+
+    let create_section_info_command = |sk: &bls::SecretKey, elders_info| -> Result<_> {
+        let vote = Vote::SectionInfo(elders_info);
+        let signature = sk.sign(&bincode::serialize(&vote.as_signable())?);
+        let proof = Proof {
+            signature,
+            public_key: sk.public_key(),
+        };
+        Ok(Command::HandleConsensus { vote, proof })
+    };
+
+    // Our section.
+    let sk_set0 = SecretKeySet::random();
+    let (elders_info0, mut nodes0) = gen_elders_info_in(prefix0);
+    let (section0, section_key_share0) = create_section(&sk_set0, &elders_info0)?;
+    let node0 = nodes0.remove(0);
+    let node0_addr = *node0.addr();
+    let state0 = Approved::new(
+        node0,
+        section0,
+        Some(section_key_share0),
+        mpsc::unbounded_channel().0,
+    );
+    let routing0 = Routing {
+        stage: Arc::new(Stage::new(state0, create_comm()?)),
+    };
+
+    // The neighbour section, after it changed its elders.
+    let sk_set1 = SecretKeySet::random();
+    let (elders_info1, mut nodes1) = gen_elders_info_in(prefix1);
+    let (section1, section_key_share1) = create_section(&sk_set1, &elders_info1)?;
+    let state1 = Approved::new(
+        nodes1.remove(0),
+        section1,
+        Some(section_key_share1),
+        mpsc::unbounded_channel().0,
+    );
+    let stage1 = Stage::new(state1, create_comm()?);
+    let command = create_section_info_command(sk_set1.secret_key(), elders_info0)?;
+    let _ = stage1.handle_command(command).await?;
+
+    // Refreshing an unknown neighbour fails.
+    let result = routing0
+        .stage
+        .handle_command(Command::RefreshNeighbour(prefix1))
+        .await;
+    assert_matches!(result, Err(Error::InvalidDstLocation));
+
+    // Our stale view of the neighbour, from before it changed its elders.
+    let (stale_elders_info1, _) = gen_elders_info_in(prefix1);
+    let command = create_section_info_command(sk_set0.secret_key(), stale_elders_info1.clone())?;
+    let _ = routing0.stage.handle_command(command).await?;
+    assert_eq!(
+        routing0.neighbour_sections().await,
+        vec![stale_elders_info1.clone()]
+    );
+
+    // Refresh sends the request to the elders we know of.
+    let commands = routing0
+        .stage
+        .handle_command(Command::RefreshNeighbour(prefix1))
+        .await?;
+    let (recipients, request) = match &commands[..] {
+        [Command::SendMessage {
+            recipients,
+            message: MessageType::NodeMessage(NodeMessage(msg_bytes)),
+            ..
+        }] => (
+            recipients.clone(),
+            Message::from_bytes(Bytes::from(msg_bytes.clone()))?,
+        ),
+        _ => panic!("unexpected commands: {:?}", commands),
+    };
+    let expected_recipients: Vec<_> = stale_elders_info1
+        .peers()
+        .map(Peer::addr)
+        .copied()
+        .collect();
+    assert_eq!(recipients, expected_recipients);
+    assert_matches!(request.variant(), Variant::NeighbourInfoRequest);
+
+    // The neighbour responds with its current info.
+    let commands = stage1
+        .handle_command(Command::HandleMessage {
+            sender: Some(node0_addr),
+            message: request,
+        })
+        .await?;
+    let mut response = None;
+    for command in commands {
+        let message = match command {
+            Command::SendMessage {
+                message: MessageType::NodeMessage(NodeMessage(msg_bytes)),
+                ..
+            } => Message::from_bytes(Bytes::from(msg_bytes))?,
+            _ => continue,
+        };
+
+        if let Variant::NeighbourInfo { elders_info, .. } = message.variant() {
+            response = Some(elders_info.clone());
+        }
+    }
+    let response = response.expect("NeighbourInfo not sent");
+    assert_eq!(response.value, elders_info1);
+
+    // Once we reach consensus on the response, our view is up to date.
+    let command = create_section_info_command(sk_set0.secret_key(), response.value)?;
+    let _ = routing0.stage.handle_command(command).await?;
+    assert_eq!(routing0.neighbour_sections().await, vec![elders_info1]);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn can_sign() -> Result<()> {
+    let sk_set = SecretKeySet::random();
+    let (elders_info, mut nodes) = create_elders_info();
+    let (section, section_key_share) = create_section(&sk_set, &elders_info)?;
+
+    // Just joined, no key share yet.
+    let state = Approved::new(
+        create_node(),
+        section.clone(),
+        None,
+        mpsc::unbounded_channel().0,
+    );
+    assert!(!state.can_sign());
+
+    // Key share of a different (e.g. stale) key.
+    let state = Approved::new(
+        nodes[1].clone(),
+        section.clone(),
+        Some(create_section_key_share(&SecretKeySet::random(), 1)),
+        mpsc::unbounded_channel().0,
+    );
+    assert!(!state.can_sign());
+
+    // Keyed elder.
+    let state = Approved::new(
+        nodes.remove(0),
+        section,
+        Some(section_key_share),
+        mpsc::unbounded_channel().0,
+    );
+    let routing = Routing {
+        stage: Arc::new(Stage::new(state, create_comm()?)),
+    };
+    assert!(routing.can_sign().await);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn unknown_section_policy_bounce() -> Result<()> {
+    // Bouncing means the message isn't handled (yet).
+    assert!(!handle_message_from_unknown_section(UnknownSectionPolicy::Bounce, true).await?);
+    assert!(!handle_message_from_unknown_section(UnknownSectionPolicy::Bounce, false).await?);
+    Ok(())
+}
+
+#[tokio::test]
+async fn unknown_section_policy_drop() -> Result<()> {
+    assert!(!handle_message_from_unknown_section(UnknownSectionPolicy::Drop, true).await?);
+    assert!(!handle_message_from_unknown_section(UnknownSectionPolicy::Drop, false).await?);
+    Ok(())
+}
+
+#[tokio::test]
+async fn unknown_section_policy_verify_then_accept() -> Result<()> {
+    let policy = UnknownSectionPolicy::VerifyThenAccept;
+    assert!(handle_message_from_unknown_section(policy, true).await?);
+    assert!(!handle_message_from_unknown_section(policy, false).await?);
+    Ok(())
+}
+
+#[tokio::test]
+async fn unknown_section_policy_accept_unverified() -> Result<()> {
+    let policy = UnknownSectionPolicy::AcceptUnverified;
+    assert!(handle_message_from_unknown_section(policy, true).await?);
+    assert!(handle_message_from_unknown_section(policy, false).await?);
+    Ok(())
+}
+
+#[tokio::test]
+async fn unknown_section_policy_ignores_non_user_messages() -> Result<()> {
+    let our_prefix = Prefix::default().pushed(false);
+    let their_prefix = Prefix::default().pushed(true);
+
+    let sk_set = SecretKeySet::random();
+    let (elders_info, _) = gen_elders_info(our_prefix, ELDER_SIZE);
+    let (section, _) = create_section(&sk_set, &elders_info)?;
+
+    let node = create_node();
+    let node_name = node.name();
+    let state = Approved::new(node, section, None, mpsc::unbounded_channel().0)
+        .with_unknown_section_policy(UnknownSectionPolicy::AcceptUnverified);
+    let stage = Stage::new(state, create_comm()?);
+
+    let their_sk = bls::SecretKey::random();
+    let message = PlainMessage {
+        src: their_prefix,
+        dst: DstLocation::Node(node_name),
+        dst_key: sk_set.secret_key().public_key(),
+        variant: Variant::RelocatePromise(relocation::RelocatePromise {
+            name: node_name,
+            destination: rand::random(),
+        }),
+    };
+    let signature = their_sk.sign(&bincode::serialize(&message.as_signable())?);
+    let message = Message::section_src(
+        message,
+        signature,
+        SectionProofChain::new(their_sk.public_key()),
+    )?;
+
+    let commands = stage
+        .handle_command(Command::HandleMessage {
+            message: message.clone(),
+            sender: Some(gen_addr()),
+        })
+        .await?;
+
+    // Not accepted, but bounced as untrusted.
+    assert_matches!(
+        &commands[..],
+        [Command::SendMessage {
+            message: MessageType::NodeMessage(NodeMessage(msg_bytes)),
+            ..
+        }] => {
+            let bounce = Message::from_bytes(Bytes::from(msg_bytes.clone()))?;
+            assert_matches!(
+                bounce.variant(),
+                Variant::BouncedUntrustedMessage(bounced) => assert_eq!(**bounced, message)
+            );
+        }
+    );
+
+    Ok(())
+}
+
+// Handle a user message from a section we don't know, using the given policy. Returns whether the
+// message was received.
+async fn handle_message_from_unknown_section(
+    policy: UnknownSectionPolicy,
+    valid: bool,
+) -> Result<bool> {
+    let our_prefix = Prefix::default().pushed(false);
+    let their_prefix = Prefix::default().pushed(true);
+
+    let sk_set = SecretKeySet::random();
+    let (elders_info, _) = gen_elders_info(our_prefix, ELDER_SIZE);
+    let (section, _) = create_section(&sk_set, &elders_info)?;
+
+    let node = create_node();
+    let node_name = node.name();
+    let (event_tx, mut event_rx) = mpsc::unbounded_channel();
+    let state = Approved::new(node, section, None, event_tx).with_unknown_section_policy(policy);
+    let stage = Stage::new(state, create_comm()?);
+
+    let their_sk = bls::SecretKey::random();
+    let their_pk = their_sk.public_key();
+
+    let message = PlainMessage {
+        src: their_prefix,
+        dst: DstLocation::Node(node_name),
+        dst_key: sk_set.secret_key().public_key(),
+        variant: Variant::UserMessage(Bytes::from_static(b"hello")),
+    };
+    // An invalid message is signed with a key that doesn't match its proof chain.
+    let signature = if valid {
+        their_sk.sign(&bincode::serialize(&message.as_signable())?)
+    } else {
+        bls::SecretKey::random().sign(&bincode::serialize(&message.as_signable())?)
+    };
+    let message = Message::section_src(message, signature, SectionProofChain::new(their_pk))?;
+
+    let _ = stage
+        .handle_command(Command::HandleMessage {
+            message,
+            sender: Some(gen_addr()),
+        })
+        .await;
+
+    let mut received = false;
+    while let Ok(event) = event_rx.try_recv() {
+        if let Event::MessageReceived { .. } = event {
+            received = true;
+        }
+    }
+
+    Ok(received)
+}
+
+#[derive(Debug)]
+struct TestInterceptor;
+
+impl OutgoingInterceptor for TestInterceptor {
+    fn on_send(&self, _: &SrcLocation, _: &DstLocation, content: &Bytes) -> InterceptDecision {
+        match &content[..] {
+            b"reject" => InterceptDecision::Reject(Error::InvalidMessage),
+            b"replace" => InterceptDecision::Replace(Bytes::from_static(b"replaced")),
+            _ => InterceptDecision::Allow,
+        }
+    }
+}
+
+#[tokio::test]
+async fn outgoing_interceptor() -> Result<()> {
+    let node = create_node();
+    let peer = node.peer();
+    let state = Approved::first_node(node, mpsc::unbounded_channel().0)?;
+    let stage = Stage::new(state, create_comm()?)
+        .with_outgoing_interceptor(Some(Arc::new(TestInterceptor)));
+
+    let send = |content: &'static [u8]| {
+        stage.handle_command(Command::SendUserMessage {
+            src: SrcLocation::Node(*peer.name()),
+            dst: DstLocation::Node(*peer.name()),
+            content: Bytes::from_static(content),
+        })
+    };
+
+    // Allow
+    let commands = send(b"hello").await?;
+    assert_matches!(&commands[..], [Command::HandleMessage { message, .. }] => {
+        assert_matches!(message.variant(), Variant::UserMessage(content) if content == &b"hello"[..]);
+    });
+
+    // Reject
+    assert_matches!(send(b"reject").await, Err(Error::InvalidMessage));
+
+    // Replace
+    let commands = send(b"replace").await?;
+    assert_matches!(&commands[..], [Command::HandleMessage { message, .. }] => {
+        assert_matches!(message.variant(), Variant::UserMessage(content) if content == &b"replaced"[..]);
+    });
+
+    // The direct sends go through the interceptor too.
+    let result = stage
+        .handle_command(Command::SendToName {
+            name: *peer.name(),
+            content: Bytes::from_static(b"reject"),
+        })
+        .await;
+    assert_matches!(result, Err(Error::InvalidMessage));
+
+    let commands = stage
+        .handle_command(Command::SendToAnyElder {
+            content: Bytes::from_static(b"replace"),
+        })
+        .await?;
+    assert_matches!(&commands[..], [Command::HandleMessage { message, .. }] => {
+        assert_matches!(message.variant(), Variant::UserMessage(content) if content == &b"replaced"[..]);
+    });
+
+    Ok(())
+}
+
+#[derive(Debug)]
+struct TestInspector;
+
+impl IncomingInspector for TestInspector {
+    fn on_receive(&self, _: &SrcLocation, content: &Bytes) -> bool {
+        &content[..] != b"drop"
+    }
+}
+
+#[tokio::test]
+async fn incoming_inspector() -> Result<()> {
+    let node = create_node();
+    let peer = node.peer();
+    let (event_tx, mut event_rx) = mpsc::unbounded_channel();
+    let state = Approved::first_node(node, event_tx)?
+        .with_incoming_inspector(Some(Arc::new(TestInspector)));
+    let stage = Stage::new(state, create_comm()?);
+
+    for content in &[&b"drop"[..], &b"keep"[..]] {
+        let commands = stage
+            .handle_command(Command::SendUserMessage {
+                src: SrcLocation::Node(*peer.name()),
+                dst: DstLocation::Node(*peer.name()),
+                content: Bytes::copy_from_slice(content),
+            })
+            .await?;
+        for command in commands {
+            let _ = stage.handle_command(command).await?;
+        }
+    }
+
+    let mut received = vec![];
+    while let Ok(event) = event_rx.try_recv() {
+        if let Event::MessageReceived { content, .. } = event {
+            received.push(content);
+        }
+    }
+
+    assert_eq!(received, vec![Bytes::from_static(b"keep")]);
+
+    Ok(())
+}
+
+#[derive(Debug, Default)]
+struct RecordingSink(std::sync::Mutex<Vec<String>>);
+
+impl EventSink for RecordingSink {
+    fn record(&self, event: &Event) {
+        self.0
+            .lock()
+            .unwrap_or_else(|err| err.into_inner())
+            .push(format!("{:?}", event))
+    }
+}
+
+#[tokio::test]
+async fn event_sink() -> Result<()> {
+    let node = create_node();
+    let peer = node.peer();
+    let sink = Arc::new(RecordingSink::default());
+    let (event_tx, mut event_rx) = mpsc::unbounded_channel();
+    let state = Approved::first_node(node, event_tx)?.with_event_sink(Some(sink.clone()));
+    let stage = Stage::new(state, create_comm()?);
+
+    for content in &[&b"one"[..], &b"two"[..]] {
+        let commands = stage
+            .handle_command(Command::SendUserMessage {
+                src: SrcLocation::Node(*peer.name()),
+                dst: DstLocation::Node(*peer.name()),
+                content: Bytes::copy_from_slice(content),
+            })
+            .await?;
+        for command in commands {
+            let _ = stage.handle_command(command).await?;
+        }
+    }
+
+    let mut received = vec![];
+    while let Ok(event) = event_rx.try_recv() {
+        received.push(format!("{:?}", event));
+    }
+
+    assert_eq!(received.len(), 2);
+    assert_eq!(
+        *sink.0.lock().unwrap_or_else(|err| err.into_inner()),
+        received
+    );
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn message_loop() -> Result<()> {
+    let sk_set = SecretKeySet::random();
+    let (elders_info, mut nodes) = create_elders_info();
+    let (mut section, section_key_share) = create_section(&sk_set, &elders_info)?;
+
+    let node = nodes.remove(0);
+
+    // A member that (mistakenly) has our address.
+    let impostor = Peer::new(rand::random(), node.addr, MIN_AGE + 1);
+    let member_info = proven(sk_set.secret_key(), MemberInfo::joined(impostor))?;
+    assert!(section.update_member(member_info));
+
+    let state = Approved::new(
+        node,
+        section,
+        Some(section_key_share),
+        mpsc::unbounded_channel().0,
+    );
+    let stage = Stage::new(state, create_comm()?);
+
+    let create_message = |dst| {
+        Message::single_src(
+            &create_node(),
+            dst,
+            Variant::UserMessage(Bytes::from_static(b"hello")),
+            None,
+            None,
+        )
+    };
+
+    // Relaying to ourselves is refused.
+    let message = create_message(DstLocation::Node(*impostor.name()))?;
+    let result = stage
+        .handle_command(Command::HandleMessage {
+            sender: Some(gen_addr()),
+            message,
+        })
+        .await;
+    assert_matches!(result, Err(Error::MessageLoop));
+
+    // A relayed message coming back to us from a peer we relayed it to is dropped.
+    let message = create_message(DstLocation::Node(rand::random()))?;
+    let commands = stage
+        .handle_command(Command::HandleMessage {
+            sender: Some(gen_addr()),
+            message: message.clone(),
+        })
+        .await?;
+    let recipients = assert_matches!(
+        &commands[..],
+        [Command::SendMessage { recipients, .. }] => recipients.clone()
+    );
+
+    // The same message from someone else is just a duplicate.
+    let commands = stage
+        .handle_command(Command::HandleMessage {
+            sender: Some(gen_addr()),
+            message: message.clone(),
+        })
+        .await?;
+    assert!(commands.is_empty());
+
+    let result = stage
+        .handle_command(Command::HandleMessage {
+            sender: Some(recipients[0]),
+            message,
+        })
+        .await;
+    assert_matches!(result, Err(Error::MessageLoop));
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn hop_limit() -> Result<()> {
+    let sk_set = SecretKeySet::random();
+    let (elders_info, mut nodes) = create_elders_info();
+    let (section, section_key_share) = create_section(&sk_set, &elders_info)?;
+
+    let mut sender = Approved::new(
+        nodes.remove(0),
+        section.clone(),
+        Some(section_key_share),
+        mpsc::unbounded_channel().0,
+    );
+    let (event_tx, mut event_rx) = mpsc::unbounded_channel();
+    let relay = Stage::new(
+        Approved::new(nodes.remove(0), section, None, event_tx),
+        create_comm()?,
+    );
+
+    // Destination not in our section, so the message has to be relayed further.
+    let src = SrcLocation::Node(sender.node().name());
+    let dst = DstLocation::Node(rand::random());
+    let commands =
+        sender.send_user_message_with_hop_limit(src, dst, Bytes::from_static(b"hello"), 1)?;
+
+    // Sending it took the only hop.
+    let message = assert_matches!(
+        &commands[..],
+        [Command::SendMessage { message: MessageType::NodeMessage(NodeMessage(message)), .. }] => {
+            Message::from_bytes(Bytes::from(message.clone()))?
+        }
+    );
+    assert_eq!(message.hop_limit(), 0);
+
+    // So the next node drops it instead of relaying it.
+    let commands = relay
+        .handle_command(Command::HandleMessage {
+            sender: Some(gen_addr()),
+            message,
+        })
+        .await?;
+    assert!(commands.is_empty());
+    assert_matches!(
+        event_rx.try_recv(),
+        Ok(Event::MessageDropped {
+            reason: DropReason::TtlExpired,
+            src: Some(SrcLocation::Node(name)),
+        }) => assert_eq!(name, sender.node().name())
+    );
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn request_demotion() -> Result<()> {
+    let (elders_info, mut nodes) = create_elders_info();
+    let sk_set = SecretKeySet::random();
+    let (mut section, section_key_share) = create_section(&sk_set, &elders_info)?;
+
+    let adult = create_peer();
+    let member_info = proven(sk_set.secret_key(), MemberInfo::joined(adult))?;
+    assert!(section.update_member(member_info));
+
+    // Adults can't request demotion.
+    let state = Approved::new(
+        create_node(),
+        section.clone(),
+        None,
+        mpsc::unbounded_channel().0,
+    );
+    let stage = Stage::new(state, create_comm()?);
+    let result = stage.handle_command(Command::RequestDemotion).await;
+    assert_matches!(result, Err(Error::InvalidState));
+
+    let node = nodes.remove(0);
+    let node_name = node.name();
+    let state = Approved::new(
+        node,
+        section,
+        Some(section_key_share),
+        mpsc::unbounded_channel().0,
+    );
+    let stage = Stage::new(state, create_comm()?);
+
+    // Requesting demotion votes for us to step down.
+    let commands = stage.handle_command(Command::RequestDemotion).await?;
+    let vote_sent = commands.iter().any(|command| match command {
+        Command::HandleVote { vote, .. } => *vote == Vote::StepDown(node_name),
+        _ => false,
+    });
+    assert!(vote_sent);
+
+    // Once the vote reaches consensus, a new elder set without us is proposed.
+    let vote = Vote::StepDown(node_name);
+    let proof = prove(sk_set.secret_key(), &vote.as_signable())?;
+    let commands = stage
+        .handle_command(Command::HandleConsensus { vote, proof })
+        .await?;
+
+    let mut dkg_start_sent = false;
+
+    for command in commands {
+        let message = match command {
+            Command::SendMessage {
+                message: MessageType::NodeMessage(NodeMessage(msg_bytes)),
+                ..
+            } => Message::from_bytes(Bytes::from(msg_bytes))?,
+            _ => continue,
+        };
+
+        let message = match message.variant() {
+            Variant::Vote {
+                content: Vote::SendMessage { message, .. },
+                ..
+            } => message,
+            _ => continue,
+        };
+
+        let new_elders_info = match &message.variant {
+            Variant::DKGStart { elders_info, .. } => elders_info,
+            _ => continue,
+        };
+
+        assert!(!new_elders_info.elders.contains_key(&node_name));
+        assert!(new_elders_info.elders.contains_key(adult.name()));
+        assert_eq!(new_elders_info.elders.len(), ELDER_SIZE);
+
+        dkg_start_sent = true;
+    }
+
+    assert!(dkg_start_sent);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn cancel_demotion() -> Result<()> {
+    let (elders_info, mut nodes) = create_elders_info();
+    let sk_set = SecretKeySet::random();
+    let (mut section, section_key_share) = create_section(&sk_set, &elders_info)?;
+
+    let adult_node = create_node();
+    let member_info = proven(sk_set.secret_key(), MemberInfo::joined(adult_node.peer()))?;
+    assert!(section.update_member(member_info));
+
+    let stepping_down_node = nodes.remove(1);
+    let stepping_down_name = stepping_down_node.name();
+
+    let state = Approved::new(
+        nodes.remove(0),
+        section.clone(),
+        Some(section_key_share),
+        mpsc::unbounded_channel().0,
+    );
+    let stage = Stage::new(state, create_comm()?);
+
+    let cancel_voted = |commands: Vec<Command>| {
+        commands.iter().any(|command| {
+            matches!(
+                command,
+                Command::HandleVote {
+                    vote: Vote::CancelStepDown(name),
+                    ..
+                } if *name == stepping_down_name
+            )
+        })
+    };
+
+    // Requests from members not stepping down are ignored.
+    let request = create_cancel_demotion_request(adult_node.clone(), section.clone()).await?;
+    assert_matches!(request.variant(), Variant::CancelDemotion);
+    let commands = stage
+        .handle_command(Command::HandleMessage {
+            sender: Some(adult_node.addr),
+            message: request,
+        })
+        .await?;
+    assert!(commands
+        .iter()
+        .all(|command| !matches!(command, Command::HandleVote { .. })));
+
+    let vote = Vote::StepDown(stepping_down_name);
+    let proof = prove(sk_set.secret_key(), &vote.as_signable())?;
+    let _ = stage
+        .handle_command(Command::HandleConsensus { vote, proof })
+        .await?;
+
+    // Once stepping down, the request is voted on.
+    let stepping_down_addr = stepping_down_node.addr;
+    let request = create_cancel_demotion_request(stepping_down_node, section).await?;
+    let commands = stage
+        .handle_command(Command::HandleMessage {
+            sender: Some(stepping_down_addr),
+            message: request,
+        })
+        .await?;
+    assert!(cancel_voted(commands));
+
+    // Once the cancellation reaches consensus, the current elders are kept.
+    let vote = Vote::CancelStepDown(stepping_down_name);
+    let proof = prove(sk_set.secret_key(), &vote.as_signable())?;
+    let commands = stage
+        .handle_command(Command::HandleConsensus { vote, proof })
+        .await?;
+
+    for command in commands {
+        if let Command::SendMessage {
+            message: MessageType::NodeMessage(NodeMessage(msg_bytes)),
+            ..
+        } = command
+        {
+            let message = Message::from_bytes(Bytes::from(msg_bytes))?;
+            assert!(!matches!(message.variant(), Variant::DKGStart { .. }));
+        }
+    }
+
+    Ok(())
+}
+
+async fn create_cancel_demotion_request(node: Node, section: Section) -> Result<Message> {
+    let state = Approved::new(node, section, None, mpsc::unbounded_channel().0);
+    let stage = Stage::new(state, create_comm()?);
+    let commands = stage.handle_command(Command::CancelDemotion).await?;
+
+    match &commands[..] {
+        [Command::SendMessage {
+            message: MessageType::NodeMessage(NodeMessage(msg_bytes)),
+            ..
+        }] => Ok(Message::from_bytes(Bytes::from(msg_bytes.clone()))?),
+        _ => panic!("unexpected commands: {:?}", commands),
+    }
+}
+
+#[tokio::test]
+async fn public_key_bytes() -> Result<()> {
+    let node = create_node();
+    let name = node.name();
+    let state = Approved::first_node(node, mpsc::unbounded_channel().0)?;
+    let routing = Routing {
+        stage: Arc::new(Stage::new(state, create_comm()?)),
+    };
+
+    let bytes = routing.public_key_bytes().await;
+    assert_eq!(bytes, routing.public_key().await.to_bytes());
+
+    let public_key = ed25519_dalek::PublicKey::from_bytes(&bytes)?;
+    assert_eq!(crypto::name(&public_key), name);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn closest_neighbour() -> Result<()> {
+    let p0 = Prefix::default().pushed(false);
+    let p1 = Prefix::default().pushed(true);
+    let p00 = p0.pushed(false);
+    let p01 = p0.pushed(true);
+
+    let sk_set = SecretKeySet::random();
+    let (elders_info, mut nodes) = gen_elders_info(p00, ELDER_SIZE);
+    let (section, section_key_share) = create_section(&sk_set, &elders_info)?;
+    let state = Approved::new(
+        nodes.remove(0),
+        section,
+        Some(section_key_share),
+        mpsc::unbounded_channel().0,
+    );
+    let routing = Routing {
+        stage: Arc::new(Stage::new(state, create_comm()?)),
+    };
+
+    assert!(routing.closest_neighbour(&rand::random()).await.is_none());
+
+    for prefix in &[p01, p1] {
+        let (elders_info, _) = gen_elders_info(*prefix, ELDER_SIZE);
+        let vote = Vote::SectionInfo(elders_info);
+        let proof = prove(&bls::SecretKey::random(), &vote.as_signable())?;
+        let _ = routing
+            .stage
+            .handle_command(Command::HandleConsensus { vote, proof })
+            .await?;
+    }
+
+    // Our own section is never returned, even for names that belong to it.
+    for (target_prefix, expected_prefix) in &[
+        (p00, p01),
+        (p01, p01),
+        (p1.pushed(false), p1),
+        (p1.pushed(true), p1),
+    ] {
+        let name = target_prefix.substituted_in(rand::random());
+        let actual = routing.closest_neighbour(&name).await;
+        assert_eq!(actual.map(|info| info.prefix), Some(*expected_prefix));
+    }
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn neighbour_keys() -> Result<()> {
+    let p0 = Prefix::default().pushed(false);
+    let p1 = Prefix::default().pushed(true);
+    let p00 = p0.pushed(false);
+    let p01 = p0.pushed(true);
+
+    let sk_set = SecretKeySet::random();
+    let (elders_info, mut nodes) = gen_elders_info(p00, ELDER_SIZE);
+    let (section, section_key_share) = create_section(&sk_set, &elders_info)?;
+    let state = Approved::new(
+        nodes.remove(0),
+        section,
+        Some(section_key_share),
+        mpsc::unbounded_channel().0,
+    );
+    let routing = Routing {
+        stage: Arc::new(Stage::new(state, create_comm()?)),
+    };
+
+    assert!(routing.neighbour_keys().await.is_empty());
+
+    // The neighbours' elders info is agreed on, and so signed, by our section.
+    for prefix in &[p01, p1] {
+        let (elders_info, _) = gen_elders_info(*prefix, ELDER_SIZE);
+        let vote = Vote::SectionInfo(elders_info);
+        let proof = prove(sk_set.secret_key(), &vote.as_signable())?;
+        let _ = routing
+            .stage
+            .handle_command(Command::HandleConsensus { vote, proof })
+            .await?;
+    }
+
+    // We know the neighbours now, but not their keys.
+    assert!(routing.neighbour_keys().await.is_empty());
+
+    let mut expected = HashMap::new();
+    for prefix in &[p01, p1] {
+        let key = bls::SecretKey::random().public_key();
+        let vote = Vote::TheirKey {
+            prefix: *prefix,
+            key,
+        };
+        let proof = prove(sk_set.secret_key(), &vote.as_signable())?;
+        let _ = routing
+            .stage
+            .handle_command(Command::HandleConsensus { vote, proof })
+            .await?;
+
+        let _ = expected.insert(*prefix, key);
+    }
+
+    assert_eq!(routing.neighbour_keys().await, expected);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn drop_grace_period() -> Result<()> {
+    let (peer_tx, mut peer_rx) = mpsc::channel(1);
+    let peer_comm = Comm::new(
+        qp2p::Config {
+            ip: Some(Ipv4Addr::LOCALHOST.into()),
+            ..Default::default()
+        },
+        peer_tx,
+    )?;
+    let peer_node = Node::new(
+        crypto::gen_keypair(),
+        peer_comm.our_connection_info().await?,
+    );
+
+    let node = create_node();
+    let node_name = node.name();
+    let elders_info = EldersInfo::new(vec![node.peer(), peer_node.peer()], Prefix::default());
+    let sk_set = SecretKeySet::random();
+    let (section, section_key_share) = create_section(&sk_set, &elders_info)?;
+    let state = Approved::new(
+        node,
+        section,
+        Some(section_key_share),
+        mpsc::unbounded_channel().0,
+    );
+    let stage = Stage::new(state, create_comm()?).with_drop_grace_period(Duration::from_secs(5));
+    let routing = Routing {
+        stage: Arc::new(stage),
+    };
+
+    routing
+        .send_message(
+            SrcLocation::Node(node_name),
+            DstLocation::Node(peer_node.name()),
+            Bytes::from_static(b"hello"),
+        )
+        .await?;
+    drop(routing);
+
+    // The message sent just before the drop still arrives.
+    let received = tokio::time::timeout(Duration::from_secs(5), async {
+        while let Some(event) = peer_rx.recv().await {
+            if let ConnectionEvent::Received(_) = event {
+                return true;
+            }
+        }
+
+        false
+    })
+    .await?;
+    assert!(received);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn relocation_pending() -> Result<()> {
+    let (elders_info, mut nodes) = create_elders_info();
+    let sk_set = SecretKeySet::random();
+    let (section, section_key_share) = create_section(&sk_set, &elders_info)?;
+
+    let relocated_peer = *elders_info
+        .elders
+        .values()
+        .rev()
+        .next()
+        .expect("elders_info is empty");
+    let relocated_member_info = section
+        .members()
+        .get(relocated_peer.name())
+        .expect("member not found")
+        .relocate(rand::random());
+
+    let state = Approved::new(
+        nodes.remove(0),
+        section,
+        Some(section_key_share),
+        mpsc::unbounded_channel().0,
+    );
+    let routing = Routing {
+        stage: Arc::new(Stage::new(state, create_comm()?)),
+    };
+    assert!(routing.relocation_pending().await.is_empty());
+
+    // Agree on the relocation, but never complete the elder change it needs to be carried out.
+    let vote = Vote::Offline(relocated_member_info);
+    let proof = prove(sk_set.secret_key(), &vote.as_signable())?;
+    let _ = routing
+        .stage
+        .handle_command(Command::HandleConsensus { vote, proof })
+        .await?;
+
+    let pending = routing.relocation_pending().await;
+    assert_matches!(&pending[..], [(name, _)] => assert_eq!(name, relocated_peer.name()));
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn sign_as_section() -> Result<()> {
+    let (elders_info, nodes) = create_elders_info();
+    let sk_set = SecretKeySet::random();
+    let (section, _) = create_section(&sk_set, &elders_info)?;
+
+    let mut routings = vec![];
+    for (index, node) in nodes.into_iter().enumerate() {
+        let state = Approved::new(
+            node,
+            section.clone(),
+            Some(create_section_key_share(&sk_set, index)),
+            mpsc::unbounded_channel().0,
+        );
+        routings.push(Routing {
+            stage: Arc::new(Stage::new(state, create_comm()?)),
+        });
+    }
+
+    let payload = b"hello";
+    let mut shares = BTreeMap::new();
+    let mut signature = None;
+
+    for routing in &routings {
+        let share = routing.sign_as_section(payload).await?;
+        let _ = shares.insert(routing.our_index().await?, share);
+
+        let result = routings[0]
+            .try_complete_section_signature(payload, &shares)
+            .await?;
+        if shares.len() <= sk_set.threshold() {
+            assert!(result.is_none());
+        } else {
+            signature = result;
+        }
+    }
+
+    let signature = signature.expect("signature not completed");
+    assert!(sk_set
+        .public_keys()
+        .public_key()
+        .verify(&signature, payload));
+
+    // Shares of a different payload are not accepted.
+    assert!(routings[0]
+        .try_complete_section_signature(b"bye", &shares)
+        .await?
+        .is_none());
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn custom_age_policy() -> Result<()> {
+    // Ages grow by one until `cap` and stay there.
+    #[derive(Debug)]
+    struct CappedAgePolicy {
+        cap: u8,
+    }
+
+    impl AgePolicy for CappedAgePolicy {
+        fn next_age(&self, current: u8, _churn_count: u64) -> u8 {
+            cmp::min(current.saturating_add(1), cmp::max(current, self.cap))
+        }
+    }
+
+    let cap = MIN_AGE + 1;
+    let sk_set = SecretKeySet::random();
+    let (elders_info, mut nodes) = create_elders_info();
+    let (mut section, section_key_share) = create_section(&sk_set, &elders_info)?;
+
+    let young_peer = create_peer();
+    let old_peer = create_peer().with_age(cap);
+    for peer in &[young_peer, old_peer] {
+        let member_info = proven(sk_set.secret_key(), MemberInfo::joined(*peer))?;
+        assert!(section.update_member(member_info));
+    }
+
+    let state = Approved::new(
+        nodes.remove(0),
+        section,
+        Some(section_key_share),
+        mpsc::unbounded_channel().0,
+    )
+    .with_age_policy(Arc::new(CappedAgePolicy { cap }));
+    let stage = Stage::new(state, create_comm()?);
+
+    for (peer, expected_age) in &[(young_peer, MIN_AGE + 1), (old_peer, cap)] {
+        let predicted_age = stage.state.lock().await.predict_relocation_age(peer.name());
+        assert_eq!(predicted_age, Some(*expected_age));
+
+        let (vote, proof) = create_relocation_trigger(sk_set.secret_key(), peer.age())?;
+        let commands = stage
+            .handle_command(Command::HandleConsensus { vote, proof })
+            .await?;
+
+        let details = commands
+            .into_iter()
+            .filter_map(|command| match command {
+                Command::SendMessage {
+                    recipients,
+                    message: MessageType::NodeMessage(NodeMessage(msg_bytes)),
+                    ..
+                } if recipients == [*peer.addr()] => {
+                    Message::from_bytes(Bytes::from(msg_bytes)).ok()
+                }
+                _ => None,
+            })
+            .find_map(|message| match message.variant() {
+                Variant::Vote {
+                    content: Vote::SendMessage { message, .. },
+                    ..
+                } => match &message.variant {
+                    Variant::Relocate(details) => Some(details.clone()),
+                    _ => None,
+                },
+                _ => None,
+            })
+            .expect("Relocate not sent");
+
+        assert_eq!(details.pub_id, *peer.name());
+        assert_eq!(details.age, *expected_age);
+    }
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn debug_command_trace() -> Result<()> {
+    let node = create_node();
+    let node_name = node.name();
+    let state = Approved::first_node(node, mpsc::unbounded_channel().0)?;
+    let routing = Routing {
+        stage: Arc::new(Stage::new(state, create_comm()?).with_command_trace()),
+    };
+
+    assert!(routing.recent_commands().is_empty());
+
+    let content = Bytes::from_static(b"hello");
+    routing
+        .send_message(
+            SrcLocation::Node(node_name),
+            DstLocation::Node(node_name),
+            content.clone(),
+        )
+        .await?;
+
+    let summary = routing
+        .recent_commands()
+        .into_iter()
+        .find(|summary| summary.kind == "SendUserMessage")
+        .expect("SendUserMessage not traced");
+    assert!(summary.details.contains(&format!("len: {}", content.len())));
+
+    // Not traced unless enabled.
+    let node = create_node();
+    let node_name = node.name();
+    let state = Approved::first_node(node, mpsc::unbounded_channel().0)?;
+    let routing = Routing {
+        stage: Arc::new(Stage::new(state, create_comm()?)),
+    };
+    routing
+        .send_message(
+            SrcLocation::Node(node_name),
+            DstLocation::Node(node_name),
+            content,
+        )
+        .await?;
+    assert!(routing.recent_commands().is_empty());
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn min_and_max_elder_age() -> Result<()> {
+    let mut nodes: Vec<_> = (0..ELDER_SIZE)
+        .map(|index| create_node().with_age(MIN_AGE + 1 + index as u8))
+        .collect();
+    let elders_info = EldersInfo::new(nodes.iter().map(Node::peer), Prefix::default());
+    let sk_set = SecretKeySet::random();
+    let (section, section_key_share) = create_section(&sk_set, &elders_info)?;
+    let state = Approved::new(
+        nodes.remove(0),
+        section,
+        Some(section_key_share),
+        mpsc::unbounded_channel().0,
+    );
+    let routing = Routing {
+        stage: Arc::new(Stage::new(state, create_comm()?)),
+    };
+
+    assert_eq!(routing.min_elder_age().await, Some(MIN_AGE + 1));
+    assert_eq!(
+        routing.max_elder_age().await,
+        Some(MIN_AGE + ELDER_SIZE as u8)
+    );
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn membership_proof() -> Result<()> {
+    let node = create_node();
+    let node_name = node.name();
+    let node_age = node.age;
+    let state = Approved::first_node(node, mpsc::unbounded_channel().0)?;
+    let genesis_key = *state.section().chain().first_key();
+    let routing = Routing {
+        stage: Arc::new(Stage::new(state, create_comm()?)),
+    };
+
+    let proof = routing.membership_proof().await?;
+    assert_eq!(*proof.name(), node_name);
+    assert_eq!(proof.age(), node_age);
+    assert!(proof.verify(&genesis_key));
+    assert!(!proof.verify(&bls::SecretKey::random().public_key()));
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn neighbour_reconciliation() -> Result<()> {
+    let prefix0 = Prefix::default().pushed(false);
+    let prefix1 = Prefix::default().pushed(true);
+
+    let gen_elders_info_in = |prefix: Prefix| {
+        let nodes: Vec<_> = iter::repeat_with(|| {
+            let keypair = crypto::gen_keypair_within_range(&prefix.range_inclusive());
+            Node::new(keypair, gen_addr()).with_age(MIN_AGE + 1)
+        })
+        .take(ELDER_SIZE)
+        .collect();
+        let elders_info = EldersInfo::new(nodes.iter().map(Node::peer), prefix);
+        (elders_info, nodes)
+    };
+
+    // Our section.
+    let sk_set0 = SecretKeySet::random();
+    let (elders_info0, mut nodes0) = gen_elders_info_in(prefix0);
+    let (section0, section_key_share0) = create_section(&sk_set0, &elders_info0)?;
+    let node0 = nodes0.remove(0);
+    let node0_addr = *node0.addr();
+    let state0 = Approved::new(
+        node0,
+        section0,
+        Some(section_key_share0),
+        mpsc::unbounded_channel().0,
+    )
+    .with_neighbour_reconciliation(Some(Duration::from_secs(1)));
+    let stage0 = Stage::new(state0, create_comm()?);
+
+    // The neighbour section, after it changed its elders.
+    let sk_set1 = SecretKeySet::random();
+    let (elders_info1, mut nodes1) = gen_elders_info_in(prefix1);
+    let (section1, section_key_share1) = create_section(&sk_set1, &elders_info1)?;
+    let state1 = Approved::new(
+        nodes1.remove(0),
+        section1,
+        Some(section_key_share1),
+        mpsc::unbounded_channel().0,
+    );
+    let stage1 = Stage::new(state1, create_comm()?);
+    let vote = Vote::SectionInfo(elders_info0);
+    let proof = prove(sk_set1.secret_key(), &vote.as_signable())?;
+    let _ = stage1
+        .handle_command(Command::HandleConsensus { vote, proof })
+        .await?;
+
+    // Our stale view of the neighbour, from before it changed its elders.
+    let (stale_elders_info1, _) = gen_elders_info_in(prefix1);
+    let vote = Vote::SectionInfo(stale_elders_info1);
+    let proof = prove(sk_set0.secret_key(), &vote.as_signable())?;
+    let _ = stage0
+        .handle_command(Command::HandleConsensus { vote, proof })
+        .await?;
+
+    let mut token = match stage0
+        .state
+        .lock()
+        .await
+        .schedule_neighbour_reconciliation()
+    {
+        Some(Command::ScheduleTimeout { token, .. }) => token,
+        command => panic!("unexpected command: {:?}", command),
+    };
+
+    // Runs one round of the reconciliation, returning the requests sent.
+    async fn run_round(stage: &Stage, token: &mut u64) -> Result<Vec<Message>> {
+        let mut requests = vec![];
+
+        for command in stage.handle_command(Command::HandleTimeout(*token)).await? {
+            match command {
+                Command::SendMessage {
+                    message: MessageType::NodeMessage(NodeMessage(msg_bytes)),
+                    ..
+                } => requests.push(Message::from_bytes(Bytes::from(msg_bytes))?),
+                Command::ScheduleTimeout { token: next, .. } => *token = next,
+                command => panic!("unexpected command: {:?}", command),
+            }
+        }
+
+        Ok(requests)
+    }
+
+    // The first round queries the neighbour.
+    let mut requests = run_round(&stage0, &mut token).await?;
+    assert_eq!(requests.len(), 1);
+    let request = requests.remove(0);
+    assert_matches!(request.variant(), Variant::NeighbourInfoRequest);
+
+    // The neighbour responds with its current info and, once we reach consensus on it, our view is
+    // corrected.
+    let commands = stage1
+        .handle_command(Command::HandleMessage {
+            sender: Some(node0_addr),
+            message: request,
+        })
+        .await?;
+    let response = commands
+        .into_iter()
+        .filter_map(|command| match command {
+            Command::SendMessage {
+                message: MessageType::NodeMessage(NodeMessage(msg_bytes)),
+                ..
+            } => Message::from_bytes(Bytes::from(msg_bytes)).ok(),
+            _ => None,
+        })
+        .find_map(|message| match message.variant() {
+            Variant::NeighbourInfo { elders_info, .. } => Some(elders_info.value.clone()),
+            _ => None,
+        })
+        .expect("NeighbourInfo not sent");
+    let vote = Vote::SectionInfo(response);
+    let proof = prove(sk_set0.secret_key(), &vote.as_signable())?;
+    let _ = stage0
+        .handle_command(Command::HandleConsensus { vote, proof })
+        .await?;
+    assert_eq!(
+        stage0
+            .state
+            .lock()
+            .await
+            .network()
+            .all()
+            .cloned()
+            .collect::<Vec<_>>(),
+        vec![elders_info1]
+    );
+
+    // From now on the neighbour doesn't respond, so it gets queried less and less often.
+    let mut queried = vec![];
+    for _ in 0..8 {
+        queried.push(!run_round(&stage0, &mut token).await?.is_empty());
+    }
+    assert_eq!(
+        queried,
+        vec![true, true, false, true, false, false, false, true]
+    );
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn is_closest_elder_to() -> Result<()> {
+    let sk_set = SecretKeySet::random();
+    let (elders_info, nodes) = create_elders_info();
+    let (section, _) = create_section(&sk_set, &elders_info)?;
+    let name = rand::random();
+
+    let mut closest_count = 0;
+    for (index, node) in nodes.into_iter().enumerate() {
+        let state = Approved::new(
+            node,
+            section.clone(),
+            Some(create_section_key_share(&sk_set, index)),
+            mpsc::unbounded_channel().0,
+        );
+        let routing = Routing {
+            stage: Arc::new(Stage::new(state, create_comm()?)),
+        };
+
+        if routing.is_closest_elder_to(&name).await? {
+            closest_count += 1;
+        }
+    }
+    assert_eq!(closest_count, 1);
+
+    // Non-elders can't tell.
+    let state = Approved::new(create_node(), section, None, mpsc::unbounded_channel().0);
+    let routing = Routing {
+        stage: Arc::new(Stage::new(state, create_comm()?)),
+    };
+    assert_matches!(
+        routing.is_closest_elder_to(&name).await,
+        Err(Error::InvalidState)
+    );
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn our_age_changed_event() -> Result<()> {
+    let (event_tx, mut event_rx) = mpsc::unbounded_channel();
+    let node = create_node().with_age(MIN_AGE + 1);
+    let state = Approved::first_node(node, event_tx)?;
+    let stage = Stage::new(state, create_comm()?);
+
+    // Relocation to a new section with an increased age.
+    let (elders_info, _) = create_elders_info();
+    let (section, _) = create_section(&SecretKeySet::random(), &elders_info)?;
+    let relocated_node = create_node().with_age(MIN_AGE + 2);
+    let _ = stage
+        .complete_relocation(relocated_node, section.clone(), vec![])
+        .await;
+
+    assert_matches!(event_rx.try_recv(), Ok(Event::Relocated { .. }));
+    assert_matches!(
+        event_rx.try_recv(),
+        Ok(Event::OurAgeChanged { old, new }) if old == MIN_AGE + 1 && new == MIN_AGE + 2
+    );
+    assert!(event_rx.try_recv().is_err());
+
+    // Relocation without an age change.
+    let relocated_node = create_node().with_age(MIN_AGE + 2);
+    let _ = stage
+        .complete_relocation(relocated_node, section, vec![])
+        .await;
+
+    assert_matches!(event_rx.try_recv(), Ok(Event::Relocated { .. }));
+    assert!(event_rx.try_recv().is_err());
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn settings_carried_over_on_relocation() -> Result<()> {
+    let quorum = Quorum::new(2, 3).ok_or(Error::InvalidState)?;
+    let interval = Duration::from_secs(30);
+    let state = Approved::first_node(create_node(), mpsc::unbounded_channel().0)?
+        .with_unknown_section_policy(UnknownSectionPolicy::Drop)
+        .with_min_elder_age(MIN_AGE + 2)
+        .with_strict_proof_verification(true)
+        .with_signed_messages_required(true)
+        .with_max_proof_chain_len(Some(5))
+        .with_quorum(quorum)
+        .with_neighbour_reconciliation(Some(interval));
+    let stage = Stage::new(state, create_comm()?);
+
+    let (elders_info, _) = create_elders_info();
+    let (section, _) = create_section(&SecretKeySet::random(), &elders_info)?;
+    let _ = stage
+        .complete_relocation(create_node(), section, vec![])
+        .await;
+
+    let settings = stage.state.lock().await.settings();
+    assert_eq!(settings.unknown_section_policy, UnknownSectionPolicy::Drop);
+    assert_eq!(settings.min_elder_age, MIN_AGE + 2);
+    assert!(settings.strict_proof_verification);
+    assert!(settings.require_signed_messages);
+    assert_eq!(settings.max_proof_chain_len, Some(5));
+    assert_eq!(settings.quorum, quorum);
+    assert_eq!(settings.neighbour_reconciliation_interval, Some(interval));
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn send_message_to_name() -> Result<()> {
+    let (elders_info, mut nodes) = create_elders_info();
+    let sk_set = SecretKeySet::random();
+    let (mut section, section_key_share) = create_section(&sk_set, &elders_info)?;
+
+    let peer = create_peer();
+    let member_info = MemberInfo::joined(peer);
+    let member_info = proven(sk_set.secret_key(), member_info)?;
+    assert!(section.update_member(member_info));
+
+    let node = nodes.remove(0);
+    let state = Approved::new(
+        node,
+        section,
+        Some(section_key_share),
+        mpsc::unbounded_channel().0,
+    );
+    let routing = Routing {
+        stage: Arc::new(Stage::new(state, create_comm()?)),
+    };
+
+    let content = Bytes::from_static(b"hello");
+    let commands = routing
+        .stage
+        .handle_command(Command::SendToName {
+            name: *peer.name(),
+            content: content.clone(),
+        })
+        .await?;
+
+    let (recipients, message) = match &commands[..] {
+        [Command::SendMessage {
+            recipients,
+            message: MessageType::NodeMessage(NodeMessage(msg_bytes)),
+            ..
+        }] => (
+            recipients,
+            Message::from_bytes(Bytes::from(msg_bytes.clone()))?,
+        ),
+        _ => panic!("unexpected commands: {:?}", commands),
+    };
+
+    assert_eq!(recipients, &[*peer.addr()]);
+    assert_eq!(message.dst(), &DstLocation::Node(*peer.name()));
+    assert_matches!(message.variant(), Variant::UserMessage(actual) if *actual == content);
+
+    assert_matches!(
+        routing.send_message_to_name(rand::random(), content).await,
+        Err(Error::UnknownPeer)
+    );
+
+    Ok(())
+}
+
+#[test]
+fn min_elder_age() -> Result<()> {
+    // Leave two vacancies among the elders.
+    let (elders_info, nodes) = gen_elders_info(Prefix::default(), ELDER_SIZE - 2);
+    let sk_set = SecretKeySet::random();
+    let (mut section, _) = create_section(&sk_set, &elders_info)?;
+    let our_name = nodes[0].name();
+    let min_elder_age = MIN_AGE + 2;
+
+    let young_peers = [
+        create_peer().with_age(MIN_AGE + 1),
+        create_peer().with_age(MIN_AGE + 1),
+    ];
+    for peer in &young_peers {
+        let member_info = proven(sk_set.secret_key(), MemberInfo::joined(*peer))?;
+        assert!(section.update_member(member_info));
+    }
+
+    // None of the members is old enough, so the vacancies are filled with the oldest ones.
+    let new_infos = section.promote_and_demote_elders(&our_name, min_elder_age, Quorum::default());
+    assert_eq!(new_infos.len(), 1);
+    assert_eq!(new_infos[0].elders.len(), ELDER_SIZE);
+    for peer in &young_peers {
+        assert!(new_infos[0].elders.contains_key(peer.name()));
+    }
+
+    let old_peer = create_peer().with_age(min_elder_age);
+    let member_info = proven(sk_set.secret_key(), MemberInfo::joined(old_peer))?;
+    assert!(section.update_member(member_info));
+
+    // The old enough member is promoted and the current elders stay even though they are too
+    // young. The one remaining seat is filled by one of the young members.
+    let new_infos = section.promote_and_demote_elders(&our_name, min_elder_age, Quorum::default());
+    assert_eq!(new_infos.len(), 1);
+    assert_eq!(new_infos[0].elders.len(), ELDER_SIZE);
+    for name in elders_info.elders.keys().chain(iter::once(old_peer.name())) {
+        assert!(new_infos[0].elders.contains_key(name));
+    }
+    assert_eq!(
+        young_peers
+            .iter()
+            .filter(|peer| new_infos[0].elders.contains_key(peer.name()))
+            .count(),
+        1
+    );
+
+    // Without the floor, both young members would be promoted, replacing one of the current
+    // elders who are younger.
+    let new_infos = section.promote_and_demote_elders(&our_name, MIN_AGE, Quorum::default());
+    assert_eq!(new_infos.len(), 1);
+    assert_eq!(new_infos[0].elders.len(), ELDER_SIZE);
+    for peer in &young_peers {
+        assert!(new_infos[0].elders.contains_key(peer.name()));
+    }
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn recently_joined() -> Result<()> {
+    let (elders_info, mut nodes) = create_elders_info();
+    let sk_set = SecretKeySet::random();
+    let (section, section_key_share) = create_section(&sk_set, &elders_info)?;
+    let node = nodes.remove(0);
+    let state = Approved::new(
+        node,
+        section,
+        Some(section_key_share),
+        mpsc::unbounded_channel().0,
+    );
+    let routing = Routing {
+        stage: Arc::new(Stage::new(state, create_comm()?)),
+    };
+
+    // The initial members joined before us, so they are not reported.
+    assert!(routing
+        .recently_joined(Duration::from_secs(60))
+        .await
+        .is_empty());
+
+    // A node relocates into our section.
+    let peer = create_peer().with_age(MIN_AGE + 2);
+    let vote = Vote::Online {
+        member_info: MemberInfo::joined(peer),
+        previous_name: Some(rand::random()),
+        their_knowledge: None,
+    };
+    let proof = prove(sk_set.secret_key(), &vote.as_signable())?;
+    let _ = routing
+        .stage
+        .handle_command(Command::HandleConsensus { vote, proof })
+        .await?;
+
+    assert_eq!(
+        routing.recently_joined(Duration::from_secs(60)).await,
+        vec![(*peer.name(), MIN_AGE + 2)]
+    );
+
+    // Once the window passes, it's not recent anymore.
+    tokio::time::delay_for(Duration::from_millis(50)).await;
+    assert!(routing
+        .recently_joined(Duration::from_millis(10))
+        .await
+        .is_empty());
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn simulate_split() -> Result<()> {
+    let prefix: Prefix = "0".parse().unwrap();
+    let (elders_info, mut nodes) = gen_elders_info(prefix, ELDER_SIZE);
+    let sk_set = SecretKeySet::random();
+    let (mut section, section_key_share) = create_section(&sk_set, &elders_info)?;
+
+    let prefix00: Prefix = "00".parse().unwrap();
+    let prefix01: Prefix = "01".parse().unwrap();
+
+    let mut expected00 = BTreeSet::new();
+    let mut expected01 = BTreeSet::new();
+    for name in elders_info.elders.keys() {
+        if prefix00.matches(name) {
+            let _ = expected00.insert(*name);
+        } else {
+            let _ = expected01.insert(*name);
+        }
+    }
+
+    let peers = vec![
+        create_peer_in_prefix(&prefix00),
+        create_peer_in_prefix(&prefix01),
+        create_peer_in_prefix(&prefix01),
+    ];
+    for peer in &peers {
+        let member_info = proven(sk_set.secret_key(), MemberInfo::joined(*peer))?;
+        assert!(section.update_member(member_info));
+    }
+    let _ = expected00.insert(*peers[0].name());
+    let _ = expected01.insert(*peers[1].name());
+    let _ = expected01.insert(*peers[2].name());
+
+    // Members that left are not taken into account.
+    let left_peer = create_peer_in_prefix(&prefix00);
+    let member_info = MemberInfo {
+        peer: left_peer,
+        state: PeerState::Left,
+    };
+    let member_info = proven(sk_set.secret_key(), member_info)?;
+    assert!(section.update_member(member_info));
+
+    let node = nodes.remove(0);
+    let state = Approved::new(
+        node,
+        section,
+        Some(section_key_share),
+        mpsc::unbounded_channel().0,
+    );
+    let routing = Routing {
+        stage: Arc::new(Stage::new(state, create_comm()?)),
+    };
+
+    let (actual_prefix0, names0, actual_prefix1, names1) = routing.simulate_split().await;
+    assert_eq!(actual_prefix0, prefix00);
+    assert_eq!(actual_prefix1, prefix01);
+    assert_eq!(names0.into_iter().collect::<BTreeSet<_>>(), expected00);
+    assert_eq!(names1.into_iter().collect::<BTreeSet<_>>(), expected01);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn defer_relocation() -> Result<()> {
+    let (elders_info, mut nodes) = create_elders_info();
+    let sk_set = SecretKeySet::random();
+    let (mut section, section_key_share) = create_section(&sk_set, &elders_info)?;
+
+    // Younger than the elders, so it's the only one the triggers below select.
+    let member_node = create_node();
+    let member_info = proven(sk_set.secret_key(), MemberInfo::joined(member_node.peer()))?;
+    assert!(section.update_member(member_info));
+
+    // The member asks our elders not to relocate it for a while.
+    let member_state = Approved::new(
+        member_node.clone(),
+        section.clone(),
+        None,
+        mpsc::unbounded_channel().0,
+    );
+    let member_stage = Stage::new(member_state, create_comm()?);
+    let commands = member_stage
+        .handle_command(Command::RequestRelocationDeferral(Duration::from_secs(60)))
+        .await?;
+    let (request, token) = match &commands[..] {
+        [Command::SendMessage {
+            message: MessageType::NodeMessage(NodeMessage(msg_bytes)),
+            ..
+        }, Command::ScheduleTimeout { duration, token }] => {
+            assert_eq!(*duration, Duration::from_secs(60));
+            (Message::from_bytes(Bytes::from(msg_bytes.clone()))?, *token)
+        }
+        _ => panic!("unexpected commands: {:?}", commands),
+    };
+    assert_matches!(request.variant(), Variant::RelocationDeferral(_));
+
+    let elder_state = Approved::new(
+        nodes.remove(0),
+        section.clone(),
+        Some(section_key_share),
+        mpsc::unbounded_channel().0,
+    );
+    let elder_stage = Stage::new(elder_state, create_comm()?);
+
+    let deferral_voted = |commands: Vec<Command>| {
+        commands.iter().any(|command| {
+            matches!(
+                command,
+                Command::HandleVote {
+                    vote: Vote::DeferRelocation { name, .. },
+                    ..
+                } if *name == member_node.name()
+            )
+        })
+    };
+
+    // The elder votes on the request, but doesn't defer anything until the vote is agreed on.
+    let commands = elder_stage
+        .handle_command(Command::HandleMessage {
+            sender: Some(member_node.addr),
+            message: request,
+        })
+        .await?;
+    assert!(deferral_voted(commands));
+
+    let vote = Vote::DeferRelocation {
+        name: member_node.name(),
+        churn_events: 16,
+    };
+    let proof = prove(sk_set.secret_key(), &vote.as_signable())?;
+    let _ = elder_stage
+        .handle_command(Command::HandleConsensus { vote, proof })
+        .await?;
+
+    // Churn that would relocate the member doesn't while the deferral lasts.
+    let (vote, proof) = create_relocation_trigger(sk_set.secret_key(), member_node.age)?;
+    let commands = elder_stage
+        .handle_command(Command::HandleConsensus { vote, proof })
+        .await?;
+    assert!(!relocate_sent(commands, &member_node.addr)?);
+
+    // Once the duration elapses, the member asks our elders to end the deferral.
+    let commands = member_stage
+        .handle_command(Command::HandleTimeout(token))
+        .await?;
+    let request = match &commands[..] {
+        [Command::SendMessage {
+            message: MessageType::NodeMessage(NodeMessage(msg_bytes)),
+            ..
+        }] => Message::from_bytes(Bytes::from(msg_bytes.clone()))?,
+        _ => panic!("unexpected commands: {:?}", commands),
+    };
+    assert_matches!(request.variant(), Variant::CancelRelocationDeferral);
+
+    let commands = elder_stage
+        .handle_command(Command::HandleMessage {
+            sender: Some(member_node.addr),
+            message: request,
+        })
+        .await?;
+    assert!(commands.iter().any(|command| matches!(
+        command,
+        Command::HandleVote {
+            vote: Vote::CancelRelocationDeferral(name),
+            ..
+        } if *name == member_node.name()
+    )));
+
+    let vote = Vote::CancelRelocationDeferral(member_node.name());
+    let proof = prove(sk_set.secret_key(), &vote.as_signable())?;
+    let _ = elder_stage
+        .handle_command(Command::HandleConsensus { vote, proof })
+        .await?;
+
+    // The member can't defer again right after.
+    let request = create_relocation_deferral_request(member_node.clone(), section).await?;
+    let commands = elder_stage
+        .handle_command(Command::HandleMessage {
+            sender: Some(member_node.addr),
+            message: request,
+        })
+        .await?;
+    assert!(!deferral_voted(commands));
+
+    // And the next churn event relocates it, as the deferral is over.
+    let (vote, proof) = create_relocation_trigger(sk_set.secret_key(), member_node.age)?;
+    let commands = elder_stage
+        .handle_command(Command::HandleConsensus { vote, proof })
+        .await?;
+    assert!(relocate_sent(commands, &member_node.addr)?);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn relocation_deferral_dropped_on_elder_change() -> Result<()> {
+    let (elders_info, mut nodes) = create_elders_info();
+    let sk_set0 = SecretKeySet::random();
+    let pk0 = sk_set0.secret_key().public_key();
+    let (mut section, section_key_share) = create_section(&sk_set0, &elders_info)?;
+
+    let member_node = create_node();
+    let member_info = proven(sk_set0.secret_key(), MemberInfo::joined(member_node.peer()))?;
+    assert!(section.update_member(member_info));
+
+    let elder_state = Approved::new(
+        nodes.remove(0),
+        section,
+        Some(section_key_share),
+        mpsc::unbounded_channel().0,
+    );
+    let elder_stage = Stage::new(elder_state, create_comm()?);
+
+    let vote = Vote::DeferRelocation {
+        name: member_node.name(),
+        churn_events: 16,
+    };
+    let proof = prove(sk_set0.secret_key(), &vote.as_signable())?;
+    let _ = elder_stage
+        .handle_command(Command::HandleConsensus { vote, proof })
+        .await?;
+
+    // Our section switches to a new key, as it does whenever our elders change. Elders that were
+    // just promoted wouldn't know about the deferral.
+    let sk_set1 = SecretKeySet::random();
+    let _ = elder_stage
+        .handle_command(Command::HandleDkgOutcome {
+            elders_info: elders_info.clone(),
+            outcome: create_section_key_share(&sk_set1, 0),
+        })
+        .await?;
+
+    let vote = Vote::OurElders(proven(sk_set1.secret_key(), elders_info)?);
+    let signature = sk_set0
+        .secret_key()
+        .sign(&bincode::serialize(&vote.as_signable())?);
+    let proof = Proof {
+        signature,
+        public_key: pk0,
+    };
+    let _ = elder_stage
+        .handle_command(Command::HandleConsensus { vote, proof })
+        .await?;
+
+    // We dropped the deferral as well, so we relocate the member like they would.
+    let (vote, proof) = create_relocation_trigger(sk_set1.secret_key(), member_node.age)?;
+    let commands = elder_stage
+        .handle_command(Command::HandleConsensus { vote, proof })
+        .await?;
+    assert!(relocate_sent(commands, &member_node.addr)?);
+
+    Ok(())
+}
+
+// Returns whether the commands send a `Relocate` message to the node with the given address.
+fn relocate_sent(commands: Vec<Command>, addr: &SocketAddr) -> Result<bool> {
+    for command in commands {
+        let msg_bytes = match command {
+            Command::SendMessage {
+                recipients,
+                message: MessageType::NodeMessage(NodeMessage(msg_bytes)),
+                ..
+            } if recipients == [*addr] => msg_bytes,
+            _ => continue,
+        };
+
+        if let Variant::Vote {
+            content: Vote::SendMessage { message, .. },
+            ..
+        } = Message::from_bytes(Bytes::from(msg_bytes))?.variant()
+        {
+            if matches!(message.variant, Variant::Relocate(_)) {
+                return Ok(true);
+            }
+        }
+    }
+
+    Ok(false)
+}
+
+async fn create_relocation_deferral_request(node: Node, section: Section) -> Result<Message> {
+    let state = Approved::new(node, section, None, mpsc::unbounded_channel().0);
+    let stage = Stage::new(state, create_comm()?);
+    let commands = stage
+        .handle_command(Command::RequestRelocationDeferral(Duration::from_secs(60)))
+        .await?;
+
+    match &commands[..] {
+        [Command::SendMessage {
+            message: MessageType::NodeMessage(NodeMessage(msg_bytes)),
+            ..
+        }, Command::ScheduleTimeout { .. }] => {
+            Ok(Message::from_bytes(Bytes::from(msg_bytes.clone()))?)
+        }
+        _ => panic!("unexpected commands: {:?}", commands),
+    }
+}
+
+#[tokio::test]
+async fn rotate_keypair() -> Result<()> {
+    let (elders_info, mut nodes) = create_elders_info();
+    let sk_set = SecretKeySet::random();
+    let (mut section, section_key_share) = create_section(&sk_set, &elders_info)?;
+
+    let member_node = create_node().with_age(MIN_AGE + 2);
+    let member_info = proven(sk_set.secret_key(), MemberInfo::joined(member_node.peer()))?;
+    assert!(section.update_member(member_info));
+
+    // The member asks our elders to relocate it.
+    let member_state = Approved::new(
+        member_node.clone(),
+        section.clone(),
+        None,
+        mpsc::unbounded_channel().0,
+    );
+    let member_stage = Stage::new(member_state, create_comm()?);
+    let commands = member_stage
+        .handle_command(Command::RequestKeyRotation)
+        .await?;
+
+    let (recipients, request) = match &commands[..] {
+        [Command::SendMessage {
+            recipients,
+            message: MessageType::NodeMessage(NodeMessage(msg_bytes)),
+            ..
+        }] => (
+            recipients,
+            Message::from_bytes(Bytes::from(msg_bytes.clone()))?,
+        ),
+        _ => panic!("unexpected commands: {:?}", commands),
+    };
+    itertools::assert_equal(recipients, elders_info.peers().map(Peer::addr));
+    assert_matches!(request.variant(), Variant::RelocateRequest);
+
+    // An elder relocates the member back into our section, with the same age.
+    let elder_state = Approved::new(
+        nodes.remove(0),
+        section,
+        Some(section_key_share),
+        mpsc::unbounded_channel().0,
+    );
+    let elder_stage = Stage::new(elder_state, create_comm()?);
+    let commands = elder_stage
+        .handle_command(Command::HandleMessage {
+            sender: Some(member_node.addr),
+            message: request,
+        })
+        .await?;
+
+    let mut offline_voted = false;
+    let mut relocate_sent = false;
+
+    for command in commands {
+        let vote = match command {
+            Command::HandleVote { vote, .. } => vote,
+            Command::SendMessage {
+                recipients,
+                message: MessageType::NodeMessage(NodeMessage(msg_bytes)),
+                ..
+            } if recipients == [member_node.addr] => {
+                match Message::from_bytes(Bytes::from(msg_bytes))?.variant() {
+                    Variant::Vote { content, .. } => content.clone(),
+                    _ => continue,
+                }
+            }
+            _ => continue,
+        };
+
+        match vote {
+            Vote::Offline(member_info) => {
+                assert_eq!(member_info.peer.name(), &member_node.name());
+                assert_eq!(member_info.state, PeerState::Relocated(member_node.name()));
+                offline_voted = true;
+            }
+            Vote::SendMessage { message, .. } => {
+                assert_matches!(&message.variant, Variant::Relocate(details) => {
+                    assert_eq!(details.pub_id, member_node.name());
+                    assert_eq!(details.destination, member_node.name());
+                    assert_eq!(details.age, MIN_AGE + 2);
+                });
+                relocate_sent = true;
+            }
+            _ => continue,
+        }
+    }
+
+    assert!(offline_voted);
+    assert!(relocate_sent);
+
+    // Once the relocation completes, the new name is reported.
+    let relocated_rx = member_stage.wait_for_relocation().await;
+    let (section, _) = create_section(&sk_set, &elders_info)?;
+    let relocated_node = create_node().with_age(MIN_AGE + 2);
+    let _ = member_stage
+        .complete_relocation(relocated_node.clone(), section, vec![])
+        .await;
+
+    let new_name = relocated_rx.await?;
+    assert_eq!(new_name, relocated_node.name());
+    assert_ne!(new_name, member_node.name());
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn strict_proof_verification() -> Result<()> {
+    let (elders_info, mut nodes) = create_elders_info();
+    let sk_set = SecretKeySet::random();
+    let sender = nodes.remove(1);
+
+    let trusted_chain = SectionProofChain::new(sk_set.secret_key().public_key());
+    let untrusted_chain = SectionProofChain::new(bls::SecretKey::random().public_key());
+
+    let create_stage = |strict| -> Result<_> {
+        let (section, section_key_share) = create_section(&sk_set, &elders_info)?;
+        let (event_tx, event_rx) = mpsc::unbounded_channel();
+        let state = Approved::new(nodes[0].clone(), section, Some(section_key_share), event_tx)
+            .with_strict_proof_verification(strict);
+        Ok((Stage::new(state, create_comm()?), event_rx))
+    };
+    let create_message = |proof_chain: &SectionProofChain| {
+        Message::single_src(
+            &sender,
+            DstLocation::Node(nodes[0].name()),
+            Variant::UserMessage(Bytes::from_static(b"hello")),
+            Some(proof_chain.clone()),
+            None,
+        )
+    };
+
+    for (strict, proof_chain, accepted) in vec![
+        (true, &trusted_chain, true),
+        (true, &untrusted_chain, false),
+        // By default, the proof chain of a user message from a single node is not checked.
+        (false, &untrusted_chain, true),
+    ] {
+        let (stage, mut event_rx) = create_stage(strict)?;
+        let commands = stage
+            .handle_command(Command::HandleMessage {
+                sender: Some(sender.addr),
+                message: create_message(proof_chain)?,
+            })
+            .await?;
+
+        if accepted {
+            assert_matches!(event_rx.try_recv(), Ok(Event::MessageReceived { .. }));
+        } else {
+            // Dropped, not bounced.
+            assert!(commands.is_empty());
+            assert_matches!(
+                event_rx.try_recv(),
+                Ok(Event::MessageDropped {
+                    reason: DropReason::FailedVerification,
+                    ..
+                })
+            );
+        }
+    }
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn neighbour_proof_slice() -> Result<()> {
+    let p0 = Prefix::default().pushed(false);
+    let p1 = Prefix::default().pushed(true);
+
+    let genesis_sk = bls::SecretKey::random();
+    let genesis_pk = genesis_sk.public_key();
+
+    let sk_set = SecretKeySet::random();
+    let pk = sk_set.secret_key().public_key();
+    let signature = genesis_sk.sign(&bincode::serialize(&pk)?);
+
+    let mut chain = SectionProofChain::new(genesis_pk);
+    assert!(chain.push(pk, signature));
+
+    let (elders_info, mut nodes) = gen_elders_info(p0, ELDER_SIZE);
+    let proven_elders_info = proven(sk_set.secret_key(), elders_info)?;
+    let section = Section::new(chain, proven_elders_info)?;
+
+    let section_key_share = create_section_key_share(&sk_set, 0);
+    let state = Approved::new(
+        nodes.remove(0),
+        section,
+        Some(section_key_share),
+        mpsc::unbounded_channel().0,
+    );
+    let routing = Routing {
+        stage: Arc::new(Stage::new(state, create_comm()?)),
+    };
+
+    assert!(routing.neighbour_proof_slice(&p1).await.is_none());
+
+    let their_key = bls::SecretKey::random().public_key();
+    let vote = Vote::TheirKey {
+        prefix: p1,
+        key: their_key,
+    };
+    let proof = prove(sk_set.secret_key(), &vote.as_signable())?;
+    let _ = routing
+        .stage
+        .handle_command(Command::HandleConsensus {
+            vote: vote.clone(),
+            proof: proof.clone(),
+        })
+        .await?;
+
+    let slice = routing
+        .neighbour_proof_slice(&p1)
+        .await
+        .expect("missing proof slice");
+    assert_eq!(
+        slice.check_trust(iter::once(&genesis_pk)),
+        TrustStatus::Trusted
+    );
+    assert_eq!(slice.last_key(), &pk);
+
+    // The neighbour key record verifies against the last key of the slice.
+    let bytes = bincode::serialize(&vote.as_signable())?;
+    assert!(slice.last_key().verify(&proof.signature, &bytes));
+
+    // Unknown prefix.
+    assert!(routing
+        .neighbour_proof_slice(&p1.pushed(false))
+        .await
+        .is_none());
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn address_space_fraction() -> Result<()> {
+    let p0 = Prefix::default().pushed(false);
+    let p1 = Prefix::default().pushed(true);
+    let p00 = p0.pushed(false);
+    let p000 = p00.pushed(false);
+    let p001 = p00.pushed(true);
+    let p01 = p0.pushed(true);
+
+    let sk_set = SecretKeySet::random();
+    let (elders_info, mut nodes) = gen_elders_info(p000, ELDER_SIZE);
+    let (section, section_key_share) = create_section(&sk_set, &elders_info)?;
+    let state = Approved::new(
+        nodes.remove(0),
+        section,
+        Some(section_key_share),
+        mpsc::unbounded_channel().0,
+    );
+    let routing = Routing {
+        stage: Arc::new(Stage::new(state, create_comm()?)),
+    };
+
+    let approx_eq = |lhs: f64, rhs: f64| (lhs - rhs).abs() < f64::EPSILON;
+
+    assert!(approx_eq(routing.address_space_fraction().await, 0.125));
+
+    for prefix in &[p001, p01, p1] {
+        let neighbour_sk_set = SecretKeySet::random();
+        let (elders_info, _) = gen_elders_info(*prefix, ELDER_SIZE);
+        let vote = Vote::SectionInfo(elders_info);
+        let proof = prove(neighbour_sk_set.secret_key(), &vote.as_signable())?;
+        let _ = routing
+            .stage
+            .handle_command(Command::HandleConsensus { vote, proof })
+            .await?;
+    }
+
+    let coverage = routing.coverage_by_prefix().await;
+    assert_eq!(coverage.len(), 4);
+    assert!(approx_eq(coverage[&p000], 0.125));
+    assert!(approx_eq(coverage[&p1], 0.5));
+
+    // Siblings cover their parent.
+    assert!(approx_eq(coverage[&p000] + coverage[&p001], 0.25));
+    // All the sections together cover the whole name space.
+    assert!(approx_eq(coverage.values().sum(), 1.0));
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn age_progress() -> Result<()> {
+    let (elders_info, mut nodes) = create_elders_info();
+    let sk_set = SecretKeySet::random();
+    let (section, section_key_share) = create_section(&sk_set, &elders_info)?;
+    let node = nodes.remove(0);
+    let age = node.age;
+    let state = Approved::new(
+        node,
+        section,
+        Some(section_key_share),
+        mpsc::unbounded_channel().0,
+    );
+    let routing = Routing {
+        stage: Arc::new(Stage::new(state, create_comm()?)),
+    };
+
+    let threshold = 1 << age;
+    assert_eq!(routing.age_progress().await?, (age, 0, threshold));
+
+    for _ in 0..3 {
+        let _ =
+            handle_online_command(&create_peer(), &sk_set, &routing.stage, &elders_info).await?;
+    }
+
+    assert_eq!(routing.age_progress().await?, (age, 3, threshold));
+
+    Ok(())
+}
+
+#[test]
+fn age_progress_of_non_member() -> Result<()> {
+    let (elders_info, _) = create_elders_info();
+    let (section, _) = create_section(&SecretKeySet::random(), &elders_info)?;
+    let state = Approved::new(create_node(), section, None, mpsc::unbounded_channel().0);
+
+    assert_matches!(state.age_progress(), Err(Error::InvalidState));
+
+    Ok(())
+}
+
+#[test]
+fn check_stream_message() -> Result<()> {
+    let sk_set = SecretKeySet::random();
+    let (elders_info, mut nodes) = create_elders_info();
+    let (section, section_key_share) = create_section(&sk_set, &elders_info)?;
+    let node = nodes.remove(0);
+    let node_name = node.name();
+    let mut state = Approved::new(
+        node,
+        section,
+        Some(section_key_share),
+        mpsc::unbounded_channel().0,
+    );
+
+    let sender = create_node();
+    let header = Message::single_src(
+        &sender,
+        DstLocation::Node(node_name),
+        Variant::UserMessageStream,
+        None,
+        None,
+    )?;
+
+    // Accepted once, a replay of the same header is not.
+    assert!(state.check_stream_message(&header));
+    assert!(!state.check_stream_message(&header));
+
+    // Not for us.
+    let header = Message::single_src(
+        &sender,
+        DstLocation::Node(rand::random()),
+        Variant::UserMessageStream,
+        None,
+        None,
+    )?;
+    assert!(!state.check_stream_message(&header));
+
+    // Not signed by its claimed source.
+    let message = PlainMessage {
+        src: Prefix::default(),
+        dst: DstLocation::Node(node_name),
+        dst_key: sk_set.secret_key().public_key(),
+        variant: Variant::UserMessageStream,
+    };
+    let signature = bls::SecretKey::random().sign(&bincode::serialize(&message.as_signable())?);
+    let header = Message::section_src(
+        message,
+        signature,
+        SectionProofChain::new(sk_set.secret_key().public_key()),
+    )?;
+    assert!(!state.check_stream_message(&header));
+
+    Ok(())
+}
+
+#[derive(Debug)]
+struct EchoQueryHandler;
+
+impl QueryHandler for EchoQueryHandler {
+    fn on_query(&self, _: &SrcLocation, content: &Bytes) -> Option<Bytes> {
+        Some(content.clone())
+    }
+}
+
+#[tokio::test]
+async fn query_elders() -> Result<()> {
+    // Two elders answer, one doesn't, one is unreachable and one is us.
+    let handlers: Vec<Option<Arc<dyn QueryHandler>>> = vec![
+        Some(Arc::new(EchoQueryHandler)),
+        Some(Arc::new(EchoQueryHandler)),
+        None,
+    ];
+
+    let mut responders = vec![];
+    for handler in handlers {
+        let (tx, rx) = mpsc::channel(1);
+        let comm = Comm::new(
+            qp2p::Config {
+                ip: Some(Ipv4Addr::LOCALHOST.into()),
+                ..Default::default()
+            },
+            tx,
+        )?;
+        let node = Node::new(crypto::gen_keypair(), comm.our_connection_info().await?);
+        responders.push((node, comm, rx, handler));
+    }
+
+    let unreachable_node = create_node();
+    let our_node = create_node();
+
+    let elders_info = EldersInfo::new(
+        responders
+            .iter()
+            .map(|(node, ..)| node.peer())
+            .chain(vec![unreachable_node.peer(), our_node.peer()]),
+        Prefix::default(),
+    );
+    let sk_set = SecretKeySet::random();
+    let (section, _) = create_section(&sk_set, &elders_info)?;
+
+    let mut expected = BTreeSet::new();
+    for (node, comm, rx, handler) in responders {
+        if handler.is_some() {
+            let _ = expected.insert(node.name());
+        }
+
+        let state = Approved::new(node, section.clone(), None, mpsc::unbounded_channel().0);
+        let stage = Arc::new(Stage::new(state, comm).with_query_handler(handler));
+        let _ = tokio::spawn(handle_connection_events(stage, rx, 1024 * 1024));
+    }
+
+    let state = Approved::new(our_node, section, None, mpsc::unbounded_channel().0);
+    let routing = Routing {
+        stage: Arc::new(Stage::new(state, create_comm()?)),
+    };
+
+    let content = Bytes::from_static(b"hello");
+    let responses = routing
+        .query_elders(content.clone(), Duration::from_secs(1))
+        .await;
+
+    let actual: BTreeSet<_> = responses.iter().map(|(name, _)| *name).collect();
+    assert_eq!(actual, expected);
+    assert_eq!(responses.len(), expected.len());
+    assert!(responses.iter().all(|(_, response)| *response == content));
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn measure_clock_skew() -> Result<()> {
+    let offset = Duration::from_secs(3600);
+
+    // One elder whose clock is `offset` ahead of ours, one honest elder and us.
+    let (tx, mut skewed_rx) = mpsc::channel(1);
+    let skewed_comm = Comm::new(
+        qp2p::Config {
+            ip: Some(Ipv4Addr::LOCALHOST.into()),
+            ..Default::default()
+        },
+        tx,
+    )?;
+    let skewed_node = Node::new(
+        crypto::gen_keypair(),
+        skewed_comm.our_connection_info().await?,
+    );
+
+    let (tx, honest_rx) = mpsc::channel(1);
+    let honest_comm = Comm::new(
+        qp2p::Config {
+            ip: Some(Ipv4Addr::LOCALHOST.into()),
+            ..Default::default()
+        },
+        tx,
+    )?;
+    let honest_node = Node::new(
+        crypto::gen_keypair(),
+        honest_comm.our_connection_info().await?,
+    );
+
+    let our_node = create_node();
+    let elders_info = EldersInfo::new(
+        vec![skewed_node.peer(), honest_node.peer(), our_node.peer()],
+        Prefix::default(),
+    );
+    let (section, _) = create_section(&SecretKeySet::random(), &elders_info)?;
+
+    // The skewed elder is a mock answering every probe with its offset time.
+    let skewed_name = skewed_node.name();
+    let _ = tokio::spawn(async move {
+        let _comm = skewed_comm;
+        while let Some(event) = skewed_rx.recv().await {
+            if let ConnectionEvent::Received(qp2p::Message::BiStream { mut send, .. }) = event {
+                let time = SystemTime::now().duration_since(UNIX_EPOCH).unwrap() + offset;
+                let response = Bytes::from(bincode::serialize(&time).unwrap());
+                let _ = send.send_user_msg(response).await;
+                let _ = send.finish().await;
+            }
+        }
+    });
+
+    let honest_name = honest_node.name();
+    let state = Approved::new(
+        honest_node,
+        section.clone(),
+        None,
+        mpsc::unbounded_channel().0,
+    );
+    let stage = Arc::new(Stage::new(state, honest_comm));
+    let _ = tokio::spawn(handle_connection_events(stage, honest_rx, 1024 * 1024));
+
+    let (event_tx, mut event_rx) = mpsc::unbounded_channel();
+    let state = Approved::new(our_node, section, None, event_tx);
+    let routing = Routing {
+        stage: Arc::new(Stage::new(state, create_comm()?)),
+    };
+
+    let skews: HashMap<_, _> = routing.measure_clock_skew().await.into_iter().collect();
+    assert_eq!(skews.len(), 2);
+
+    let tolerance = Duration::from_secs(1);
+    assert!(skews[&skewed_name] > offset - tolerance);
+    assert!(skews[&skewed_name] < offset + tolerance);
+    assert!(skews[&honest_name] < tolerance);
+
+    // Only the skewed elder is reported.
+    assert_matches!(
+        event_rx.try_recv(),
+        Ok(Event::ClockSkewDetected { name, skew }) => {
+            assert_eq!(name, skewed_name);
+            assert_eq!(skew, skews[&skewed_name]);
+        }
+    );
+    assert!(event_rx.try_recv().is_err());
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn elder_last_contact() -> Result<()> {
+    let (tx, _rx) = mpsc::channel(1);
+    let contacted_comm = Comm::new(
+        qp2p::Config {
+            ip: Some(Ipv4Addr::LOCALHOST.into()),
+            ..Default::default()
+        },
+        tx,
+    )?;
+    let contacted_node = Node::new(
+        crypto::gen_keypair(),
+        contacted_comm.our_connection_info().await?,
+    );
+    let uncontacted_node = create_node();
+
+    let our_node = create_node();
+    let elders_info = EldersInfo::new(
+        vec![
+            contacted_node.peer(),
+            uncontacted_node.peer(),
+            our_node.peer(),
+        ],
+        Prefix::default(),
+    );
+    let (section, _) = create_section(&SecretKeySet::random(), &elders_info)?;
+
+    let state = Approved::new(our_node, section, None, mpsc::unbounded_channel().0);
+    let routing = Routing {
+        stage: Arc::new(Stage::new(state, create_comm()?)),
+    };
+
+    routing
+        .stage
+        .comm
+        .send(&[contacted_node.addr], 1, Bytes::from_static(b"hello"))
+        .await
+        .0?;
+
+    let last_contact: HashMap<_, _> = routing.elder_last_contact().await.into_iter().collect();
+    assert_matches!(last_contact.get(&contacted_node.name()), Some(Some(elapsed)) => {
+        assert!(*elapsed < Duration::from_secs(5))
+    });
+    assert_eq!(last_contact.get(&uncontacted_node.name()), Some(&None));
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn key_rotation_log() -> Result<()> {
+    // Genesis key followed by three rotations.
+    let secret_keys: Vec<_> = iter::repeat_with(bls::SecretKey::random).take(4).collect();
+    let mut chain = SectionProofChain::new(secret_keys[0].public_key());
+    for window in secret_keys.windows(2) {
+        let key = window[1].public_key();
+        assert!(chain.push(key, window[0].sign(&bincode::serialize(&key)?)));
+    }
+
+    let (elders_info, mut nodes) = create_elders_info();
+    let last_sk = &secret_keys[secret_keys.len() - 1];
+    let section = Section::new(chain, proven(last_sk, elders_info.clone())?)?;
+    let state = Approved::new(nodes.remove(0), section, None, mpsc::unbounded_channel().0);
+    let routing = Routing {
+        stage: Arc::new(Stage::new(state, create_comm()?)),
+    };
+
+    let log = routing.key_rotation_log().await;
+    assert_eq!(log.len(), secret_keys.len());
+
+    for (index, (entry, sk)) in log.iter().zip(&secret_keys).enumerate() {
+        assert_eq!(entry.version, index as u64);
+        assert_eq!(entry.key, sk.public_key());
+    }
+
+    assert!(log[0].proof_slice_from_previous.is_none());
+
+    for window in log.windows(2) {
+        let (previous, entry) = (&window[0], &window[1]);
+        let slice = entry
+            .proof_slice_from_previous
+            .as_ref()
+            .expect("missing proof slice");
+        assert_eq!(slice.first_key(), &previous.key);
+        assert_eq!(slice.last_key(), &entry.key);
+        assert_eq!(
+            slice.check_trust(iter::once(&previous.key)),
+            TrustStatus::Trusted
+        );
+    }
+
+    // We only remember the elders of the current key.
+    assert!(log[..log.len() - 1]
+        .iter()
+        .all(|entry| entry.prefix.is_none()));
+    assert_eq!(log[log.len() - 1].prefix, Some(elders_info.prefix));
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn key_rotation_log_after_compaction() -> Result<()> {
+    // Genesis key followed by five rotations.
+    let secret_keys: Vec<_> = iter::repeat_with(bls::SecretKey::random).take(6).collect();
+    let mut chain = SectionProofChain::new(secret_keys[0].public_key());
+    for window in secret_keys.windows(2) {
+        let key = window[1].public_key();
+        assert!(chain.push(key, window[0].sign(&bincode::serialize(&key)?)));
+    }
+
+    let (elders_info, mut nodes) = create_elders_info();
+    let last_sk = &secret_keys[secret_keys.len() - 1];
+    let mut section = Section::new(chain, proven(last_sk, elders_info)?)?;
+
+    // Keeps the genesis key, the last removed key (3) and the two blocks after it.
+    section.compact_chain(2);
+
+    let state = Approved::new(nodes.remove(0), section, None, mpsc::unbounded_channel().0);
+    let routing = Routing {
+        stage: Arc::new(Stage::new(state, create_comm()?)),
+    };
+
+    let log = routing.key_rotation_log().await;
+    let versions: Vec<_> = log.iter().map(|entry| entry.version).collect();
+    assert_eq!(versions, vec![0, 3, 4, 5]);
+
+    for entry in &log {
+        assert_eq!(entry.key, secret_keys[entry.version as usize].public_key());
+    }
+
+    // Nothing proves the key after the gap was signed by the genesis key.
+    assert!(log[0].proof_slice_from_previous.is_none());
+    assert!(log[1].proof_slice_from_previous.is_none());
+
+    for window in log[1..].windows(2) {
+        let (previous, entry) = (&window[0], &window[1]);
+        let slice = entry
+            .proof_slice_from_previous
+            .as_ref()
+            .expect("missing proof slice");
+        assert_eq!(slice.first_key(), &previous.key);
+        assert_eq!(slice.last_key(), &entry.key);
+        assert_eq!(
+            slice.check_trust(iter::once(&previous.key)),
+            TrustStatus::Trusted
+        );
+    }
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn new_with_preset_section() -> Result<()> {
+    let genesis_sk = bls::SecretKey::random();
+    let secret_key_poly = bls::poly::Poly::random(THRESHOLD, &mut rand::thread_rng());
+    let pk = bls::SecretKeySet::from(secret_key_poly.clone())
+        .public_keys()
+        .public_key();
+    let mut chain = SectionProofChain::new(genesis_sk.public_key());
+    let _ = chain.push(pk, genesis_sk.sign(&bincode::serialize(&pk)?));
+
+    let keypair = crypto::gen_keypair();
+    let our_name = crypto::name(&keypair.public);
+    let our_age = MIN_AGE + 3;
+    let prefix = Prefix::new(2, our_name);
+
+    // Us and `ELDER_SIZE - 1` others as elders plus two adults.
+    let mut members = vec![PresetMember {
+        name: our_name,
+        addr: gen_addr(),
+        age: our_age,
+        is_elder: true,
+    }];
+    members.extend((0..ELDER_SIZE + 1).map(|index| PresetMember {
+        name: prefix.substituted_in(rand::random()),
+        addr: gen_addr(),
+        age: MIN_AGE + 1 + index as u8,
+        is_elder: index < ELDER_SIZE - 1,
+    }));
+
+    let preset = SectionPreset {
+        prefix,
+        secret_key_poly,
+        chain: chain.clone(),
+        members: members.clone(),
+    };
+    let (routing, _) = Routing::new_with_preset_section(
+        Config {
+            keypair: Some(keypair),
+            transport_config: qp2p::Config {
+                ip: Some(Ipv4Addr::LOCALHOST.into()),
+                ..Default::default()
+            },
+            ..Default::default()
+        },
+        preset,
+    )
+    .await?;
+
+    assert_eq!(routing.name().await, our_name);
+    assert_eq!(routing.age().await, our_age);
+    assert_eq!(routing.our_prefix().await, prefix);
+    assert!(routing.is_elder().await);
+    assert_eq!(routing.our_history().await, chain);
+    assert_eq!(routing.section_public_key().await?, pk);
+
+    let expected_elders: BTreeSet<_> = members
+        .iter()
+        .filter(|member| member.is_elder)
+        .map(|member| member.name)
+        .collect();
+    let elders: BTreeSet<_> = routing
+        .our_elders()
+        .await
+        .iter()
+        .map(|peer| *peer.name())
+        .collect();
+    assert_eq!(elders, expected_elders);
+
+    let expected_adults: BTreeSet<_> = members
+        .iter()
+        .filter(|member| !member.is_elder)
+        .map(|member| (member.name, member.addr, member.age))
+        .collect();
+    let adults: BTreeSet<_> = routing
+        .our_adults()
+        .await
+        .iter()
+        .map(|peer| (*peer.name(), *peer.addr(), peer.age()))
+        .collect();
+    assert_eq!(adults, expected_adults);
+
+    let our_index = expected_elders.iter().position(|name| *name == our_name);
+    assert_eq!(routing.our_index().await.ok(), our_index);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn new_with_preset_section_without_us() {
+    let secret_key_poly = bls::poly::Poly::random(THRESHOLD, &mut rand::thread_rng());
+    let pk = bls::SecretKeySet::from(secret_key_poly.clone())
+        .public_keys()
+        .public_key();
+    let preset = SectionPreset {
+        prefix: Prefix::default(),
+        secret_key_poly,
+        chain: SectionProofChain::new(pk),
+        members: vec![PresetMember {
+            name: rand::random(),
+            addr: gen_addr(),
+            age: MIN_AGE + 1,
+            is_elder: true,
+        }],
+    };
+    let config = Config {
+        transport_config: qp2p::Config {
+            ip: Some(Ipv4Addr::LOCALHOST.into()),
+            ..Default::default()
+        },
+        ..Default::default()
+    };
+
+    assert_matches!(
+        Routing::new_with_preset_section(config, preset).await.err(),
+        Some(Error::InvalidState)
+    );
+}
+
+#[tokio::test]
+async fn metrics_snapshot() -> Result<()> {
+    let (elders_info, mut nodes) = create_elders_info();
+    let sk_set = SecretKeySet::random();
+    let sender = nodes.remove(1);
+    let (section, section_key_share) = create_section(&sk_set, &elders_info)?;
+    let (event_tx, mut event_rx) = mpsc::unbounded_channel();
+    let node = nodes.remove(0);
+    let our_name = node.name();
+    let state = Approved::new(node, section, Some(section_key_share), event_tx)
+        .with_strict_proof_verification(true);
+    let stage = Stage::new(state, create_comm()?);
+
+    // Add some adults.
+    for age in MIN_AGE + 1..MIN_AGE + 4 {
+        let peer = create_peer().with_age(age);
+        let _ = handle_online_command(&peer, &sk_set, &stage, &elders_info).await?;
+    }
+
+    // Receive some messages with untrusted proof chains, which get dropped.
+    let untrusted_chain = SectionProofChain::new(bls::SecretKey::random().public_key());
+    for index in 0..3u8 {
+        let message = Message::single_src(
+            &sender,
+            DstLocation::Node(our_name),
+            Variant::UserMessage(Bytes::from(vec![index])),
+            Some(untrusted_chain.clone()),
+            None,
+        )?;
+        let _ = stage
+            .handle_command(Command::HandleMessage {
+                sender: Some(sender.addr),
+                message,
+            })
+            .await?;
+    }
+
+    let mut dropped = 0;
+    while let Ok(event) = event_rx.try_recv() {
+        if let Event::MessageDropped { .. } = event {
+            dropped += 1;
+        }
+    }
+
+    let routing = Routing {
+        stage: Arc::new(stage),
+    };
+    let metrics = routing.metrics_snapshot().await;
+
+    assert_eq!(dropped, 3);
+    assert_eq!(metrics.messages_dropped, dropped);
+    assert_eq!(metrics.connection_cache, routing.connection_cache_stats());
+    assert_eq!(metrics.send_failures, routing.recent_send_failures().len());
+    assert_eq!(metrics.unreachable_peers, routing.unreachable_peers().len());
+    assert_eq!(
+        metrics.buffered_connections,
+        routing.connection_buffer_usage().len()
+    );
+
+    assert_eq!(metrics.section.elders, routing.our_elders().await.len());
+    assert_eq!(metrics.section.adults, routing.our_adults().await.len());
+    assert_eq!(metrics.section.members, ELDER_SIZE + 3);
+    assert_eq!(metrics.section.min_age, MIN_AGE + 1);
+    assert_eq!(metrics.section.max_age, MIN_AGE + 3);
+    assert!(metrics.section.mean_age >= f64::from(MIN_AGE + 1));
+    assert!(metrics.section.mean_age <= f64::from(MIN_AGE + 3));
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn validate_peer_addr() -> Result<()> {
+    let create_routing = |allow_loopback_addrs| -> Result<_> {
+        let node = create_node();
+        let state = Approved::first_node(node, mpsc::unbounded_channel().0)?;
+        let stage =
+            Stage::new(state, create_comm()?).with_loopback_addrs_allowed(allow_loopback_addrs);
+        Ok(Routing {
+            stage: Arc::new(stage),
+        })
+    };
+
+    let routing = create_routing(false)?;
+
+    let addr: SocketAddr = "203.0.113.7:12000".parse()?;
+    assert_eq!(routing.validate_peer_addr(addr)?, addr);
+    let addr: SocketAddr = "[2001:db8::1]:12000".parse()?;
+    assert_eq!(routing.validate_peer_addr(addr)?, addr);
+
+    for addr in &[
+        "0.0.0.0:12000",
+        "[::]:12000",
+        "224.0.0.1:12000",
+        "[ff02::1]:12000",
+        "255.255.255.255:12000",
+        "203.0.113.7:0",
+        "127.0.0.1:12000",
+        "[::1]:12000",
+    ] {
+        assert_matches!(
+            routing.validate_peer_addr(addr.parse()?),
+            Err(Error::InvalidPeerAddr)
+        );
+    }
+
+    let routing = create_routing(true)?;
+    let addr: SocketAddr = "127.0.0.1:12000".parse()?;
+    assert_eq!(routing.validate_peer_addr(addr)?, addr);
+    assert_matches!(
+        routing.validate_peer_addr("0.0.0.0:12000".parse()?),
+        Err(Error::InvalidPeerAddr)
+    );
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn membership_events() -> Result<()> {
+    let (elders_info, mut nodes) = create_elders_info();
+    let sk_set = SecretKeySet::random();
+    let (section, section_key_share) = create_section(&sk_set, &elders_info)?;
+    let state = Approved::new(
+        nodes.remove(0),
+        section,
+        Some(section_key_share),
+        mpsc::unbounded_channel().0,
+    );
+    let routing = Routing {
+        stage: Arc::new(Stage::new(state, create_comm()?)),
+    };
+    let mut membership_events = routing.membership_events().await;
+
+    async fn handle_offline(
+        stage: &Stage,
+        sk_set: &SecretKeySet,
+        member_info: MemberInfo,
+    ) -> Result<Vec<Command>> {
+        let vote = Vote::Offline(member_info);
+        let proof = prove(sk_set.secret_key(), &vote.as_signable())?;
+        Ok(stage
+            .handle_command(Command::HandleConsensus { vote, proof })
+            .await?)
+    }
+
+    // Join
+    let peer = create_peer();
+    let _ = handle_online_command(&peer, &sk_set, &routing.stage, &elders_info).await?;
+    assert_eq!(
+        membership_events.next().await,
+        Some(MembershipEvent::Joined {
+            name: *peer.name(),
+            age: peer.age(),
+        })
+    );
+
+    // Leave
+    let _ = handle_offline(&routing.stage, &sk_set, MemberInfo::joined(peer).leave()?).await?;
+    assert_eq!(
+        membership_events.next().await,
+        Some(MembershipEvent::Left {
+            name: *peer.name(),
+            age: peer.age(),
+        })
+    );
+
+    // Relocation out
+    let peer = create_peer();
+    let _ = handle_online_command(&peer, &sk_set, &routing.stage, &elders_info).await?;
+    assert_matches!(
+        membership_events.next().await,
+        Some(MembershipEvent::Joined { .. })
+    );
+
+    let destination = rand::random();
+    let _ = handle_offline(
+        &routing.stage,
+        &sk_set,
+        MemberInfo::joined(peer).relocate(destination),
+    )
+    .await?;
+    assert_eq!(
+        membership_events.next().await,
+        Some(MembershipEvent::RelocatingOut {
+            name: *peer.name(),
+            age: peer.age(),
+            destination,
+        })
+    );
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn announce_network_wide() -> Result<()> {
+    let p0 = Prefix::default().pushed(false);
+    let p10 = Prefix::default().pushed(true).pushed(false);
+    let p11 = Prefix::default().pushed(true).pushed(true);
+
+    let (elders_info, mut nodes) = gen_elders_info(p0, ELDER_SIZE);
+    let sk_set = SecretKeySet::random();
+    let (section, section_key_share) = create_section(&sk_set, &elders_info)?;
+    let state = Approved::new(
+        nodes.remove(0),
+        section,
+        Some(section_key_share),
+        mpsc::unbounded_channel().0,
+    );
+    let (tx, _rx) = mpsc::channel(1);
+    let comm = Comm::new(
+        qp2p::Config {
+            ip: Some(Ipv4Addr::LOCALHOST.into()),
+            // Makes the sends to the unreachable elders fail fast.
+            idle_timeout_msec: Some(1),
+            ..Default::default()
+        },
+        tx,
+    )?;
+    let routing = Routing {
+        stage: Arc::new(Stage::new(state, comm)),
+    };
+
+    for prefix in &[p10, p11] {
+        let (elders_info, _) = gen_elders_info(*prefix, ELDER_SIZE);
+        let vote = Vote::SectionInfo(elders_info);
+        let proof = prove(&bls::SecretKey::random(), &vote.as_signable())?;
+        let _ = routing
+            .stage
+            .handle_command(Command::HandleConsensus { vote, proof })
+            .await?;
+    }
+
+    let reports: HashMap<_, _> = routing
+        .announce_network_wide(Bytes::from_static(b"hello"))
+        .await
+        .collect()
+        .await;
+
+    // We don't send to ourselves, only to the other elders of our section.
+    let expected: HashMap<_, _> = vec![(p0, ELDER_SIZE - 1), (p10, ELDER_SIZE), (p11, ELDER_SIZE)]
+        .into_iter()
+        .collect();
+    assert_eq!(reports.len(), expected.len());
+
+    for (prefix, report) in reports {
+        assert_eq!(report.recipients, expected[&prefix]);
+        // All the elders are unreachable.
+        assert_eq!(report.delivered, 0);
+        assert_eq!(report.failed.len(), report.recipients);
+    }
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn routable_section_count() -> Result<()> {
+    let p0 = Prefix::default().pushed(false);
+    let p10 = Prefix::default().pushed(true).pushed(false);
+    let p11 = Prefix::default().pushed(true).pushed(true);
+
+    let (elders_info, mut nodes) = gen_elders_info(p0, ELDER_SIZE);
+    let sk_set = SecretKeySet::random();
+    let (section, section_key_share) = create_section(&sk_set, &elders_info)?;
+    let state = Approved::new(
+        nodes.remove(0),
+        section,
+        Some(section_key_share),
+        mpsc::unbounded_channel().0,
+    );
+    let routing = Routing {
+        stage: Arc::new(Stage::new(state, create_comm()?)),
+    };
+
+    let update_neighbour = |elders_info: EldersInfo| {
+        let stage = routing.stage.clone();
+        let vote = Vote::SectionInfo(elders_info);
+        let proof = prove(sk_set.secret_key(), &vote.as_signable());
+        async move {
+            stage
+                .handle_command(Command::HandleConsensus {
+                    vote,
+                    proof: proof?,
+                })
+                .await
+        }
+    };
+
+    assert_eq!(routing.routable_section_count().await, 1);
+
+    for prefix in &[p10, p11] {
+        let _ = update_neighbour(gen_elders_info(*prefix, ELDER_SIZE).0).await?;
+    }
+    assert_eq!(routing.routable_section_count().await, 3);
+
+    // We lose track of the elders of one of the neighbours, but still know of it.
+    let _ = update_neighbour(EldersInfo::new(iter::empty(), p11)).await?;
+    assert_eq!(routing.neighbour_sections().await.len(), 2);
+    assert_eq!(routing.routable_section_count().await, 2);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn section_override() -> Result<()> {
+    let p0 = Prefix::default().pushed(false);
+    let p1 = Prefix::default().pushed(true);
+
+    let (elders_info0, mut nodes) = gen_elders_info(p0, ELDER_SIZE);
+    let node = nodes.remove(0);
+    let sk_set = SecretKeySet::random();
+    let (elders_info1, _) = gen_elders_info(p1, ELDER_SIZE);
+
+    let dst = DstLocation::Section(p0.substituted_in(rand::random()));
+
+    for section_override in vec![None, Some(SectionOverride::new(move |_| p1))] {
+        let overridden = section_override.is_some();
+        let (section, section_key_share) = create_section(&sk_set, &elders_info0)?;
+        let state = Approved::new(
+            node.clone(),
+            section,
+            Some(section_key_share),
+            mpsc::unbounded_channel().0,
+        )
+        .with_section_override(section_override.map(Arc::new));
+        let stage = Stage::new(state, create_comm()?);
+
+        let vote = Vote::SectionInfo(elders_info1.clone());
+        let proof = prove(&bls::SecretKey::random(), &vote.as_signable())?;
+        let _ = stage
+            .handle_command(Command::HandleConsensus { vote, proof })
+            .await?;
+
+        let commands = stage
+            .handle_command(Command::SendUserMessage {
+                src: SrcLocation::Node(node.name()),
+                dst,
+                content: Bytes::from_static(b"hello"),
+            })
+            .await?;
+
+        // The destination is in our section, so we handle the message too.
+        let recipients = assert_matches!(
+            &commands[..],
+            [Command::HandleMessage { .. }, Command::SendMessage { recipients, .. }] => recipients
+        );
+        let recipients: HashSet<_> = recipients.iter().copied().collect();
+
+        // The destination name belongs to our section, but the override sends the message to the
+        // elders of the other one instead.
+        let expected: HashSet<_> = if overridden {
+            elders_info1.peers().map(|peer| *peer.addr()).collect()
+        } else {
+            elders_info0
+                .peers()
+                .filter(|peer| peer.name() != &node.name())
+                .map(|peer| *peer.addr())
+                .collect()
+        };
+        assert_eq!(recipients, expected);
+    }
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn send_message_with_retry() -> Result<()> {
+    let p0 = Prefix::default().pushed(false);
+    let p1 = Prefix::default().pushed(true);
+
+    let (elders_info, mut nodes) = gen_elders_info(p0, ELDER_SIZE);
+    let sk_set = SecretKeySet::random();
+    let (section, section_key_share) = create_section(&sk_set, &elders_info)?;
+    let node = nodes.remove(0);
+    let our_name = node.name();
+    let state = Approved::new(
+        node,
+        section,
+        Some(section_key_share),
+        mpsc::unbounded_channel().0,
+    );
+    let (tx, _rx) = mpsc::channel(1);
+    let comm = Comm::new(
+        qp2p::Config {
+            ip: Some(Ipv4Addr::LOCALHOST.into()),
+            // Makes the sends to the unreachable elders fail fast.
+            idle_timeout_msec: Some(1),
+            ..Default::default()
+        },
+        tx,
+    )?;
+    let routing = Routing {
+        stage: Arc::new(Stage::new(state, comm)),
+    };
+
+    let update_neighbour = |elders_info: EldersInfo| {
+        let stage = routing.stage.clone();
+        async move {
+            let vote = Vote::SectionInfo(elders_info);
+            let proof = prove(&bls::SecretKey::random(), &vote.as_signable())?;
+            stage
+                .handle_command(Command::HandleConsensus { vote, proof })
+                .await
+        }
+    };
+
+    // The elders of the neighbour section we know of are all unreachable. Keep their sockets
+    // bound, but never respond on them.
+    let sockets = (0..ELDER_SIZE)
+        .map(|_| std::net::UdpSocket::bind((Ipv4Addr::LOCALHOST, 0)))
+        .collect::<Result<Vec<_>, _>>()?;
+    let unreachable_elders = sockets
+        .iter()
+        .map(|socket| {
+            Ok(Peer::new(
+                p1.substituted_in(rand::random()),
+                socket.local_addr()?,
+                MIN_AGE + 1,
+            ))
+        })
+        .collect::<Result<Vec<_>>>()?;
+    let _ = update_neighbour(EldersInfo::new(unreachable_elders, p1)).await?;
+
+    // The neighbour section is about to change its elders to this single reachable one.
+    let (peer_tx, mut peer_rx) = mpsc::channel(1);
+    let peer_comm = Comm::new(
+        qp2p::Config {
+            ip: Some(Ipv4Addr::LOCALHOST.into()),
+            ..Default::default()
+        },
+        peer_tx,
+    )?;
+    let new_elder = Peer::new(
+        p1.substituted_in(rand::random()),
+        peer_comm.our_connection_info().await?,
+        MIN_AGE + 1,
+    );
+
+    let policy = RetryPolicy {
+        max_rounds: 2,
+        initial_backoff: Duration::from_secs(1),
+    };
+    let (result, update_result) = futures::future::join(
+        routing.send_message_with_retry(
+            SrcLocation::Node(our_name),
+            DstLocation::Section(p1.substituted_in(rand::random())),
+            Bytes::from_static(b"hello"),
+            policy,
+        ),
+        async {
+            // Update our knowledge while the first attempt fails.
+            tokio::time::delay_for(policy.initial_backoff / 5).await;
+            update_neighbour(EldersInfo::new(iter::once(new_elder), p1)).await
+        },
+    )
+    .await;
+    let _ = update_result?;
+    result?;
+
+    let received = tokio::time::timeout(Duration::from_secs(5), async {
+        while let Some(event) = peer_rx.recv().await {
+            if let ConnectionEvent::Received(_) = event {
+                return true;
+            }
+        }
+
+        false
+    })
+    .await?;
+    assert!(received);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn verify_member_claim() -> Result<()> {
+    let (elders_info, mut nodes) = create_elders_info();
+    let sk_set = SecretKeySet::random();
+    let (section, section_key_share) = create_section(&sk_set, &elders_info)?;
+    let state = Approved::new(
+        nodes.remove(0),
+        section,
+        Some(section_key_share),
+        mpsc::unbounded_channel().0,
+    );
+    let routing = Routing {
+        stage: Arc::new(Stage::new(state, create_comm()?)),
+    };
+
+    let adult = create_peer().with_age(MIN_AGE + 6);
+    let _ = handle_online_command(&adult, &sk_set, &routing.stage, &elders_info).await?;
+
+    assert!(
+        routing
+            .verify_member_claim(*adult.name(), adult.age())
+            .await
+    );
+    assert!(
+        !routing
+            .verify_member_claim(*adult.name(), adult.age() + 1)
+            .await
+    );
+    assert!(
+        !routing
+            .verify_member_claim(rand::random(), adult.age())
+            .await
+    );
+
+    let elder = nodes[0].peer();
+    assert!(
+        routing
+            .verify_member_claim(*elder.name(), elder.age())
+            .await
+    );
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn routing_digest() -> Result<()> {
+    let p0 = Prefix::default().pushed(false);
+    let p1 = Prefix::default().pushed(true);
+
+    let sk_set = SecretKeySet::random();
+    let (elders_info, mut nodes) = gen_elders_info(p0, ELDER_SIZE);
+    let (section, _) = create_section(&sk_set, &elders_info)?;
+
+    let create_routing = |node: Node, index| -> Result<_> {
+        let state = Approved::new(
+            node,
+            section.clone(),
+            Some(create_section_key_share(&sk_set, index)),
+            mpsc::unbounded_channel().0,
+        );
+        Ok(Routing {
+            stage: Arc::new(Stage::new(state, create_comm()?)),
+        })
+    };
+    let routing0 = create_routing(nodes.remove(0), 0)?;
+    let routing1 = create_routing(nodes.remove(0), 1)?;
+
+    // Only the first node knows the key of the neighbour section.
+    let their_sk = bls::SecretKey::random();
+    let their_key = their_sk.public_key();
+    let vote = Vote::TheirKey {
+        prefix: p1,
+        key: their_key,
+    };
+    let proof = prove(sk_set.secret_key(), &vote.as_signable())?;
+    let _ = routing0
+        .stage
+        .handle_command(Command::HandleConsensus { vote, proof })
+        .await?;
+
+    let digest = routing0.routing_digest().await;
+    assert_eq!(digest.prefix, p0);
+    assert_eq!(digest.key, sk_set.secret_key().public_key());
+
+    // Round-trip the digest through serialisation, as if gossiped.
+    let digest: RoutingDigest = bincode::deserialize(&bincode::serialize(&digest)?)?;
+
+    assert!(routing1.routing_digest().await.keys.is_empty());
+    routing1.apply_digest(digest.clone()).await?;
+
+    let keys: Vec<_> = routing1
+        .routing_digest()
+        .await
+        .keys
+        .into_iter()
+        .map(|key| key.value)
+        .collect();
+    assert_eq!(keys, [(p1, their_key)]);
+
+    // A tampered key is rejected.
+    let mut tampered_digest = digest.clone();
+    tampered_digest.keys[0].value.1 = bls::SecretKey::random().public_key();
+    assert_matches!(
+        routing1.apply_digest(tampered_digest).await,
+        Err(Error::FailedSignature)
+    );
+
+    // The neighbour can't vouch for the key of a third section, nor can anyone for an ancestor of
+    // our section, even signed by our own key.
+    let third_prefix = p1.pushed(false);
+    let digest = RoutingDigest {
+        keys: vec![
+            proven(
+                &their_sk,
+                (third_prefix, bls::SecretKey::random().public_key()),
+            )?,
+            proven(
+                sk_set.secret_key(),
+                (Prefix::default(), bls::SecretKey::random().public_key()),
+            )?,
+        ],
+        ..digest
+    };
+    routing1.apply_digest(digest).await?;
+
+    let prefixes: Vec<_> = routing1
+        .routing_digest()
+        .await
+        .keys
+        .into_iter()
+        .map(|key| key.value.0)
+        .collect();
+    assert_eq!(prefixes, [p1]);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn per_prefix_rate_limit() -> Result<()> {
+    let p0 = Prefix::default().pushed(false);
+    let p1 = Prefix::default().pushed(true);
+
+    let (elders_info, mut nodes) = gen_elders_info(p0, ELDER_SIZE);
+    let sk_set = SecretKeySet::random();
+    let (section, section_key_share) = create_section(&sk_set, &elders_info)?;
+    let node = nodes.remove(0);
+    let src = SrcLocation::Node(node.name());
+    let state = Approved::new(
+        node,
+        section,
+        Some(section_key_share),
+        mpsc::unbounded_channel().0,
+    );
+    let limit = RateLimit {
+        burst: 3,
+        per_second: 0.001,
+    };
+    let stage = Stage::new(state, create_comm()?).with_per_prefix_rate_limit(Some(limit));
+
+    let (neighbour_info, _) = gen_elders_info(p1, ELDER_SIZE);
+    let vote = Vote::SectionInfo(neighbour_info);
+    let proof = prove(&bls::SecretKey::random(), &vote.as_signable())?;
+    let _ = stage
+        .handle_command(Command::HandleConsensus { vote, proof })
+        .await?;
+
+    let send = |prefix: Prefix| {
+        stage.handle_command(Command::SendUserMessage {
+            src,
+            dst: DstLocation::Section(prefix.substituted_in(rand::random())),
+            content: Bytes::from_static(b"hello"),
+        })
+    };
+
+    // The burst toward the neighbour goes through, the excess is rejected.
+    for _ in 0..limit.burst {
+        let _ = send(p1).await?;
+    }
+    assert_matches!(send(p1).await, Err(Error::RateLimited));
+    assert_matches!(send(p1).await, Err(Error::RateLimited));
+
+    // So are the other kinds of sends toward the neighbour.
+    let result = stage
+        .handle_command(Command::SendToward {
+            target: p1.substituted_in(rand::random()),
+            fanout: 1,
+            content: Bytes::from_static(b"hello"),
+        })
+        .await;
+    assert_matches!(result, Err(Error::RateLimited));
+
+    let result = stage
+        .handle_command(Command::SendToName {
+            name: p1.substituted_in(rand::random()),
+            content: Bytes::from_static(b"hello"),
+        })
+        .await;
+    assert_matches!(result, Err(Error::RateLimited));
+
+    // Sends toward another section are not affected.
+    for _ in 0..limit.burst {
+        let _ = send(p0).await?;
+    }
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn relocation_history() -> Result<()> {
+    let sk_set = SecretKeySet::random();
+
+    let prefix: Prefix = "0".parse().unwrap();
+    let (elders_info, mut nodes) = gen_elders_info(prefix, ELDER_SIZE);
+    let (mut section, section_key_share) = create_section(&sk_set, &elders_info)?;
+
+    let non_elder_peer = create_peer();
+    let member_info = proven(sk_set.secret_key(), MemberInfo::joined(non_elder_peer))?;
+    assert!(section.update_member(member_info));
+
+    let state = Approved::new(
+        nodes.remove(0),
+        section,
+        Some(section_key_share),
+        mpsc::unbounded_channel().0,
+    );
+    let routing = Routing {
+        stage: Arc::new(Stage::new(state, create_comm()?)),
+    };
+
+    assert!(routing.relocation_history().await.is_empty());
+
+    let mut churn_names = vec![];
+    for _ in 0..2 {
+        let (vote, proof) = create_relocation_trigger(sk_set.secret_key(), non_elder_peer.age())?;
+        if let Vote::Online { member_info, .. } = &vote {
+            churn_names.push(*member_info.peer.name());
+        }
+
+        let _ = routing
+            .stage
+            .handle_command(Command::HandleConsensus { vote, proof })
+            .await?;
+    }
+
+    let history = routing.relocation_history().await;
+    assert_eq!(history.len(), churn_names.len());
+
+    for (record, churn_name) in history.iter().zip(&churn_names) {
+        assert_eq!(record.relocating_name, *non_elder_peer.name());
+        assert_eq!(record.churn_name, *churn_name);
+        assert_eq!(
+            record.destination,
+            relocation::destination(non_elder_peer.name(), churn_name)
+        );
+    }
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn require_signed_messages() -> Result<()> {
+    let our_prefix = Prefix::default().pushed(false);
+    let their_prefix = Prefix::default().pushed(true);
+
+    let sk_set = SecretKeySet::random();
+    let (elders_info, _) = gen_elders_info(our_prefix, ELDER_SIZE);
+    let (section, _) = create_section(&sk_set, &elders_info)?;
+
+    let node = create_node();
+    let node_name = node.name();
+    let (event_tx, mut event_rx) = mpsc::unbounded_channel();
+    // Without requiring signed messages, this policy would let the spoofed message below through.
+    let state = Approved::new(node, section, None, event_tx)
+        .with_unknown_section_policy(UnknownSectionPolicy::AcceptUnverified)
+        .with_signed_messages_required(true);
+    let stage = Stage::new(state, create_comm()?);
+
+    // Message correctly signed by the sending node.
+    let sender = create_node();
+    let message = Message::single_src(
+        &sender,
+        DstLocation::Node(node_name),
+        Variant::UserMessage(Bytes::from_static(b"signed")),
+        None,
+        None,
+    )?;
+    let _ = stage
+        .handle_command(Command::HandleMessage {
+            message,
+            sender: Some(sender.addr),
+        })
+        .await?;
+
+    assert_matches!(
+        event_rx.try_recv(),
+        Ok(Event::MessageReceived { content, src: SrcLocation::Node(name), .. }) => {
+            assert_eq!(content, Bytes::from_static(b"signed"));
+            assert_eq!(name, sender.name());
+        }
+    );
+
+    // Message claiming to come from a section, but not signed by its key.
+    let their_pk = bls::SecretKey::random().public_key();
+    let message = PlainMessage {
+        src: their_prefix,
+        dst: DstLocation::Node(node_name),
+        dst_key: sk_set.secret_key().public_key(),
+        variant: Variant::UserMessage(Bytes::from_static(b"spoofed")),
+    };
+    let signature = bls::SecretKey::random().sign(&bincode::serialize(&message.as_signable())?);
+    let message = Message::section_src(message, signature, SectionProofChain::new(their_pk))?;
+    let _ = stage
+        .handle_command(Command::HandleMessage {
+            message,
+            sender: Some(gen_addr()),
+        })
+        .await;
+
+    assert_matches!(
+        event_rx.try_recv(),
+        Ok(Event::MessageDropped {
+            reason: DropReason::Unsigned,
+            src: Some(SrcLocation::Section(prefix)),
+        }) => assert_eq!(prefix, their_prefix)
+    );
+
+    // Message signed by a made up section key, proven by a chain consisting of that key only.
+    let their_sk = bls::SecretKey::random();
+    let message = PlainMessage {
+        src: their_prefix,
+        dst: DstLocation::Node(node_name),
+        dst_key: sk_set.secret_key().public_key(),
+        variant: Variant::UserMessage(Bytes::from_static(b"self-signed")),
+    };
+    let signature = their_sk.sign(&bincode::serialize(&message.as_signable())?);
+    let message = Message::section_src(
+        message,
+        signature,
+        SectionProofChain::new(their_sk.public_key()),
+    )?;
+    let _ = stage
+        .handle_command(Command::HandleMessage {
+            message,
+            sender: Some(gen_addr()),
+        })
+        .await;
+
+    assert_matches!(
+        event_rx.try_recv(),
+        Ok(Event::MessageDropped {
+            reason: DropReason::Unsigned,
+            src: Some(SrcLocation::Section(prefix)),
+        }) => assert_eq!(prefix, their_prefix)
+    );
+    assert!(event_rx.try_recv().is_err());
+
+    Ok(())
+}
+
 // TODO: add more tests here
 
 fn create_peer() -> Peer {
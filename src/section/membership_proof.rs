@@ -0,0 +1,53 @@
+// Copyright 2020 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under The General Public License (GPL), version 3.
+// Unless required by applicable law or agreed to in writing, the SAFE Network Software distributed
+// under the GPL Licence is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied. Please review the Licences for the specific language governing
+// permissions and limitations relating to use of the SAFE Network Software.
+
+use super::{MemberInfo, PeerState, SectionProofChain};
+use crate::consensus::Proven;
+use serde::{Deserialize, Serialize};
+use xor_name::XorName;
+
+/// Proof that a node is a member of its section with a given age. It can be verified by anyone who
+/// knows the genesis key of the network.
+#[derive(Clone, Debug, Eq, PartialEq, Serialize, Deserialize)]
+pub struct MembershipProof {
+    // Member info of the node, signed by its section.
+    member_info: Proven<MemberInfo>,
+    // Section chain from the genesis key to the key the member info is signed with.
+    chain: SectionProofChain,
+}
+
+impl MembershipProof {
+    pub(crate) fn new(member_info: Proven<MemberInfo>, chain: SectionProofChain) -> Self {
+        Self { member_info, chain }
+    }
+
+    /// Name of the node.
+    pub fn name(&self) -> &XorName {
+        self.member_info.value.peer.name()
+    }
+
+    /// Age of the node.
+    pub fn age(&self) -> u8 {
+        self.member_info.value.peer.age()
+    }
+
+    /// Key of the section that signed the membership.
+    pub fn section_key(&self) -> &bls::PublicKey {
+        &self.member_info.proof.public_key
+    }
+
+    /// Returns whether the node is a current member of its section as signed by a section key
+    /// that is rooted in `genesis_key` via a valid chain.
+    pub fn verify(&self, genesis_key: &bls::PublicKey) -> bool {
+        self.member_info.value.state == PeerState::Joined
+            && self.chain.first_key() == genesis_key
+            && self.chain.last_key() == self.section_key()
+            && self.chain.self_verify()
+            && self.member_info.self_verify()
+    }
+}
@@ -8,6 +8,7 @@
 
 mod elders_info;
 mod member_info;
+mod membership_proof;
 mod section_keys;
 mod section_peers;
 mod section_proof_chain;
@@ -18,12 +19,15 @@ pub(crate) use self::section_peers::SectionPeers;
 pub use self::{
     elders_info::EldersInfo,
     member_info::{MemberInfo, PeerState, MIN_AGE},
+    membership_proof::MembershipProof,
     section_keys::{SectionKeyShare, SectionKeysProvider},
-    section_proof_chain::{ExtendError, SectionProofChain, TrustStatus},
+    section_proof_chain::{
+        ExtendError, SectionProofBlock, SectionProofChain, StructureError, TrustStatus,
+    },
 };
 
 use crate::{
-    consensus::Proven,
+    consensus::{Proven, Quorum},
     error::{Error, Result},
     peer::Peer,
     ELDER_SIZE, RECOMMENDED_SECTION_SIZE,
@@ -207,6 +211,12 @@ impl Section {
         self.chain.extend(new_first_key, full_chain)
     }
 
+    // Compacts our proof chain to at most `max_blocks` blocks after the first key (see
+    // `SectionProofChain::compact`).
+    pub(crate) fn compact_chain(&mut self, max_blocks: usize) {
+        self.chain.compact(max_blocks)
+    }
+
     // Creates the shortest proof chain that includes both the key at `their_knowledge`
     // (if provided) and the key our current `elders_info` was signed with.
     pub fn create_proof_chain_for_our_info(
@@ -230,20 +240,51 @@ impl Section {
         self.elders_info().elders.contains_key(name)
     }
 
-    /// Generate a new section info(s) based on the current set of members.
+    /// Generate a new section info(s) based on the current set of members. Members younger than
+    /// `min_elder_age` are only promoted if there aren't enough members old enough. A change that
+    /// would leave fewer elders than `quorum` of the current ones is refused.
     /// Returns a set of EldersInfos to vote for.
-    pub fn promote_and_demote_elders(&self, our_name: &XorName) -> Vec<EldersInfo> {
-        if let Some((our_info, other_info)) = self.try_split(our_name) {
+    pub fn promote_and_demote_elders(
+        &self,
+        our_name: &XorName,
+        min_elder_age: u8,
+        quorum: Quorum,
+    ) -> Vec<EldersInfo> {
+        self.promote_and_demote_elders_excluding(our_name, min_elder_age, quorum, &BTreeSet::new())
+    }
+
+    /// Same as `promote_and_demote_elders` but avoids picking any of the `excluded` nodes as
+    /// elders, unless there are not enough other candidates to replace them.
+    pub fn promote_and_demote_elders_excluding(
+        &self,
+        our_name: &XorName,
+        min_elder_age: u8,
+        quorum: Quorum,
+        excluded: &BTreeSet<XorName>,
+    ) -> Vec<EldersInfo> {
+        if let Some((our_info, other_info)) = self.try_split(our_name, min_elder_age) {
             return vec![our_info, other_info];
         }
 
-        let expected_peers = self.elder_candidates(ELDER_SIZE);
+        let mut expected_peers = self.elder_candidates(ELDER_SIZE, min_elder_age);
+        if !excluded.is_empty() {
+            let filtered: Vec<_> = self
+                .elder_candidates(ELDER_SIZE + excluded.len(), min_elder_age)
+                .into_iter()
+                .filter(|peer| !excluded.contains(peer.name()))
+                .take(ELDER_SIZE)
+                .collect();
+            if filtered.len() == expected_peers.len() {
+                expected_peers = filtered;
+            }
+        }
+
         let expected_names: BTreeSet<_> = expected_peers.iter().map(Peer::name).collect();
         let current_names: BTreeSet<_> = self.elders_info().elders.keys().collect();
 
         if expected_names == current_names {
             vec![]
-        } else if expected_names.len() < crate::majority(current_names.len()) {
+        } else if expected_names.len() < quorum.count(current_names.len()) {
             warn!("ignore attempt to reduce the number of elders too much");
             vec![]
         } else {
@@ -297,7 +338,7 @@ impl Section {
     // Tries to split our section.
     // If we have enough mature nodes for both subsections, returns the elders infos of the two
     // subsections. Otherwise returns `None`.
-    fn try_split(&self, our_name: &XorName) -> Option<(EldersInfo, EldersInfo)> {
+    fn try_split(&self, our_name: &XorName, min_elder_age: u8) -> Option<(EldersInfo, EldersInfo)> {
         let next_bit_index = if let Ok(index) = self.prefix().bit_count().try_into() {
             index
         } else {
@@ -330,11 +371,13 @@ impl Section {
         let our_elders = self.members.elder_candidates_matching_prefix(
             &our_prefix,
             ELDER_SIZE,
+            min_elder_age,
             self.elders_info(),
         );
         let other_elders = self.members.elder_candidates_matching_prefix(
             &other_prefix,
             ELDER_SIZE,
+            min_elder_age,
             self.elders_info(),
         );
 
@@ -346,9 +389,9 @@ impl Section {
 
     // Returns the candidates for elders out of all the nodes in the section, even out of the
     // relocating nodes if there would not be enough instead.
-    fn elder_candidates(&self, elder_size: usize) -> Vec<Peer> {
+    fn elder_candidates(&self, elder_size: usize, min_elder_age: u8) -> Vec<Peer> {
         self.members
-            .elder_candidates(elder_size, self.elders_info())
+            .elder_candidates(elder_size, min_elder_age, self.elders_info())
     }
 }
 
@@ -6,6 +6,7 @@
 // KIND, either express or implied. Please review the Licences for the specific language governing
 // permissions and limitations relating to use of the SAFE Network Software.
 
+use super::EldersInfo;
 use crate::error::{Error, Result};
 use std::collections::VecDeque;
 
@@ -25,8 +26,8 @@ pub struct SectionKeyShare {
 pub struct SectionKeysProvider {
     /// A cache for current and previous section BLS keys.
     cache: MiniKeyCache,
-    /// The new keys to use when section update completes.
-    pending: Option<SectionKeyShare>,
+    /// The new keys to use when section update completes, with the elders they are for.
+    pending: Option<(EldersInfo, SectionKeyShare)>,
 }
 
 impl SectionKeysProvider {
@@ -37,8 +38,7 @@ impl SectionKeysProvider {
         };
         if let Some(share) = current {
             let public_key = share.public_key_set.public_key();
-            provider.insert_dkg_outcome(share);
-            provider.finalise_dkg(&public_key);
+            let _ = provider.cache.add(&public_key, share);
         }
         provider
     }
@@ -47,6 +47,12 @@ impl SectionKeysProvider {
         self.cache.get_most_recent()
     }
 
+    /// Returns the public key of the current key share. Unlike
+    /// `key_share()?.public_key_set.public_key()` this doesn't need to recompute the key.
+    pub fn public_key(&self) -> Result<&bls::PublicKey> {
+        self.cache.get_most_recent_key()
+    }
+
     pub fn sign_with(
         &self,
         data: &[u8],
@@ -59,17 +65,32 @@ impl SectionKeysProvider {
         self.cache.has_key_share()
     }
 
-    pub fn insert_dkg_outcome(&mut self, share: SectionKeyShare) {
-        self.pending = Some(share);
+    /// Returns the elders the outcome of a DKG whose key isn't in use yet is for, if we hold one.
+    pub fn pending_dkg_elders_info(&self) -> Option<&EldersInfo> {
+        self.pending.as_ref().map(|(elders_info, _)| elders_info)
+    }
+
+    pub fn insert_dkg_outcome(&mut self, elders_info: EldersInfo, share: SectionKeyShare) {
+        self.pending = Some((elders_info, share));
+    }
+
+    /// Discards the pending DKG outcome, if any, because our section switched to a different key.
+    pub fn discard_dkg_outcome(&mut self) {
+        if let Some((_, share)) = self.pending.take() {
+            trace!(
+                "discarded superseded DKG outcome: {:?}",
+                share.public_key_set.public_key()
+            );
+        }
     }
 
     pub fn finalise_dkg(&mut self, public_key: &bls::PublicKey) {
-        if let Some(share) = &self.pending {
+        if let Some((_, share)) = &self.pending {
             if *public_key != share.public_key_set.public_key() {
                 return;
             }
         }
-        if let Some(share) = self.pending.take() {
+        if let Some((_, share)) = self.pending.take() {
             if let Some(evicted) = self.cache.add(public_key, share) {
                 trace!("evicted old key from cache: {:?}", evicted);
             }
@@ -105,6 +126,14 @@ impl MiniKeyCache {
         Err(Error::MissingSecretKeyShare)
     }
 
+    /// Returns the public key of the most recently added key share.
+    pub fn get_most_recent_key(&self) -> Result<&bls::PublicKey> {
+        if let Some((public_key, _)) = self.list.back() {
+            return Ok(public_key);
+        }
+        Err(Error::MissingSecretKeyShare)
+    }
+
     /// Uses the secret key from cache, corresponding to
     /// the provided public key.
     pub fn sign_with(
@@ -62,10 +62,17 @@ impl SectionPeers {
         self.members.get(name)
     }
 
-    /// Returns the candidates for elders out of all the nodes in this section.
-    pub fn elder_candidates(&self, elder_size: usize, current_elders: &EldersInfo) -> Vec<Peer> {
+    /// Returns the candidates for elders out of all the nodes in this section. Nodes younger than
+    /// `min_elder_age` are only promoted if there aren't enough nodes old enough.
+    pub fn elder_candidates(
+        &self,
+        elder_size: usize,
+        min_elder_age: u8,
+        current_elders: &EldersInfo,
+    ) -> Vec<Peer> {
         elder_candidates(
             elder_size,
+            min_elder_age,
             current_elders,
             self.members
                 .values()
@@ -78,10 +85,12 @@ impl SectionPeers {
         &self,
         prefix: &Prefix,
         elder_size: usize,
+        min_elder_age: u8,
         current_elders: &EldersInfo,
     ) -> Vec<Peer> {
         elder_candidates(
             elder_size,
+            min_elder_age,
             current_elders,
             self.members.values().filter(|info| {
                 info.value.state == PeerState::Joined && prefix.matches(info.value.peer.name())
@@ -169,13 +178,30 @@ impl IntoIterator for SectionPeers {
 // Returns the nodes that should become the next elders out of the given members, sorted by names.
 // It is assumed that `members` contains only "active" peers (see the `is_active` function below
 // for explanation)
-fn elder_candidates<'a, I>(elder_size: usize, current_elders: &EldersInfo, members: I) -> Vec<Peer>
+//
+// Members younger than `min_elder_age` are not promoted, but current elders are not demoted just
+// for being too young. Seats left over once all the members old enough are picked are filled with
+// the oldest of the remaining members.
+fn elder_candidates<'a, I>(
+    elder_size: usize,
+    min_elder_age: u8,
+    current_elders: &EldersInfo,
+    members: I,
+) -> Vec<Peer>
 where
     I: IntoIterator<Item = &'a Proven<MemberInfo>>,
 {
+    let is_eligible = |info: &Proven<MemberInfo>| {
+        info.value.peer.age() >= min_elder_age || is_elder(&info.value, current_elders)
+    };
+
     members
         .into_iter()
-        .sorted_by(|lhs, rhs| cmp_elder_candidates(lhs, rhs, current_elders))
+        .sorted_by(|lhs, rhs| {
+            cmp_elder_candidates_by_peer_state(&lhs.value.state, &rhs.value.state)
+                .then_with(|| is_eligible(rhs).cmp(&is_eligible(lhs)))
+                .then_with(|| cmp_elder_candidates(lhs, rhs, current_elders))
+        })
         .map(|info| info.value.peer)
         .take(elder_size)
         .collect()
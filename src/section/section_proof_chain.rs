@@ -6,29 +6,34 @@
 // KIND, either express or implied. Please review the Licences for the specific language governing
 // permissions and limitations relating to use of the SAFE Network Software.
 
-use serde::{Deserialize, Serialize};
+use serde::{ser::SerializeStruct, Deserialize, Serialize, Serializer};
 use std::{
     collections::HashSet,
-    iter, mem,
+    hash::{Hash, Hasher},
+    iter,
     ops::{Bound, RangeBounds},
 };
 use thiserror::Error;
 
 /// Chain of section BLS keys where every key is proven (signed) by the previous key, except the
 /// first one.
-#[derive(Debug, Eq, PartialEq, Clone, Hash, Serialize, Deserialize)]
+#[derive(Debug, Eq, PartialEq, Clone, Hash, Deserialize)]
 pub struct SectionProofChain {
     head: bls::PublicKey,
-    tail: Vec<Block>,
+    tail: Vec<SectionProofBlock>,
+    // Stands for the blocks removed from between `head` and `tail` by `compact`, if any. Local
+    // only: a compacted chain is serialized without `head` and the link (see `Serialize`).
+    #[serde(skip)]
+    link: Option<CompactedLink>,
 }
 
-#[allow(clippy::len_without_is_empty)]
 impl SectionProofChain {
     /// Creates new chain consisting of only one block.
     pub fn new(first: bls::PublicKey) -> Self {
         Self {
             head: first,
             tail: Vec::new(),
+            link: None,
         }
     }
 
@@ -44,7 +49,7 @@ impl SectionProofChain {
             .unwrap_or(false);
 
         if valid {
-            self.tail.push(Block { key, signature });
+            self.tail.push(SectionProofBlock { key, signature });
             true
         } else {
             error!(
@@ -63,7 +68,7 @@ impl SectionProofChain {
         key: bls::PublicKey,
         signature: bls::Signature,
     ) {
-        self.tail.push(Block { key, signature })
+        self.tail.push(SectionProofBlock { key, signature })
     }
 
     /// Returns the first key of the chain.
@@ -76,12 +81,14 @@ impl SectionProofChain {
         self.tail
             .last()
             .map(|block| &block.key)
+            .or_else(|| self.link.as_ref().map(|link| &link.key))
             .unwrap_or(&self.head)
     }
 
-    /// Returns all the keys of the chain as a DoubleEndedIterator.
+    /// Returns all the keys of the chain as a DoubleEndedIterator. If the chain was compacted, the
+    /// removed keys are skipped, except the last one.
     pub fn keys(&self) -> impl DoubleEndedIterator<Item = &bls::PublicKey> {
-        iter::once(&self.head).chain(self.tail.iter().map(|block| &block.key))
+        self.indexed_keys().map(|(_, key)| key)
     }
 
     /// Returns whether this chain contains the given key.
@@ -91,90 +98,203 @@ impl SectionProofChain {
 
     /// Returns the index of the key in the chain or `None` if not present in the chain.
     pub fn index_of(&self, key: &bls::PublicKey) -> Option<u64> {
-        self.keys()
-            .position(|existing_key| existing_key == key)
-            .map(|index| index as u64)
+        self.indexed_keys()
+            .find(|(_, existing_key)| *existing_key == key)
+            .map(|(index, _)| index)
     }
 
     /// Returns a subset of this chain specified by the given index range.
     ///
     /// Note: unlike `std::slice`, if the range is invalid or out of bounds, it is silently adjusted
-    /// to the nearest valid range and so this function never panics.
+    /// to the nearest valid range and so this function never panics. This includes ranges that
+    /// start or end at a key removed by compaction.
     pub fn slice<B: RangeBounds<u64>>(&self, range: B) -> Self {
+        let last_index = self.last_key_index();
+
         let start = match range.start_bound() {
-            Bound::Included(index) => *index as usize,
-            Bound::Excluded(index) => *index as usize + 1,
+            Bound::Included(index) => *index,
+            Bound::Excluded(index) => *index + 1,
             Bound::Unbounded => 0,
         };
 
         let end = match range.end_bound() {
-            Bound::Included(index) => *index as usize + 1,
-            Bound::Excluded(index) => *index as usize,
-            Bound::Unbounded => self.tail.len() + 1,
+            Bound::Included(index) => *index + 1,
+            Bound::Excluded(index) => *index,
+            Bound::Unbounded => last_index + 1,
         };
 
-        let start = start.min(self.tail.len());
-        let end = end.min(self.tail.len() + 1).max(start + 1);
+        let link_index = self.link.as_ref().map_or(0, |link| link.index);
+
+        let mut start = start.min(last_index);
+        if start > 0 && start < link_index {
+            start = link_index;
+        }
+
+        let mut end = end.min(last_index + 1).max(start + 1);
+        if start == 0 && end > 1 && end - 1 < link_index {
+            end = 1;
+        }
+
+        let offset = self.tail_offset();
 
         if start == 0 {
+            let link = self.link.clone().filter(|_| end > 1);
+            let tail_end = if link.is_some() {
+                end - 1 - offset
+            } else {
+                end - 1
+            };
+
             Self {
                 head: self.head,
-                tail: self.tail[0..end - 1].to_vec(),
+                tail: self.tail[0..tail_end as usize].to_vec(),
+                link,
             }
         } else {
+            let head = if start > offset {
+                self.tail[(start - offset - 1) as usize].key
+            } else {
+                self.link.as_ref().map_or(self.head, |link| link.key)
+            };
+
             Self {
-                head: self.tail[start - 1].key,
-                tail: self.tail[start..end - 1].to_vec(),
+                head,
+                tail: self.tail[(start - offset) as usize..(end - 1 - offset) as usize].to_vec(),
+                link: None,
             }
         }
     }
 
-    /// Number of blocks in the chain (including the first block)
+    /// Number of blocks in the chain (including the first block and the blocks removed by
+    /// compaction)
     pub fn len(&self) -> usize {
-        1 + self.tail.len()
+        self.last_key_index() as usize + 1
+    }
+
+    /// Returns whether the chain is empty, which is never the case as it always contains at least
+    /// the first key.
+    pub fn is_empty(&self) -> bool {
+        false
+    }
+
+    /// Returns the key at the given index or `None` if the index is out of bounds or the key was
+    /// removed by compaction.
+    pub fn key_at(&self, index: usize) -> Option<&bls::PublicKey> {
+        let index = index as u64;
+        let offset = self.tail_offset();
+
+        if index == 0 {
+            Some(&self.head)
+        } else if index > offset {
+            self.tail
+                .get((index - offset - 1) as usize)
+                .map(|block| &block.key)
+        } else {
+            self.link
+                .as_ref()
+                .filter(|link| link.index == index)
+                .map(|link| &link.key)
+        }
+    }
+
+    /// Returns the block at the given index (using the same indexing as `keys` and `index_of`) or
+    /// `None` if the index is out of bounds or the block was removed by compaction. The first key
+    /// (index 0) is not signed by any previous key and so has no block - use `first_key` or
+    /// `key_at` for it instead.
+    pub fn block_at(&self, index: usize) -> Option<&SectionProofBlock> {
+        (index as u64)
+            .checked_sub(self.tail_offset() + 1)
+            .and_then(|tail_index| self.tail.get(tail_index as usize))
+    }
+
+    /// Returns whether some of the blocks of this chain were removed by compaction.
+    pub(crate) fn is_compacted(&self) -> bool {
+        self.link.is_some()
     }
 
     /// Index of the last key in the chain.
     pub fn last_key_index(&self) -> u64 {
-        self.tail.len() as u64
+        self.tail_offset() + self.tail.len() as u64
     }
 
     /// Check that all the blocks in the chain except the first one have valid signatures.
     /// The first one cannot be verified and requires matching against already trusted keys. Thus
     /// this function alone cannot be used to determine whether this chain is trusted. Use
-    /// `check_trust` for that.
+    /// `check_trust` for that. The blocks removed by compaction are not checked.
     pub fn self_verify(&self) -> bool {
+        self.self_verify_with(&mut |block, key| block.verify(key))
+    }
+
+    /// Checks that this chain could have been built by `push`: every block is signed by the
+    /// previous key and no key appears more than once. Like `self_verify`, this doesn't establish
+    /// trust in the chain, but it's useful to reject malformed chains imported from untrusted
+    /// sources early.
+    pub fn validate_structure(&self) -> Result<(), StructureError> {
+        let mut seen = HashSet::new();
+        let _ = seen.insert(&self.head);
+
         let mut current_key = &self.head;
-        for block in &self.tail {
+        if let Some(link) = &self.link {
+            if !seen.insert(&link.key) {
+                return Err(StructureError::DuplicateKey(link.index));
+            }
+
+            current_key = &link.key;
+        }
+
+        let offset = self.tail_offset();
+        for (index, block) in self.tail.iter().enumerate() {
+            let index = offset + index as u64 + 1;
+
+            if !seen.insert(&block.key) {
+                return Err(StructureError::DuplicateKey(index));
+            }
+
             if !block.verify(current_key) {
-                return false;
+                return Err(StructureError::InvalidSignature(index));
             }
 
             current_key = &block.key;
         }
-        true
+
+        Ok(())
     }
 
     /// Verify this proof chain against the given trusted keys.
+    ///
+    /// If the chain was compacted, trust can be established from a key that precedes the removed
+    /// blocks (typically the first key) only if we verified those blocks ourselves before
+    /// removing them. Otherwise such a check yields `TrustStatus::Unknown`.
     pub fn check_trust<'a, I>(&self, trusted_keys: I) -> TrustStatus
     where
         I: IntoIterator<Item = &'a bls::PublicKey>,
     {
-        if let Some((index, mut trusted_key)) = self.latest_trusted_key(trusted_keys) {
-            for block in &self.tail[index..] {
-                if !block.verify(trusted_key) {
-                    return TrustStatus::Invalid;
-                }
-
-                trusted_key = &block.key;
-            }
+        self.check_trust_with(trusted_keys, |block, key| block.verify(key))
+    }
 
-            TrustStatus::Trusted
-        } else if self.self_verify() {
-            TrustStatus::Unknown
-        } else {
-            TrustStatus::Invalid
-        }
+    /// Verify each of the given chains against the given trusted keys, with the same results as
+    /// calling `check_trust` on each of them. A block present in several of the chains, for
+    /// example because they are overlapping slices of the same chain, has its signature verified
+    /// only once.
+    pub fn check_trust_batch(chains: &[Self], trusted_keys: &[bls::PublicKey]) -> Vec<TrustStatus> {
+        // Blocks already found to be validly signed by the paired key.
+        let mut verified = HashSet::new();
+
+        chains
+            .iter()
+            .map(|chain| {
+                chain.check_trust_with(trusted_keys, |block, key| {
+                    if verified.contains(&(key, block)) {
+                        true
+                    } else if block.verify(key) {
+                        let _ = verified.insert((key, block));
+                        true
+                    } else {
+                        false
+                    }
+                })
+            })
+            .collect()
     }
 
     // Extend `self` so it starts at `new_first_key` while keeping the last key intact.
@@ -205,92 +325,217 @@ impl SectionProofChain {
     }
 
     pub(crate) fn merge(&mut self, other: Self) -> Result<(), MergeError> {
-        fn check_same_keys<'a>(
-            a: impl IntoIterator<Item = &'a bls::PublicKey>,
-            b: impl IntoIterator<Item = &'a bls::PublicKey>,
-        ) -> Result<(), MergeError> {
-            if a.into_iter().zip(b).all(|(a, b)| a == b) {
-                Ok(())
-            } else {
-                Err(MergeError)
+        if let Some(first) = self.index_of(other.first_key()) {
+            // The keys present in both chains at the same index must be the same.
+            for (index, key) in other.indexed_keys().skip(1) {
+                match self.key_at((first + index) as usize) {
+                    Some(existing_key) if existing_key != key => return Err(MergeError),
+                    _ => (),
+                }
             }
-        }
 
-        if let Some(first) = self.index_of(other.first_key()) {
-            check_same_keys(self.keys().skip(first as usize + 1), other.keys().skip(1))?;
+            let last_index = self.last_key_index();
+            let other_last_index = first + other.last_key_index();
 
-            if self.has_key(other.last_key()) {
+            if other_last_index <= last_index {
                 // self:   [a b c]
                 // other:    [b]
                 // result: [a b c]
-                Ok(())
-            } else {
-                // self:   [a b c]
-                // other:    [b c d]
-                // result: [a b c d]
-                self.tail = mem::take(&mut self.tail)
-                    .into_iter()
-                    .take(first as usize)
-                    .chain(other.tail)
-                    .collect();
-                Ok(())
+                return Ok(());
             }
-        } else if let Some(first) = other.index_of(self.first_key()) {
-            check_same_keys(self.keys().skip(1), other.keys().skip(first as usize + 1))?;
 
-            if other.has_key(self.last_key()) {
-                // self:     [b]
-                // other:  [a b c]
-                // result: [a b c]
-                self.head = other.head;
-                self.tail = other.tail;
-                Ok(())
-            } else {
-                // self:     [b c d]
-                // other:  [a b c]
-                // result: [a b c d]
-                self.head = other.head;
-                self.tail = other
-                    .tail
-                    .into_iter()
-                    .take(first as usize)
-                    .chain(mem::take(&mut self.tail))
-                    .collect();
-                Ok(())
+            // self:   [a b c]
+            // other:    [b c d]
+            // result: [a b c d]
+
+            // Our last key must be in `other` too, otherwise the blocks past it can't be joined.
+            if other.key_at((last_index - first) as usize).is_none() {
+                return Err(MergeError);
             }
+
+            let blocks = (last_index + 1..=other_last_index)
+                .map(|index| other.block_at((index - first) as usize).cloned())
+                .collect::<Option<Vec<_>>>()
+                .ok_or(MergeError)?;
+            self.tail.extend(blocks);
+
+            Ok(())
+        } else if other.has_key(self.first_key()) {
+            // self:     [b c d]
+            // other:  [a b c]
+            // result: [a b c d]
+            let mut merged = other;
+            merged.merge(self.clone())?;
+            *self = merged;
+
+            Ok(())
         } else {
             Err(MergeError)
         }
     }
 
+    // Removes the oldest blocks so that at most `max_blocks` blocks remain after the first key.
+    // The removed blocks are replaced with a link to the last removed key, so the indices of the
+    // remaining keys stay the same. If the removed blocks are valid, the chain can still be
+    // trusted from the first key by us. Those we send it to only get the part starting at the
+    // last removed key, which they can verify block by block, but not from the first key.
+    pub(crate) fn compact(&mut self, max_blocks: usize) {
+        if self.tail.len() <= max_blocks {
+            return;
+        }
+
+        let (link_index, mut verified) = self
+            .link
+            .as_ref()
+            .map_or((0, true), |link| (link.index, link.verified));
+        let mut current_key = *self.link.as_ref().map_or(&self.head, |link| &link.key);
+
+        let count = self.tail.len() - max_blocks;
+        for block in self.tail.drain(..count) {
+            verified = verified && block.verify(&current_key);
+            current_key = block.key;
+        }
+
+        self.link = Some(CompactedLink {
+            key: current_key,
+            index: link_index + count as u64,
+            verified,
+        });
+    }
+
+    // `check_trust` with a custom check of the signature of a block by the previous key.
+    fn check_trust_with<'a, 'b, I, F>(&'a self, trusted_keys: I, mut verify: F) -> TrustStatus
+    where
+        I: IntoIterator<Item = &'b bls::PublicKey>,
+        F: FnMut(&'a SectionProofBlock, &'a bls::PublicKey) -> bool,
+    {
+        if let Some((mut index, mut trusted_key)) = self.latest_trusted_key(trusted_keys) {
+            if let Some(link) = self.link.as_ref().filter(|link| index < link.index) {
+                if !link.verified {
+                    return if self.self_verify_with(&mut verify) {
+                        TrustStatus::Unknown
+                    } else {
+                        TrustStatus::Invalid
+                    };
+                }
+
+                index = link.index;
+                trusted_key = &link.key;
+            }
+
+            for block in &self.tail[(index - self.tail_offset()) as usize..] {
+                if !verify(block, trusted_key) {
+                    return TrustStatus::Invalid;
+                }
+
+                trusted_key = &block.key;
+            }
+
+            TrustStatus::Trusted
+        } else if self.self_verify_with(&mut verify) {
+            TrustStatus::Unknown
+        } else {
+            TrustStatus::Invalid
+        }
+    }
+
+    // `self_verify` with a custom check of the signature of a block by the previous key.
+    fn self_verify_with<'a, F>(&'a self, verify: &mut F) -> bool
+    where
+        F: FnMut(&'a SectionProofBlock, &'a bls::PublicKey) -> bool,
+    {
+        let mut current_key = self.link.as_ref().map_or(&self.head, |link| &link.key);
+        for block in &self.tail {
+            if !verify(block, current_key) {
+                return false;
+            }
+
+            current_key = &block.key;
+        }
+        true
+    }
+
+    // Index of the key that signs the first block of `tail`.
+    fn tail_offset(&self) -> u64 {
+        self.link.as_ref().map_or(0, |link| link.index)
+    }
+
+    // Returns the keys that are still present in the chain, together with their indices.
+    pub(crate) fn indexed_keys(&self) -> impl DoubleEndedIterator<Item = (u64, &bls::PublicKey)> {
+        let offset = self.tail_offset();
+
+        iter::once((0, &self.head))
+            .chain(self.link.iter().map(|link| (link.index, &link.key)))
+            .chain(
+                self.tail
+                    .iter()
+                    .enumerate()
+                    .map(move |(index, block)| (offset + index as u64 + 1, &block.key)),
+            )
+    }
+
     // Returns the latest key in this chain that is among the trusted keys, together with its index.
-    fn latest_trusted_key<'a, 'b, I>(
-        &'a self,
-        trusted_keys: I,
-    ) -> Option<(usize, &'a bls::PublicKey)>
+    fn latest_trusted_key<'a, 'b, I>(&'a self, trusted_keys: I) -> Option<(u64, &'a bls::PublicKey)>
     where
         I: IntoIterator<Item = &'b bls::PublicKey>,
     {
         let trusted_keys: HashSet<_> = trusted_keys.into_iter().collect();
-        let last_index = self.len() - 1;
 
-        self.keys()
+        self.indexed_keys()
             .rev()
-            .enumerate()
-            .map(|(rev_index, key)| (last_index - rev_index, key))
             .find(|(_, key)| trusted_keys.contains(key))
     }
 }
 
-// Result of a message trust check.
+// Serialized the same way whether compacted or not: a compacted chain is sent as the uncompacted
+// part only, starting at the last removed key, so the wire format has no place for the link and
+// every chain received is a plain chain where each block is signed by the previous key.
+impl Serialize for SectionProofChain {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let head = self.link.as_ref().map_or(&self.head, |link| &link.key);
+
+        let mut state = serializer.serialize_struct("SectionProofChain", 2)?;
+        state.serialize_field("head", head)?;
+        state.serialize_field("tail", &self.tail)?;
+        state.end()
+    }
+}
+
+// Stands for the blocks removed from a `SectionProofChain` by `compact`.
+#[derive(Clone, Debug)]
+struct CompactedLink {
+    // The last removed key. The first remaining block is signed by it.
+    key: bls::PublicKey,
+    // Index of `key` in the chain.
+    index: u64,
+    // Whether the removed blocks were validly signed when we removed them.
+    verified: bool,
+}
+
+impl PartialEq for CompactedLink {
+    fn eq(&self, other: &Self) -> bool {
+        self.key == other.key && self.index == other.index
+    }
+}
+
+impl Eq for CompactedLink {}
+
+impl Hash for CompactedLink {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.key.hash(state);
+        self.index.hash(state);
+    }
+}
+
+/// Result of a proof chain trust check.
 #[derive(Debug, Eq, PartialEq)]
 pub enum TrustStatus {
-    // Proof chain is trusted.
+    /// Proof chain is trusted.
     Trusted,
-    // Proof chain is untrusted because one or more blocks in the chain have invalid signatures.
+    /// Proof chain is untrusted because one or more blocks in the chain have invalid signatures.
     Invalid,
-    // Proof chain is self-validated but its trust cannot be determined because none of the keys
-    // in the chain is among the trusted keys.
+    /// Proof chain is self-validated but its trust cannot be determined because none of the keys
+    /// in the chain is among the trusted keys.
     Unknown,
 }
 
@@ -305,21 +550,42 @@ pub enum ExtendError {
     AlreadySufficient,
 }
 
+/// Error returned from `SectionProofChain::validate_structure`
+#[derive(Debug, Error, Eq, PartialEq)]
+pub enum StructureError {
+    /// The key at the given index already appears earlier in the chain.
+    #[error("duplicate key at index {0}")]
+    DuplicateKey(u64),
+    /// The key at the given index is not signed by the previous key.
+    #[error("invalid signature at index {0}")]
+    InvalidSignature(u64),
+}
+
 /// Error returned from `SectionProofChain::merge`
 #[derive(Debug, Error, Eq, PartialEq)]
 #[error("incompatible chains cannot be merged")]
 pub struct MergeError;
 
-// Block of the section proof chain. Contains the section BLS public key and is signed by the
-// previous block. Note that the first key in the chain is not signed and so is not stored in
-// `Block`.
+/// Block of the section proof chain. Contains the section BLS public key and is signed by the
+/// previous block. Note that the first key in the chain is not signed and so is not stored in
+/// `SectionProofBlock`.
 #[derive(Debug, Eq, PartialEq, Clone, Hash, Serialize, Deserialize)]
-struct Block {
+pub struct SectionProofBlock {
     key: bls::PublicKey,
     signature: bls::Signature,
 }
 
-impl Block {
+impl SectionProofBlock {
+    /// Returns the key of this block.
+    pub fn key(&self) -> &bls::PublicKey {
+        &self.key
+    }
+
+    /// Returns the signature of the key of this block by the key of the previous block.
+    pub fn signature(&self) -> &bls::Signature {
+        &self.signature
+    }
+
     fn verify(&self, public_key: &bls::PublicKey) -> bool {
         bincode::serialize(&self.key)
             .map(|bytes| public_key.verify(&self.signature, &bytes))
@@ -425,6 +691,39 @@ mod tests {
         assert_keys_eq(chain.slice(0..=3), &keys[0..3]);
     }
 
+    #[test]
+    fn len() {
+        let (chain, _) = gen_chain(1);
+        assert_eq!(chain.len(), 1);
+        assert!(!chain.is_empty());
+
+        let (chain, _) = gen_chain(3);
+        assert_eq!(chain.len(), 3);
+        assert!(!chain.is_empty());
+    }
+
+    #[test]
+    fn block_at() {
+        let (chain, _) = gen_chain(3);
+        let keys: Vec<_> = chain.keys().collect();
+
+        assert_eq!(chain.key_at(0), Some(keys[0]));
+        assert!(chain.block_at(0).is_none());
+
+        for index in 1..3 {
+            assert_eq!(chain.key_at(index), Some(keys[index]));
+
+            let block = chain.block_at(index).expect("block not found");
+            assert_eq!(block.key(), keys[index]);
+            assert!(keys[index - 1]
+                .verify(block.signature(), &bincode::serialize(block.key()).unwrap()));
+        }
+
+        assert!(chain.key_at(3).is_none());
+        assert!(chain.block_at(3).is_none());
+        assert!(chain.block_at(usize::MAX).is_none());
+    }
+
     #[test]
     fn merge() {
         let (chain, _) = gen_chain(4);
@@ -547,6 +846,131 @@ mod tests {
         assert_eq!(chain0.merge(chain1), Err(MergeError));
     }
 
+    #[test]
+    fn compact() {
+        let (mut chain, mut secret_key) = gen_chain(1);
+        let first_key = *chain.first_key();
+
+        for len in 2..=10 {
+            let (key, signature, new_secret_key) = gen_block(&secret_key);
+            assert!(chain.push(key, signature));
+            chain.compact(3);
+            secret_key = new_secret_key;
+
+            // The first key, the last removed key and at most three blocks.
+            assert!(chain.keys().count() <= 5);
+            assert_eq!(chain.len(), len);
+            assert_eq!(chain.index_of(&key), Some(len as u64 - 1));
+
+            assert_eq!(
+                chain.check_trust(iter::once(&first_key)),
+                TrustStatus::Trusted
+            );
+            assert_eq!(chain.check_trust(iter::once(&key)), TrustStatus::Trusted);
+            assert_eq!(chain.validate_structure(), Ok(()));
+        }
+
+        let last_key = *chain.last_key();
+        let recent_key = *chain.key_at(7).expect("key not found");
+        assert!(chain.key_at(3).is_none());
+
+        // Compaction doesn't change the indices, so slices of the remaining keys still match.
+        assert_eq!(chain.slice(7..).first_key(), &recent_key);
+        assert_eq!(chain.slice(7..).last_key(), &last_key);
+
+        // Those we send the chain to get only the blocks we still have, starting at the last
+        // removed key, as a plain uncompacted chain. They can't trust it from the first key, only
+        // from the remaining ones.
+        let received: SectionProofChain =
+            bincode::deserialize(&bincode::serialize(&chain).unwrap()).unwrap();
+        assert!(received.link.is_none());
+        assert_eq!(received.keys().count(), chain.keys().count() - 1);
+        assert_eq!(received.last_key(), &last_key);
+        assert_eq!(received.validate_structure(), Ok(()));
+        assert_eq!(
+            received.check_trust(iter::once(&first_key)),
+            TrustStatus::Unknown
+        );
+        assert_eq!(
+            received.check_trust(iter::once(&recent_key)),
+            TrustStatus::Trusted
+        );
+
+        // An uncompacted chain is serialized exactly as before.
+        let (chain, _) = gen_chain(3);
+        let received: SectionProofChain =
+            bincode::deserialize(&bincode::serialize(&chain).unwrap()).unwrap();
+        assert_eq!(received, chain);
+    }
+
+    #[test]
+    fn check_trust_batch() {
+        let (mut chain, _) = gen_chain(6);
+        let keys: Vec<_> = chain.keys().copied().collect();
+
+        let mut slices = vec![
+            chain.slice(0..=3),
+            chain.slice(2..=5),
+            chain.slice(1..=4),
+            chain.slice(4..),
+        ];
+
+        // A slice ending with an invalid block.
+        let (_, invalid_secret_key) = gen_keys();
+        let (key, signature, _) = gen_block(&invalid_secret_key);
+        chain.push_without_validation(key, signature);
+        slices.push(chain.slice(3..));
+
+        for trusted_keys in &[vec![], vec![keys[0]], vec![keys[2]], vec![keys[1], keys[4]]] {
+            let expected: Vec<_> = slices
+                .iter()
+                .map(|slice| slice.check_trust(trusted_keys))
+                .collect();
+            assert_eq!(
+                SectionProofChain::check_trust_batch(&slices, trusted_keys),
+                expected
+            );
+        }
+    }
+
+    #[test]
+    fn validate_structure_valid() {
+        let (chain, _) = gen_chain(4);
+        assert_eq!(chain.validate_structure(), Ok(()));
+    }
+
+    #[test]
+    fn validate_structure_duplicate_key() {
+        let (key, first_secret_key) = gen_keys();
+        let mut chain = SectionProofChain::new(key);
+
+        let (second_key, signature, second_secret_key) = gen_block(&first_secret_key);
+        assert!(chain.push(second_key, signature));
+
+        // Properly signed, but loops back to the first key.
+        let signature = second_secret_key.sign(&bincode::serialize(&key).unwrap());
+        chain.push_without_validation(key, signature);
+
+        assert_eq!(
+            chain.validate_structure(),
+            Err(StructureError::DuplicateKey(2))
+        );
+    }
+
+    #[test]
+    fn validate_structure_invalid_signature() {
+        let (mut chain, _) = gen_chain(2);
+
+        let (_, invalid_secret_key) = gen_keys();
+        let (key, signature, _) = gen_block(&invalid_secret_key);
+        chain.push_without_validation(key, signature);
+
+        assert_eq!(
+            chain.validate_structure(),
+            Err(StructureError::InvalidSignature(2))
+        );
+    }
+
     fn gen_keys() -> (bls::PublicKey, bls::SecretKey) {
         let secret_key = bls::SecretKey::random();
         (secret_key.public_key(), secret_key)
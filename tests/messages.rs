@@ -14,10 +14,15 @@ use qp2p::QuicP2p;
 use sn_data_types::Keypair;
 use sn_messaging::{
     client::{Message, MessageId, MsgEnvelope, MsgSender, Query, TransferQuery},
-    WireMsg,
+    MessageType, WireMsg,
+};
+use sn_routing::{
+    decrypt_payload, Config, DropReason, DstLocation, Error, Event, NodeElderChange, SrcLocation,
+};
+use std::{
+    net::{IpAddr, Ipv4Addr},
+    time::Duration,
 };
-use sn_routing::{Config, DstLocation, Error, Event, NodeElderChange, SrcLocation};
-use std::net::{IpAddr, Ipv4Addr};
 use utils::*;
 use xor_name::XorName;
 
@@ -101,6 +106,75 @@ async fn test_messages_client_node() -> Result<()> {
     Ok(())
 }
 
+#[tokio::test]
+async fn test_encrypted_message_to_client() -> Result<()> {
+    let (node, mut event_stream) = create_node(Config {
+        first: true,
+        ..Default::default()
+    })
+    .await?;
+
+    // create a client message and the key the client wants the responses encrypted to
+    let mut rng = rand::thread_rng();
+    let keypair = Keypair::new_ed25519(&mut rng);
+    let pk = keypair.public_key();
+    let signature = keypair.sign(b"blabla");
+
+    let msg_envelope = MsgEnvelope {
+        message: Message::Query {
+            query: Query::Transfer(TransferQuery::GetBalance(pk)),
+            id: MessageId(XorName::random()),
+        },
+        origin: MsgSender::client(pk, signature)?,
+        proxies: vec![],
+    };
+    let client_sk = bls::SecretKey::random();
+
+    // create a client which sends a message to the node
+    let node_addr = node.our_connection_info().await?;
+    let config = sn_routing::TransportConfig {
+        ip: Some(IpAddr::V4(Ipv4Addr::new(127, 0, 0, 1))),
+        ..Default::default()
+    };
+    let client = QuicP2p::with_config(Some(config), &[node_addr], false)?;
+    let client_endpoint = client.new_endpoint()?;
+    let (conn, incoming_messages) = client_endpoint.connect_to(&node_addr).await?;
+    let mut incoming_messages =
+        incoming_messages.ok_or_else(|| format_err!("connection already existed"))?;
+
+    let _ = conn
+        .send_uni(WireMsg::serialize_client_msg(&msg_envelope)?)
+        .await?;
+
+    let client_addr = loop {
+        match tokio::time::timeout(TIMEOUT, event_stream.next()).await {
+            Ok(Some(Event::ClientMessageReceived { src, .. })) => break src,
+            Ok(Some(_)) => {}
+            Ok(None) | Err(_) => return Err(format_err!("client message not received")),
+        }
+    };
+
+    // the node responds encrypted to the client key
+    node.send_encrypted_to_client(client_addr, client_sk.public_key(), msg_envelope.clone())
+        .await?;
+
+    let bytes = match tokio::time::timeout(TIMEOUT, incoming_messages.next()).await {
+        Ok(Some(qp2p::Message::UniStream { bytes, .. })) => bytes,
+        _ => return Err(format_err!("encrypted message not received")),
+    };
+
+    // only the holder of the client key can read it, and what it reads is a client message
+    assert_eq!(decrypt_payload(&bls::SecretKey::random(), &bytes), None);
+    let decrypted =
+        decrypt_payload(&client_sk, &bytes).ok_or_else(|| format_err!("failed to decrypt"))?;
+    match WireMsg::deserialize(decrypted)? {
+        MessageType::ClientMessage(envelope) => assert_eq!(envelope, msg_envelope),
+        _ => return Err(format_err!("not a client message")),
+    }
+
+    Ok(())
+}
+
 #[tokio::test]
 async fn test_messages_between_nodes() -> Result<()> {
     let msg = b"hello!";
@@ -131,7 +205,13 @@ async fn test_messages_between_nodes() -> Result<()> {
     // start a second node which sends a message to the first node
     let (node2, mut event_stream) = create_node(config_with_contact(node1_contact)).await?;
 
-    assert_event!(event_stream, Event::EldersChanged { self_status_change: NodeElderChange::Promoted, .. });
+    assert_event!(
+        event_stream,
+        Event::EldersChanged {
+            self_status_change: NodeElderChange::Promoted,
+            ..
+        }
+    );
 
     let node2_name = node2.name().await;
 
@@ -169,3 +249,142 @@ async fn test_messages_between_nodes() -> Result<()> {
 
     Err(format_err!("message not received"))
 }
+
+#[tokio::test]
+async fn test_oversized_message_dropped() -> Result<()> {
+    // create a client message and a bigger version of it
+    let mut rng = rand::thread_rng();
+    let keypair = Keypair::new_ed25519(&mut rng);
+    let pk = keypair.public_key();
+    let signature = keypair.sign(b"blabla");
+
+    let msg_envelope = MsgEnvelope {
+        message: Message::Query {
+            query: Query::Transfer(TransferQuery::GetBalance(pk)),
+            id: MessageId(XorName::random()),
+        },
+        origin: MsgSender::client(pk, signature)?,
+        proxies: vec![],
+    };
+    let oversized_msg_envelope = MsgEnvelope {
+        proxies: vec![msg_envelope.origin.clone(); 10],
+        ..msg_envelope.clone()
+    };
+
+    let msg_bytes = WireMsg::serialize_client_msg(&msg_envelope)?;
+    let oversized_msg_bytes = WireMsg::serialize_client_msg(&oversized_msg_envelope)?;
+    assert!(oversized_msg_bytes.len() > msg_bytes.len());
+
+    let (node, mut event_stream) = create_node(Config {
+        first: true,
+        max_incoming_message_size: msg_bytes.len(),
+        ..Default::default()
+    })
+    .await?;
+    let node_addr = node.our_connection_info().await?;
+
+    let config = sn_routing::TransportConfig {
+        ip: Some(IpAddr::V4(Ipv4Addr::new(127, 0, 0, 1))),
+        ..Default::default()
+    };
+    let client = QuicP2p::with_config(Some(config), &[node_addr], false)?;
+    let client_endpoint = client.new_endpoint()?;
+    let (conn, _) = client_endpoint.connect_to(&node_addr).await?;
+
+    conn.send_uni(oversized_msg_bytes).await?;
+    conn.send_uni(msg_bytes).await?;
+
+    // only the message within the size limit is received
+    assert_event!(event_stream, Event::ClientMessageReceived { content, .. } if *content == msg_envelope);
+
+    let result = tokio::time::timeout(Duration::from_secs(1), async {
+        while let Some(event) = event_stream.next().await {
+            if let Event::ClientMessageReceived { .. } = event {
+                return Some(event);
+            }
+        }
+        None
+    })
+    .await;
+    assert!(matches!(result, Err(_) | Ok(None)));
+
+    // the node is still operational
+    let _ = node.our_connection_info().await?;
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_oversized_message_drop_reported() -> Result<()> {
+    let max_incoming_message_size = 1024;
+
+    let (node, mut event_stream) = create_node(Config {
+        first: true,
+        max_incoming_message_size,
+        ..Default::default()
+    })
+    .await?;
+    let node_addr = node.our_connection_info().await?;
+
+    let config = sn_routing::TransportConfig {
+        ip: Some(IpAddr::V4(Ipv4Addr::new(127, 0, 0, 1))),
+        ..Default::default()
+    };
+    let client = QuicP2p::with_config(Some(config), &[node_addr], false)?;
+    let client_endpoint = client.new_endpoint()?;
+    let (conn, _) = client_endpoint.connect_to(&node_addr).await?;
+
+    conn.send_uni(Bytes::from(vec![0; max_incoming_message_size + 1]))
+        .await?;
+
+    assert_event!(
+        event_stream,
+        Event::MessageDropped {
+            reason: DropReason::TooLarge,
+            src: None
+        }
+    );
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_stream_between_nodes() -> Result<()> {
+    let chunks = vec![
+        Bytes::from_static(b"hello"),
+        Bytes::from_static(b" "),
+        Bytes::from_static(b"streaming"),
+        Bytes::from_static(b" world!"),
+    ];
+
+    let (node1, mut event_stream1) = create_node(Config {
+        first: true,
+        ..Default::default()
+    })
+    .await?;
+    let node1_contact = node1.our_connection_info().await?;
+    let node1_name = node1.name().await;
+
+    let (node2, mut event_stream2) = create_node(config_with_contact(node1_contact)).await?;
+    assert_event!(
+        event_stream2,
+        Event::EldersChanged {
+            self_status_change: NodeElderChange::Promoted,
+            ..
+        }
+    );
+    let node2_name = node2.name().await;
+
+    node2
+        .send_stream(
+            DstLocation::Node(node1_name),
+            futures::stream::iter(chunks.clone()),
+        )
+        .await?;
+
+    // the chunks are received as a single message
+    let expected = Bytes::from(chunks.concat());
+    assert_event!(event_stream1, Event::MessageReceived { content, src: SrcLocation::Node(src), .. } if content == expected && src == node2_name);
+
+    Ok(())
+}